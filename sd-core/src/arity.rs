@@ -0,0 +1,167 @@
+//! Checking operations against the number of inputs and outputs their language expects, to catch
+//! malformed input when it is loaded rather than as a bizarre diagram.
+
+use std::fmt::Display;
+
+use derivative::Derivative;
+
+use crate::{
+    language::{Expr, Language, OpInfo, Value},
+    prettyprinter::PrettyPrint,
+};
+
+/// How many inputs or outputs an operation may have.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bounds {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Bounds {
+    pub const ANY: Self = Self::at_least(0);
+
+    #[must_use]
+    pub const fn exactly(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    #[must_use]
+    pub const fn at_least(n: usize) -> Self {
+        Self { min: n, max: None }
+    }
+
+    #[must_use]
+    pub fn contains(self, n: usize) -> bool {
+        self.min <= n && self.max.map_or(true, |max| n <= max)
+    }
+}
+
+impl Display for Bounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) => write!(f, "{} to {max}", self.min),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+/// The numbers of inputs and outputs an operation expects.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Arity {
+    pub inputs: Bounds,
+    pub outputs: Bounds,
+}
+
+impl Arity {
+    #[must_use]
+    pub const fn new(inputs: Bounds, outputs: Bounds) -> Self {
+        Self { inputs, outputs }
+    }
+}
+
+/// An operation used with a number of inputs or outputs its language does not expect.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct ArityViolation<T: Language> {
+    pub op: T::Op,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub expected: Arity,
+}
+
+impl<T: Language> Display for ArityViolation<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` expects {} inputs and {} outputs, but has {} inputs and {} outputs",
+            self.op.to_pretty(),
+            self.expected.inputs,
+            self.expected.outputs,
+            self.inputs,
+            self.outputs,
+        )
+    }
+}
+
+impl<T: Language> Expr<T> {
+    /// The operations in the expression, including those inside thunks, whose numbers of inputs
+    /// or outputs differ from the arities their language declares.
+    #[must_use]
+    pub fn arity_violations(&self) -> Vec<ArityViolation<T>> {
+        let mut violations = Vec::new();
+        self.check_arities(&mut violations);
+        violations
+    }
+
+    fn check_arities(&self, violations: &mut Vec<ArityViolation<T>>) {
+        for bind in &self.binds {
+            bind.value.check_arities(bind.defs.len(), violations);
+        }
+        for value in &self.values {
+            value.check_arities(1, violations);
+        }
+    }
+}
+
+impl<T: Language> Value<T> {
+    fn check_arities(&self, outputs: usize, violations: &mut Vec<ArityViolation<T>>) {
+        match self {
+            Self::Variable(_) => {}
+            Self::Thunk(thunk) => {
+                thunk.body.check_arities(violations);
+                for block in &thunk.blocks {
+                    block.expr.check_arities(violations);
+                }
+            }
+            Self::Op { op, args } => {
+                if let Some(expected) = op.arity() {
+                    if !expected.inputs.contains(args.len()) || !expected.outputs.contains(outputs)
+                    {
+                        violations.push(ArityViolation {
+                            op: op.clone(),
+                            inputs: args.len(),
+                            outputs,
+                            expected,
+                        });
+                    }
+                }
+                for arg in args {
+                    arg.check_arities(1, violations);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use crate::language::spartan::{Expr, Op, Rule, SpartanParser};
+
+    #[test]
+    fn violations() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind x = plus(1) in bind (y, z) = not(x) in tuple(x, y, z)",
+        )?;
+        let violations = Expr::from_pest(&mut pairs)?.arity_violations();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| (violation.op, violation.inputs, violation.outputs))
+                .collect::<Vec<_>>(),
+            vec![(Op::Plus, 1, 1), (Op::Not, 1, 2)]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "`plus` expects 2 inputs and 1 outputs, but has 1 inputs and 1 outputs"
+        );
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ use std::{
 
 use either::Either;
 use pretty::RcDoc;
+use regex::{Regex, RegexBuilder};
 #[cfg(test)]
 use serde::Serialize;
 
@@ -48,13 +49,58 @@ pub trait InOutIter: InOut {
     fn output_links<'a>(&'a self) -> Box<dyn Iterator<Item = Link<Self::T>> + 'a>;
 }
 
+/// A search for operations, thunks and wires by their names.
+#[derive(Clone, Debug)]
+pub enum Query {
+    /// Names equal to the text, as annotations refer to nodes.
+    Exact(String),
+    /// Names containing the text, ignoring case.
+    Substring(String),
+    Regex(Regex),
+}
+
+impl Query {
+    /// Parses a query typed into a search box: `/pattern/` is a case-insensitive regular
+    /// expression, and anything else is searched for as a substring.
+    pub fn parse(text: &str) -> Result<Self, regex::Error> {
+        match text
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+        {
+            Some(pattern) => Ok(Self::Regex(
+                RegexBuilder::new(pattern).case_insensitive(true).build()?,
+            )),
+            None => Ok(Self::Substring(text.to_lowercase())),
+        }
+    }
+
+    #[must_use]
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Exact(text) => name == text,
+            Self::Substring(text) => name.to_lowercase().contains(text.as_str()),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl PartialEq for Query {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Exact(l), Self::Exact(r)) | (Self::Substring(l), Self::Substring(r)) => l == r,
+            (Self::Regex(l), Self::Regex(r)) => l.as_str() == r.as_str(),
+            _ => false,
+        }
+    }
+}
+
 /// Check if an object matches a query.
 pub trait Matchable {
-    fn is_match(&self, query: &str) -> bool;
+    fn is_match(&self, query: &Query) -> bool;
 }
 
 impl<W: Weight> Matchable for hypergraph::Edge<W> {
-    fn is_match(&self, _query: &str) -> bool {
+    fn is_match(&self, _query: &Query) -> bool {
         false
     }
 }
@@ -64,7 +110,7 @@ where
     W::EdgeWeight: Matchable,
     W::OperationWeight: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.weight().is_match(query) || self.outputs().any(|edge| edge.weight().is_match(query))
     }
 }
@@ -73,13 +119,13 @@ impl<W: Weight> Matchable for hypergraph::Thunk<W>
 where
     W::ThunkWeight: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.weight().is_match(query)
     }
 }
 
 impl<S: Matchable, T: Matchable> Matchable for Either<S, T> {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         match self {
             Either::Left(l) => l.is_match(query),
             Either::Right(r) => r.is_match(query),
@@ -87,6 +133,42 @@ impl<S: Matchable, T: Matchable> Matchable for Either<S, T> {
     }
 }
 
+/// Where something was written in the source it was parsed from, as a range of byte offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[must_use]
+    pub const fn contains(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// The smallest span covering both spans.
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// Weights which may know where in the source they were written.
+pub trait Spanned {
+    fn span(&self) -> Option<Span>;
+}
+
+/// Adapters put the weight of the operation they wrap on the left, and thunks and folded
+/// constants, which have no span of their own, on the right.
+impl<S: Spanned, T> Spanned for Either<S, T> {
+    fn span(&self) -> Option<Span> {
+        self.as_ref().left().and_then(Spanned::span)
+    }
+}
+
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
 #[cfg_attr(test, derive(Serialize))]
 pub enum Empty {}
@@ -98,7 +180,7 @@ impl Display for Empty {
 }
 
 impl Matchable for Empty {
-    fn is_match(&self, _: &str) -> bool {
+    fn is_match(&self, _: &Query) -> bool {
         match *self {}
     }
 }
@@ -120,7 +202,7 @@ impl Display for Unit {
 }
 
 impl Matchable for Unit {
-    fn is_match(&self, _: &str) -> bool {
+    fn is_match(&self, _: &Query) -> bool {
         false
     }
 }
@@ -130,3 +212,27 @@ impl PrettyPrint for Unit {
         RcDoc::nil()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+
+    #[test]
+    fn queries() {
+        let exact = Query::Exact("plus".to_owned());
+        assert!(exact.matches("plus"));
+        assert!(!exact.matches("Plus"));
+
+        let substring = Query::parse("LU").unwrap();
+        assert!(substring.matches("plus"));
+        assert!(!substring.matches("times"));
+
+        let regex = Query::parse("/^arith\\.(add|sub)i$/").unwrap();
+        assert!(regex.matches("arith.addi"));
+        assert!(!regex.matches("arith.muli"));
+
+        assert!(Query::parse("/(/").is_err());
+        // A lone slash is not a pattern
+        assert!(Query::parse("/").unwrap().matches("a/b"));
+    }
+}
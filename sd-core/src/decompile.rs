@@ -91,7 +91,11 @@ impl<T: Language> Expr<T> {
                         }
                         Some(defs) => {
                             // The node has any number of outputs which are all bound variables.
-                            binds.push(Bind { defs, value });
+                            binds.push(Bind {
+                                defs,
+                                value,
+                                comments: vec![],
+                            });
                         }
                     }
                 }
@@ -157,6 +161,7 @@ impl<T: Language> SThunk<T> {
                 .collect::<Result<Vec<_>, _>>()?,
             body: Expr::decompile(thunk)?,
             blocks: vec![],
+            comments: vec![],
         })
     }
 }
@@ -245,3 +250,27 @@ impl<T: Language> PrettyPrint for FakeValue<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use crate::{
+        language::spartan::{Expr, Rule, SpartanParser},
+        prettyprinter::PrettyPrint,
+    };
+
+    #[test]
+    fn labelled_outputs() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind sum = plus(x, y) in bind carry = and(x, y) in (sum, carry)",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let decompiled = Expr::decompile(&graph)?;
+        assert_eq!(decompiled.values.len(), 2);
+        assert!(decompiled.to_pretty().ends_with("(sum, carry)"));
+        Ok(())
+    }
+}
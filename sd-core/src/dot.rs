@@ -2,21 +2,25 @@ use std::{collections::HashMap, fmt::Display, mem};
 
 use dot_structures::{Id, Vertex};
 use pretty::RcDoc;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
     codeable::Codeable,
-    common::Matchable,
+    common::{Matchable, Query},
     hypergraph::{
         builder::{Fragment, HypergraphBuilder, HypergraphError, InPort, OutPort},
-        traits::{WireType, WithType, WithWeight},
+        generic::{self, Endpoint, Node},
+        traits::{EdgeLike, Graph, NodeLike, WireType, WithType, WithWeight},
         Edge, Hypergraph, Operation, Thunk, Weight,
     },
+    interner::InternedStr,
     prettyprinter::PrettyPrint,
 };
 
-#[derive(Debug, Clone)]
-pub struct Label(pub String);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Label(pub InternedStr);
 
 impl WithType for Label {
     fn get_type(&self) -> WireType {
@@ -25,14 +29,14 @@ impl WithType for Label {
 }
 
 impl Matchable for Label {
-    fn is_match(&self, query: &str) -> bool {
-        self.0 == query
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.0)
     }
 }
 
 impl PrettyPrint for Label {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
     }
 }
 
@@ -149,6 +153,7 @@ pub fn dot_to_graph(
                 (Vec<InPort<DotWeight>>, Vec<OutPort<DotWeight>>),
             > = HashMap::new();
             for (id, (inputs, outputs, label)) in in_out {
+                let label = InternedStr::from(label);
                 let op = builder.add_operation(
                     inputs,
                     if settings.collect {
@@ -180,3 +185,179 @@ pub fn dot_to_graph(
         }
     }
 }
+
+/// Writes `graph` as a dot digraph, for tools such as Graphviz.
+///
+/// Operations become nodes labelled by their weight and thunks become clusters, each with a point
+/// standing for the thunk itself where its captured values and outputs are attached. The inputs
+/// and outputs of the graph, and of each thunk, are points on its boundary.
+pub fn graph_to_dot<G: Graph>(graph: &G) -> String
+where
+    generic::Edge<G::Ctx>: WithWeight,
+    generic::Operation<G::Ctx>: WithWeight,
+    generic::Thunk<G::Ctx>: WithWeight,
+    generic::Weight<generic::Edge<G::Ctx>>: Display,
+    generic::Weight<generic::Operation<G::Ctx>>: Display,
+    generic::Weight<generic::Thunk<G::Ctx>>: Display,
+{
+    let mut writer = DotWriter {
+        out: "digraph {\n  compound=true;\n".to_owned(),
+        nodes: HashMap::new(),
+        inputs: HashMap::new(),
+        outputs: Vec::new(),
+        edges: Vec::new(),
+    };
+    writer.graph(graph, graph.graph_inputs(), 1);
+    writer.edges();
+    writer.out.push_str("}\n");
+    writer.out
+}
+
+struct DotWriter<T: generic::Ctx> {
+    out: String,
+    nodes: HashMap<Node<T>, String>,
+    /// The points standing for the edges coming into a graph or thunk.
+    inputs: HashMap<generic::Edge<T>, String>,
+    /// The points standing for the edges leaving a graph or thunk.
+    outputs: Vec<(generic::Edge<T>, String)>,
+    edges: Vec<generic::Edge<T>>,
+}
+
+impl<T: generic::Ctx> DotWriter<T>
+where
+    generic::Weight<generic::Edge<T>>: Display,
+    generic::Weight<generic::Operation<T>>: Display,
+    generic::Weight<generic::Thunk<T>>: Display,
+{
+    fn graph<'a, G: Graph<Ctx = T>>(
+        &mut self,
+        graph: &'a G,
+        inputs: Box<dyn DoubleEndedIterator<Item = generic::Edge<T>> + 'a>,
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth);
+        for edge in inputs {
+            let id = format!("i{}", self.inputs.len());
+            self.out.push_str(&format!("{indent}{id} [shape=point];\n"));
+            self.inputs.insert(edge.clone(), id);
+            self.edges.push(edge);
+        }
+        for node in graph.nodes() {
+            self.edges.extend(node.outputs());
+            match &node {
+                Node::Operation(op) => {
+                    let id = format!("n{}", self.nodes.len());
+                    self.out
+                        .push_str(&format!("{indent}{id} [label={}];\n", quote(op.weight())));
+                    self.nodes.insert(node.clone(), id);
+                }
+                Node::Thunk(thunk) => {
+                    let id = format!("t{}", self.nodes.len());
+                    self.out
+                        .push_str(&format!("{indent}subgraph cluster_{id} {{\n"));
+                    self.out
+                        .push_str(&format!("{indent}  label={};\n", quote(thunk.weight())));
+                    self.out
+                        .push_str(&format!("{indent}  {id} [shape=point];\n"));
+                    self.nodes.insert(node.clone(), id);
+                    self.graph(thunk, thunk.bound_graph_inputs(), depth + 1);
+                    self.out.push_str(&format!("{indent}}}\n"));
+                }
+            }
+        }
+        for edge in graph.graph_outputs() {
+            let id = format!("o{}", self.outputs.len());
+            self.out.push_str(&format!("{indent}{id} [shape=point];\n"));
+            self.outputs.push((edge, id));
+        }
+    }
+
+    /// The end of a wire at `endpoint`, with the attribute clipping it to the cluster of a thunk.
+    fn end(&self, endpoint: &Endpoint<T>, attribute: &str) -> Option<(String, String)> {
+        match endpoint {
+            Endpoint::Node(node) => {
+                let id = self.nodes.get(node)?;
+                let clip = match node {
+                    Node::Operation(_) => String::new(),
+                    Node::Thunk(_) => format!("{attribute}=cluster_{id}"),
+                };
+                Some((id.clone(), clip))
+            }
+            Endpoint::Boundary(_) => None,
+        }
+    }
+
+    fn edge(&mut self, edge: &generic::Edge<T>, target: (String, String)) {
+        let source = match self.inputs.get(edge) {
+            Some(id) => Some((id.clone(), String::new())),
+            None => self.end(&edge.source(), "ltail"),
+        };
+        // Wires from outside what is being written have nowhere to start
+        let Some((source, tail)) = source else {
+            return;
+        };
+        let (target, head) = target;
+        let mut attributes = [tail, head]
+            .into_iter()
+            .filter(|attribute| !attribute.is_empty())
+            .collect::<Vec<_>>();
+        let label = edge.weight().to_string();
+        if !label.is_empty() {
+            attributes.push(format!("label={}", quote(label)));
+        }
+        if attributes.is_empty() {
+            self.out.push_str(&format!("  {source} -> {target};\n"));
+        } else {
+            self.out.push_str(&format!(
+                "  {source} -> {target} [{}];\n",
+                attributes.join(", ")
+            ));
+        }
+    }
+
+    fn edges(&mut self) {
+        for edge in mem::take(&mut self.edges) {
+            let targets = edge
+                .targets()
+                .filter_map(|target| self.end(&target, "lhead"))
+                .collect::<Vec<_>>();
+            for target in targets {
+                self.edge(&edge, target);
+            }
+        }
+        for (edge, id) in mem::take(&mut self.outputs) {
+            self.edge(&edge, (id, String::new()));
+        }
+    }
+}
+
+fn quote(label: impl Display) -> String {
+    format!(
+        "\"{}\"",
+        label.to_string().replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::graph_to_dot;
+    use crate::language::spartan::{Expr, Rule, SpartanParser};
+
+    #[test]
+    fn graph_to_dot_clusters_thunks() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y . bind a = times(x, y) in a) in f",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let dot = graph_to_dot(&graph);
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("[label=\"plus\"]"));
+        assert!(dot.contains("[label=\"times\"]"));
+        assert_eq!(dot.matches("subgraph cluster_").count(), 1);
+        Ok(())
+    }
+}
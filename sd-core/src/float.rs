@@ -0,0 +1,352 @@
+//! Let-floating, which moves bindings to where people would scope them: bindings only used inside
+//! one thunk are moved into it, and bindings repeated in several thunks are moved out to be shared.
+//! It only changes how a program is displayed, never its source.
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::language::{Bind, Expr, GetVar, Language, OpInfo, Thunk, Value};
+
+impl<T: Language> Expr<T> {
+    /// A copy of the expression with its bindings floated into and out of thunks.
+    #[must_use]
+    pub fn float_lets(&self) -> Self {
+        let mut expr = self.clone();
+        expr.float();
+        expr
+    }
+
+    fn float(&mut self) {
+        self.float_out();
+        while self.float_in() {}
+        for thunk in self.thunks_mut() {
+            thunk.body.float();
+            for block in &mut thunk.blocks {
+                block.expr.float();
+            }
+        }
+    }
+
+    /// The thunks directly inside the expression, leaving out those nested in other thunks.
+    fn thunks(&self) -> Vec<&Thunk<T>> {
+        let mut thunks = Vec::new();
+        for value in self
+            .binds
+            .iter()
+            .map(|bind| &bind.value)
+            .chain(&self.values)
+        {
+            value.thunks(&mut thunks);
+        }
+        thunks
+    }
+
+    fn thunks_mut(&mut self) -> Vec<&mut Thunk<T>> {
+        let mut thunks = Vec::new();
+        for value in self
+            .binds
+            .iter_mut()
+            .map(|bind| &mut bind.value)
+            .chain(&mut self.values)
+        {
+            value.thunks_mut(&mut thunks);
+        }
+        thunks
+    }
+
+    /// Moves a binding used only inside a single thunk into it, returning whether there was one.
+    fn float_in(&mut self) -> bool {
+        for i in (0..self.binds.len()).rev() {
+            let bind = self.binds.remove(i);
+            match self.float_in_target(&bind) {
+                Some(target) => {
+                    self.thunks_mut()
+                        .swap_remove(target)
+                        .body
+                        .binds
+                        .insert(0, bind);
+                    return true;
+                }
+                None => self.binds.insert(i, bind),
+            }
+        }
+        false
+    }
+
+    /// The index of the only thunk using the variables bound by `bind`, which has been taken out
+    /// of the expression, if it can be moved into that thunk.
+    fn float_in_target(&self, bind: &Bind<T>) -> Option<usize> {
+        if bind.defs.is_empty() || !movable(&bind.value) {
+            return None;
+        }
+        let defs: IndexSet<T::Var> = bind.defs.iter().map(|def| def.var().clone()).collect();
+
+        let mut outside = IndexSet::new();
+        for value in self
+            .binds
+            .iter()
+            .map(|bind| &bind.value)
+            .chain(&self.values)
+        {
+            value.uses_outside_thunks(&mut outside);
+        }
+        if !outside.is_disjoint(&defs) {
+            return None;
+        }
+
+        let mut users = self.thunks().into_iter().enumerate().filter(|(_, thunk)| {
+            let mut vars = IndexSet::new();
+            thunk.free_vars(&mut vars, false);
+            !vars.is_disjoint(&defs)
+        });
+        let (target, thunk) = users.next()?;
+        if users.next().is_some() || !thunk.blocks.is_empty() {
+            return None;
+        }
+
+        // Moving the binding must not capture the variables it uses, or shadow those it binds
+        let bound = thunk.bound_vars();
+        (free_vars(&bind.value).is_disjoint(&bound) && defs.is_disjoint(&bound)).then_some(target)
+    }
+
+    /// Moves bindings which are repeated in several thunks out of them, to be shared.
+    fn float_out(&mut self) {
+        let thunks = self.thunks();
+
+        // The bindings in each thunk which do not depend on anything bound inside it
+        let mut repeated = IndexMap::<Value<T>, IndexMap<usize, usize>>::new();
+        for (t, thunk) in thunks.iter().enumerate() {
+            let bound = thunk.bound_vars();
+            for (b, bind) in thunk.body.binds.iter().enumerate() {
+                if bind.defs.len() == 1
+                    && matches!(bind.value, Value::Op { .. })
+                    && movable(&bind.value)
+                    && free_vars(&bind.value).is_disjoint(&bound)
+                {
+                    repeated
+                        .entry(bind.value.clone())
+                        .or_default()
+                        .entry(t)
+                        .or_insert(b);
+                }
+            }
+        }
+
+        let mut outer = self.free_vars(false);
+        outer.extend(
+            self.binds
+                .iter()
+                .flat_map(|bind| &bind.defs)
+                .map(|def| def.var().clone()),
+        );
+
+        // For each thunk, the bindings to remove from it, and the variables to rename in it
+        let mut removals = IndexMap::<usize, Vec<(usize, Option<(T::Var, T::Var)>)>>::new();
+        let mut shared = Vec::new();
+        for occurrences in repeated.into_values() {
+            let mut occurrences = occurrences.into_iter();
+            let Some((first, b)) = occurrences.next() else {
+                continue;
+            };
+            let bind = &thunks[first].body.binds[b];
+            let var = bind.defs[0].var();
+            if outer.contains(var) {
+                continue;
+            }
+            let others = occurrences
+                .filter(|&(t, _)| {
+                    let mut vars = IndexSet::new();
+                    thunks[t].free_vars(&mut vars, false);
+                    !vars.contains(var) && !thunks[t].bound_vars().contains(var)
+                })
+                .collect::<Vec<_>>();
+            if others.is_empty() {
+                continue;
+            }
+            outer.insert(var.clone());
+            removals.entry(first).or_default().push((b, None));
+            for (t, b) in others {
+                let from = thunks[t].body.binds[b].defs[0].var().clone();
+                removals
+                    .entry(t)
+                    .or_default()
+                    .push((b, Some((from, var.clone()))));
+            }
+            shared.push(bind.clone());
+        }
+
+        let mut thunks = self.thunks_mut();
+        for (t, mut removals) in removals {
+            // Removing from the back keeps the indices of the remaining bindings valid
+            removals.sort_by_key(|&(b, _)| std::cmp::Reverse(b));
+            for (b, rename) in removals {
+                thunks[t].body.binds.remove(b);
+                if let Some((from, to)) = rename {
+                    thunks[t].rename(&from, &to);
+                }
+            }
+        }
+        self.binds.splice(0..0, shared);
+    }
+
+    fn rename(&mut self, from: &T::Var, to: &T::Var) {
+        for value in self
+            .binds
+            .iter_mut()
+            .map(|bind| &mut bind.value)
+            .chain(&mut self.values)
+        {
+            value.rename(from, to);
+        }
+    }
+}
+
+impl<T: Language> Value<T> {
+    fn thunks<'a>(&'a self, thunks: &mut Vec<&'a Thunk<T>>) {
+        match self {
+            Value::Variable(_) => {}
+            Value::Thunk(thunk) => thunks.push(thunk),
+            Value::Op { args, .. } => {
+                for arg in args {
+                    arg.thunks(thunks);
+                }
+            }
+        }
+    }
+
+    fn thunks_mut<'a>(&'a mut self, thunks: &mut Vec<&'a mut Thunk<T>>) {
+        match self {
+            Value::Variable(_) => {}
+            Value::Thunk(thunk) => thunks.push(thunk),
+            Value::Op { args, .. } => {
+                for arg in args {
+                    arg.thunks_mut(thunks);
+                }
+            }
+        }
+    }
+
+    /// The variables used by the value other than inside thunks.
+    fn uses_outside_thunks(&self, vars: &mut IndexSet<T::Var>) {
+        match self {
+            Value::Variable(var) => {
+                vars.insert(var.clone());
+            }
+            Value::Thunk(_) => {}
+            Value::Op { args, .. } => {
+                for arg in args {
+                    arg.uses_outside_thunks(vars);
+                }
+            }
+        }
+    }
+
+    fn rename(&mut self, from: &T::Var, to: &T::Var) {
+        match self {
+            Value::Variable(var) => {
+                if var == from {
+                    *var = to.clone();
+                }
+            }
+            Value::Thunk(thunk) => thunk.rename(from, to),
+            Value::Op { args, .. } => {
+                for arg in args {
+                    arg.rename(from, to);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Language> Thunk<T> {
+    /// The variables bound by the thunk itself, rather than by thunks nested in it.
+    fn bound_vars(&self) -> IndexSet<T::Var> {
+        let body = std::iter::once(&self.body).chain(self.blocks.iter().map(|block| &block.expr));
+        self.args
+            .iter()
+            .chain(self.blocks.iter().flat_map(|block| &block.args))
+            .chain(
+                body.flat_map(|expr| &expr.binds)
+                    .flat_map(|bind| &bind.defs),
+            )
+            .map(|def| def.var().clone())
+            .collect()
+    }
+
+    fn rename(&mut self, from: &T::Var, to: &T::Var) {
+        self.body.rename(from, to);
+        for block in &mut self.blocks {
+            block.expr.rename(from, to);
+        }
+    }
+}
+
+fn free_vars<T: Language>(value: &Value<T>) -> IndexSet<T::Var> {
+    let mut vars = IndexSet::new();
+    value.free_vars(&mut vars, &mut IndexSet::new(), false);
+    vars
+}
+
+/// Whether a binding of `value` can be moved, which is not the case for control flow or the
+/// definitions of symbols.
+fn movable<T: Language>(value: &Value<T>) -> bool {
+    match value {
+        Value::Op { op, .. } => op.get_cf().is_none() && op.sym_name().is_none(),
+        Value::Variable(_) | Value::Thunk(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use crate::{
+        language::spartan::{Expr, Rule, SpartanParser},
+        prettyprinter::PrettyPrint,
+    };
+
+    fn float(source: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, source)?;
+        Ok(Expr::from_pest(&mut pairs)?.float_lets().to_pretty())
+    }
+
+    fn pretty(source: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, source)?;
+        Ok(Expr::from_pest(&mut pairs)?.to_pretty())
+    }
+
+    #[test]
+    fn float_in() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            float("bind a = plus(x, y) in bind b = times(a, a) in bind f = z . plus(b, z) in f")?,
+            pretty("bind f = z . bind a = plus(x, y) in bind b = times(a, a) in plus(b, z) in f")?
+        );
+        // Used outside the thunk, or by more than one
+        assert_eq!(
+            float("bind a = plus(x, y) in bind f = z . plus(a, z) in tuple(a, f)")?,
+            pretty("bind a = plus(x, y) in bind f = z . plus(a, z) in tuple(a, f)")?
+        );
+        assert_eq!(
+            float("bind a = plus(x, y) in tuple(z . plus(a, z), w . times(a, w))")?,
+            pretty("bind a = plus(x, y) in tuple(z . plus(a, z), w . times(a, w))")?
+        );
+        // Would capture a variable
+        assert_eq!(
+            float("bind a = plus(x, y) in bind f = x . plus(a, x) in f")?,
+            pretty("bind a = plus(x, y) in bind f = x . plus(a, x) in f")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn float_out() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            float(
+                "tuple(z . bind a = plus(x, y) in times(a, z), \
+                 w . bind b = plus(x, y) in minus(b, w))"
+            )?,
+            pretty("bind a = plus(x, y) in tuple(z . times(a, z), w . minus(a, w))")?
+        );
+        Ok(())
+    }
+}
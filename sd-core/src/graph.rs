@@ -12,17 +12,19 @@ use thiserror::Error;
 use tracing::debug;
 
 use crate::{
-    common::Matchable,
+    common::{Matchable, Query, Span},
     hypergraph::{
         builder::{
             fragment::{Fragment, ThunkCursor},
-            HypergraphBuilder, HypergraphError, InPort, OutPort, ThunkBuilder,
+            HypergraphBuildError, HypergraphBuilder, HypergraphError, InPort, OutPort,
+            ThunkBuilder,
         },
-        traits::{WireType, WithType},
-        Hypergraph, Weight,
+        traits::{NodeLike, WireType, WithType, WithWeight},
+        Hypergraph, Node, Weight,
     },
-    language::{Expr, GetVar, Language, OpInfo, Value, CF},
+    language::{Expr, GetVar, Language, OpDoc, OpInfo, Value, CF},
     prettyprinter::PrettyPrint,
+    progress::{Callback, Tracker},
 };
 
 pub struct Syntax<T: Language> {
@@ -33,6 +35,10 @@ impl<T: Language> Weight for Syntax<T> {
     type EdgeWeight = Name<T>;
     type OperationWeight = T::Op;
     type ThunkWeight = Either<T::Addr, T::BlockAddr>;
+
+    fn operation_doc(op: &T::Op) -> Option<OpDoc> {
+        op.doc()
+    }
 }
 
 #[derive(Derivative)]
@@ -61,7 +67,15 @@ impl<T: Language> WithType for Name<T> {
             Name::CF(_) => WireType::ControlFlow,
             Name::Nil => WireType::Data,
             Name::FreeVar(v) => v.get_type(),
-            Name::BoundVar(v) => v.var().get_type(),
+            Name::BoundVar(v) => v.get_type(),
+        }
+    }
+
+    fn type_name(&self) -> Option<String> {
+        match self {
+            Name::CF(_) | Name::Nil => None,
+            Name::FreeVar(v) => v.type_name(),
+            Name::BoundVar(v) => v.type_name(),
         }
     }
 }
@@ -79,7 +93,7 @@ impl<T: Language> Display for Name<T> {
 }
 
 impl<T: Language> Matchable for Name<T> {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         match self {
             Name::Nil | Name::CF(_) => false,
             Name::FreeVar(var) => var.is_match(query),
@@ -115,6 +129,30 @@ pub enum ConvertError<T: Language> {
     NoOutputError,
     #[error("Uninitialised Inports for variables: {0:?}")]
     UnitialisedInput(Vec<T::Var>),
+    /// Variables defined in terms of each other, with where the operations defining them were
+    /// written, for languages which keep track.
+    #[error("Cyclic definitions of `{}`: each is computed from the others", .0.to_pretty())]
+    Cycle(Vec<T::Var>, Vec<Span>),
+}
+
+impl<T: Language + 'static> ConvertError<T> {
+    /// The error for the `nodes` of a cycle in the hypergraph built.
+    fn cycle(nodes: &[Node<Syntax<T>>]) -> Self {
+        let vars = nodes
+            .iter()
+            .flat_map(NodeLike::outputs)
+            .filter_map(|edge| edge.weight().into_var())
+            .unique()
+            .collect();
+        let spans = nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Operation(op) => op.weight().span(),
+                Node::Thunk(_) => None,
+            })
+            .collect();
+        Self::Cycle(vars, spans)
+    }
 }
 
 /// Environments capture the local information needed to build a hypergraph from an AST
@@ -132,6 +170,9 @@ struct Environment<F, T: Language> {
     cf_outputs: Vec<(Option<T::BlockAddr>, OutPort<Syntax<T>>)>,
     /// Whether to link symbols in mlir
     sym_name_link: bool,
+    /// Counts the values processed so far
+    #[derivative(Debug = "ignore")]
+    progress: Tracker,
 }
 
 enum ProcessInput<T: Language> {
@@ -145,13 +186,14 @@ where
     F: Fragment<Weight = Syntax<T>>,
 {
     /// Create a new empty environment from a given `fragment`
-    fn new(fragment: F, sym_name_link: bool) -> Self {
+    fn new(fragment: F, sym_name_link: bool, progress: Tracker) -> Self {
         Self {
             fragment,
             inputs: Vec::default(),
             outputs: HashMap::default(),
             cf_outputs: Vec::default(),
             sym_name_link,
+            progress,
         }
     }
 
@@ -167,6 +209,7 @@ where
                 outputs: std::mem::take(&mut self.outputs),
                 cf_outputs: std::mem::take(&mut self.cf_outputs),
                 sym_name_link: self.sym_name_link,
+                progress: self.progress.clone(),
             };
             let ret = f(&mut new_env);
             self.inputs = std::mem::take(&mut new_env.inputs);
@@ -192,6 +235,7 @@ where
         value: &Value<T>,
         input: ProcessInput<T>,
     ) -> Result<(), ConvertError<T>> {
+        self.progress.step();
         match value {
             Value::Variable(var) => {
                 match input {
@@ -231,7 +275,11 @@ where
 
                 self.fragment
                     .in_thunk(thunk_node.clone(), |inner_fragment| {
-                        let mut thunk_env = Environment::new(inner_fragment, self.sym_name_link);
+                        let mut thunk_env = Environment::new(
+                            inner_fragment,
+                            self.sym_name_link,
+                            self.progress.clone(),
+                        );
 
                         // Add bound inputs of the thunk to the environment
                         for (def, out_port) in thunk.args.iter().zip(thunk_node.bound_inputs()) {
@@ -451,6 +499,24 @@ where
 
 impl<T: Language + 'static> Expr<T> {
     pub fn to_graph(&self, sym_name_link: bool) -> Result<SyntaxHypergraph<T>, ConvertError<T>> {
+        self.to_graph_tracked(sym_name_link, Tracker::default())
+    }
+
+    /// Like [`Expr::to_graph`], telling `progress` how many of the values in the expression have
+    /// been processed out of how many in total.
+    pub fn to_graph_with_progress(
+        &self,
+        sym_name_link: bool,
+        progress: Callback,
+    ) -> Result<SyntaxHypergraph<T>, ConvertError<T>> {
+        self.to_graph_tracked(sym_name_link, Tracker::new(progress, self.num_values()))
+    }
+
+    fn to_graph_tracked(
+        &self,
+        sym_name_link: bool,
+        progress: Tracker,
+    ) -> Result<SyntaxHypergraph<T>, ConvertError<T>> {
         let free = self.free_vars(sym_name_link);
         debug!("free variables: {:?}", free);
 
@@ -460,7 +526,7 @@ impl<T: Language + 'static> Expr<T> {
         );
         debug!("made initial hypergraph: {:?}", graph);
 
-        let mut env = Environment::new(graph, sym_name_link);
+        let mut env = Environment::new(graph, sym_name_link, progress);
         debug!("determined environment: {:?}", env);
 
         for (var, out_port) in free.iter().zip(env.fragment.graph_inputs()) {
@@ -482,7 +548,38 @@ impl<T: Language + 'static> Expr<T> {
             ));
         }
 
-        Ok(env.fragment.build()?)
+        // Binds may refer to each other in any order, so nothing stops them going round in circles
+        env.fragment.build().map_err(|err| match err {
+            HypergraphError::BuildError(HypergraphBuildError::Cycle(cycle)) => {
+                ConvertError::cycle(&cycle)
+            }
+            err => err.into(),
+        })
+    }
+
+    /// The number of values in the expression, including those nested inside other values.
+    fn num_values(&self) -> usize {
+        fn value<T: Language + 'static>(value: &Value<T>) -> usize {
+            1 + match value {
+                Value::Variable(_) => 0,
+                Value::Thunk(thunk) => {
+                    thunk.body.num_values()
+                        + thunk
+                            .blocks
+                            .iter()
+                            .map(|block| block.expr.num_values())
+                            .sum::<usize>()
+                }
+                Value::Op { args, .. } => args.iter().map(value).sum(),
+            }
+        }
+
+        self.binds
+            .iter()
+            .map(|bind| &bind.value)
+            .chain(&self.values)
+            .map(value)
+            .sum()
     }
 }
 
@@ -493,6 +590,37 @@ mod tests {
 
     use crate::language::tests::ExprTest;
 
+    #[test]
+    fn cyclic_binds() -> Result<()> {
+        use from_pest::FromPest;
+        use itertools::Itertools;
+        use pest::Parser;
+
+        use super::ConvertError;
+        use crate::{
+            language::spartan::{Expr, Rule, SpartanParser},
+            prettyprinter::PrettyPrint,
+        };
+
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind a = plus(b, b) in bind b = times(a, a) in bind c = minus(a, b) in c",
+        )?;
+        let expr = Expr::from_pest(&mut pairs)?;
+        let Err(ConvertError::Cycle(vars, spans)) = expr.to_graph(false) else {
+            panic!("cycle not detected");
+        };
+        let vars = vars
+            .iter()
+            .map(PrettyPrint::to_pretty)
+            .sorted()
+            .collect_vec();
+        assert_eq!(vars, ["a", "b"]);
+        // Spartan does not keep track of where operations were written
+        assert!(spans.is_empty());
+        Ok(())
+    }
+
     #[allow(clippy::needless_pass_by_value)]
     #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*", loader: crate::language::tests::parse, postfix: "free_vars")]
     fn free_vars(fixture: Fixture<(&str, &str, Box<dyn ExprTest>)>) {
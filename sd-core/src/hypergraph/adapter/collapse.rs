@@ -6,7 +6,7 @@ use itertools::Either;
 
 use crate::{
     codeable::{Code, Codeable},
-    common::Matchable,
+    common::{Matchable, Query},
     hypergraph::{
         generic::{Ctx, Edge, Endpoint, Key, Node, Operation, Thunk, Weight},
         mapping::ThunkMap,
@@ -55,6 +55,14 @@ impl<G: Graph> CollapseGraph<G> {
         expanded.values_mut().for_each(|x| *x = value);
         self.expanded = ByThinAddress(Arc::new(expanded));
     }
+
+    pub fn set(&mut self, values: impl IntoIterator<Item = (Key<Thunk<G::Ctx>>, bool)>) {
+        let mut expanded = self.expanded().clone();
+        for (thunk, value) in values {
+            expanded[&thunk] = value;
+        }
+        self.expanded = ByThinAddress(Arc::new(expanded));
+    }
 }
 
 #[derive(Derivative)]
@@ -482,7 +490,7 @@ impl<G: Graph> Matchable for CollapseEdge<G>
 where
     Edge<G::Ctx>: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.edge.is_match(query)
     }
 }
@@ -492,7 +500,7 @@ where
     Operation<G::Ctx>: Matchable,
     Thunk<G::Ctx>: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         match &self.node {
             Node::Operation(op) => op.is_match(query),
             Node::Thunk(thunk) => thunk.is_match(query),
@@ -504,7 +512,7 @@ impl<G: Graph> Matchable for CollapseThunk<G>
 where
     Thunk<G::Ctx>: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.thunk.is_match(query)
     }
 }
@@ -7,7 +7,7 @@ use itertools::Either;
 
 use crate::{
     codeable::{Code, Codeable},
-    common::Matchable,
+    common::{Matchable, Query},
     hypergraph::{
         generic::{Ctx, Edge, Endpoint, Key, Node, Operation, Thunk, Weight},
         mapping::EdgeMap,
@@ -50,6 +50,32 @@ impl<G: Graph> CutGraph<G> {
         cut_edges[&edge.key()] ^= true;
         self.cut_edges = ByThinAddress(Arc::new(cut_edges));
     }
+
+    pub fn set(&mut self, values: impl IntoIterator<Item = (Key<Edge<G::Ctx>>, bool)>) {
+        let mut cut_edges = self.cut_edges().clone();
+        for (edge, value) in values {
+            cut_edges[&edge] = value;
+        }
+        self.cut_edges = ByThinAddress(Arc::new(cut_edges));
+    }
+}
+
+/// Whether `op` is a constant, with no inputs and a single output, whose output is cut.
+///
+/// Such constants are left out of the graph entirely, with the reuses of their output standing in
+/// for them as literals.
+fn is_folded<T: Ctx>(op: &T::Operation, cut_edges: &EdgeMap<T, bool>) -> bool {
+    op.number_of_inputs() == 0
+        && op.number_of_outputs() == 1
+        && op.outputs().all(|edge| cut_edges[&edge.key()])
+}
+
+/// The folded constant which is the source of `edge`, if any.
+fn folded_source<T: Ctx>(edge: &T::Edge, cut_edges: &EdgeMap<T, bool>) -> Option<T::Operation> {
+    match edge.source() {
+        Endpoint::Node(Node::Operation(op)) if is_folded::<T>(&op, cut_edges) => Some(op),
+        _ => None,
+    }
 }
 
 #[derive(Derivative)]
@@ -131,6 +157,16 @@ impl<G: Graph> CutOperation<G> {
             Self::Store { edge, .. } | Self::Reuse { edge, .. } => Either::Right(edge),
         }
     }
+
+    /// The folded constant this reuse stands in for, if any.
+    pub fn folded(&self) -> Option<Operation<G::Ctx>> {
+        match self {
+            Self::Reuse {
+                edge, cut_edges, ..
+            } => folded_source::<G::Ctx>(edge, cut_edges),
+            Self::Inner { .. } | Self::Store { .. } => None,
+        }
+    }
 }
 
 #[derive(Derivative)]
@@ -266,6 +302,9 @@ impl<G: Graph> Graph for CutGraph<G> {
             }
         }
         for node in self.graph.nodes() {
+            if matches!(&node, Node::Operation(op) if is_folded::<G::Ctx>(op, &self.cut_edges)) {
+                continue;
+            }
             for edge in node.outputs().filter(|edge| self.cut_edges[&edge.key()]) {
                 nodes.insert(Node::store(edge, self.cut_edges.clone()));
             }
@@ -510,6 +549,9 @@ impl<G: Graph> Graph for CutThunk<G> {
             }
         }
         for node in self.thunk.nodes() {
+            if matches!(&node, Node::Operation(op) if is_folded::<G::Ctx>(op, &self.cut_edges)) {
+                continue;
+            }
             for edge in node.outputs().filter(|edge| self.cut_edges[&edge.key()]) {
                 nodes.insert(Node::store(edge, self.cut_edges.clone()));
             }
@@ -643,9 +685,14 @@ impl<G: Graph> WithWeight for CutEdge<G> {
 impl<G: Graph> WithWeight for CutOperation<G> {
     type Weight = Either<Weight<Operation<G::Ctx>>, Weight<Edge<G::Ctx>>>;
 
+    /// Folded constants are labelled by the constant rather than the name of its output.
     fn weight(&self) -> Self::Weight {
-        self.inner()
-            .map_either(WithWeight::weight, WithWeight::weight)
+        match self.folded() {
+            Some(op) => Either::Left(op.weight()),
+            None => self
+                .inner()
+                .map_either(WithWeight::weight, WithWeight::weight),
+        }
     }
 }
 
@@ -703,7 +750,7 @@ impl<G: Graph> Matchable for CutEdge<G>
 where
     Edge<G::Ctx>: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.inner().is_match(query)
     }
 }
@@ -713,9 +760,13 @@ where
     Edge<G::Ctx>: Matchable,
     Operation<G::Ctx>: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
-        self.inner()
-            .either(|op| op.is_match(query), |edge| edge.is_match(query))
+    fn is_match(&self, query: &Query) -> bool {
+        match self.folded() {
+            Some(op) => op.is_match(query),
+            None => self
+                .inner()
+                .either(|op| op.is_match(query), |edge| edge.is_match(query)),
+        }
     }
 }
 
@@ -723,7 +774,7 @@ impl<G: Graph> Matchable for CutThunk<G>
 where
     Thunk<G::Ctx>: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.inner().is_match(query)
     }
 }
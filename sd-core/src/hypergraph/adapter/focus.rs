@@ -0,0 +1,143 @@
+use derivative::Derivative;
+
+use crate::{
+    codeable::{Code, Codeable},
+    hypergraph::{
+        generic::{Ctx, Edge, Key, Node, Thunk},
+        traits::{Graph, Keyable},
+    },
+};
+
+////////////////////////////////////////////////////////////////
+
+/// A graph drawn from inside one of its thunks, whose body stands in for the whole graph.
+///
+/// The thunk is kept by its key and looked up whenever the graph is walked, so that its body
+/// follows the graph beneath as that changes. While the thunk cannot be found, because it or a
+/// thunk around it is collapsed, the whole graph is shown.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct FocusGraph<G: Graph> {
+    graph: G,
+    focus: Option<Key<Thunk<G::Ctx>>>,
+}
+
+impl<G: Graph> FocusGraph<G> {
+    pub fn new(graph: G) -> Self {
+        Self { graph, focus: None }
+    }
+
+    pub fn inner(&self) -> &G {
+        &self.graph
+    }
+
+    pub fn inner_mut(&mut self) -> &mut G {
+        &mut self.graph
+    }
+
+    /// The thunk whose body is shown, if any.
+    pub fn focused(&self) -> Option<Thunk<G::Ctx>> {
+        find_thunk(&self.graph, self.focus.as_ref()?)
+    }
+
+    /// Shows the body of `thunk`, or the whole graph again if it is `None`.
+    pub fn set_focus(&mut self, thunk: Option<&Thunk<G::Ctx>>) {
+        self.focus = thunk.map(Keyable::key);
+    }
+}
+
+/// The thunk of `graph` with key `key`, looking inside the other thunks.
+fn find_thunk<T: Ctx>(graph: &impl Graph<Ctx = T>, key: &Key<T::Thunk>) -> Option<T::Thunk> {
+    graph.thunks().find_map(|thunk| {
+        if thunk.key() == *key {
+            Some(thunk)
+        } else {
+            find_thunk(&thunk, key)
+        }
+    })
+}
+
+/// Calls `$method` on the focused thunk, or on the whole graph if there is none.
+///
+/// The thunk is found afresh on each call, so the iterators it returns are collected before it
+/// goes out of scope.
+macro_rules! focused {
+    ($self:ident.$method:ident()) => {
+        match $self.focused() {
+            Some(thunk) => Box::new(thunk.$method().collect::<Vec<_>>().into_iter()),
+            None => $self.graph.$method(),
+        }
+    };
+}
+
+impl<G: Graph> Graph for FocusGraph<G> {
+    type Ctx = G::Ctx;
+
+    fn free_graph_inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        focused!(self.free_graph_inputs())
+    }
+
+    fn bound_graph_inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        focused!(self.bound_graph_inputs())
+    }
+
+    fn free_graph_outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        focused!(self.free_graph_outputs())
+    }
+
+    fn bound_graph_outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        focused!(self.bound_graph_outputs())
+    }
+
+    fn nodes(&self) -> Box<dyn DoubleEndedIterator<Item = Node<Self::Ctx>> + '_> {
+        focused!(self.nodes())
+    }
+
+    fn graph_backlink(&self) -> Option<Thunk<Self::Ctx>> {
+        self.focused().or_else(|| self.graph.graph_backlink())
+    }
+
+    fn number_of_free_graph_inputs(&self) -> usize {
+        self.focused().map_or_else(
+            || self.graph.number_of_free_graph_inputs(),
+            |thunk| thunk.number_of_free_graph_inputs(),
+        )
+    }
+
+    fn number_of_bound_graph_inputs(&self) -> usize {
+        self.focused().map_or_else(
+            || self.graph.number_of_bound_graph_inputs(),
+            |thunk| thunk.number_of_bound_graph_inputs(),
+        )
+    }
+
+    fn number_of_free_graph_outputs(&self) -> usize {
+        self.focused().map_or_else(
+            || self.graph.number_of_free_graph_outputs(),
+            |thunk| thunk.number_of_free_graph_outputs(),
+        )
+    }
+
+    fn number_of_bound_graph_outputs(&self) -> usize {
+        self.focused().map_or_else(
+            || self.graph.number_of_bound_graph_outputs(),
+            |thunk| thunk.number_of_bound_graph_outputs(),
+        )
+    }
+}
+
+impl<G: Graph> Keyable for FocusGraph<G> {
+    type Key = (Key<G>, Option<Key<Thunk<G::Ctx>>>);
+
+    fn key(&self) -> Self::Key {
+        (self.graph.key(), self.focus.clone())
+    }
+}
+
+impl<G: Graph + Codeable> Codeable for FocusGraph<G> {
+    type Code = Code<G>;
+
+    fn code(&self) -> Self::Code {
+        self.graph.code()
+    }
+}
@@ -0,0 +1,796 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    iter::successors,
+    sync::Arc,
+};
+
+use by_address::ByThinAddress;
+use derivative::Derivative;
+use itertools::Either;
+
+use crate::{
+    codeable::{Code, Codeable},
+    common::{Matchable, Query},
+    hypergraph::{
+        generic::{Ctx, Edge, Endpoint, Key, Node, Operation, Thunk, Weight},
+        subgraph::ExtensibleEdge,
+        traits::{EdgeLike, Graph, Keyable, NodeLike, WithWeight},
+    },
+};
+
+////////////////////////////////////////////////////////////////
+
+/// The operation using the only output of `op`, if it is the only use of the output and is in the
+/// same graph as `op`.
+fn successor<T: Ctx>(op: &T::Operation) -> Option<T::Operation> {
+    let mut outputs = op.outputs();
+    let output = outputs.next()?;
+    if outputs.next().is_some() {
+        return None;
+    }
+    let mut targets = output.targets();
+    let Some(Endpoint::Node(Node::Operation(target))) = targets.next() else {
+        return None;
+    };
+    (targets.next().is_none() && target.backlink() == op.backlink()).then_some(target)
+}
+
+/// The operation before `op` in a chain, which is the only operation whose successor is `op`.
+fn predecessor<T: Ctx>(op: &T::Operation) -> Option<T::Operation> {
+    let mut candidates = op.inputs().filter_map(|edge| match edge.source() {
+        Endpoint::Node(Node::Operation(source)) if successor::<T>(&source).as_ref() == Some(op) => {
+            Some(source)
+        }
+        _ => None,
+    });
+    let predecessor = candidates.next()?;
+    candidates.next().is_none().then_some(predecessor)
+}
+
+/// The operation after `op` in a chain.
+fn next<T: Ctx>(op: &T::Operation) -> Option<T::Operation> {
+    successor::<T>(op).filter(|next| predecessor::<T>(next).as_ref() == Some(op))
+}
+
+/// The chains of a graph: straight lines of at least two operations, in which the only output of
+/// each operation but the last is used only by the next.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct Chains<T: Ctx> {
+    /// Each chain, from its first operation to its last.
+    chains: Vec<Arc<[T::Operation]>>,
+    /// The chain each operation is in, for the operations in one.
+    chain_of: HashMap<Key<T::Operation>, usize>,
+}
+
+impl<T: Ctx> Chains<T> {
+    fn new(graph: &impl Graph<Ctx = T>) -> Self {
+        fn helper<T: Ctx>(graph: &impl Graph<Ctx = T>, chains: &mut Vec<Arc<[T::Operation]>>) {
+            for thunk in graph.thunks() {
+                helper(&thunk, chains);
+            }
+            for op in graph.operations() {
+                if predecessor::<T>(&op).is_some() {
+                    continue;
+                }
+                let chain = successors(Some(op), next::<T>).collect::<Vec<_>>();
+                if chain.len() > 1 {
+                    chains.push(chain.into());
+                }
+            }
+        }
+
+        let mut chains = Vec::new();
+        helper(graph, &mut chains);
+        let chain_of = chains
+            .iter()
+            .enumerate()
+            .flat_map(|(i, chain)| chain.iter().map(move |op| (op.key(), i)))
+            .collect();
+        Self { chains, chain_of }
+    }
+}
+
+/// The chains of a graph and which of them are folded, shared by the edges and nodes of a
+/// [`FoldGraph`].
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = ""),
+    Eq(bound = ""),
+    PartialEq(bound = ""),
+    Hash(bound = ""),
+    Debug(bound = "")
+)]
+pub struct Folding<T: Ctx> {
+    #[derivative(PartialEq = "ignore", Hash = "ignore", Debug = "ignore")]
+    chains: Arc<Chains<T>>,
+    folded: ByThinAddress<Arc<Vec<bool>>>,
+}
+
+impl<T: Ctx> Folding<T> {
+    /// The chain containing `op`, if it is folded.
+    fn folded_chain(&self, op: &T::Operation) -> Option<&Arc<[T::Operation]>> {
+        let &chain = self.chains.chain_of.get(&op.key())?;
+        self.folded[chain].then(|| &self.chains.chains[chain])
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct FoldGraph<G: Graph> {
+    graph: G,
+    folding: Folding<G::Ctx>,
+}
+
+impl<G: Graph> FoldGraph<G> {
+    /// Finds the chains of `graph`, none of which are folded at first.
+    pub fn new(graph: G) -> Self {
+        let chains = Chains::new(&graph);
+        let folded = vec![false; chains.chains.len()];
+        Self {
+            graph,
+            folding: Folding {
+                chains: Arc::new(chains),
+                folded: ByThinAddress(Arc::new(folded)),
+            },
+        }
+    }
+
+    pub fn inner(&self) -> &G {
+        &self.graph
+    }
+
+    pub fn inner_mut(&mut self) -> &mut G {
+        &mut self.graph
+    }
+
+    /// Folds or unfolds the chain containing `op`, returning false if it is in none.
+    pub fn toggle(&mut self, op: &Operation<G::Ctx>) -> bool {
+        let Some(&chain) = self.folding.chains.chain_of.get(&op.key()) else {
+            return false;
+        };
+        let mut folded = self.folding.folded.to_vec();
+        folded[chain] ^= true;
+        self.folding.folded = ByThinAddress(Arc::new(folded));
+        true
+    }
+
+    pub fn set_all(&mut self, value: bool) {
+        let folded = vec![value; self.folding.chains.chains.len()];
+        self.folding.folded = ByThinAddress(Arc::new(folded));
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = ""),
+    Eq(bound = ""),
+    PartialEq(bound = ""),
+    Hash(bound = ""),
+    Debug = "transparent"
+)]
+pub struct FoldEdge<G: Graph> {
+    edge: Edge<G::Ctx>,
+    #[derivative(Debug = "ignore")]
+    folding: Folding<G::Ctx>,
+}
+
+impl<G: Graph> FoldEdge<G> {
+    pub fn inner(&self) -> &Edge<G::Ctx> {
+        &self.edge
+    }
+
+    pub fn into_inner(self) -> Edge<G::Ctx> {
+        self.edge
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = ""),
+    Eq(bound = ""),
+    PartialEq(bound = ""),
+    Hash(bound = ""),
+    Debug(bound = "")
+)]
+pub enum FoldOperation<G: Graph> {
+    #[derivative(Debug = "transparent")]
+    Inner {
+        op: Operation<G::Ctx>,
+        #[derivative(Debug = "ignore")]
+        folding: Folding<G::Ctx>,
+    },
+    /// A folded chain, standing in for all of its operations.
+    Chain {
+        ops: Arc<[Operation<G::Ctx>]>,
+        #[derivative(Debug = "ignore")]
+        folding: Folding<G::Ctx>,
+    },
+}
+
+impl<G: Graph> FoldOperation<G> {
+    /// The operations this stands in for, in the order they are chained.
+    pub fn inner(&self) -> &[Operation<G::Ctx>] {
+        match self {
+            Self::Inner { op, .. } => std::slice::from_ref(op),
+            Self::Chain { ops, .. } => ops,
+        }
+    }
+
+    fn folding(&self) -> &Folding<G::Ctx> {
+        match self {
+            Self::Inner { folding, .. } | Self::Chain { folding, .. } => folding,
+        }
+    }
+
+    fn last(&self) -> &Operation<G::Ctx> {
+        // Chains are never empty
+        self.inner().last().unwrap()
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = ""),
+    Eq(bound = ""),
+    PartialEq(bound = ""),
+    Hash(bound = ""),
+    Debug = "transparent"
+)]
+pub struct FoldThunk<G: Graph> {
+    thunk: Thunk<G::Ctx>,
+    #[derivative(Debug = "ignore")]
+    folding: Folding<G::Ctx>,
+}
+
+impl<G: Graph> FoldThunk<G> {
+    pub fn inner(&self) -> &Thunk<G::Ctx> {
+        &self.thunk
+    }
+
+    pub fn into_inner(self) -> Thunk<G::Ctx> {
+        self.thunk
+    }
+}
+
+/// The weights of the operations of a folded chain, which display as a single label.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ChainWeight<W>(pub Vec<W>);
+
+impl<W: Display> Display for ChainWeight<W> {
+    /// Long chains are labelled by their ends and length.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.as_slice() {
+            [first, .., last] if self.0.len() > 3 => {
+                write!(f, "{first} → … → {last} ({})", self.0.len())
+            }
+            weights => {
+                for (i, weight) in weights.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" → ")?;
+                    }
+                    write!(f, "{weight}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+pub type FoldNode<G> = Node<FoldGraph<G>>;
+
+impl<G: Graph> FoldNode<G> {
+    fn new(node: Node<G::Ctx>, folding: Folding<G::Ctx>) -> Self {
+        match node {
+            Node::Operation(op) => match folding.folded_chain(&op) {
+                Some(ops) => Node::Operation(FoldOperation::Chain {
+                    ops: ops.clone(),
+                    folding,
+                }),
+                None => Node::Operation(FoldOperation::Inner { op, folding }),
+            },
+            Node::Thunk(thunk) => Node::Thunk(FoldThunk { thunk, folding }),
+        }
+    }
+
+    /// The nodes this stands in for, which are all the operations of a folded chain.
+    pub fn into_inner(self) -> Vec<Node<G::Ctx>> {
+        match self {
+            Node::Operation(op) => op.inner().iter().cloned().map(Node::Operation).collect(),
+            Node::Thunk(thunk) => vec![Node::Thunk(thunk.into_inner())],
+        }
+    }
+}
+
+/// The nodes of a graph with `nodes`, with each folded chain in place of its first operation.
+fn fold_nodes<'a, G: Graph>(
+    nodes: Box<dyn DoubleEndedIterator<Item = Node<G::Ctx>> + 'a>,
+    folding: &'a Folding<G::Ctx>,
+) -> Box<dyn DoubleEndedIterator<Item = FoldNode<G>> + 'a> {
+    Box::new(nodes.filter_map(move |node| {
+        if let Node::Operation(op) = &node {
+            if let Some(ops) = folding.folded_chain(op) {
+                return (ops[0] == *op).then(|| {
+                    Node::Operation(FoldOperation::Chain {
+                        ops: ops.clone(),
+                        folding: folding.clone(),
+                    })
+                });
+            }
+        }
+        Some(Node::new(node, folding.clone()))
+    }))
+}
+
+////////////////////////////////////////////////////////////////
+
+pub type FoldEndpoint<G> = Endpoint<FoldGraph<G>>;
+
+impl<G: Graph> FoldEndpoint<G> {
+    pub fn new(endpoint: Endpoint<G::Ctx>, folding: Folding<G::Ctx>) -> Self {
+        match endpoint {
+            Endpoint::Node(node) => Endpoint::Node(Node::new(node, folding)),
+            Endpoint::Boundary(graph) => {
+                Endpoint::Boundary(graph.map(|thunk| FoldThunk { thunk, folding }))
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl<G: Graph> Ctx for FoldGraph<G> {
+    type Edge = FoldEdge<G>;
+    type Operation = FoldOperation<G>;
+    type Thunk = FoldThunk<G>;
+}
+
+impl<G: Graph> Graph for FoldGraph<G> {
+    type Ctx = FoldGraph<G>;
+
+    fn free_graph_inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.graph.free_graph_inputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn bound_graph_inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(std::iter::empty())
+    }
+
+    fn free_graph_outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(std::iter::empty())
+    }
+
+    fn bound_graph_outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.graph.bound_graph_outputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn nodes(&self) -> Box<dyn DoubleEndedIterator<Item = Node<Self::Ctx>> + '_> {
+        fold_nodes(self.graph.nodes(), &self.folding)
+    }
+
+    fn graph_backlink(&self) -> Option<Thunk<Self::Ctx>> {
+        None
+    }
+
+    fn number_of_free_graph_inputs(&self) -> usize {
+        self.graph.number_of_free_graph_inputs()
+    }
+
+    fn number_of_bound_graph_inputs(&self) -> usize {
+        0
+    }
+
+    fn number_of_free_graph_outputs(&self) -> usize {
+        0
+    }
+
+    fn number_of_bound_graph_outputs(&self) -> usize {
+        self.graph.number_of_bound_graph_outputs()
+    }
+}
+
+impl<G: Graph> EdgeLike for FoldEdge<G> {
+    type Ctx = FoldGraph<G>;
+
+    fn source(&self) -> Endpoint<Self::Ctx> {
+        FoldEndpoint::new(self.edge.source(), self.folding.clone())
+    }
+
+    fn targets(&self) -> Box<dyn DoubleEndedIterator<Item = Endpoint<Self::Ctx>> + '_> {
+        Box::new(
+            self.edge
+                .targets()
+                .map(|endpoint| FoldEndpoint::new(endpoint, self.folding.clone())),
+        )
+    }
+}
+
+impl<G: Graph> NodeLike for FoldOperation<G> {
+    type Ctx = FoldGraph<G>;
+
+    /// The inputs of a folded chain are those of its operations, except for the outputs of the
+    /// operations before them.
+    fn inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        let ops = self.inner();
+        let links = ops[..ops.len() - 1]
+            .iter()
+            .flat_map(NodeLike::outputs)
+            .collect::<HashSet<_>>();
+        Box::new(
+            ops.iter()
+                .flat_map(NodeLike::inputs)
+                .filter(move |edge| !links.contains(edge))
+                .map(|edge| FoldEdge {
+                    edge,
+                    folding: self.folding().clone(),
+                }),
+        )
+    }
+
+    fn outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.last().outputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding().clone(),
+        }))
+    }
+
+    fn backlink(&self) -> Option<Thunk<Self::Ctx>> {
+        self.inner()[0].backlink().map(|thunk| FoldThunk {
+            thunk,
+            folding: self.folding().clone(),
+        })
+    }
+
+    fn number_of_inputs(&self) -> usize {
+        match self {
+            Self::Inner { op, .. } => op.number_of_inputs(),
+            Self::Chain { .. } => self.inputs().count(), // can't do any better
+        }
+    }
+
+    fn number_of_outputs(&self) -> usize {
+        self.last().number_of_outputs()
+    }
+}
+
+impl<G: Graph> Graph for FoldThunk<G> {
+    type Ctx = FoldGraph<G>;
+
+    fn free_graph_inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.thunk.free_graph_inputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn bound_graph_inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.thunk.bound_graph_inputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn free_graph_outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.thunk.free_graph_outputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn bound_graph_outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.thunk.bound_graph_outputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn nodes(&self) -> Box<dyn DoubleEndedIterator<Item = Node<Self::Ctx>> + '_> {
+        fold_nodes(self.thunk.nodes(), &self.folding)
+    }
+
+    fn graph_backlink(&self) -> Option<Thunk<Self::Ctx>> {
+        Some(self.clone())
+    }
+
+    fn number_of_free_graph_inputs(&self) -> usize {
+        self.thunk.number_of_free_graph_inputs()
+    }
+
+    fn number_of_bound_graph_inputs(&self) -> usize {
+        self.thunk.number_of_bound_graph_inputs()
+    }
+
+    fn number_of_free_graph_outputs(&self) -> usize {
+        self.thunk.number_of_free_graph_outputs()
+    }
+
+    fn number_of_bound_graph_outputs(&self) -> usize {
+        self.thunk.number_of_bound_graph_outputs()
+    }
+}
+
+impl<G: Graph> NodeLike for FoldThunk<G> {
+    type Ctx = FoldGraph<G>;
+
+    fn inputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.thunk.inputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn outputs(&self) -> Box<dyn DoubleEndedIterator<Item = Edge<Self::Ctx>> + '_> {
+        Box::new(self.thunk.outputs().map(|edge| FoldEdge {
+            edge,
+            folding: self.folding.clone(),
+        }))
+    }
+
+    fn backlink(&self) -> Option<Thunk<Self::Ctx>> {
+        self.thunk.backlink().map(|thunk| FoldThunk {
+            thunk,
+            folding: self.folding.clone(),
+        })
+    }
+
+    fn number_of_inputs(&self) -> usize {
+        self.thunk.number_of_inputs()
+    }
+
+    fn number_of_outputs(&self) -> usize {
+        self.thunk.number_of_outputs()
+    }
+}
+
+impl<G: Graph + Codeable> Codeable for FoldGraph<G> {
+    type Code = Code<G>;
+
+    fn code(&self) -> Self::Code {
+        self.graph.code()
+    }
+}
+
+impl<G: Graph> Codeable for FoldEdge<G>
+where
+    Edge<G::Ctx>: Codeable,
+{
+    type Code = Code<Edge<G::Ctx>>;
+
+    fn code(&self) -> Self::Code {
+        self.edge.code()
+    }
+}
+
+impl<G: Graph> Codeable for FoldOperation<G>
+where
+    Operation<G::Ctx>: Codeable,
+{
+    type Code = Either<Code<Operation<G::Ctx>>, Vec<Code<Operation<G::Ctx>>>>;
+
+    fn code(&self) -> Self::Code {
+        match self {
+            Self::Inner { op, .. } => Either::Left(op.code()),
+            Self::Chain { ops, .. } => Either::Right(ops.iter().map(Codeable::code).collect()),
+        }
+    }
+}
+
+impl<G: Graph> Codeable for FoldThunk<G>
+where
+    Thunk<G::Ctx>: Codeable,
+{
+    type Code = Code<Thunk<G::Ctx>>;
+
+    fn code(&self) -> Self::Code {
+        self.thunk.code()
+    }
+}
+
+impl<G: Graph> Matchable for FoldEdge<G>
+where
+    Edge<G::Ctx>: Matchable,
+{
+    fn is_match(&self, query: &Query) -> bool {
+        self.edge.is_match(query)
+    }
+}
+
+/// Folded chains match if any of their operations do.
+impl<G: Graph> Matchable for FoldOperation<G>
+where
+    Operation<G::Ctx>: Matchable,
+{
+    fn is_match(&self, query: &Query) -> bool {
+        self.inner().iter().any(|op| op.is_match(query))
+    }
+}
+
+impl<G: Graph> Matchable for FoldThunk<G>
+where
+    Thunk<G::Ctx>: Matchable,
+{
+    fn is_match(&self, query: &Query) -> bool {
+        self.thunk.is_match(query)
+    }
+}
+
+impl<G: Graph> Keyable for FoldGraph<G> {
+    type Key = (Key<G>, ByThinAddress<Arc<Vec<bool>>>);
+
+    fn key(&self) -> Self::Key {
+        (self.graph.key(), self.folding.folded.clone())
+    }
+}
+
+impl<G: Graph> Keyable for FoldEdge<G> {
+    type Key = Key<Edge<G::Ctx>>;
+
+    fn key(&self) -> Self::Key {
+        self.edge.key()
+    }
+}
+
+impl<G: Graph> Keyable for FoldOperation<G> {
+    type Key = Either<Key<Operation<G::Ctx>>, Key<Operation<G::Ctx>>>;
+
+    /// Folded chains are keyed by their first operation.
+    fn key(&self) -> Self::Key {
+        match self {
+            Self::Inner { op, .. } => Either::Left(op.key()),
+            Self::Chain { ops, .. } => Either::Right(ops[0].key()),
+        }
+    }
+}
+
+impl<G: Graph> Keyable for FoldThunk<G> {
+    type Key = Key<Thunk<G::Ctx>>;
+
+    fn key(&self) -> Self::Key {
+        self.thunk.key()
+    }
+}
+
+impl<G: Graph> WithWeight for FoldEdge<G> {
+    type Weight = Weight<Edge<G::Ctx>>;
+
+    fn weight(&self) -> Self::Weight {
+        self.edge.weight()
+    }
+}
+
+impl<G: Graph> WithWeight for FoldOperation<G> {
+    type Weight = Either<Weight<Operation<G::Ctx>>, ChainWeight<Weight<Operation<G::Ctx>>>>;
+
+    fn weight(&self) -> Self::Weight {
+        match self {
+            Self::Inner { op, .. } => Either::Left(op.weight()),
+            Self::Chain { ops, .. } => {
+                Either::Right(ChainWeight(ops.iter().map(WithWeight::weight).collect()))
+            }
+        }
+    }
+}
+
+impl<G: Graph> WithWeight for FoldThunk<G> {
+    type Weight = Weight<Thunk<G::Ctx>>;
+
+    fn weight(&self) -> Self::Weight {
+        self.thunk.weight()
+    }
+}
+
+impl<G: Graph> ExtensibleEdge for FoldEdge<G>
+where
+    Edge<G::Ctx>: ExtensibleEdge,
+{
+    fn extend_source(&self) -> Option<Node<Self::Ctx>> {
+        self.inner()
+            .extend_source()
+            .map(|node| Node::new(node, self.folding.clone()))
+    }
+
+    fn extend_targets(&self) -> Box<dyn DoubleEndedIterator<Item = Node<Self::Ctx>> + '_> {
+        Box::new(
+            self.inner()
+                .extend_targets()
+                .map(|node| Node::new(node, self.folding.clone())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::FoldGraph;
+    use crate::{
+        graph::SyntaxHypergraph,
+        hypergraph::{
+            generic::{Endpoint, Node},
+            traits::{EdgeLike, Graph, NodeLike, WithWeight},
+        },
+        language::spartan::{Expr, Op, Rule, Spartan, SpartanParser},
+    };
+
+    type SpartanFoldGraph = FoldGraph<SyntaxHypergraph<Spartan>>;
+
+    fn fold(source: &str) -> Result<SpartanFoldGraph, Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, source)?;
+        let mut graph = FoldGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        graph.set_all(true);
+        Ok(graph)
+    }
+
+    /// The weights of the operations each operation of `graph` stands in for.
+    fn chains(graph: &impl Graph<Ctx = SpartanFoldGraph>) -> Vec<Vec<Op>> {
+        graph
+            .operations()
+            .map(|op| op.inner().iter().map(WithWeight::weight).collect())
+            .collect()
+    }
+
+    #[test]
+    fn chains_inside_thunks() -> Result<(), Box<dyn std::error::Error>> {
+        let mut graph = fold(
+            "bind f = lambda(y .
+               bind a = plus(y, y) in
+               bind b = times(a, y) in
+               bind c = minus(b, y) in
+               c) in
+             f",
+        )?;
+        let thunk = graph.thunks().next().ok_or("no thunk")?;
+        assert_eq!(chains(&thunk), vec![vec![Op::Plus, Op::Times, Op::Minus]]);
+        let chain = thunk.operations().next().ok_or("no chain")?;
+        assert_eq!(chain.backlink(), Some(thunk.clone()));
+        assert_eq!(chains(&graph), vec![vec![Op::Lambda]]);
+
+        // The thunk and the lambda using it are in no chain
+        let lambda = graph.operations().next().ok_or("no lambda")?.inner()[0].clone();
+        assert!(!graph.toggle(&lambda));
+        assert!(graph.toggle(&chain.inner()[1]));
+        let thunk = graph.thunks().next().ok_or("no thunk")?;
+        assert_eq!(chains(&thunk).len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn chain_with_extra_inputs() -> Result<(), Box<dyn std::error::Error>> {
+        let graph =
+            fold("bind a = plus(x, y) in bind b = times(a, z) in bind c = minus(b, w) in c")?;
+        assert_eq!(chains(&graph), vec![vec![Op::Plus, Op::Times, Op::Minus]]);
+        let chain = graph.operations().next().ok_or("no chain")?;
+        // Only the links between the operations of the chain are hidden
+        assert_eq!(chain.number_of_inputs(), 4);
+        for input in graph.free_graph_inputs() {
+            assert!(input
+                .targets()
+                .all(|target| target == Endpoint::Node(Node::Operation(chain.clone()))));
+        }
+
+        // An operation with two operations before it starts a chain of its own
+        let graph = fold(
+            "bind a = plus(x, y) in
+             bind d = div(x, y) in
+             bind b = times(a, d) in
+             bind c = minus(b, w) in
+             c",
+        )?;
+        let chains = chains(&graph);
+        assert_eq!(chains.len(), 3);
+        assert!(chains.contains(&vec![Op::Times, Op::Minus]));
+        Ok(())
+    }
+}
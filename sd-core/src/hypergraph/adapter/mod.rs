@@ -2,4 +2,6 @@
 
 pub mod collapse;
 pub mod cut;
+pub mod focus;
+pub mod fold;
 pub mod selectable;
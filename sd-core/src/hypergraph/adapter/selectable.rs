@@ -39,6 +39,10 @@ impl<G: Graph> SelectableGraph<G> {
         &mut self.graph
     }
 
+    pub fn selection(&self) -> &SelectionMap<G::Ctx> {
+        &self.selection
+    }
+
     pub fn to_subgraph(&self) -> Subgraph<G::Ctx> {
         Subgraph::new(self.selection.clone())
     }
@@ -53,6 +57,7 @@ impl<G: Graph> SelectableGraph<G> {
 
             pub fn is_empty(&self) -> bool;
             pub fn clear_selection(&mut self);
+            pub fn select_all(&mut self);
             pub fn extend_selection(&mut self, direction: Option<(Direction, usize)>);
         }
     }
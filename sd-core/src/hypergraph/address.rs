@@ -0,0 +1,356 @@
+//! Stable addresses for the nodes of a hypergraph.
+//!
+//! Nodes are identified in memory by their allocation, which changes every time a program is
+//! compiled. A [`NodeAddress`] instead names a node by its syntactic path: the thunks enclosing
+//! it, outermost first, followed by the node itself. Each step of the path is the label of a node
+//! (the weight of the operation or thunk) together with how many siblings with the same label
+//! come before it in the source.
+//!
+//! Compiling the same program twice therefore gives every node the same address, and editing one
+//! part of a program leaves the addresses of nodes elsewhere unchanged unless the edit adds or
+//! removes same-labelled siblings before them.
+//!
+//! Addresses are written as their steps separated by `/`, where a step is the label followed by
+//! `#` and the occurrence when it is not the first, for example `%3/add#1`. Any `/`, `#` or `\`
+//! in a label is escaped with a `\`. Labels may be empty, as thunks have no label in some
+//! languages, so a node in the second unlabelled thunk is written like `#1/plus`.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use indexmap::IndexMap;
+use thiserror::Error;
+
+use super::{
+    generic::{Ctx, Node, Operation, Thunk, Weight},
+    traits::{Graph, NodeLike, WithWeight},
+};
+
+/// One step of a [`NodeAddress`]: the `occurrence`th node labelled `label` in its thunk.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Step {
+    pub label: String,
+    pub occurrence: usize,
+}
+
+/// The syntactic path of a node, which survives recompiling the program it came from.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default)]
+pub struct NodeAddress(pub Vec<Step>);
+
+impl Display for NodeAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+            for c in step.label.chars() {
+                if matches!(c, '/' | '#' | '\\') {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+            if step.occurrence > 0 {
+                write!(f, "#{}", step.occurrence)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+#[error("invalid node address `{0}`")]
+pub struct AddressParseError(String);
+
+impl FromStr for NodeAddress {
+    type Err = AddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || AddressParseError(s.to_owned());
+        if s.is_empty() {
+            return Err(err());
+        }
+        let mut steps = Vec::new();
+        let mut label = String::new();
+        let mut occurrence: Option<String> = None;
+        let mut chars = s.chars();
+        loop {
+            match chars.next() {
+                // Occurrences are only ever digits, so an escape there is left for them to reject
+                Some('\\') if occurrence.is_none() => label.push(chars.next().ok_or_else(err)?),
+                Some('#') if occurrence.is_none() => occurrence = Some(String::new()),
+                Some(c @ ('/' | '#')) | c @ None => {
+                    if c == Some('#') {
+                        return Err(err());
+                    }
+                    let occurrence = occurrence
+                        .take()
+                        .map_or(Ok(0), |occurrence| occurrence.parse())
+                        .map_err(|_| err())?;
+                    steps.push(Step {
+                        label: std::mem::take(&mut label),
+                        occurrence,
+                    });
+                    if c.is_none() {
+                        return Ok(Self(steps));
+                    }
+                }
+                Some(c) => match &mut occurrence {
+                    Some(occurrence) => occurrence.push(c),
+                    None => label.push(c),
+                },
+            }
+        }
+    }
+}
+
+pub(crate) fn label<T: Ctx>(node: &Node<T>) -> String
+where
+    Operation<T>: WithWeight,
+    Thunk<T>: WithWeight,
+    Weight<Operation<T>>: Display,
+    Weight<Thunk<T>>: Display,
+{
+    match node {
+        Node::Operation(op) => op.weight().to_string(),
+        Node::Thunk(thunk) => thunk.weight().to_string(),
+    }
+}
+
+/// The nodes of `graph` with their steps, in the order they appear.
+fn steps<T: Ctx>(graph: &impl Graph<Ctx = T>) -> Vec<(Step, Node<T>)>
+where
+    Operation<T>: WithWeight,
+    Thunk<T>: WithWeight,
+    Weight<Operation<T>>: Display,
+    Weight<Thunk<T>>: Display,
+{
+    let mut seen = HashMap::<String, usize>::new();
+    graph
+        .nodes()
+        .map(|node| {
+            let label = label(&node);
+            let count = seen.entry(label.clone()).or_default();
+            let step = Step {
+                label,
+                occurrence: *count,
+            };
+            *count += 1;
+            (step, node)
+        })
+        .collect()
+}
+
+/// The addresses of all nodes of `graph`, including those nested in thunks.
+pub fn addresses<G: Graph>(graph: &G) -> IndexMap<NodeAddress, Node<G::Ctx>>
+where
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    fn helper<T: Ctx>(
+        graph: &impl Graph<Ctx = T>,
+        prefix: &[Step],
+        addresses: &mut IndexMap<NodeAddress, Node<T>>,
+    ) where
+        Operation<T>: WithWeight,
+        Thunk<T>: WithWeight,
+        Weight<Operation<T>>: Display,
+        Weight<Thunk<T>>: Display,
+    {
+        for (step, node) in steps(graph) {
+            let mut address = prefix.to_vec();
+            address.push(step);
+            if let Node::Thunk(thunk) = &node {
+                helper(thunk, &address, addresses);
+            }
+            addresses.insert(NodeAddress(address), node);
+        }
+    }
+
+    let mut addresses = IndexMap::new();
+    helper(graph, &[], &mut addresses);
+    addresses
+}
+
+/// The address of `node`, which must belong to `graph` or one of its thunks.
+pub fn address_of<G: Graph>(graph: &G, node: &Node<G::Ctx>) -> Option<NodeAddress>
+where
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    let step = |node: &Node<G::Ctx>| {
+        match node.backlink() {
+            Some(thunk) => steps(&thunk),
+            None => steps(graph),
+        }
+        .into_iter()
+        .find_map(|(step, sibling)| (sibling == *node).then_some(step))
+    };
+
+    let mut address = vec![step(node)?];
+    let mut backlink = node.backlink();
+    while let Some(thunk) = backlink {
+        backlink = thunk.backlink();
+        address.push(step(&Node::Thunk(thunk))?);
+    }
+    address.reverse();
+    Some(NodeAddress(address))
+}
+
+/// The node of `graph` at `address`, if there is one.
+pub fn node_at<G: Graph>(graph: &G, address: &NodeAddress) -> Option<Node<G::Ctx>>
+where
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    let find = |steps: Vec<(Step, Node<G::Ctx>)>, target: &Step| {
+        steps
+            .into_iter()
+            .find_map(|(step, node)| (step == *target).then_some(node))
+    };
+
+    let (first, rest) = address.0.split_first()?;
+    let mut node = find(steps(graph), first)?;
+    for target in rest {
+        let Node::Thunk(thunk) = node else {
+            return None;
+        };
+        node = find(steps(&thunk), target)?;
+    }
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use dir_test::{dir_test, Fixture};
+
+    use std::fmt::Display;
+
+    use super::{address_of, addresses, node_at, NodeAddress, Step};
+    use crate::{
+        hypergraph::{
+            generic::{Operation, Thunk, Weight},
+            traits::{Graph, WithWeight},
+        },
+        language::{
+            chil::{tests::parse_chil, Expr},
+            mlir::{
+                self,
+                internal::{Operation as MlirOperation, TopLevelItem},
+            },
+            spartan,
+        },
+    };
+
+    /// Checks that the address of every node of `graph` parses back to itself, and leads back to
+    /// the node.
+    fn assert_round_trip<G: Graph>(graph: &G)
+    where
+        Operation<G::Ctx>: WithWeight,
+        Thunk<G::Ctx>: WithWeight,
+        Weight<Operation<G::Ctx>>: Display,
+        Weight<Thunk<G::Ctx>>: Display,
+    {
+        for (address, node) in &addresses(graph) {
+            assert_eq!(address.to_string().parse().as_ref(), Ok(address));
+            assert_eq!(node_at(graph, address).as_ref(), Some(node));
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for address in ["add", "%3/add#1", "%3#2/%4/bool\\/and", "a\\#b#3"] {
+            assert_eq!(address.parse::<NodeAddress>().unwrap().to_string(), address);
+        }
+        assert!("".parse::<NodeAddress>().is_err());
+        assert!("add#x".parse::<NodeAddress>().is_err());
+        assert!("add#1#2".parse::<NodeAddress>().is_err());
+        assert_eq!("a\\#b#3".parse::<NodeAddress>().unwrap().0[0].label, "a#b");
+    }
+
+    #[test]
+    fn empty_labels() {
+        for address in ["/plus", "#1/plus", "%3//add", "/"] {
+            assert_eq!(address.parse::<NodeAddress>().unwrap().to_string(), address);
+        }
+        let address = "#1/plus".parse::<NodeAddress>().unwrap();
+        assert_eq!(address.0[0].label, "");
+        assert_eq!(address.0[0].occurrence, 1);
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let address = NodeAddress(vec![
+            Step {
+                label: "a/b#c\\d".to_owned(),
+                occurrence: 2,
+            },
+            Step {
+                label: "#".to_owned(),
+                occurrence: 0,
+            },
+        ]);
+        assert_eq!(address.to_string(), "a\\/b\\#c\\\\d#2/\\#");
+        assert_eq!(address.to_string().parse::<NodeAddress>(), Ok(address));
+
+        // Escapes belong to labels, so are not read as part of an occurrence
+        assert!("a#\\1".parse::<NodeAddress>().is_err());
+        assert!("a#1\\/b".parse::<NodeAddress>().is_err());
+        assert!("a#1\\".parse::<NodeAddress>().is_err());
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.chil", loader: crate::language::chil::tests::parse_chil, postfix: "stable_addresses")]
+    fn stable_addresses(fixture: Fixture<(&str, Expr)>) {
+        let (_name, expr) = fixture.content();
+        let graph = expr.to_graph(false).unwrap();
+        let addresses = addresses(&graph);
+
+        for (address, node) in &addresses {
+            assert_eq!(address_of(&graph, node).as_ref(), Some(address));
+            assert_eq!(node_at(&graph, address).as_ref(), Some(node));
+        }
+
+        // Compiling the program again gives the same addresses
+        let (_, expr) = parse_chil(fixture.path());
+        let recompiled = expr.to_graph(false).unwrap();
+        assert!(super::addresses(&recompiled).keys().eq(addresses.keys()));
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.chil", loader: crate::language::chil::tests::parse_chil, postfix: "chil_addresses_round_trip")]
+    fn chil_addresses_round_trip(fixture: Fixture<(&str, Expr)>) {
+        let (_name, expr) = fixture.content();
+        assert_round_trip(&expr.to_graph(false).unwrap());
+    }
+
+    /// Spartan thunks have no label, so nodes inside them have addresses with empty steps.
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.sd", loader: crate::language::spartan::tests::parse_sd, postfix: "spartan_addresses_round_trip")]
+    fn spartan_addresses_round_trip(fixture: Fixture<(&str, spartan::Expr)>) {
+        let (_name, expr) = fixture.content();
+        // Some of the examples are rejected on purpose
+        if let Ok(graph) = expr.to_graph(false) {
+            assert_round_trip(&graph);
+        }
+    }
+
+    /// As for Spartan, the regions of MLIR operations have no label.
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.mlir", loader: crate::language::mlir::internal::tests::parse_mlir, postfix: "mlir_addresses_round_trip")]
+    fn mlir_addresses_round_trip(fixture: Fixture<(&str, Vec<TopLevelItem>)>) {
+        let (_name, items) = fixture.into_content();
+        let ops: Vec<MlirOperation> = items
+            .into_iter()
+            .filter_map(|item| match item {
+                TopLevelItem::Operation(op) => Some(op),
+                TopLevelItem::Other(_) => None,
+            })
+            .collect();
+        assert_round_trip(&mlir::Expr::from(ops).to_graph(false).unwrap());
+    }
+}
@@ -13,7 +13,7 @@ use super::{
     traits::{EdgeLike, Graph, NodeLike},
     Hypergraph, Node, Operation, Thunk, Weight,
 };
-use crate::hypergraph::{utils::find_ancestor, EndPoint};
+use crate::hypergraph::{reachability::find_cycle, utils::find_ancestor, EndPoint};
 
 pub mod fragment;
 pub use self::fragment::Fragment;
@@ -40,6 +40,8 @@ pub enum HypergraphBuildError<W: Weight> {
     UninitializedOutPort(OutPort<W>),
     #[error("Strong cycle of linked ports detected")]
     StrongCycle,
+    #[error("Cycle of data wires through nodes: {0:#?}")]
+    Cycle(Vec<Node<W>>),
 }
 
 #[derive(Derivative)]
@@ -448,6 +450,14 @@ impl<W: Weight> HypergraphBuilder<W> {
             },
         )?;
 
+        // Nothing stops nodes being linked round in circles, which leaves no order to evaluate
+        // them in
+        if let Some(cycle) = find_cycle(&self.0) {
+            return Err(HypergraphError::BuildError(HypergraphBuildError::Cycle(
+                cycle,
+            )));
+        }
+
         Ok(self.0)
     }
 }
@@ -0,0 +1,288 @@
+//! Structural differences between two versions of a hypergraph, such as a program before and
+//! after a compiler pass.
+//!
+//! Nodes of the old graph are matched with nodes of the new graph one thunk at a time, starting
+//! with the top level of both graphs and continuing into each pair of matched thunks. Within a
+//! thunk, a node is first matched with a node of the same label whose inputs come from the
+//! counterparts of its own sources, over and over until no more pairs are found. The nodes left
+//! are then paired with the remaining nodes of the same label in the order they appear, and are
+//! marked as [`Change::Changed`] if they are wired differently. Anything still unmatched was added
+//! or removed, along with everything inside it.
+
+use std::{collections::HashMap, fmt::Display};
+
+use derivative::Derivative;
+
+use super::{
+    address::label,
+    generic::{Ctx, Edge, Endpoint, Node, Operation, Thunk, Weight},
+    traits::{EdgeLike, Graph, NodeLike, WithWeight},
+};
+
+/// How a node differs between the two graphs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Change {
+    /// The node is only in the new graph.
+    Added,
+    /// The node is only in the old graph.
+    Removed,
+    /// The node is in both graphs, but its inputs come from different places.
+    Changed,
+}
+
+/// The matching between the nodes of two graphs, and how the nodes which are not the same in
+/// both have changed.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), Default(bound = ""))]
+pub struct Diff<T: Ctx> {
+    /// The node of the new graph matched with each node of the old graph.
+    pub matching: HashMap<Node<T>, Node<T>>,
+    /// The change to each node of either graph which is not the same in the other.
+    pub changes: HashMap<Node<T>, Change>,
+}
+
+impl<T: Ctx> Diff<T> {
+    /// Whether the two graphs have the same structure.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// How many nodes have changed in the way given, counting each pair of changed nodes once.
+    #[must_use]
+    pub fn count(&self, change: Change) -> usize {
+        let count = self
+            .changes
+            .values()
+            .filter(|other| **other == change)
+            .count();
+        match change {
+            Change::Changed => count / 2,
+            Change::Added | Change::Removed => count,
+        }
+    }
+}
+
+/// Where the value on a wire comes from: an output of a node, or an input of the whole graph
+/// (`None`) or of a thunk.
+#[derive(Derivative)]
+#[derivative(PartialEq(bound = ""), Eq(bound = ""))]
+enum Source<T: Ctx> {
+    Output(Node<T>, usize),
+    Input(Option<Node<T>>, usize),
+}
+
+fn source<T: Ctx>(graph: &impl Graph<Ctx = T>, edge: &Edge<T>) -> Option<Source<T>> {
+    match edge.source() {
+        Endpoint::Node(node) => {
+            let port = node.outputs().position(|output| output == *edge)?;
+            Some(Source::Output(node, port))
+        }
+        Endpoint::Boundary(Some(thunk)) => {
+            let port = thunk
+                .bound_graph_inputs()
+                .position(|input| input == *edge)?;
+            Some(Source::Input(Some(Node::Thunk(thunk)), port))
+        }
+        Endpoint::Boundary(None) => {
+            let port = graph.graph_inputs().position(|input| input == *edge)?;
+            Some(Source::Input(None, port))
+        }
+    }
+}
+
+struct Matcher<'a, G: Graph> {
+    old: &'a G,
+    new: &'a G,
+    diff: Diff<G::Ctx>,
+}
+
+impl<G: Graph> Matcher<'_, G>
+where
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    /// Whether the inputs of `old` come from the counterparts of the sources of the inputs of
+    /// `new`, and both have as many outputs.
+    fn same_wiring(&self, old: &Node<G::Ctx>, new: &Node<G::Ctx>) -> bool {
+        let counterpart = |node: &Node<G::Ctx>| self.diff.matching.get(node).cloned();
+        let translate = |edge: Edge<G::Ctx>| match source(self.old, &edge)? {
+            Source::Output(node, port) => Some(Source::Output(counterpart(&node)?, port)),
+            Source::Input(Some(thunk), port) => {
+                Some(Source::Input(Some(counterpart(&thunk)?), port))
+            }
+            Source::Input(None, port) => Some(Source::Input(None, port)),
+        };
+        old.number_of_outputs() == new.number_of_outputs()
+            && old.number_of_inputs() == new.number_of_inputs()
+            && old.inputs().zip(new.inputs()).all(|(old, new)| {
+                translate(old).is_some_and(|old| Some(old) == source(self.new, &new))
+            })
+    }
+
+    /// Marks `node` and everything inside it as changed in the same way.
+    fn mark(&mut self, node: Node<G::Ctx>, change: Change) {
+        if let Node::Thunk(thunk) = &node {
+            for inner in thunk.nodes() {
+                self.mark(inner, change);
+            }
+        }
+        self.diff.changes.insert(node, change);
+    }
+
+    /// Matches the nodes of the same thunk, or the top level, of both graphs.
+    fn scope(
+        &mut self,
+        old: impl Iterator<Item = Node<G::Ctx>>,
+        new: impl Iterator<Item = Node<G::Ctx>>,
+    ) {
+        let mut old: Vec<_> = old.map(|node| Some((label(&node), node))).collect();
+        let mut new: Vec<_> = new.map(|node| Some((label(&node), node))).collect();
+        let mut pairs = Vec::new();
+
+        // Pairs which are wired the same, each of which may let more nodes below them be paired
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for slot in &mut new {
+                let Some((label, node)) = slot.as_ref() else {
+                    continue;
+                };
+                let Some(i) = old.iter().position(|other| {
+                    other.as_ref().is_some_and(|(other_label, other)| {
+                        other_label == label && self.same_wiring(other, node)
+                    })
+                }) else {
+                    continue;
+                };
+                if let (Some((_, old_node)), Some((_, new_node))) = (old[i].take(), slot.take()) {
+                    self.diff
+                        .matching
+                        .insert(old_node.clone(), new_node.clone());
+                    pairs.push((old_node, new_node));
+                    progress = true;
+                }
+            }
+        }
+
+        // Pairs with the same label, in order
+        for slot in &mut new {
+            let Some((label, _)) = slot.as_ref() else {
+                continue;
+            };
+            let Some(i) = old.iter().position(|other| {
+                other
+                    .as_ref()
+                    .is_some_and(|(other_label, _)| other_label == label)
+            }) else {
+                continue;
+            };
+            if let (Some((_, old_node)), Some((_, new_node))) = (old[i].take(), slot.take()) {
+                self.diff
+                    .matching
+                    .insert(old_node.clone(), new_node.clone());
+                pairs.push((old_node, new_node));
+            }
+        }
+
+        for (_, node) in old.into_iter().flatten() {
+            self.mark(node, Change::Removed);
+        }
+        for (_, node) in new.into_iter().flatten() {
+            self.mark(node, Change::Added);
+        }
+        for (old_node, new_node) in &pairs {
+            if !self.same_wiring(old_node, new_node) {
+                self.diff.changes.insert(old_node.clone(), Change::Changed);
+                self.diff.changes.insert(new_node.clone(), Change::Changed);
+            }
+        }
+        for (old_node, new_node) in pairs {
+            if let (Node::Thunk(old_thunk), Node::Thunk(new_thunk)) = (old_node, new_node) {
+                self.scope(old_thunk.nodes(), new_thunk.nodes());
+            }
+        }
+    }
+}
+
+/// Matches the nodes of `old` with the nodes of `new` by their labels and how they are wired.
+pub fn diff<G: Graph>(old: &G, new: &G) -> Diff<G::Ctx>
+where
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    let mut matcher = Matcher {
+        old,
+        new,
+        diff: Diff::default(),
+    };
+    matcher.scope(old.nodes(), new.nodes());
+    matcher.diff
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::{diff, Change};
+    use crate::{
+        graph::SyntaxHypergraph,
+        hypergraph::{
+            generic::Node,
+            traits::{Graph, WithWeight},
+        },
+        language::spartan::{Expr, Op, Rule, Spartan, SpartanParser},
+    };
+
+    fn parse(source: &str) -> Result<SyntaxHypergraph<Spartan>, Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, source)?;
+        Ok(Expr::from_pest(&mut pairs)?.to_graph(false)?)
+    }
+
+    #[test]
+    fn changes_between_versions() -> Result<(), Box<dyn std::error::Error>> {
+        let old = parse(
+            "bind a = plus(x, z) in
+             bind f = lambda(y . bind b = times(a, y) in b) in
+             bind c = minus(a, x) in
+             c",
+        )?;
+        let new = parse(
+            "bind d = div(x, z) in
+             bind a = plus(x, z) in
+             bind f = lambda(y . y) in
+             bind c = minus(a, z) in
+             c",
+        )?;
+        let result = diff(&old, &new);
+        let change = |graph: &SyntaxHypergraph<Spartan>, weight| {
+            let op = graph.operations().find(|op| op.weight() == weight)?;
+            result.changes.get(&Node::Operation(op)).copied()
+        };
+
+        assert_eq!(change(&new, Op::Div), Some(Change::Added));
+        assert_eq!(change(&old, Op::Plus), None);
+        assert_eq!(change(&new, Op::Plus), None);
+        assert_eq!(change(&old, Op::Minus), Some(Change::Changed));
+        assert_eq!(change(&new, Op::Minus), Some(Change::Changed));
+        assert_eq!(result.count(Change::Added), 1);
+
+        // The body of the matched thunk is compared too
+        let times = old
+            .thunks()
+            .flat_map(|thunk| thunk.operations().collect::<Vec<_>>())
+            .next()
+            .ok_or("no body")?;
+        assert_eq!(
+            result.changes.get(&Node::Operation(times)),
+            Some(&Change::Removed)
+        );
+        assert!(diff(&old, &old).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+//! JSON serialization of hypergraphs, so that other tools, such as compiler passes, can produce
+//! graphs directly rather than through one of the textual languages.
+//!
+//! # Schema
+//!
+//! A hypergraph is an object listing its wires and the nodes between them. Wires are referred to
+//! by their index in `wires`, which holds the weight of each of them.
+//!
+//! ```json
+//! {
+//!   "wires": [<edge weight>, ...],
+//!   "inputs": [<wire>, ...],
+//!   "outputs": [<wire>, ...],
+//!   "nodes": [<node>, ...]
+//! }
+//! ```
+//!
+//! `inputs` are the wires entering the graph and `outputs` the wires leaving it. A node is either
+//! an operation,
+//!
+//! ```json
+//! { "operation": { "weight": <operation weight>, "inputs": [<wire>, ...], "outputs": [<wire>, ...] } }
+//! ```
+//!
+//! or a thunk, whose body is a graph of its own nodes:
+//!
+//! ```json
+//! {
+//!   "thunk": {
+//!     "weight": <thunk weight>,
+//!     "inputs": [<wire>, ...],
+//!     "bound_inputs": [<wire>, ...],
+//!     "bound_outputs": [<wire>, ...],
+//!     "outputs": [<wire>, ...],
+//!     "nodes": [<node>, ...]
+//!   }
+//! }
+//! ```
+//!
+//! The `bound_inputs` of a thunk are the wires its body takes as arguments, and `bound_outputs`
+//! are the wires its body returns. Its body may also use wires from outside of the thunk, which
+//! become inputs of the thunk without being listed in `inputs`.
+//!
+//! Each wire has exactly one source: it is an input of the graph, an output of a node or a bound
+//! input of a thunk. It may be used any number of times, including by nodes listed before its
+//! source, as long as no node ends up computed from its own outputs. The weights are in the format of the serde implementations of the [`Weight`] of the
+//! graph, for example strings for the labels of [`DotWeight`](crate::dot::DotWeight).
+
+use std::{collections::HashMap, sync::Arc};
+
+use by_address::ByThinAddress;
+use derivative::Derivative;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use super::{
+    builder::{fragment::Fragment, HypergraphBuilder, HypergraphError, InPort, OutPort},
+    internal::{InPortInternal, NodeInternal, OutPortInternal},
+    Hypergraph, Weight,
+};
+
+#[derive(Derivative, Error)]
+#[derivative(Debug(bound = ""))]
+pub enum JsonError<W: Weight> {
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Wire {0} does not exist")]
+    UnknownWire(usize),
+    #[error("Wire {0} has more than one source")]
+    DuplicateSource(usize),
+    #[error("Wire {0} has no source")]
+    NoSource(usize),
+    #[error("Error constructing hypergraph: {0}")]
+    Hypergraph(#[from] HypergraphError<W>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "W::EdgeWeight: Serialize, W::OperationWeight: Serialize, W::ThunkWeight: Serialize",
+    deserialize = "W::EdgeWeight: DeserializeOwned, W::OperationWeight: DeserializeOwned, \
+                   W::ThunkWeight: DeserializeOwned"
+))]
+struct JsonGraph<W: Weight> {
+    wires: Vec<W::EdgeWeight>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    nodes: Vec<JsonNode<W>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    rename_all = "snake_case",
+    bound(
+        serialize = "W::OperationWeight: Serialize, W::ThunkWeight: Serialize",
+        deserialize = "W::OperationWeight: DeserializeOwned, W::ThunkWeight: DeserializeOwned"
+    )
+)]
+enum JsonNode<W: Weight> {
+    Operation {
+        weight: W::OperationWeight,
+        inputs: Vec<usize>,
+        outputs: Vec<usize>,
+    },
+    Thunk {
+        weight: W::ThunkWeight,
+        inputs: Vec<usize>,
+        bound_inputs: Vec<usize>,
+        bound_outputs: Vec<usize>,
+        outputs: Vec<usize>,
+        nodes: Vec<JsonNode<W>>,
+    },
+}
+
+/// Numbers the wires of a hypergraph in the order they are first mentioned.
+struct Wires<W: Weight> {
+    weights: Vec<W::EdgeWeight>,
+    ids: HashMap<ByThinAddress<Arc<OutPortInternal<W>>>, usize>,
+}
+
+impl<W: Weight> Wires<W> {
+    fn id(&mut self, out_port: &Arc<OutPortInternal<W>>) -> usize {
+        *self
+            .ids
+            .entry(ByThinAddress(out_port.clone()))
+            .or_insert_with(|| {
+                self.weights.push(out_port.weight.clone());
+                self.weights.len() - 1
+            })
+    }
+
+    fn sources(&mut self, out_ports: &[Arc<OutPortInternal<W>>]) -> Vec<usize> {
+        out_ports.iter().map(|out_port| self.id(out_port)).collect()
+    }
+
+    fn targets(&mut self, in_ports: &[Arc<InPortInternal<W>>]) -> Vec<usize> {
+        in_ports
+            .iter()
+            .map(|in_port| self.id(&in_port.link()))
+            .collect()
+    }
+
+    fn nodes(&mut self, nodes: &[NodeInternal<W>]) -> Vec<JsonNode<W>> {
+        nodes
+            .iter()
+            .map(|node| match node {
+                NodeInternal::Operation(op) => JsonNode::Operation {
+                    weight: op.weight.clone(),
+                    inputs: self.targets(&op.inputs),
+                    outputs: self.sources(&op.outputs),
+                },
+                NodeInternal::Thunk(thunk) => JsonNode::Thunk {
+                    weight: thunk.weight.clone(),
+                    inputs: self.targets(&thunk.inputs),
+                    bound_inputs: self.sources(&thunk.bound_inputs),
+                    bound_outputs: self.targets(&thunk.bound_outputs),
+                    outputs: self.sources(&thunk.outputs),
+                    nodes: self.nodes(&thunk.nodes.try_read().expect("Lock unexpectedly taken")),
+                },
+            })
+            .collect()
+    }
+}
+
+impl<W: Weight> From<&Hypergraph<W>> for JsonGraph<W> {
+    fn from(graph: &Hypergraph<W>) -> Self {
+        let mut wires = Wires {
+            weights: Vec::default(),
+            ids: HashMap::default(),
+        };
+        let inputs = graph
+            .graph_inputs
+            .iter()
+            .map(|out_port| wires.id(out_port))
+            .collect();
+        let outputs = graph
+            .graph_outputs
+            .iter()
+            .map(|in_port| wires.id(&in_port.link()))
+            .collect();
+        let nodes = wires.nodes(&graph.nodes);
+        JsonGraph {
+            wires: wires.weights,
+            inputs,
+            outputs,
+            nodes,
+        }
+    }
+}
+
+/// The ports of the wires of a graph being built, linked once every node has been added so that
+/// wires may be used before their source.
+struct Ports<'a, W: Weight> {
+    weights: &'a [W::EdgeWeight],
+    sources: Vec<Option<OutPort<W>>>,
+    targets: Vec<(usize, InPort<W>)>,
+}
+
+impl<W: Weight> Ports<'_, W> {
+    fn weights(&self, wires: &[usize]) -> Result<Vec<W::EdgeWeight>, JsonError<W>> {
+        wires
+            .iter()
+            .map(|&wire| {
+                self.weights
+                    .get(wire)
+                    .cloned()
+                    .ok_or(JsonError::UnknownWire(wire))
+            })
+            .collect()
+    }
+
+    fn sources(
+        &mut self,
+        wires: &[usize],
+        out_ports: impl Iterator<Item = OutPort<W>>,
+    ) -> Result<(), JsonError<W>> {
+        for (&wire, out_port) in wires.iter().zip(out_ports) {
+            let source = self
+                .sources
+                .get_mut(wire)
+                .ok_or(JsonError::UnknownWire(wire))?;
+            if source.replace(out_port).is_some() {
+                return Err(JsonError::DuplicateSource(wire));
+            }
+        }
+        Ok(())
+    }
+
+    fn targets(&mut self, wires: &[usize], in_ports: impl Iterator<Item = InPort<W>>) {
+        self.targets.extend(wires.iter().copied().zip(in_ports));
+    }
+
+    fn add_nodes(
+        &mut self,
+        fragment: &mut impl Fragment<Weight = W>,
+        nodes: Vec<JsonNode<W>>,
+    ) -> Result<(), JsonError<W>> {
+        for node in nodes {
+            match node {
+                JsonNode::Operation {
+                    weight,
+                    inputs,
+                    outputs,
+                } => {
+                    let op = fragment.add_operation(inputs.len(), self.weights(&outputs)?, weight);
+                    self.targets(&inputs, op.inputs());
+                    self.sources(&outputs, op.outputs())?;
+                }
+                JsonNode::Thunk {
+                    weight,
+                    inputs,
+                    bound_inputs,
+                    bound_outputs,
+                    outputs,
+                    nodes,
+                } => {
+                    let thunk = fragment.add_thunk(
+                        inputs.len(),
+                        self.weights(&bound_inputs)?,
+                        bound_outputs.len(),
+                        self.weights(&outputs)?,
+                        weight,
+                    );
+                    self.targets(&inputs, thunk.inputs());
+                    self.targets(&bound_outputs, thunk.graph_outputs());
+                    self.sources(&bound_inputs, thunk.bound_inputs())?;
+                    self.sources(&outputs, thunk.outputs())?;
+                    fragment.in_thunk(thunk, |mut cursor| self.add_nodes(&mut cursor, nodes))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Weight> TryFrom<JsonGraph<W>> for Hypergraph<W> {
+    type Error = JsonError<W>;
+
+    fn try_from(graph: JsonGraph<W>) -> Result<Self, Self::Error> {
+        let mut ports = Ports {
+            weights: &graph.wires,
+            sources: vec![None; graph.wires.len()],
+            targets: Vec::default(),
+        };
+        let mut builder =
+            HypergraphBuilder::new(ports.weights(&graph.inputs)?, graph.outputs.len());
+        ports.sources(&graph.inputs, builder.graph_inputs())?;
+        ports.targets(&graph.outputs, builder.graph_outputs());
+        ports.add_nodes(&mut builder, graph.nodes)?;
+
+        for (wire, in_port) in ports.targets {
+            let out_port = ports
+                .sources
+                .get(wire)
+                .ok_or(JsonError::UnknownWire(wire))?
+                .clone()
+                .ok_or(JsonError::NoSource(wire))?;
+            builder.link(out_port, in_port)?;
+        }
+        Ok(builder.build()?)
+    }
+}
+
+impl<W: Weight> Hypergraph<W>
+where
+    W::EdgeWeight: Serialize,
+    W::OperationWeight: Serialize,
+    W::ThunkWeight: Serialize,
+{
+    /// Writes the graph as JSON in the format described in [the module documentation](self).
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl<W: Weight> Hypergraph<W>
+where
+    W::EdgeWeight: DeserializeOwned,
+    W::OperationWeight: DeserializeOwned,
+    W::ThunkWeight: DeserializeOwned,
+{
+    /// Reads a graph from JSON in the format described in [the module documentation](self).
+    pub fn from_json(json: &str) -> Result<Self, JsonError<W>> {
+        serde_json::from_str::<JsonGraph<W>>(json)?.try_into()
+    }
+}
+
+impl<W: Weight> Serialize for Hypergraph<W>
+where
+    W::EdgeWeight: Serialize,
+    W::OperationWeight: Serialize,
+    W::ThunkWeight: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        JsonGraph::from(self).serialize(serializer)
+    }
+}
+
+impl<'de, W: Weight> Deserialize<'de> for Hypergraph<W>
+where
+    W::EdgeWeight: DeserializeOwned,
+    W::OperationWeight: DeserializeOwned,
+    W::ThunkWeight: DeserializeOwned,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JsonGraph::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonError;
+    use crate::{
+        dot::DotWeight,
+        hypergraph::{
+            builder::{HypergraphBuildError, HypergraphError},
+            traits::{Graph, NodeLike},
+            Hypergraph,
+        },
+    };
+
+    // The successor of the argument is passed to a thunk which adds the argument of its body to
+    // it, so the body uses a wire from outside of the thunk
+    const GRAPH: &str = r#"{
+        "wires": ["x", "succ", "f", "y", "sum"],
+        "inputs": [0],
+        "outputs": [2],
+        "nodes": [
+            { "operation": { "weight": "+1", "inputs": [0], "outputs": [1] } },
+            {
+                "thunk": {
+                    "weight": "f",
+                    "inputs": [],
+                    "bound_inputs": [3],
+                    "bound_outputs": [4],
+                    "outputs": [2],
+                    "nodes": [
+                        { "operation": { "weight": "+", "inputs": [1, 3], "outputs": [4] } }
+                    ]
+                }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn round_trip() {
+        let graph = Hypergraph::<DotWeight>::from_json(GRAPH).unwrap();
+        let thunk = graph.thunks().next().unwrap();
+        // The wire from outside of the thunk becomes one of its inputs
+        assert_eq!(thunk.number_of_inputs(), 1);
+        assert_eq!(thunk.number_of_free_graph_inputs(), 1);
+
+        let json = graph.to_json().unwrap();
+        let again = Hypergraph::<DotWeight>::from_json(&json).unwrap();
+        assert_eq!(again.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn invalid_wires() {
+        let unknown = GRAPH.replace(r#""outputs": [2]"#, r#""outputs": [7]"#);
+        assert!(matches!(
+            Hypergraph::<DotWeight>::from_json(&unknown),
+            Err(JsonError::UnknownWire(7))
+        ));
+
+        let duplicate = GRAPH.replace(r#""outputs": [4]"#, r#""outputs": [1]"#);
+        assert!(matches!(
+            Hypergraph::<DotWeight>::from_json(&duplicate),
+            Err(JsonError::DuplicateSource(1))
+        ));
+
+        let no_source = GRAPH.replace(r#""outputs": [1]"#, r#""outputs": []"#);
+        assert!(matches!(
+            Hypergraph::<DotWeight>::from_json(&no_source),
+            Err(JsonError::NoSource(1))
+        ));
+    }
+
+    #[test]
+    fn cycles() {
+        // Each operation takes the output of the other
+        let cyclic = r#"{
+            "wires": ["a", "b"],
+            "inputs": [],
+            "outputs": [0],
+            "nodes": [
+                { "operation": { "weight": "f", "inputs": [1], "outputs": [0] } },
+                { "operation": { "weight": "g", "inputs": [0], "outputs": [1] } }
+            ]
+        }"#;
+        let Err(JsonError::Hypergraph(HypergraphError::BuildError(HypergraphBuildError::Cycle(
+            cycle,
+        )))) = Hypergraph::<DotWeight>::from_json(cyclic)
+        else {
+            panic!("cycle not detected");
+        };
+        assert_eq!(cycle.len(), 2);
+        assert!(serde_json::from_str::<Hypergraph<DotWeight>>(cyclic).is_err());
+    }
+}
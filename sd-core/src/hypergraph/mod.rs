@@ -7,9 +7,12 @@ use by_address::ByThinAddress;
 use derivative::Derivative;
 
 pub mod adapter;
+pub mod address;
 pub mod builder;
+pub mod diff;
 pub mod generic;
 mod internal;
+pub mod json;
 pub mod mapping;
 pub mod petgraph;
 pub mod reachability;
@@ -27,11 +30,17 @@ use self::{
     traits::{EdgeLike, Graph, Keyable, NodeLike, WithType, WithWeight},
     weakbyaddress::WeakByAddress,
 };
+use crate::language::OpDoc;
 
 pub trait Weight {
     type EdgeWeight: Clone + Debug + Send + Sync + WithType;
     type OperationWeight: Clone + Debug + Send + Sync;
     type ThunkWeight: Clone + Debug + Send + Sync;
+
+    /// The documentation of an operation with the weight `op`, if there is any.
+    fn operation_doc(_op: &Self::OperationWeight) -> Option<OpDoc> {
+        None
+    }
 }
 
 #[derive(Derivative)]
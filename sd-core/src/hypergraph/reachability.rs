@@ -1,11 +1,12 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use indexmap::IndexSet;
 use itertools::Itertools;
 
 use super::{
-    generic::{Ctx, Endpoint, Node},
-    traits::{EdgeLike, NodeLike, WireType, WithType, WithWeight},
+    generic::{Ctx, Edge, Endpoint, Node, Weight},
+    traits::{EdgeLike, Graph, NodeLike, WireType, WithType, WithWeight},
+    utils::find_ancestor,
 };
 
 impl<T: Ctx> Endpoint<T> {
@@ -127,6 +128,60 @@ where
     }
 }
 
+/// The nodes of a cycle of data wires in `graph`, in order along the wires, if there is one.
+///
+/// The nodes of a cycle all live in the same thunk, as a wire into a thunk counts as a wire into
+/// the thunk itself. Such a cycle leaves no order in which to evaluate the nodes on it, so
+/// building a graph with one fails, though malformed input can link one together.
+pub fn find_cycle<G: Graph>(graph: &G) -> Option<Vec<Node<G::Ctx>>>
+where
+    Weight<Edge<G::Ctx>>: WithType,
+{
+    cycle_among(graph.nodes()).or_else(|| graph.thunks().find_map(|thunk| find_cycle(&thunk)))
+}
+
+/// A cycle of data wires among `nodes`, which all live in the same thunk, found by a depth first
+/// search kept on the heap, as graphs can be too deep to recurse through.
+fn cycle_among<T: Ctx>(nodes: impl Iterator<Item = Node<T>>) -> Option<Vec<Node<T>>>
+where
+    Weight<T::Edge>: WithType,
+{
+    let successors = |node: &Node<T>| node.flat_successors(true).collect::<Vec<_>>().into_iter();
+    // Whether each node visited is on the path being searched, rather than finished with
+    let mut on_path: HashMap<Node<T>, bool> = HashMap::new();
+    for root in nodes {
+        if on_path.contains_key(&root) {
+            continue;
+        }
+        let mut path = vec![root.clone()];
+        let mut stack = vec![successors(&root)];
+        on_path.insert(root, true);
+        while let Some(next) = stack.last_mut().map(Iterator::next) {
+            match next {
+                Some(node) => match on_path.get(&node) {
+                    Some(true) => {
+                        let start = path.iter().position(|n| *n == node)?;
+                        return Some(path.split_off(start));
+                    }
+                    Some(false) => {}
+                    None => {
+                        stack.push(successors(&node));
+                        path.push(node.clone());
+                        on_path.insert(node, true);
+                    }
+                },
+                None => {
+                    stack.pop();
+                    if let Some(done) = path.pop() {
+                        on_path.insert(done, false);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
 type NextFn<T> = fn(&T) -> Box<dyn Iterator<Item = T> + '_>;
 pub struct NReachable<T: Ctx> {
     depth_limit: usize,
@@ -183,6 +238,38 @@ impl<T: Ctx> NReachable<T> {
         forward.intersection(&backward).cloned().collect()
     }
 
+    /// The nodes on the paths from `a` to `b` or from `b` to `a`, including both of them, which
+    /// is empty if neither reaches the other.
+    #[must_use]
+    pub fn between(a: &Node<T>, b: &Node<T>) -> IndexSet<Node<T>> {
+        let paths = |from: &Node<T>, to: &Node<T>| -> IndexSet<Node<T>> {
+            let forward: IndexSet<_> = Self::forward_from([from.clone()]).collect();
+            if !forward.contains(to) {
+                return IndexSet::new();
+            }
+            let backward: IndexSet<_> = Self::backward_from([to.clone()]).collect();
+            forward.intersection(&backward).cloned().collect()
+        };
+        let mut nodes = paths(a, b);
+        nodes.extend(paths(b, a));
+        nodes
+    }
+
+    /// The nodes on the paths between `a` and `b`, as for [`Self::between`], at the depth of the
+    /// innermost thunk containing both of them: nodes inside thunks at that depth are replaced by
+    /// the thunks.
+    #[must_use]
+    pub fn between_flat(a: &Node<T>, b: &Node<T>) -> IndexSet<Node<T>> {
+        let mut containing = a.backlink();
+        while find_ancestor::<T>(containing.as_ref(), b).no_ancestor() {
+            containing = containing.and_then(|thunk| thunk.backlink());
+        }
+        Self::between(a, b)
+            .into_iter()
+            .filter_map(|node| find_ancestor::<T>(containing.as_ref(), &node).to_node(node))
+            .collect()
+    }
+
     #[inline]
     pub fn bump_depth_limit(&mut self) {
         self.increase_depth_limit(1);
@@ -214,3 +301,66 @@ impl<T: Ctx> Iterator for NReachable<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::{find_cycle, NReachable};
+    use crate::{
+        hypergraph::{
+            generic::Node,
+            traits::{Graph, WithWeight},
+        },
+        language::spartan::{Expr, Op, Rule, SpartanParser},
+    };
+
+    #[test]
+    fn paths_between() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind a = plus(x, z) in
+             bind f = lambda(y . bind b = times(a, y) in b) in
+             bind c = minus(f, a) in
+             bind d = div(x, z) in
+             c",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let op = |weight| {
+            graph
+                .operations()
+                .find(|op| op.weight() == weight)
+                .map(Node::Operation)
+                .ok_or("no operation")
+        };
+        let thunk = graph.thunks().next().ok_or("no thunk")?;
+        let times = Node::Operation(thunk.operations().next().ok_or("no body")?);
+        let thunk = Node::Thunk(thunk);
+        let (plus, minus, div) = (op(Op::Plus)?, op(Op::Minus)?, op(Op::Div)?);
+
+        let paths = NReachable::between(&minus, &plus);
+        assert!(paths.contains(&times) && paths.contains(&thunk));
+        assert!(paths.contains(&op(Op::Lambda)?));
+        assert!(!paths.contains(&div));
+
+        let flat = NReachable::between_flat(&plus, &minus);
+        assert!(flat.contains(&thunk) && !flat.contains(&times));
+
+        assert!(NReachable::between(&div, &minus).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn acyclic() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind a = plus(x, x) in
+             bind f = lambda(y . bind b = times(a, y) in b) in
+             app(f, a)",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        assert!(find_cycle(&graph).is_none());
+        Ok(())
+    }
+}
@@ -9,7 +9,7 @@ use super::{
 };
 use crate::{
     codeable::{Code, Codeable},
-    common::Matchable,
+    common::{Matchable, Query},
     hypergraph::generic::Ctx,
     selection::SelectionMap,
 };
@@ -52,6 +52,25 @@ impl<T: Ctx> Subgraph<T> {
         selection.normalize();
         self.selection = Arc::new(selection);
     }
+
+    /// Operations outside the subgraph which are directly connected to its outermost nodes.
+    #[must_use]
+    pub fn context(&self) -> IndexSet<Node<T>> {
+        self.selection
+            .roots()
+            .flat_map(|node| {
+                let backlink = node.backlink();
+                node.predecessors()
+                    .chain(node.successors())
+                    .filter(|neighbour| {
+                        matches!(neighbour, Node::Operation(_))
+                            && neighbour.backlink() == backlink
+                            && !self.selection[neighbour]
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
 }
 
 pub type SubNode<T> = Node<Subgraph<T>>;
@@ -481,7 +500,7 @@ impl<T: Ctx> Matchable for SubEdge<T>
 where
     T::Edge: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.edge.is_match(query)
     }
 }
@@ -490,7 +509,7 @@ impl<T: Ctx> Matchable for SubOperation<T>
 where
     T::Operation: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.op.is_match(query)
     }
 }
@@ -499,7 +518,7 @@ impl<T: Ctx> Matchable for SubThunk<T>
 where
     T::Thunk: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.thunk.is_match(query)
     }
 }
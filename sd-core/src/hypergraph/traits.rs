@@ -17,10 +17,17 @@ pub enum WireType {
     Data,
     ControlFlow,
     SymName,
+    /// A dependency which only orders effects, without passing any data along.
+    Ordering,
 }
 
 pub trait WithType {
     fn get_type(&self) -> WireType;
+
+    /// The pretty-printed type of the values on the wire, for languages which annotate them.
+    fn type_name(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait NodeLike: Clone + Eq + Hash + Debug + Send + Sync {
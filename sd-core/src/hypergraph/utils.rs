@@ -1,8 +1,8 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt::Display};
 
 use super::{
-    generic::{Ctx, Endpoint, Node},
-    traits::{EdgeLike, Graph, NodeLike},
+    generic::{Ctx, Edge, Endpoint, Node, Operation, Weight},
+    traits::{EdgeLike, Graph, NodeLike, WithWeight},
 };
 
 pub enum Ancestor<T: Ctx> {
@@ -46,6 +46,32 @@ pub fn find_ancestor<T: Ctx>(containing: Option<&T::Thunk>, node: &Node<T>) -> A
     }
 }
 
+/// A name for `thunk` derived from its body, for languages whose thunks have no name of their own.
+///
+/// The name is made from the first output of the thunk: the variable it is bound to and the
+/// operation producing it, such as `a = plus`, or just one of the two if the other is missing.
+pub fn derived_thunk_name<T: Ctx>(thunk: &T::Thunk) -> Option<String>
+where
+    Edge<T>: WithWeight,
+    Operation<T>: WithWeight,
+    Weight<Edge<T>>: Display,
+    Weight<Operation<T>>: Display,
+{
+    let output = thunk.graph_outputs().next()?;
+    let var = output.weight().to_string();
+    let op = match output.source() {
+        Endpoint::Node(Node::Operation(op)) => Some(op.weight().to_string()),
+        Endpoint::Node(Node::Thunk(_)) | Endpoint::Boundary(_) => None,
+    }
+    .filter(|op| !op.is_empty());
+    match (op, var.is_empty()) {
+        (Some(op), false) => Some(format!("{var} = {op}")),
+        (Some(op), true) => Some(op),
+        (None, false) => Some(var),
+        (None, true) => None,
+    }
+}
+
 pub fn normalised_targets<T: Ctx>(
     edge: &T::Edge,
     containing: Option<&T::Thunk>,
@@ -117,3 +143,29 @@ pub fn normalised_targets<T: Ctx>(
     outputs.extend(non_dupe_outputs);
     outputs
 }
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::derived_thunk_name;
+    use crate::{
+        graph::Syntax,
+        hypergraph::traits::Graph,
+        language::spartan::{Expr, Rule, Spartan, SpartanParser},
+    };
+
+    #[test]
+    fn thunk_name() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y z . bind a = plus(x, y) in bind b = plus(a, z) in b) in f",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let thunk = graph.thunks().next().ok_or("no thunk")?;
+        let name = derived_thunk_name::<Syntax<Spartan>>(&thunk).ok_or("no name")?;
+        assert!(name.ends_with("plus"), "unexpected name {name}");
+        Ok(())
+    }
+}
@@ -1,28 +1,47 @@
 #![allow(clippy::inline_always)]
 
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    hash::Hash,
+    iter::successors,
+};
+
 use delegate::delegate;
 use derivative::Derivative;
+use indexmap::IndexSet;
 
 use crate::{
     codeable::{Code, Codeable},
-    common::Direction,
+    common::{Direction, Matchable, Query},
+    decompile::DecompileError,
+    graph::SyntaxHypergraph,
     hypergraph::{
-        adapter::{collapse::CollapseGraph, cut::CutGraph, selectable::SelectableGraph},
-        generic::{Ctx, Edge, Key, Node, Thunk},
+        adapter::{
+            collapse::CollapseGraph, cut::CutGraph, focus::FocusGraph, fold::FoldGraph,
+            selectable::SelectableGraph,
+        },
+        address::{addresses, node_at, NodeAddress},
+        generic::{Ctx, Edge, Key, Node, Operation, Thunk, Weight},
         mapping::{edge_map, thunk_map},
         subgraph::Subgraph,
-        traits::{Graph, Keyable},
+        traits::{Graph, Keyable, NodeLike, WithWeight},
     },
+    language::{Expr, Language},
 };
 
-/// An interactive graph is a graph with cut edges, collapsible thunks, and selectable nodes.
+/// An interactive graph is a graph which can be focused on the body of one of its thunks, with
+/// cut edges, collapsible thunks, foldable chains of operations, and selectable nodes.
 #[derive(Derivative)]
 #[derivative(Clone(bound = ""), Debug(bound = ""))]
-pub struct InteractiveGraph<G: Graph>(pub CutGraph<CollapseGraph<SelectableGraph<G>>>);
+pub struct InteractiveGraph<G: Graph>(
+    pub FocusGraph<CutGraph<CollapseGraph<FoldGraph<SelectableGraph<G>>>>>,
+);
 
 impl<G: Graph> InteractiveGraph<G> {
     pub fn new(graph: G) -> Self {
         let graph = SelectableGraph::new(graph);
+        let graph = FoldGraph::new(graph);
 
         let expanded = thunk_map(&graph, true);
         let graph = CollapseGraph::new(graph, expanded);
@@ -30,36 +49,315 @@ impl<G: Graph> InteractiveGraph<G> {
         let cut_edges = edge_map(&graph, false);
         let graph = CutGraph::new(graph, cut_edges);
 
+        let graph = FocusGraph::new(graph);
+
         Self(graph)
     }
 
+    /// The graph being interacted with.
+    pub fn inner(&self) -> &G {
+        self.0.inner().inner().inner().inner().inner()
+    }
+
     delegate! {
-        to self.0.inner().inner() {
+        to self.0.inner().inner().inner().inner() {
             pub fn is_empty(&self) -> bool;
         }
 
-        to self.0.inner_mut().inner_mut() {
+        to self.0.inner_mut().inner_mut().inner_mut().inner_mut() {
             pub fn clear_selection(&mut self);
+            pub fn select_all(&mut self);
             pub fn extend_selection(&mut self, direction: Option<(Direction, usize)>);
         }
     }
 
     delegate! {
-        to self.0.inner_mut() {
+        to self.0.inner_mut().inner_mut() {
             #[call(set_all)]
             pub fn set_expanded_all(&mut self, value: bool);
         }
     }
 
     pub fn to_subgraph(&self) -> InteractiveSubgraph<G::Ctx> {
-        let subgraph = self.0.inner().inner().to_subgraph();
-        let expanded = self.0.inner().expanded().clone();
+        let subgraph = self.0.inner().inner().inner().inner().to_subgraph();
+        let expanded = self.0.inner().inner().expanded().clone();
         InteractiveSubgraph(CollapseGraph::new(subgraph, expanded))
     }
+
+    /// The subgraph of selected nodes together with the operations connected to it, which are
+    /// also returned.
+    pub fn to_subgraph_with_context(
+        &self,
+    ) -> (InteractiveSubgraph<G::Ctx>, IndexSet<Node<G::Ctx>>) {
+        let mut subgraph = self.0.inner().inner().inner().inner().to_subgraph();
+        let context = subgraph.context();
+        subgraph.extend(context.iter().cloned());
+        let expanded = self.0.inner().inner().expanded().clone();
+        (
+            InteractiveSubgraph(CollapseGraph::new(subgraph, expanded)),
+            context,
+        )
+    }
+
+    /// Expands or collapses each thunk to match the thunk with the same address in `other`, so
+    /// that a program and a transformed version of it can be explored in lockstep. Thunks with
+    /// no counterpart in `other` are left as they are.
+    pub fn mirror_expansion<H: Graph>(&mut self, other: &InteractiveGraph<H>)
+    where
+        Thunk<G::Ctx>: WithWeight,
+        Thunk<H::Ctx>: WithWeight<Weight = Weight<Thunk<G::Ctx>>>,
+        Weight<Thunk<G::Ctx>>: Eq + Hash,
+    {
+        let expanded = other.0.inner().inner().expanded();
+        let addresses: HashMap<_, _> = all_thunks(other.0.inner().inner().inner())
+            .into_iter()
+            .map(|thunk| (thunk.weight(), expanded[&thunk.key()]))
+            .collect();
+        let values = all_thunks(self.0.inner().inner().inner())
+            .into_iter()
+            .filter_map(|thunk| Some((thunk.key(), *addresses.get(&thunk.weight())?)))
+            .collect::<Vec<_>>();
+        self.0.inner_mut().inner_mut().set(values);
+    }
+
+    /// Folds each constant, an operation with no inputs and a single output, into literals at
+    /// its uses instead of drawing it on its own, or restores the constants if `folded` is false.
+    pub fn set_constants_folded(&mut self, folded: bool) {
+        let values = self
+            .constant_edges()
+            .into_iter()
+            .map(|edge| (edge.key(), folded))
+            .collect::<Vec<_>>();
+        self.0.inner_mut().set(values);
+    }
+
+    /// Folds each chain of operations, in which every operation but the last has a single output
+    /// used only by the next, into one node, or unfolds them all if `folded` is false.
+    pub fn set_chains_folded(&mut self, folded: bool) {
+        self.0.inner_mut().inner_mut().inner_mut().set_all(folded);
+    }
+
+    /// The thunk whose body is drawn in place of the whole graph, if any.
+    pub fn focused_thunk(&self) -> Option<Thunk<<Self as Graph>::Ctx>> {
+        self.0.focused()
+    }
+
+    /// Draws only the body of `thunk`, as if it were the whole program, or the whole graph again
+    /// if `thunk` is `None`.
+    pub fn set_focused_thunk(&mut self, thunk: Option<&Thunk<<Self as Graph>::Ctx>>) {
+        self.0.set_focus(thunk);
+    }
+
+    /// The focused thunk and the thunks around it, from the outermost in, to navigate back out
+    /// of it.
+    pub fn focus_path(&self) -> Vec<Thunk<<Self as Graph>::Ctx>> {
+        let mut path = successors(self.focused_thunk(), NodeLike::backlink).collect::<Vec<_>>();
+        path.reverse();
+        path
+    }
+
+    /// The outputs of the constants, including those inside collapsed thunks.
+    fn constant_edges(&self) -> Vec<Edge<CollapseGraph<FoldGraph<SelectableGraph<G>>>>> {
+        fn helper<T: Ctx>(graph: &impl Graph<Ctx = T>, edges: &mut Vec<T::Edge>) {
+            for node in graph.nodes() {
+                match &node {
+                    Node::Operation(op) => {
+                        if op.number_of_inputs() == 0 && op.number_of_outputs() == 1 {
+                            edges.extend(op.outputs());
+                        }
+                    }
+                    Node::Thunk(thunk) => helper(thunk, edges),
+                }
+            }
+        }
+
+        let mut graph = self.0.inner().inner().clone();
+        graph.set_all(true);
+        let mut edges = Vec::new();
+        helper(&graph, &mut edges);
+        edges
+    }
+
+    /// The operations and thunks matching `query`, including those inside collapsed thunks.
+    pub fn search(&self, query: &Query) -> IndexSet<Node<G::Ctx>>
+    where
+        Operation<G::Ctx>: Matchable,
+        Thunk<G::Ctx>: Matchable,
+    {
+        fn helper<T: Ctx>(
+            graph: &impl Graph<Ctx = T>,
+            query: &Query,
+            matches: &mut IndexSet<Node<T>>,
+        ) where
+            T::Operation: Matchable,
+            T::Thunk: Matchable,
+        {
+            for node in graph.nodes() {
+                let is_match = match &node {
+                    Node::Operation(op) => op.is_match(query),
+                    Node::Thunk(thunk) => {
+                        helper(thunk, query, matches);
+                        thunk.is_match(query)
+                    }
+                };
+                if is_match {
+                    matches.insert(node);
+                }
+            }
+        }
+
+        let mut matches = IndexSet::new();
+        helper(self.0.inner().inner().inner().inner(), query, &mut matches);
+        matches
+    }
+
+    /// Expands every collapsed thunk containing a match for `query`, so that all matches are
+    /// visible. Returns whether any thunk was expanded.
+    pub fn expand_matches(&mut self, query: &Query) -> bool
+    where
+        Operation<G::Ctx>: Matchable,
+        Thunk<G::Ctx>: Matchable,
+    {
+        let expanded = self.0.inner().inner().expanded();
+        let values = self
+            .search(query)
+            .iter()
+            .flat_map(|node| successors(node.backlink(), NodeLike::backlink))
+            .filter(|thunk| !expanded[&thunk.key()])
+            .map(|thunk| (thunk.key(), true))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return false;
+        }
+        self.0.inner_mut().inner_mut().set(values);
+        true
+    }
+
+    /// Collapses thunks so that about `budget` nodes and wires are drawn, expanding thunks
+    /// breadth first so that the outline of a large program is shown before the details of any
+    /// one part of it. Returns the number of thunks left collapsed whose contents did not fit.
+    pub fn collapse_to_budget(&mut self, budget: usize) -> usize {
+        fn level_size<G: Graph>(graph: &G) -> usize {
+            graph.number_of_graph_inputs()
+                + graph
+                    .nodes()
+                    .map(|node| 1 + node.number_of_outputs())
+                    .sum::<usize>()
+        }
+
+        let graph = self.0.inner().inner().inner().inner();
+        let mut size = level_size(graph);
+        let mut queue = graph.thunks().collect::<VecDeque<_>>();
+        let mut expanded = Vec::new();
+        let mut collapsed = 0;
+        while let Some(thunk) = queue.pop_front() {
+            let cost = level_size(&thunk);
+            if size + cost <= budget {
+                size += cost;
+                queue.extend(thunk.thunks());
+                expanded.push((thunk.key(), true));
+            } else {
+                collapsed += 1;
+            }
+        }
+        let collapse = self.0.inner_mut().inner_mut();
+        collapse.set_all(false);
+        collapse.set(expanded);
+        collapsed
+    }
+}
+
+/// A click on a node of an [`InteractiveGraph`], by the address of the node so that it can be
+/// repeated on a recompiled or different program.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Toggle {
+    /// Expanding or collapsing a thunk.
+    Expanded(NodeAddress),
+    /// Selecting or deselecting a node.
+    Selected(NodeAddress),
+}
+
+impl<G: Graph> InteractiveGraph<G>
+where
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    /// The toggles which turn `before`, an earlier state of this graph, into this one.
+    pub fn toggles_since(&self, before: &Self) -> Vec<Toggle> {
+        let (expanded, expanded_before) = (
+            self.0.inner().inner().expanded(),
+            before.0.inner().inner().expanded(),
+        );
+        let (selection, selection_before) = (
+            self.0.inner().inner().inner().inner().selection(),
+            before.0.inner().inner().inner().inner().selection(),
+        );
+        let mut toggles = Vec::new();
+        for (address, node) in addresses(self.inner()) {
+            if let Node::Thunk(thunk) = &node {
+                if expanded[&thunk.key()] != expanded_before[&thunk.key()] {
+                    toggles.push(Toggle::Expanded(address.clone()));
+                }
+            }
+            if selection[&node] != selection_before[&node] {
+                toggles.push(Toggle::Selected(address));
+            }
+        }
+        toggles
+    }
+
+    /// The toggles which recreate the state of this graph on a fresh copy of it.
+    pub fn toggles(&self) -> Vec<Toggle> {
+        self.toggles_since(&Self::new(self.inner().clone()))
+    }
+
+    /// Repeats a toggle, returning false if there is no suitable node at its address.
+    pub fn toggle(&mut self, toggle: &Toggle) -> bool {
+        match toggle {
+            Toggle::Expanded(address) => match node_at(self.inner(), address) {
+                Some(Node::Thunk(thunk)) => {
+                    let collapse = self.0.inner_mut().inner_mut();
+                    let expanded = collapse.expanded()[&thunk.key()];
+                    collapse.set([(thunk.key(), !expanded)]);
+                    true
+                }
+                _ => false,
+            },
+            Toggle::Selected(address) => match node_at(self.inner(), address) {
+                Some(node) => {
+                    *self
+                        .0
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .selected_mut(&node) ^= true;
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// The thunks of `graph`, including those nested in other thunks.
+fn all_thunks<G: Graph>(graph: &G) -> Vec<Thunk<G::Ctx>> {
+    fn helper<T: Ctx>(graph: &impl Graph<Ctx = T>, thunks: &mut Vec<T::Thunk>) {
+        for thunk in graph.thunks() {
+            helper(&thunk, thunks);
+            thunks.push(thunk);
+        }
+    }
+
+    let mut thunks = Vec::new();
+    helper(graph, &mut thunks);
+    thunks
 }
 
 impl<G: Graph> Graph for InteractiveGraph<G> {
-    type Ctx = CutGraph<CollapseGraph<SelectableGraph<G>>>;
+    type Ctx = CutGraph<CollapseGraph<FoldGraph<SelectableGraph<G>>>>;
 
     delegate! {
         to self.0 {
@@ -78,7 +376,7 @@ impl<G: Graph> Graph for InteractiveGraph<G> {
 }
 
 impl<G: Graph + Codeable> Codeable for InteractiveGraph<G> {
-    type Code = Code<CutGraph<CollapseGraph<SelectableGraph<G>>>>;
+    type Code = Code<CutGraph<CollapseGraph<FoldGraph<SelectableGraph<G>>>>>;
 
     fn code(&self) -> Self::Code {
         self.0.code()
@@ -86,13 +384,85 @@ impl<G: Graph + Codeable> Codeable for InteractiveGraph<G> {
 }
 
 impl<G: Graph> Keyable for InteractiveGraph<G> {
-    type Key = Key<CutGraph<CollapseGraph<SelectableGraph<G>>>>;
+    type Key = Key<FocusGraph<CutGraph<CollapseGraph<FoldGraph<SelectableGraph<G>>>>>>;
 
     fn key(&self) -> Self::Key {
         self.0.key()
     }
 }
 
+/// A graph whose interactive state can be compared, to tell whether an interaction changed it.
+pub trait SameState {
+    fn same_state(&self, other: &Self) -> bool;
+}
+
+impl<G: Graph> SameState for InteractiveGraph<G> {
+    fn same_state(&self, other: &Self) -> bool {
+        self.key() == other.key()
+            && self.0.inner().inner().inner().inner().selection()
+                == other.0.inner().inner().inner().inner().selection()
+    }
+}
+
+impl<T: Ctx> SameState for InteractiveSubgraph<T> {
+    fn same_state(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+/// Undo and redo history of the interactions with a graph.
+///
+/// Interactions only swap out the maps recording which thunks are expanded, which edges are cut
+/// and which nodes are selected, so each entry is the state of the graph before an interaction.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct History<G> {
+    undo: VecDeque<G>,
+    redo: Vec<G>,
+}
+
+impl<G: SameState> History<G> {
+    /// Number of interactions which can be undone.
+    const LIMIT: usize = 100;
+
+    /// Records `before` as the state prior to an interaction that left the graph as `after`,
+    /// unless the interaction changed nothing.
+    pub fn record(&mut self, before: G, after: &G) {
+        if before.same_state(after) {
+            return;
+        }
+        if self.undo.len() == Self::LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(before);
+        self.redo.clear();
+    }
+
+    /// Restores `graph` to its state before the last interaction.
+    pub fn undo(&mut self, graph: &mut G) {
+        if let Some(previous) = self.undo.pop_back() {
+            self.redo.push(std::mem::replace(graph, previous));
+        }
+    }
+
+    /// Reapplies the last undone interaction to `graph`.
+    pub fn redo(&mut self, graph: &mut G) {
+        if let Some(next) = self.redo.pop() {
+            self.undo.push_back(std::mem::replace(graph, next));
+        }
+    }
+
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
 /// An interactive subgraph is a subgraph with collapsible thunks.
 #[derive(Derivative)]
 #[derivative(Clone(bound = ""), Debug(bound = ""))]
@@ -128,6 +498,14 @@ where
     }
 }
 
+impl<T: Language> InteractiveSubgraph<SyntaxHypergraph<T>> {
+    /// Decompiles the subgraph back to a program of its own, including the bodies of collapsed
+    /// thunks, so that it can be edited separately from the program it was cut from.
+    pub fn decompile(&self) -> Result<Expr<T>, DecompileError> {
+        Expr::decompile(self.0.inner())
+    }
+}
+
 impl<T: Ctx> Keyable for InteractiveSubgraph<T> {
     type Key = Key<CollapseGraph<Subgraph<T>>>;
 
@@ -135,3 +513,178 @@ impl<T: Ctx> Keyable for InteractiveSubgraph<T> {
         self.0.key()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::{History, InteractiveGraph, Toggle};
+    use crate::{
+        common::Query,
+        hypergraph::traits::{Graph, NodeLike, WithWeight},
+        language::spartan::{Expr, Rule, SpartanParser},
+        prettyprinter::PrettyPrint,
+    };
+
+    #[test]
+    fn undo_collapse_all() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y . plus(x, y)) in bind g = plus(z . times(z, z)) in app(f, g)",
+        )?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        let mut history = History::default();
+
+        let before = graph.clone();
+        graph.set_expanded_all(false);
+        history.record(before, &graph);
+        let collapsed = graph.toggles();
+        assert_eq!(collapsed.len(), 2);
+        assert!(collapsed
+            .iter()
+            .all(|toggle| matches!(toggle, Toggle::Expanded(_))));
+
+        history.undo(&mut graph);
+        assert!(graph.toggles().is_empty());
+        assert!(!history.can_undo());
+
+        history.redo(&mut graph);
+        assert_eq!(graph.toggles(), collapsed);
+        assert!(!history.can_redo());
+        Ok(())
+    }
+
+    #[test]
+    fn collapse_to_budget() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y . plus(x, plus(y, y))) in bind g = plus(z . z) in app(f, g)",
+        )?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+
+        assert_eq!(graph.collapse_to_budget(usize::MAX), 0);
+        assert!(graph.toggles().is_empty());
+
+        assert_eq!(graph.collapse_to_budget(0), 2);
+        assert_eq!(graph.toggles().len(), 2);
+
+        // Room for the top level and the small thunk, but not the large one
+        let top = graph.number_of_graph_inputs()
+            + graph
+                .nodes()
+                .map(|node| 1 + node.number_of_outputs())
+                .sum::<usize>();
+        assert_eq!(graph.collapse_to_budget(top + 3), 1);
+        assert_eq!(graph.toggles().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn unchanged_interactions_are_not_recorded() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, "bind f = plus(y . y) in f")?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        let mut history = History::default();
+
+        let before = graph.clone();
+        graph.set_expanded_all(true);
+        history.record(before, &graph);
+        assert!(!history.can_undo());
+        Ok(())
+    }
+
+    #[test]
+    fn expand_matches_in_collapsed_thunks() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y . plus(x, y)) in bind g = plus(z . times(z, z)) in app(f, g)",
+        )?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        graph.set_expanded_all(false);
+
+        let query = Query::parse("TIM")?;
+        assert_eq!(graph.search(&query).len(), 1);
+        assert!(graph.expand_matches(&query));
+        assert_eq!(graph.toggles().len(), 1);
+        assert!(!graph.expand_matches(&query));
+
+        assert_eq!(graph.search(&Query::parse("/^(plus|times)$/")?).len(), 4);
+        assert!(graph.search(&Query::Exact("tim".to_owned())).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn fold_chains() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind a = plus(x, y) in bind b = minus(a, z) in bind c = times(b, b) in c",
+        )?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        assert_eq!(graph.nodes().count(), 3);
+
+        // The output of minus is used twice, so the chain stops there
+        graph.set_chains_folded(true);
+        assert_eq!(graph.nodes().count(), 2);
+        let fold = graph.0.inner().inner().inner();
+        let chain = fold
+            .operations()
+            .find(|op| op.inner().len() == 2)
+            .ok_or("chain was not folded")?;
+        assert_eq!(chain.weight().to_string(), "+ → -");
+        assert_eq!(chain.number_of_inputs(), 3);
+        assert_eq!(chain.number_of_outputs(), 1);
+
+        let member = chain.inner()[1].clone();
+        assert!(graph.0.inner_mut().inner_mut().inner_mut().toggle(&member));
+        assert_eq!(graph.nodes().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn focus_thunk() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y . plus(x, times(y, y))) in app(f, x)",
+        )?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        let top = graph.nodes().count();
+        let thunk = graph.thunks().next().ok_or("no thunk")?;
+
+        graph.set_focused_thunk(Some(&thunk));
+        assert_eq!(graph.nodes().count(), 2);
+        assert_eq!(graph.number_of_bound_graph_inputs(), 1);
+        assert_eq!(graph.number_of_free_graph_inputs(), 1);
+        assert_eq!(graph.graph_backlink(), Some(thunk.clone()));
+        assert_eq!(graph.focus_path(), vec![thunk]);
+
+        // The whole graph is drawn while the thunk is collapsed, and the focus comes back with it
+        graph.set_expanded_all(false);
+        assert!(graph.focused_thunk().is_none());
+        graph.set_expanded_all(true);
+        assert_eq!(graph.nodes().count(), 2);
+
+        graph.set_focused_thunk(None);
+        assert_eq!(graph.nodes().count(), top);
+        Ok(())
+    }
+
+    #[test]
+    fn decompile_selection() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind a = plus(x, y) in bind f = plus(z . times(z, a)) in app(f, a)",
+        )?;
+        let mut graph = InteractiveGraph::new(Expr::from_pest(&mut pairs)?.to_graph(false)?);
+        graph.set_expanded_all(false);
+        graph.select_all();
+        assert!(graph.toggle(&Toggle::Selected("@".parse()?)));
+
+        // The collapsed thunk is decompiled in full, and the result compiles on its own
+        let code = graph.to_subgraph().decompile()?.to_pretty();
+        assert!(code.contains("times"));
+        assert!(!code.contains("app"));
+        let mut pairs = SpartanParser::parse(Rule::program, &code)?;
+        Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    ptr,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The fewest strings interned before the interner is first pruned.
+const MIN_PRUNE: usize = 1024;
+
+#[derive(Default)]
+struct Interner {
+    strings: HashSet<Arc<str>>,
+    /// How many strings to hold before dropping those no longer used anywhere else.
+    prune_at: usize,
+}
+
+impl Interner {
+    /// Drops the strings no longer used anywhere else, once the interner has doubled in size
+    /// since it was last pruned, so that pruning takes constant time per string on average.
+    fn prune(&mut self) {
+        if self.strings.len() < self.prune_at.max(MIN_PRUNE) {
+            return;
+        }
+        // Only the interner can hand out new references, and the lock is held
+        self.strings.retain(|string| Arc::strong_count(string) > 1);
+        self.prune_at = 2 * self.strings.len();
+    }
+}
+
+static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+
+/// A string stored once for as long as it is used.
+///
+/// Interned strings are cheap to clone, and are compared and hashed by address rather than
+/// by contents.
+#[derive(Clone)]
+pub struct InternedStr(Arc<str>);
+
+impl InternedStr {
+    #[must_use]
+    pub fn new(string: &str) -> Self {
+        let interner = INTERNER.get_or_init(RwLock::default);
+        if let Some(interned) = interner.read().unwrap().strings.get(string) {
+            return Self(interned.clone());
+        }
+
+        let mut interner = interner.write().unwrap();
+        // Another thread may have interned the string in the meantime
+        if let Some(interned) = interner.strings.get(string) {
+            return Self(interned.clone());
+        }
+        interner.prune();
+        let interned: Arc<str> = string.into();
+        interner.strings.insert(interned.clone());
+        Self(interned)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for InternedStr {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for InternedStr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for InternedStr {
+    fn from(string: &str) -> Self {
+        Self::new(string)
+    }
+}
+
+impl From<String> for InternedStr {
+    fn from(string: String) -> Self {
+        Self::new(&string)
+    }
+}
+
+impl PartialEq for InternedStr {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for InternedStr {}
+
+impl PartialEq<str> for InternedStr {
+    fn eq(&self, other: &str) -> bool {
+        *self.0 == *other
+    }
+}
+
+impl PartialEq<&str> for InternedStr {
+    fn eq(&self, other: &&str) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<String> for InternedStr {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl Hash for InternedStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(Arc::as_ptr(&self.0), state);
+    }
+}
+
+impl PartialOrd for InternedStr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InternedStr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl Debug for InternedStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Display for InternedStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for InternedStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for InternedStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InternedStr, INTERNER, MIN_PRUNE};
+
+    #[test]
+    fn unused_strings_are_dropped() {
+        let kept = InternedStr::new("kept");
+        for i in 0..4 * MIN_PRUNE {
+            let _ = InternedStr::new(&format!("dropped {i}"));
+        }
+        let len = INTERNER.get().unwrap().read().unwrap().strings.len();
+        assert!(len < 4 * MIN_PRUNE, "{len} strings interned");
+
+        let again = InternedStr::new("kept");
+        assert_eq!(kept, again);
+        assert_eq!(again, "kept");
+    }
+}
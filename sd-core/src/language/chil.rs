@@ -5,17 +5,25 @@ use std::{
     hash::{Hash, Hasher},
 };
 
+#[cfg(feature = "pest-chil")]
 use from_pest::{ConversionError, FromPest, Void};
+#[cfg(feature = "pest-chil")]
 use pest::iterators::Pairs;
+#[cfg(feature = "pest-chil")]
 use pest_ast::FromPest;
+#[cfg(feature = "pest-chil")]
 use pest_derive::Parser;
 #[cfg(test)]
 use serde::Serialize;
 
-use super::{span_into_str, Fresh, GetVar, OpInfo};
+#[cfg(feature = "pest-chil")]
+use super::{comments_before, span_into_str};
+use super::{Fresh, GetVar, OpInfo};
 use crate::{
-    common::{Empty, Matchable},
+    common::{Empty, Matchable, Query, Span, Spanned},
     hypergraph::traits::{WireType, WithType},
+    interner::InternedStr,
+    prettyprinter::PrettyPrint,
 };
 
 pub struct Chil;
@@ -34,21 +42,55 @@ pub type Bind = super::Bind<Chil>;
 pub type Value = super::Value<Chil>;
 pub type Thunk = super::Thunk<Chil>;
 
+pub mod cluster;
+pub mod module;
+pub mod parser;
+
+/// The original pest grammar, kept for differential testing of [`parser`].
+#[cfg(feature = "pest-chil")]
 #[derive(Parser)]
 #[grammar = "language/chil.pest"]
 pub struct ChilParser;
 
+#[cfg(feature = "pest-chil")]
 fn parse_addr_first(input: &str) -> char {
     input.chars().next().unwrap()
 }
 
+#[cfg(feature = "pest-chil")]
 fn parse_addr_second(input: &str) -> usize {
     input[1..].parse().unwrap()
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
-#[cfg_attr(test, derive(Serialize))]
-pub struct Op(pub String);
+/// An operation, and where it was written if it was parsed.
+#[derive(Clone, Eq, Debug)]
+pub struct Op(pub InternedStr, pub Option<Span>);
+
+/// Operations are the same wherever they were written.
+impl PartialEq for Op {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Hash for Op {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+#[cfg(test)]
+impl Serialize for Op {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("Op", &self.0)
+    }
+}
+
+impl Spanned for Op {
+    fn span(&self) -> Option<Span> {
+        self.1
+    }
+}
 
 impl Display for Op {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -98,11 +140,12 @@ impl Display for Op {
 }
 
 impl Matchable for Op {
-    fn is_match(&self, query: &str) -> bool {
-        self.0 == query
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.0)
     }
 }
 
+#[cfg(feature = "pest-chil")]
 impl<'pest> FromPest<'pest> for Op {
     type Rule = Rule;
     type FatalError = Void;
@@ -116,14 +159,29 @@ impl<'pest> FromPest<'pest> for Op {
             return Err(ConversionError::NoMatch);
         }
         *pest = clone;
-        Ok(Self(pair.as_str().to_owned()))
+        let span = pair.as_span();
+        Ok(Self(
+            pair.as_str().into(),
+            Some(Span {
+                start: span.start(),
+                end: span.end(),
+            }),
+        ))
     }
 }
 
-impl OpInfo<Chil> for Op {}
+impl OpInfo<Chil> for Op {
+    fn span(&self) -> Option<Span> {
+        self.1
+    }
+}
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::variable))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "pest-chil",
+    derive(FromPest),
+    pest_ast(rule(Rule::variable))
+)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct Variable {
     pub name: Option<Identifier>,
@@ -143,10 +201,10 @@ impl From<Empty> for Variable {
 }
 
 impl Matchable for Variable {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         // If a variable is "foo(id: %0)", then we match "foo(id: %0)", "foo", and "%0".
-        self.to_string() == query
-            || self.name.as_ref().map_or(false, |id| id.0 == query)
+        query.matches(&self.to_string())
+            || self.name.as_ref().map_or(false, |id| query.matches(&id.0))
             || self.addr.is_match(query)
     }
 }
@@ -169,17 +227,25 @@ impl Fresh for Variable {
     }
 }
 
-#[derive(Clone, Eq, Debug, FromPest)]
-#[pest_ast(rule(Rule::addr))]
+#[derive(Clone, Eq, Debug)]
+#[cfg_attr(feature = "pest-chil", derive(FromPest), pest_ast(rule(Rule::addr)))]
 #[cfg_attr(test, derive(Serialize))]
 pub struct Addr(
-    #[pest_ast(outer(with(span_into_str), with(parse_addr_first)))] pub char,
-    #[pest_ast(outer(with(span_into_str), with(parse_addr_second)))] pub usize,
+    #[cfg_attr(
+        feature = "pest-chil",
+        pest_ast(outer(with(span_into_str), with(parse_addr_first)))
+    )]
+    pub char,
+    #[cfg_attr(
+        feature = "pest-chil",
+        pest_ast(outer(with(span_into_str), with(parse_addr_second)))
+    )]
+    pub usize,
 );
 
 impl Matchable for Addr {
-    fn is_match(&self, query: &str) -> bool {
-        self.to_string() == query
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.to_string())
     }
 }
 
@@ -203,7 +269,7 @@ impl Display for Addr {
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(test, derive(Serialize))]
-pub struct Identifier(pub String);
+pub struct Identifier(pub InternedStr);
 
 impl Display for Identifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -211,6 +277,7 @@ impl Display for Identifier {
     }
 }
 
+#[cfg(feature = "pest-chil")]
 impl<'pest> FromPest<'pest> for Identifier {
     type Rule = Rule;
     type FatalError = Void;
@@ -224,12 +291,16 @@ impl<'pest> FromPest<'pest> for Identifier {
             return Err(ConversionError::NoMatch);
         }
         *pest = clone;
-        Ok(Self(pair.as_str().to_owned()))
+        Ok(Self(pair.as_str().into()))
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::variable_def))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "pest-chil",
+    derive(FromPest),
+    pest_ast(rule(Rule::variable_def))
+)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct VariableDef {
     pub var: Variable,
@@ -243,11 +314,28 @@ impl Display for VariableDef {
 }
 
 impl Matchable for VariableDef {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         self.var.is_match(query)
     }
 }
 
+impl WithType for VariableDef {
+    /// Values of `Unit` or `Nothing` type carry no data, so they only order the effects which
+    /// produce them before the `seq` which consumes them.
+    fn get_type(&self) -> WireType {
+        match &self.r#type {
+            Some(Type::Base(BaseType(name))) if *name == "Unit" || *name == "Nothing" => {
+                WireType::Ordering
+            }
+            _ => self.var.get_type(),
+        }
+    }
+
+    fn type_name(&self) -> Option<String> {
+        self.r#type.as_ref().map(PrettyPrint::to_pretty)
+    }
+}
+
 impl GetVar<Variable> for VariableDef {
     fn var(&self) -> &Variable {
         &self.var
@@ -258,46 +346,84 @@ impl GetVar<Variable> for VariableDef {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::ty))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "pest-chil", derive(FromPest), pest_ast(rule(Rule::ty)))]
 #[cfg_attr(test, derive(Serialize))]
 pub enum Type {
     Base(BaseType),
     Generic(GenericType),
     Tuple(TupleType),
     Function(FunctionType),
+    /// A type annotation the parser did not understand, kept as written.
+    Unknown(UnknownType),
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::base_ty))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "pest-chil", derive(FromPest), pest_ast(rule(Rule::base_ty)))]
 #[cfg_attr(test, derive(Serialize))]
-pub struct BaseType(#[pest_ast(outer(with(span_into_str), with(str::to_string)))] pub String);
+pub struct BaseType(
+    #[cfg_attr(
+        feature = "pest-chil",
+        pest_ast(outer(with(span_into_str), with(InternedStr::from)))
+    )]
+    pub InternedStr,
+);
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::generic_ty))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "pest-chil",
+    derive(FromPest),
+    pest_ast(rule(Rule::generic_ty))
+)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct GenericType {
     pub base: BaseType,
     pub params: Vec<Type>,
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::tuple_ty))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "pest-chil",
+    derive(FromPest),
+    pest_ast(rule(Rule::tuple_ty))
+)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct TupleType {
     pub types: Vec<Type>,
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, FromPest)]
-#[pest_ast(rule(Rule::function_ty))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "pest-chil",
+    derive(FromPest),
+    pest_ast(rule(Rule::function_ty))
+)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct FunctionType {
     pub domain: TupleType,
     pub codomain: Box<Type>,
 }
 
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct UnknownType(pub InternedStr);
+
 // Conversions from pest parse trees
 
+#[cfg(feature = "pest-chil")]
+impl<'pest> FromPest<'pest> for UnknownType {
+    type Rule = Rule;
+    type FatalError = Void;
+
+    fn from_pest(
+        _pest: &mut Pairs<'pest, Self::Rule>,
+    ) -> Result<Self, ConversionError<Self::FatalError>> {
+        // The pest grammar rejects any type it does not understand
+        Err(ConversionError::NoMatch)
+    }
+}
+
+#[cfg(feature = "pest-chil")]
 impl<'pest> FromPest<'pest> for Expr {
     type Rule = Rule;
     type FatalError = Void;
@@ -325,6 +451,7 @@ impl<'pest> FromPest<'pest> for Expr {
     }
 }
 
+#[cfg(feature = "pest-chil")]
 impl<'pest> FromPest<'pest> for Bind {
     type Rule = Rule;
     type FatalError = Void;
@@ -337,10 +464,12 @@ impl<'pest> FromPest<'pest> for Bind {
         if pair.as_rule() != Rule::bind {
             return Err(ConversionError::NoMatch);
         }
+        let span = pair.as_span();
         let mut inner = pair.into_inner();
         let bind = Bind {
             defs: FromPest::from_pest(&mut inner)?,
             value: FromPest::from_pest(&mut inner)?,
+            comments: comments_before(span.get_input(), span.start()),
         };
         if inner.next().is_some() {
             return Err(ConversionError::Extraneous {
@@ -352,6 +481,7 @@ impl<'pest> FromPest<'pest> for Bind {
     }
 }
 
+#[cfg(feature = "pest-chil")]
 impl<'pest> FromPest<'pest> for Value {
     type Rule = Rule;
     type FatalError = Void;
@@ -395,6 +525,7 @@ impl<'pest> FromPest<'pest> for Value {
     }
 }
 
+#[cfg(feature = "pest-chil")]
 impl<'pest> FromPest<'pest> for Thunk {
     type Rule = Rule;
     type FatalError = Void;
@@ -407,12 +538,14 @@ impl<'pest> FromPest<'pest> for Thunk {
         if pair.as_rule() != Rule::thunk {
             return Err(ConversionError::NoMatch);
         }
+        let span = pair.as_span();
         let mut inner = pair.into_inner();
         let thunk = Thunk {
             addr: FromPest::from_pest(&mut inner)?,
             args: FromPest::from_pest(&mut inner)?,
             body: FromPest::from_pest(&mut inner)?,
             blocks: vec![],
+            comments: comments_before(span.get_input(), span.start()),
         };
         if inner.next().is_some() {
             return Err(ConversionError::Extraneous {
@@ -466,7 +599,7 @@ impl From<Op> for super::spartan::Op {
 #[cfg(test)]
 impl From<Variable> for super::spartan::Variable {
     fn from(var: Variable) -> Self {
-        Self(format!("var_{}", var.addr.1))
+        Self(format!("var_{}", var.addr.1).into())
     }
 }
 
@@ -489,28 +622,162 @@ pub(crate) mod tests {
     use std::path::Path;
 
     use dir_test::{dir_test, Fixture};
-    use from_pest::FromPest;
-    use pest::Parser;
-
-    use super::{ChilParser, Expr, Rule};
+    use petgraph::algo::is_isomorphic_matching;
+
+    use super::{parser, Expr, Op, Type, UnknownType, Value};
+    use crate::{
+        hypergraph::{
+            petgraph::{to_pet, PetGraph, PetNode},
+            traits::{WireType, WithType},
+        },
+        language::spartan::{self, Spartan},
+        prettyprinter::PrettyPrint,
+    };
 
     pub fn parse_chil(raw_path: &str) -> (&str, Expr) {
         let path = Path::new(raw_path);
         let program = std::fs::read_to_string(path).unwrap();
-        let mut pairs = ChilParser::parse(Rule::program, &program).unwrap_or_else(|err| {
+        let expr = parser::parse(&program).unwrap_or_else(|err| {
             panic!(
-                "could not parse program {:?}\n{err:?}",
+                "could not parse program {:?}\n{err}",
                 path.file_stem().unwrap()
             )
         });
         let name = path.file_stem().unwrap().to_str().unwrap();
-        let expr = Expr::from_pest(&mut pairs).unwrap();
         (name, expr)
     }
 
+    #[test]
+    fn unknown_types() {
+        let (expr, warnings) =
+            parser::parse_with_warnings("def %0: Int<?> = foo\ndef %1: Int = bar\noutput %0, %1")
+                .unwrap();
+        assert_eq!(
+            expr.binds[0].defs[0].r#type,
+            Some(Type::Unknown(UnknownType("Int<?>".into())))
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].line, warnings[0].column), (1, 9));
+    }
+
+    #[test]
+    fn ordering_types() {
+        let (expr, _) = parser::parse_with_warnings(
+            "def %0: Unit = foo
+def %1: Int64 = bar
+output %0, %1",
+        )
+        .unwrap();
+        assert_eq!(expr.binds[0].defs[0].get_type(), WireType::Ordering);
+        assert_eq!(expr.binds[1].defs[0].get_type(), WireType::Data);
+    }
+
+    #[test]
+    fn type_names() {
+        let (expr, _) = parser::parse_with_warnings(
+            "def %0: Array<Int64> = foo
+def %1 = bar
+output %0, %1",
+        )
+        .unwrap();
+        assert_eq!(
+            expr.binds[0].defs[0].type_name().as_deref(),
+            Some("Array<Int64>")
+        );
+        assert_eq!(expr.binds[1].defs[0].type_name(), None);
+    }
+
+    #[test]
+    fn comments_kept() {
+        let expr = parser::parse(
+            "# Constants
+def %0 : Int64 = int64/1
+def %1 = func(
+  # The body
+  thunk %2 = { %3 : Int64 =>
+    # Doubled
+    def %4 : Int64 = +(%3, %3) # not kept
+    output %4
+  })
+output %1",
+        )
+        .unwrap();
+        let reparsed = parser::parse(&expr.to_pretty()).unwrap();
+        assert_eq!(reparsed, expr);
+        assert_eq!(reparsed.binds[0].comments, [" Constants"]);
+        assert!(reparsed.binds[1].comments.is_empty());
+        let Value::Op { args, .. } = &reparsed.binds[1].value else {
+            panic!("expected an operation");
+        };
+        let [Value::Thunk(thunk)] = args.as_slice() else {
+            panic!("expected a thunk");
+        };
+        assert_eq!(thunk.comments, [" The body"]);
+        assert_eq!(thunk.body.binds[0].comments, [" Doubled"]);
+    }
+
     #[allow(clippy::needless_pass_by_value)]
     #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.chil", loader: crate::language::chil::tests::parse_chil, postfix: "check_parse")]
     fn check_parse(fixture: Fixture<(&str, Expr)>) {
         let (_name, _expr) = fixture.content();
     }
+
+    /// Checks two graphs are isomorphic, matching operations with `op_match` and
+    /// recursing into thunks.
+    fn isomorphic<V, W, E, F>(
+        left: &PetGraph<V, E>,
+        right: &PetGraph<W, F>,
+        op_match: &impl Fn(&V, &W) -> bool,
+    ) -> bool {
+        is_isomorphic_matching(
+            left,
+            right,
+            |x, y| match (x, y) {
+                (PetNode::Edge(_), PetNode::Edge(_)) => true,
+                (PetNode::Operation(x), PetNode::Operation(y)) => op_match(x, y),
+                (PetNode::Thunk(x), PetNode::Thunk(y)) => isomorphic(x, y, op_match),
+                _ => false,
+            },
+            |i, j| i == j,
+        )
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.chil", loader: crate::language::chil::tests::parse_chil, postfix: "spartan_isomorphic")]
+    fn spartan_isomorphic(fixture: Fixture<(&str, Expr)>) {
+        let (name, expr) = fixture.content();
+        let converted = expr.clone().into::<Spartan>();
+
+        let chil_graph = to_pet(&expr.to_graph(false).unwrap());
+        let spartan_graph = to_pet(&converted.to_graph(false).unwrap());
+        assert!(
+            isomorphic(
+                &chil_graph,
+                &spartan_graph,
+                &|op: &Op, sd_op: &spartan::Op| { spartan::Op::from(op.clone()) == *sd_op }
+            ),
+            "graph of {name} changed when converted to spartan"
+        );
+    }
+
+    #[cfg(feature = "pest-chil")]
+    pub fn parse_chil_with_pest(raw_path: &str) -> (Expr, Expr) {
+        use from_pest::FromPest;
+        use pest::Parser;
+
+        use super::{ChilParser, Rule};
+
+        let (_name, expr) = parse_chil(raw_path);
+        let program = std::fs::read_to_string(raw_path).unwrap();
+        let mut pairs = ChilParser::parse(Rule::program, &program).unwrap();
+        (expr, Expr::from_pest(&mut pairs).unwrap())
+    }
+
+    #[cfg(feature = "pest-chil")]
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*.chil", loader: crate::language::chil::tests::parse_chil_with_pest, postfix: "agrees_with_pest")]
+    fn agrees_with_pest(fixture: Fixture<(Expr, Expr)>) {
+        let (expr, pest_expr) = fixture.content();
+        assert_eq!(expr, pest_expr);
+    }
 }
@@ -0,0 +1,159 @@
+//! Clusters of the operations of a Chil program by the top-level definition they were written
+//! in, such as a function and everything in its body, found from the spans the parser records on
+//! each operation.
+
+use std::collections::BTreeSet;
+
+use indexmap::IndexSet;
+
+use super::{Bind, Expr, Op, Value};
+use crate::common::{Span, Spanned};
+
+/// The operations written in one top-level definition.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Cluster {
+    /// The variables the definition defines, which also name the operation it collapses into.
+    pub name: String,
+    /// The source from the start of the first operation of the definition to the end of the last.
+    pub span: Span,
+}
+
+/// The clusters of the definitions of `expr` with operations in them, in the order they were
+/// written.
+#[must_use]
+pub fn clusters(expr: &Expr) -> Vec<Cluster> {
+    expr.binds
+        .iter()
+        .filter_map(|bind| {
+            Some(Cluster {
+                name: name(bind),
+                span: value_span(&bind.value)?,
+            })
+        })
+        .collect()
+}
+
+/// The index of the cluster of `clusters`, as returned by [`clusters`], in which `span` was
+/// written.
+#[must_use]
+pub fn cluster_of(clusters: &[Cluster], span: Span) -> Option<usize> {
+    // Definitions do not overlap, so the clusters are ordered by both their starts and ends
+    let i = clusters.partition_point(|cluster| cluster.span.end <= span.start);
+    clusters
+        .get(i)
+        .filter(|cluster| cluster.span.contains(&span))
+        .map(|_| i)
+}
+
+/// Collapses each definition of `expr` whose cluster is named in `collapsed` into a single
+/// operation named after it, taking the variables the definition uses from elsewhere. The
+/// operation spans the whole cluster, so that it is found in the cluster by [`cluster_of`].
+#[must_use]
+pub fn collapse(mut expr: Expr, collapsed: &BTreeSet<String>) -> Expr {
+    if collapsed.is_empty() {
+        return expr;
+    }
+    for bind in &mut expr.binds {
+        let name = name(bind);
+        if !collapsed.contains(&name) {
+            continue;
+        }
+        let Some(span) = value_span(&bind.value) else {
+            continue;
+        };
+        let mut vars = IndexSet::new();
+        let mut bound = IndexSet::new();
+        bind.value.free_vars(&mut vars, &mut bound, false);
+        // Recursive definitions use their own variables, which would make a cycle
+        bound.extend(bind.defs.iter().map(|def| def.var.clone()));
+        let args = vars
+            .difference(&bound)
+            .cloned()
+            .map(Value::Variable)
+            .collect();
+        bind.value = Value::Op {
+            op: Op(name.as_str().into(), Some(span)),
+            args,
+        };
+    }
+    expr
+}
+
+fn name(bind: &Bind) -> String {
+    bind.defs
+        .iter()
+        .map(|def| def.var.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn value_span(value: &Value) -> Option<Span> {
+    match value {
+        Value::Variable(_) => None,
+        Value::Thunk(thunk) => expr_span(&thunk.body),
+        Value::Op { op, args } => args
+            .iter()
+            .filter_map(value_span)
+            .chain(op.span())
+            .reduce(Span::union),
+    }
+}
+
+fn expr_span(expr: &Expr) -> Option<Span> {
+    expr.binds
+        .iter()
+        .map(|bind| &bind.value)
+        .chain(&expr.values)
+        .filter_map(value_span)
+        .reduce(Span::union)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{cluster_of, clusters, collapse};
+    use crate::{
+        common::Spanned,
+        language::{chil::parser, Value},
+    };
+
+    const PROGRAM: &str =
+        "def %0 = func(thunk @1 = { %2 => def %3 = +(%2, int64/1)\noutput *(%3, %4) })
+def %5 = apply/1(%0, int64/2)
+output %5";
+
+    #[test]
+    fn clusters_cover_thunk_bodies() {
+        let expr = parser::parse(PROGRAM).unwrap();
+        let clusters = clusters(&expr);
+        assert_eq!(
+            clusters.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            ["%0", "%5"]
+        );
+
+        let Value::Op { args, .. } = &expr.binds[0].value else {
+            panic!("expected the function");
+        };
+        let Value::Thunk(thunk) = &args[0] else {
+            panic!("expected the body of the function");
+        };
+        let Value::Op { op, .. } = &thunk.body.binds[0].value else {
+            panic!("expected the addition");
+        };
+        assert_eq!(cluster_of(&clusters, op.span().unwrap()), Some(0));
+    }
+
+    #[test]
+    fn collapsed_clusters_take_free_variables() {
+        let expr = parser::parse(PROGRAM).unwrap();
+        let collapsed = collapse(expr.clone(), &BTreeSet::from(["%0".to_owned()]));
+        let Value::Op { op, args } = &collapsed.binds[0].value else {
+            panic!("expected the collapsed cluster");
+        };
+        assert_eq!(op.0, "%0");
+        assert_eq!(args.len(), 1);
+        assert_eq!(cluster_of(&clusters(&expr), op.span().unwrap()), Some(0));
+        collapsed.to_graph(false).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Chil programs split across several files.
+//!
+//! Compilers dump large programs as several files, each defining some of the variables of the
+//! program, such as the functions in a thunk, and using variables defined in the others. Since
+//! addresses are unique across the whole program, and binds may be used before they are defined,
+//! the files are linked by putting the binds of all of them in one expression, which outputs the
+//! outputs of each file in turn.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::{parser, Expr, Variable};
+use crate::language::GetVar;
+
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum ModuleError {
+    #[error("in {file}: {error}")]
+    Parse {
+        file: String,
+        #[source]
+        error: parser::ParseError,
+    },
+    #[error("{var} is defined in both {first} and {second}")]
+    Duplicate {
+        var: Variable,
+        first: String,
+        second: String,
+    },
+}
+
+/// Parses each of the named `files` of a program and links them into one expression.
+pub fn parse_module<'a>(
+    files: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Expr, ModuleError> {
+    let exprs = files
+        .into_iter()
+        .map(|(file, source)| {
+            parser::parse(source)
+                .map(|expr| (file, expr))
+                .map_err(|error| ModuleError::Parse {
+                    file: file.to_owned(),
+                    error,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    link(exprs)
+}
+
+/// Links the named expressions of the files of a program into one, resolving the variables used
+/// in each file to those defined in the others. Variables defined nowhere are left free.
+pub fn link<'a>(files: impl IntoIterator<Item = (&'a str, Expr)>) -> Result<Expr, ModuleError> {
+    let mut defined_in: HashMap<Variable, &str> = HashMap::new();
+    let mut linked = Expr {
+        binds: Vec::new(),
+        values: Vec::new(),
+    };
+    for (file, expr) in files {
+        for def in expr.binds.iter().flat_map(|bind| &bind.defs) {
+            if let Some(first) = defined_in.insert(def.var().clone(), file) {
+                return Err(ModuleError::Duplicate {
+                    var: def.var().clone(),
+                    first: first.to_owned(),
+                    second: file.to_owned(),
+                });
+            }
+        }
+        linked.binds.extend(expr.binds);
+        linked.values.extend(expr.values);
+    }
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_module, ModuleError};
+
+    const LIBRARY: &str = "def %0 = func(thunk @1 = { %2 => output +(%2, int64/1) })\noutput";
+    const MAIN: &str = "def %3 = apply/1(%0, int64/2)\noutput %3";
+
+    #[test]
+    fn cross_file_references() {
+        let expr = parse_module([("main.chil", MAIN), ("library.chil", LIBRARY)]).unwrap();
+        assert_eq!(expr.binds.len(), 2);
+        assert_eq!(expr.values.len(), 1);
+        assert!(expr.free_vars(false).is_empty());
+        expr.to_graph(false).unwrap();
+    }
+
+    #[test]
+    fn duplicate_definitions() {
+        let err = parse_module([("main.chil", MAIN), ("copy.chil", MAIN)]).unwrap_err();
+        assert!(matches!(
+            err,
+            ModuleError::Duplicate { first, second, .. } if first == "main.chil" && second == "copy.chil"
+        ));
+    }
+
+    #[test]
+    fn parse_errors_name_the_file() {
+        let err = parse_module([("main.chil", MAIN), ("broken.chil", "def %5 =")]).unwrap_err();
+        assert!(matches!(err, ModuleError::Parse { file, .. } if file == "broken.chil"));
+    }
+}
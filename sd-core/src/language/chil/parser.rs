@@ -0,0 +1,584 @@
+//! A hand-written recursive-descent parser for Chil.
+//!
+//! This accepts the same language as the grammar in `chil.pest` and produces the same syntax
+//! tree, but avoids building an intermediate parse tree, which dominates the time spent loading
+//! large programs.
+
+use thiserror::Error;
+
+use super::{
+    Addr, BaseType, Bind, Expr, FunctionType, GenericType, Identifier, Op, Thunk, TupleType, Type,
+    UnknownType, Value, Variable, VariableDef,
+};
+use crate::{common::Span, language::comments_before};
+
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+#[error("expected {expected} at line {line}, column {column}")]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub expected: &'static str,
+}
+
+/// A type annotation which could not be parsed, and was kept as an unknown type.
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+#[error("unknown type `{found}` at line {line}, column {column}")]
+pub struct ParseWarning {
+    pub line: usize,
+    pub column: usize,
+    pub found: String,
+}
+
+/// Parses a whole Chil program, logging a warning for each unknown type.
+pub fn parse(source: &str) -> Result<Expr, ParseError> {
+    let (expr, warnings) = parse_with_warnings(source)?;
+    for warning in warnings {
+        tracing::warn!("{warning}");
+    }
+    Ok(expr)
+}
+
+/// Parses a whole Chil program, also returning a warning for each unknown type.
+pub fn parse_with_warnings(source: &str) -> Result<(Expr, Vec<ParseWarning>), ParseError> {
+    let mut parser = Parser::new(source);
+    match parser.program() {
+        Ok(expr) => Ok((expr, parser.warnings())),
+        Err(()) => Err(parser.error()),
+    }
+}
+
+const KEYWORDS: [&str; 3] = ["def", "output", "thunk"];
+const SYMBOLS: &[u8] = b"+-*/%&|!=<>^";
+
+type PResult<T> = Result<T, ()>;
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    /// The furthest position at which something failed to parse, and what was expected there.
+    furthest: (usize, &'static str),
+    /// Type annotations which could not be parsed, and where they start.
+    unknown_types: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Parser<'a> {
+    const fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            furthest: (0, "program"),
+            unknown_types: Vec::new(),
+        }
+    }
+
+    /// The line and column of `pos`, counting from one.
+    fn location(&self, pos: usize) -> (usize, usize) {
+        let before = &self.input[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            + 1;
+        (line, column)
+    }
+
+    fn error(&self) -> ParseError {
+        let (pos, expected) = self.furthest;
+        let (line, column) = self.location(pos);
+        ParseError {
+            line,
+            column,
+            expected,
+        }
+    }
+
+    fn warnings(&self) -> Vec<ParseWarning> {
+        self.unknown_types
+            .iter()
+            .map(|&(pos, found)| {
+                let (line, column) = self.location(pos);
+                ParseWarning {
+                    line,
+                    column,
+                    found: found.to_owned(),
+                }
+            })
+            .collect()
+    }
+
+    fn fail<T>(&mut self, expected: &'static str) -> PResult<T> {
+        if self.pos >= self.furthest.0 {
+            self.furthest = (self.pos, expected);
+        }
+        Err(())
+    }
+
+    /// Runs `f`, rewinding to the current position if it fails.
+    fn attempt<T>(&mut self, f: impl FnOnce(&mut Self) -> PResult<T>) -> PResult<T> {
+        let pos = self.pos;
+        let unknown_types = self.unknown_types.len();
+        f(self).inspect_err(|()| {
+            self.pos = pos;
+            self.unknown_types.truncate(unknown_types);
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, literal: &str) -> bool {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, literal: &'static str) -> PResult<()> {
+        if self.eat(literal) {
+            Ok(())
+        } else {
+            self.fail(literal)
+        }
+    }
+
+    /// Consumes characters while `f` holds, returning how many were consumed.
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) -> usize {
+        let mut count = 0;
+        while self.peek().is_some_and(&f) {
+            self.bump();
+            count += 1;
+        }
+        count
+    }
+
+    /// Skips whitespace and comments, which may appear between the tokens of non-atomic rules.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.eat_while(|c| matches!(c, ' ' | '\t' | '\n'));
+            if self.eat("#") {
+                self.eat_while(|c| c != '\n');
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Parses a comma separated list of at least one item.
+    fn sep_by1<T>(&mut self, mut item: impl FnMut(&mut Self) -> PResult<T>) -> PResult<Vec<T>> {
+        let mut items = vec![item(self)?];
+        while let Ok(next) = self.attempt(|p| {
+            p.skip_trivia();
+            p.expect(",")?;
+            p.skip_trivia();
+            item(p)
+        }) {
+            items.push(next);
+        }
+        Ok(items)
+    }
+
+    fn program(&mut self) -> PResult<Expr> {
+        self.skip_trivia();
+        let expr = self.expr()?;
+        self.skip_trivia();
+        if self.pos == self.input.len() {
+            Ok(expr)
+        } else {
+            self.fail("end of input")
+        }
+    }
+
+    fn expr(&mut self) -> PResult<Expr> {
+        let mut binds = Vec::new();
+        while self.rest().starts_with("def") {
+            binds.push(self.bind()?);
+            self.skip_trivia();
+        }
+        self.expect("output")?;
+        let values = self
+            .attempt(|p| {
+                p.skip_trivia();
+                p.values()
+            })
+            .unwrap_or_default();
+        Ok(Expr { binds, values })
+    }
+
+    fn bind(&mut self) -> PResult<Bind> {
+        let comments = comments_before(self.input, self.pos);
+        self.expect("def")?;
+        self.skip_trivia();
+        let def = self.variable_def()?;
+        self.skip_trivia();
+        self.expect("=")?;
+        self.skip_trivia();
+        let value = self.value()?;
+        Ok(Bind {
+            defs: vec![def],
+            value,
+            comments,
+        })
+    }
+
+    fn thunk(&mut self) -> PResult<Thunk> {
+        let comments = comments_before(self.input, self.pos);
+        self.expect("thunk")?;
+        self.skip_trivia();
+        let addr = self.addr()?;
+        self.skip_trivia();
+        self.expect("=")?;
+        self.skip_trivia();
+        self.expect("{")?;
+        self.skip_trivia();
+        let args = self
+            .attempt(|p| p.sep_by1(Self::variable_def))
+            .unwrap_or_default();
+        self.skip_trivia();
+        self.expect("=>")?;
+        self.skip_trivia();
+        let body = self.expr()?;
+        self.skip_trivia();
+        self.expect("}")?;
+        Ok(Thunk {
+            addr,
+            args,
+            body,
+            blocks: vec![],
+            comments,
+        })
+    }
+
+    fn thunks(&mut self) -> PResult<Vec<Thunk>> {
+        let mut thunks = vec![self.thunk()?];
+        while let Ok(thunk) = self.attempt(|p| {
+            p.skip_trivia();
+            p.thunk()
+        }) {
+            thunks.push(thunk);
+        }
+        Ok(thunks)
+    }
+
+    fn values(&mut self) -> PResult<Vec<Value>> {
+        self.sep_by1(Self::value)
+    }
+
+    fn value(&mut self) -> PResult<Value> {
+        if let Ok(var) = self.attempt(Self::variable) {
+            return Ok(Value::Variable(var));
+        }
+        let op = self.op()?;
+        let args = self
+            .attempt(|p| {
+                p.skip_trivia();
+                p.expect("(")?;
+                p.skip_trivia();
+                let args = p.args()?;
+                p.skip_trivia();
+                p.expect(")")?;
+                Ok(args)
+            })
+            .unwrap_or_default();
+        Ok(Value::Op { op, args })
+    }
+
+    fn args(&mut self) -> PResult<Vec<Value>> {
+        if let Ok(thunks) = self.attempt(Self::thunks) {
+            return Ok(thunks.into_iter().map(Value::Thunk).collect());
+        }
+        let mut args = self.values()?;
+        if let Ok(thunks) = self.attempt(|p| {
+            p.skip_trivia();
+            p.expect(";")?;
+            p.skip_trivia();
+            p.thunks()
+        }) {
+            args.extend(thunks.into_iter().map(Value::Thunk));
+        }
+        Ok(args)
+    }
+
+    fn op(&mut self) -> PResult<Op> {
+        let start = self.pos;
+        if KEYWORDS
+            .iter()
+            .any(|keyword| self.rest().starts_with(keyword))
+        {
+            return self.fail("operation");
+        }
+
+        let symbols =
+            |p: &mut Self| p.eat_while(|c| u8::try_from(c).is_ok_and(|c| SYMBOLS.contains(&c)));
+        let matched = self
+            .attempt(|p| {
+                if !p.eat("throwing") {
+                    p.eat("wrapping");
+                }
+                if symbols(p) > 0 {
+                    Ok(())
+                } else {
+                    p.fail("operation")
+                }
+            })
+            .or_else(|()| {
+                self.attempt(|p| {
+                    p.expect("syscall/")?;
+                    if p.eat_while(|c| c.is_ascii_alphanumeric()) > 0 {
+                        Ok(())
+                    } else {
+                        p.fail("alphanumeric character")
+                    }
+                })
+            })
+            .or_else(|()| {
+                if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.eat_while(|c| c.is_ascii_alphanumeric());
+                    while self
+                        .attempt(|p| {
+                            p.expect("/")?;
+                            p.op_parameter()
+                        })
+                        .is_ok()
+                    {}
+                    Ok(())
+                } else {
+                    self.fail("operation")
+                }
+            });
+
+        matched?;
+        let span = Span {
+            start,
+            end: self.pos,
+        };
+        Ok(Op(self.input[start..self.pos].into(), Some(span)))
+    }
+
+    /// Parses a parameter of an operation such as the `5` in `int64/5`.
+    fn op_parameter(&mut self) -> PResult<()> {
+        if self.eat("true") || self.eat("false") {
+            return Ok(());
+        }
+        if self.attempt(Self::number).is_ok() {
+            return Ok(());
+        }
+        if self.eat("'") {
+            return match self.bump() {
+                Some('\'') | None => self.fail("character"),
+                Some(_) => self.expect("'"),
+            };
+        }
+        if self.eat("`") {
+            self.eat_while(|c| c != '`');
+            return self.expect("`");
+        }
+        if self.eat("\"") {
+            while !self.rest().is_empty() && !self.rest().starts_with('"') {
+                if !self.eat("\\\"") {
+                    self.bump();
+                }
+            }
+            return self.expect("\"");
+        }
+        self.ty().map(|_| ())
+    }
+
+    fn number(&mut self) -> PResult<()> {
+        self.eat("-");
+        if self.eat_while(|c| c.is_ascii_digit()) == 0 {
+            return self.fail("number");
+        }
+        let _ = self.attempt(|p| {
+            p.expect(".")?;
+            if p.eat_while(|c| c.is_ascii_digit()) > 0 {
+                Ok(())
+            } else {
+                p.fail("digit")
+            }
+        });
+        Ok(())
+    }
+
+    fn variable(&mut self) -> PResult<Variable> {
+        if let Ok(addr) = self.attempt(Self::addr) {
+            return Ok(Variable { name: None, addr });
+        }
+        let name = self.identifier()?;
+        self.skip_trivia();
+        self.expect("(")?;
+        self.skip_trivia();
+        self.expect("id")?;
+        self.skip_trivia();
+        self.expect(":")?;
+        self.skip_trivia();
+        let addr = self.addr()?;
+        self.skip_trivia();
+        self.expect(")")?;
+        Ok(Variable {
+            name: Some(name),
+            addr,
+        })
+    }
+
+    fn variable_def(&mut self) -> PResult<VariableDef> {
+        let var = self.variable()?;
+        let r#type = self
+            .attempt(|p| {
+                p.skip_trivia();
+                p.expect(":")?;
+                p.skip_trivia();
+                p.attempt(|p| {
+                    let ty = p.ty()?;
+                    if p.at_annotation_end() {
+                        Ok(ty)
+                    } else {
+                        p.fail("`,` or `=`")
+                    }
+                })
+                .or_else(|()| p.unknown_ty())
+            })
+            .ok();
+        Ok(VariableDef { var, r#type })
+    }
+
+    /// Whether the next token could follow a type annotation.
+    fn at_annotation_end(&mut self) -> bool {
+        let pos = self.pos;
+        self.skip_trivia();
+        let end = self.rest().starts_with([',', '=']);
+        self.pos = pos;
+        end
+    }
+
+    /// Skips over a type annotation which could not be parsed, up to the `,` or `=` after it.
+    fn unknown_ty(&mut self) -> PResult<Type> {
+        let start = self.pos;
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            match c {
+                ',' | '=' | '\n' if depth == 0 => break,
+                '(' | '<' | '[' | '{' => depth += 1,
+                ')' | '>' | ']' | '}' => match depth.checked_sub(1) {
+                    Some(outer) => depth = outer,
+                    None => break,
+                },
+                '-' if self.rest().starts_with("->") => {
+                    self.bump();
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+        let found = self.input[start..self.pos].trim_end();
+        if found.is_empty() {
+            return self.fail("type");
+        }
+        self.pos = start + found.len();
+        self.unknown_types.push((start, found));
+        Ok(Type::Unknown(UnknownType(found.into())))
+    }
+
+    fn addr(&mut self) -> PResult<Addr> {
+        let Some(sigil) = self.peek().filter(|c| matches!(c, '@' | '%')) else {
+            return self.fail("address");
+        };
+        self.bump();
+        let start = self.pos;
+        if self.eat_while(|c| c.is_ascii_digit()) == 0 {
+            return self.fail("digit");
+        }
+        match self.input[start..self.pos].parse() {
+            Ok(number) => Ok(Addr(sigil, number)),
+            Err(_) => self.fail("address"),
+        }
+    }
+
+    fn identifier(&mut self) -> PResult<Identifier> {
+        let start = self.pos;
+        let tangent = self.attempt(|p| {
+            p.ty()?;
+            p.expect("_tangent")?;
+            if p.eat("Add") || p.eat("Zero") {
+                Ok(())
+            } else {
+                p.fail("Add or Zero")
+            }
+        });
+        if tangent.is_err() && self.eat_while(|c| c != '(' && c != ' ') == 0 {
+            return self.fail("identifier");
+        }
+        Ok(Identifier(self.input[start..self.pos].into()))
+    }
+
+    fn ty(&mut self) -> PResult<Type> {
+        if let Ok(generic) = self.attempt(Self::generic_ty) {
+            return Ok(Type::Generic(generic));
+        }
+        if let Ok(base) = self.attempt(Self::base_ty) {
+            return Ok(Type::Base(base));
+        }
+        if let Ok(function) = self.attempt(Self::function_ty) {
+            return Ok(Type::Function(function));
+        }
+        self.tuple_ty().map(Type::Tuple)
+    }
+
+    fn tys(&mut self) -> PResult<Vec<Type>> {
+        self.sep_by1(Self::ty)
+    }
+
+    fn base_ty(&mut self) -> PResult<BaseType> {
+        let start = self.pos;
+        if !self.peek().is_some_and(|c| c.is_ascii_uppercase()) {
+            return self.fail("type");
+        }
+        self.bump();
+        self.eat_while(|c| c.is_ascii_alphanumeric() || matches!(c, '$' | '_' | '-' | '.'));
+        Ok(BaseType(self.input[start..self.pos].into()))
+    }
+
+    fn generic_ty(&mut self) -> PResult<GenericType> {
+        let base = self.base_ty()?;
+        self.skip_trivia();
+        self.expect("<")?;
+        self.skip_trivia();
+        let params = self.tys()?;
+        self.skip_trivia();
+        self.expect(">")?;
+        Ok(GenericType { base, params })
+    }
+
+    fn tuple_ty(&mut self) -> PResult<TupleType> {
+        self.expect("(")?;
+        self.skip_trivia();
+        let types = self.attempt(Self::tys).unwrap_or_default();
+        self.skip_trivia();
+        self.expect(")")?;
+        Ok(TupleType { types })
+    }
+
+    fn function_ty(&mut self) -> PResult<FunctionType> {
+        let domain = self.tuple_ty()?;
+        self.skip_trivia();
+        self.expect("->")?;
+        self.skip_trivia();
+        let codomain = Box::new(self.ty()?);
+        Ok(FunctionType { domain, codomain })
+    }
+}
@@ -0,0 +1,187 @@
+//! Filtering the operations of uninteresting dialects out of imported MLIR, so that large
+//! compiler dumps stay tractable.
+
+use super::{Expr, Op, Value};
+use crate::interner::InternedStr;
+
+/// Namespace prefixes of operations to leave out of imported graphs.
+///
+/// A prefix such as `llvm.intr.dbg` matches the operations in that namespace, like
+/// `llvm.intr.dbg.value`, as well as an operation of exactly that name.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct NamespaceFilter {
+    /// Operations which are removed entirely. Any uses of their results become free variables.
+    pub drop: Vec<String>,
+    /// Operations which are replaced by an opaque operation named after the prefix, without
+    /// their attributes or regions.
+    pub collapse: Vec<String>,
+}
+
+fn matching<'a>(prefixes: &'a [String], name: &str) -> Option<&'a str> {
+    prefixes.iter().map(String::as_str).find(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+impl NamespaceFilter {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.drop.is_empty() && self.collapse.is_empty()
+    }
+
+    /// A copy of `expr` with the matching operations, including those in nested regions,
+    /// dropped or collapsed.
+    #[must_use]
+    pub fn apply(&self, expr: &Expr) -> Expr {
+        let mut expr = expr.clone();
+        self.filter_expr(&mut expr);
+        expr
+    }
+
+    fn filter_expr(&self, expr: &mut Expr) {
+        expr.binds.retain(|bind| match &bind.value {
+            Value::Op { op, .. } => matching(&self.drop, &op.name).is_none(),
+            _ => true,
+        });
+        for bind in &mut expr.binds {
+            self.filter_value(&mut bind.value);
+        }
+        for value in &mut expr.values {
+            self.filter_value(value);
+        }
+    }
+
+    fn filter_value(&self, value: &mut Value) {
+        match value {
+            Value::Variable(_) => {}
+            Value::Thunk(thunk) => {
+                self.filter_expr(&mut thunk.body);
+                for block in &mut thunk.blocks {
+                    self.filter_expr(&mut block.expr);
+                }
+            }
+            Value::Op { op, args } => {
+                if let Some(prefix) = matching(&self.collapse, &op.name) {
+                    *op = Op {
+                        name: InternedStr::new(prefix),
+                        attributes: String::new(),
+                        ..op.clone()
+                    };
+                    args.retain(|arg| !matches!(arg, Value::Thunk(_)));
+                }
+                for arg in args {
+                    self.filter_value(arg);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NamespaceFilter;
+    use crate::{
+        interner::InternedStr,
+        language::mlir::{Bind, Expr, Op, RegionKind, Thunk, Value, Var},
+    };
+
+    fn op(name: &str, args: Vec<Value>) -> Value {
+        Value::Op {
+            op: Op {
+                name: InternedStr::new(name),
+                successors: Vec::new(),
+                attributes: "{a = 1}".to_owned(),
+                sym_name: None,
+                symbols: Vec::new(),
+            },
+            args,
+        }
+    }
+
+    fn var(id: &str) -> Var {
+        Var::Var { id: id.into() }
+    }
+
+    fn names(expr: &Expr) -> Vec<&str> {
+        expr.binds
+            .iter()
+            .filter_map(|bind| match &bind.value {
+                Value::Op { op, .. } => Some(op.name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drop_and_collapse() {
+        let region = Value::Thunk(Thunk {
+            addr: RegionKind::SsaCfg,
+            args: Vec::new(),
+            body: Expr {
+                binds: vec![Bind {
+                    defs: Vec::new(),
+                    value: op("llvm.intr.dbg.value", Vec::new()),
+                    comments: Vec::new(),
+                }],
+                values: Vec::new(),
+            },
+            blocks: Vec::new(),
+            comments: Vec::new(),
+        });
+        let expr = Expr {
+            binds: vec![
+                Bind {
+                    defs: vec![var("x")],
+                    value: op("arith.constant", Vec::new()),
+                    comments: Vec::new(),
+                },
+                Bind {
+                    defs: Vec::new(),
+                    value: op("llvm.intr.dbg", Vec::new()),
+                    comments: Vec::new(),
+                },
+                Bind {
+                    defs: vec![var("y")],
+                    value: op(
+                        "tosa.conv2d",
+                        vec![Value::Variable(var("x")), region.clone()],
+                    ),
+                    comments: Vec::new(),
+                },
+                Bind {
+                    defs: vec![var("w")],
+                    value: op("scf.execute_region", vec![region]),
+                    comments: Vec::new(),
+                },
+                Bind {
+                    defs: vec![var("z")],
+                    value: op("tosa_other", Vec::new()),
+                    comments: Vec::new(),
+                },
+            ],
+            values: Vec::new(),
+        };
+        let filter = NamespaceFilter {
+            drop: vec!["llvm.intr.dbg".to_owned()],
+            collapse: vec!["tosa".to_owned()],
+        };
+        let filtered = filter.apply(&expr);
+        assert_eq!(
+            names(&filtered),
+            ["arith.constant", "tosa", "scf.execute_region", "tosa_other"]
+        );
+        let Value::Op { op, args } = &filtered.binds[1].value else {
+            unreachable!()
+        };
+        assert!(op.attributes.is_empty());
+        assert_eq!(args.len(), 1);
+        let Value::Op { args, .. } = &filtered.binds[2].value else {
+            unreachable!()
+        };
+        let [Value::Thunk(thunk)] = args.as_slice() else {
+            unreachable!()
+        };
+        assert!(thunk.body.binds.is_empty());
+    }
+}
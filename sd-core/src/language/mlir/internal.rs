@@ -182,6 +182,8 @@ pub struct TypedArg {
     pub id: String,
     #[pest_ast(inner(rule(Rule::r#type), with(span_into_str), with(str::to_string)))]
     pub r#type: String,
+    /// Printed with `--mlir-print-debuginfo`.
+    pub location: Option<Location>,
 }
 
 #[derive(Debug, FromPest)]
@@ -294,6 +296,20 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn block_arguments_with_locations() -> Result<(), Box<dyn std::error::Error>> {
+        let mut parse_tree = MlirParser::parse(
+            Rule::block,
+            r#"^bb0(%arg0: i64 loc("main.c":3:7), %arg1: i1 loc(unknown)):
+              "func.return"(%arg0) : (i64) -> () loc("main.c":4:3)
+            "#,
+        )?;
+        let block = super::Block::from_pest(&mut parse_tree)?;
+        assert_eq!(block.label.args.len(), 2);
+        assert!(block.label.args.iter().all(|arg| arg.location.is_some()));
+        Ok(())
+    }
+
     pub fn parse_mlir(raw_path: &str) -> (&str, Vec<TopLevelItem>) {
         let path = Path::new(raw_path);
         let program = std::fs::read_to_string(path).unwrap();
@@ -4,23 +4,26 @@ use std::fmt::Display;
 
 use itertools::Itertools;
 
+pub mod filter;
 pub mod internal;
 
 use pretty::RcDoc;
 #[cfg(test)]
 use serde::Serialize;
 
-use self::internal::Attribute;
+use self::{filter::NamespaceFilter, internal::Attribute};
 use super::{Fresh, Language, OpInfo, CF};
 use crate::{
-    common::{Matchable, Unit},
+    common::{Matchable, Query},
     hypergraph::traits::{WireType, WithType},
+    interner::InternedStr,
     prettyprinter::PrettyPrint,
 };
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub struct MlirSettings {
     pub sym_name_linking: bool,
+    pub filter: NamespaceFilter,
 }
 
 pub struct Mlir;
@@ -28,7 +31,7 @@ pub struct Mlir;
 impl Language for Mlir {
     type Op = Op;
     type Var = Var;
-    type Addr = Unit;
+    type Addr = RegionKind;
     type VarDef = Var;
     type BlockAddr = BlockAddr;
     type Symbol = Symbol;
@@ -43,11 +46,11 @@ pub type Block = super::Block<Mlir>;
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct Op {
-    pub name: String,
+    pub name: InternedStr,
     pub successors: Vec<BlockAddr>,
     pub attributes: String,
-    pub sym_name: Option<String>,
-    pub symbols: Vec<String>,
+    pub sym_name: Option<InternedStr>,
+    pub symbols: Vec<InternedStr>,
 }
 
 impl Display for Op {
@@ -57,8 +60,8 @@ impl Display for Op {
 }
 
 impl Matchable for Op {
-    fn is_match(&self, query: &str) -> bool {
-        self.name == query
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.name)
     }
 }
 
@@ -78,19 +81,19 @@ impl OpInfo<Mlir> for Op {
     }
 
     fn symbols_used(&self) -> impl Iterator<Item = Symbol> {
-        self.symbols.iter().map(|x| Symbol(x.clone()))
+        self.symbols.iter().cloned().map(Symbol)
     }
 
     fn sym_name(&self) -> Option<Symbol> {
-        self.sym_name.as_ref().map(|x| Symbol(x.clone()))
+        self.sym_name.clone().map(Symbol)
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(test, derive(Serialize))]
 pub enum Var {
-    Var { id: String },
-    VarIdx { id: String, index: usize },
+    Var { id: InternedStr },
+    VarIdx { id: InternedStr, index: usize },
     Symbol(Symbol),
 }
 
@@ -120,10 +123,10 @@ impl Display for Var {
 }
 
 impl Matchable for Var {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         match self {
-            Var::Var { id } => id == query,
-            Var::VarIdx { id, .. } => id == query,
+            Var::Var { id } => query.matches(id),
+            Var::VarIdx { id, .. } => query.matches(id),
             Var::Symbol(s) => s.is_match(query),
         }
     }
@@ -132,14 +135,61 @@ impl Matchable for Var {
 impl Fresh for Var {
     fn fresh(number: usize) -> Self {
         Var::Var {
-            id: format!("?{number}"),
+            id: format!("?{number}").into(),
         }
     }
 }
 
+/// Operations whose regions are graph regions. MLIR leaves the kind of a region to the operation
+/// which holds it, so this only covers common dialects.
+const GRAPH_REGION_OPS: &[&str] = &[
+    "builtin.module",
+    "hw.module",
+    "handshake.func",
+    "llhd.entity",
+    "calyx.wires",
+];
+
+/// How the operations in an MLIR region relate to each other.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(test, derive(Serialize))]
+pub enum RegionKind {
+    /// Blocks whose operations run in order, with terminators passing control between blocks or
+    /// back to the enclosing operation.
+    #[default]
+    SsaCfg,
+    /// A single block of operations whose order does not matter, like the components of a
+    /// circuit. Values flow out of the region through its terminator rather than control.
+    Graph,
+}
+
+impl RegionKind {
+    /// The kind of the regions of operation `op`, with `blocks` blocks in its largest region.
+    #[must_use]
+    pub fn of(op: &str, blocks: usize) -> Self {
+        if blocks <= 1 && GRAPH_REGION_OPS.contains(&op) {
+            Self::Graph
+        } else {
+            Self::SsaCfg
+        }
+    }
+}
+
+impl Display for RegionKind {
+    fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Ok(())
+    }
+}
+
+impl Matchable for RegionKind {
+    fn is_match(&self, _: &Query) -> bool {
+        false
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(test, derive(Serialize))]
-pub struct BlockAddr(pub String);
+pub struct BlockAddr(pub InternedStr);
 
 impl Display for BlockAddr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -148,14 +198,14 @@ impl Display for BlockAddr {
 }
 
 impl Matchable for BlockAddr {
-    fn is_match(&self, query: &str) -> bool {
-        self.0 == query
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.0)
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(test, derive(Serialize))]
-pub struct Symbol(pub String);
+pub struct Symbol(pub InternedStr);
 
 impl Display for Symbol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -164,14 +214,14 @@ impl Display for Symbol {
 }
 
 impl Matchable for Symbol {
-    fn is_match(&self, query: &str) -> bool {
-        self.0 == query || self.0 == query.chars().filter(|c| c != &'@').collect::<String>()
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.0) || query.matches(&format!("@{}", self.0))
     }
 }
 
 impl PrettyPrint for Symbol {
     fn to_doc(&self) -> pretty::RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
     }
 }
 
@@ -181,45 +231,48 @@ impl From<internal::Value> for Var {
     fn from(value: internal::Value) -> Self {
         if let Some(i) = value.index {
             Var::VarIdx {
-                id: value.id,
+                id: value.id.into(),
                 index: i.0,
             }
         } else {
-            Var::Var { id: value.id }
+            Var::Var {
+                id: value.id.into(),
+            }
         }
     }
 }
 
 impl From<internal::TypedArg> for Var {
     fn from(arg: internal::TypedArg) -> Self {
-        Var::Var { id: arg.id }
+        Var::Var { id: arg.id.into() }
     }
 }
 
 impl From<internal::OpResult> for Vec<Var> {
     fn from(op_result: internal::OpResult) -> Vec<Var> {
+        let id = op_result.id.into();
         if let Some(idx) = op_result.index {
             (0..idx.0)
                 .map(|x| Var::VarIdx {
-                    id: op_result.id.clone(),
+                    id: id.clone(),
                     index: x,
                 })
                 .collect()
         } else {
-            vec![Var::Var { id: op_result.id }]
+            vec![Var::Var { id }]
         }
     }
 }
 
 impl From<internal::BlockId> for BlockAddr {
     fn from(id: internal::BlockId) -> Self {
-        BlockAddr(id.0)
+        BlockAddr(id.0.into())
     }
 }
 
 impl From<internal::Successor> for BlockAddr {
     fn from(successor: internal::Successor) -> Self {
-        BlockAddr(successor.id)
+        BlockAddr(successor.id.into())
     }
 }
 
@@ -237,21 +290,52 @@ impl From<internal::Operation> for Bind {
         Bind {
             defs: op.result.into_iter().map_into::<Vec<Var>>().concat(),
             value: op.operation.into(),
+            comments: Vec::new(),
         }
     }
 }
 
 impl From<internal::Region> for Thunk {
     fn from(region: internal::Region) -> Self {
+        let mut blocks = region.blocks.into_iter();
+        // A labelled entry block cannot be branched to, so its arguments are the arguments of
+        // the region itself.
+        let (args, body) = match region.entry_block {
+            Some(block) => (vec![], block.operations.into()),
+            None => blocks.next().map_or_else(Default::default, |block| {
+                (
+                    block.label.args.into_iter().map_into().collect(),
+                    block.operations.into(),
+                )
+            }),
+        };
         Thunk {
-            addr: Unit,
-            args: vec![],
-            body: region
-                .entry_block
-                .map(|block| block.operations.into())
-                .unwrap_or_default(),
-            blocks: region.blocks.into_iter().map_into().collect(),
+            addr: RegionKind::SsaCfg,
+            args,
+            body,
+            blocks: blocks.map_into().collect(),
+            comments: Vec::new(),
+        }
+    }
+}
+
+impl Thunk {
+    /// Treats the region as a graph region, whose terminator hands its operands out of the region
+    /// as values instead of returning control.
+    fn into_graph_region(mut self) -> Self {
+        self.addr = RegionKind::Graph;
+        if let Some(Bind {
+            defs,
+            value: Value::Op { op, args },
+            ..
+        }) = self.body.binds.last()
+        {
+            if defs.is_empty() && matches!(op.get_cf(), Some(CF::Return)) {
+                self.body.values = args.clone();
+                self.body.binds.pop();
+            }
         }
+        self
     }
 }
 
@@ -267,9 +351,18 @@ impl From<internal::Block> for Block {
 
 impl From<internal::GenericOperation> for Value {
     fn from(generic_op: internal::GenericOperation) -> Self {
+        let kind = RegionKind::of(
+            &generic_op.op,
+            generic_op
+                .regions
+                .iter()
+                .map(|region| usize::from(region.entry_block.is_some()) + region.blocks.len())
+                .max()
+                .unwrap_or_default(),
+        );
         Value::Op {
             op: Op {
-                name: generic_op.op,
+                name: generic_op.op.into(),
                 successors: generic_op.successors.into_iter().map_into().collect(),
                 attributes: if generic_op.attributes.len() + generic_op.properties.len() == 0 {
                     String::new()
@@ -289,25 +382,148 @@ impl From<internal::GenericOperation> for Value {
                     .chain(generic_op.properties.iter())
                     .map(Attribute::is_sym_name)
                     .find(Option::is_some)
-                    .flatten(),
+                    .flatten()
+                    .map(InternedStr::from),
                 symbols: generic_op
                     .attributes
                     .iter()
                     .chain(generic_op.properties.iter())
                     .filter_map(|attr| attr.get_symbol())
+                    .map(InternedStr::from)
                     .collect(),
             },
             args: generic_op
                 .operands
                 .into_iter()
                 .map(|x| Value::Variable(x.into()))
-                .chain(
-                    generic_op
-                        .regions
-                        .into_iter()
-                        .map(|x| Value::Thunk(x.into())),
-                )
+                .chain(generic_op.regions.into_iter().map(|x| {
+                    let thunk = Thunk::from(x);
+                    Value::Thunk(match kind {
+                        RegionKind::SsaCfg => thunk,
+                        RegionKind::Graph => thunk.into_graph_region(),
+                    })
+                }))
                 .collect(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::{
+        internal::{self, MlirParser, Rule},
+        Expr, RegionKind, Thunk, Value, Var,
+    };
+    use crate::{
+        common::InOut,
+        hypergraph::traits::{Graph, NodeLike, WireType, WithType, WithWeight},
+        lp::Solver,
+        monoidal::{
+            graph::{MonoidalGraph, MonoidalOp},
+            wired_graph::from_graph,
+        },
+    };
+
+    #[test]
+    fn entry_block_arguments() -> Result<(), Box<dyn std::error::Error>> {
+        let mut parse_tree = MlirParser::parse(
+            Rule::region,
+            r#"{
+                ^bb0(%arg0: i64, %arg1: i1):
+                  "cf.cond_br"(%arg1)[^bb1, ^bb2] : (i1) -> ()
+                ^bb1:
+                  "func.return"(%arg0) : (i64) -> ()
+                ^bb2:
+                  %0 = "arith.addi"(%arg0, %arg0) : (i64, i64) -> i64
+                  "func.return"(%0) : (i64) -> ()
+                }
+            "#,
+        )?;
+        let thunk = Thunk::from(internal::Region::from_pest(&mut parse_tree)?);
+        assert_eq!(
+            thunk.args,
+            vec![
+                Var::Var { id: "%arg0".into() },
+                Var::Var { id: "%arg1".into() }
+            ]
+        );
+        assert_eq!(thunk.body.binds.len(), 1);
+        assert_eq!(thunk.blocks.len(), 2);
+        Ok(())
+    }
+    #[test]
+    fn graph_region() -> Result<(), Box<dyn std::error::Error>> {
+        let mut parse_tree = MlirParser::parse(
+            Rule::toplevel,
+            r#""hw.module"() ({
+                ^bb0(%a: i1):
+                  %0 = "comb.xor"(%a, %a) : (i1, i1) -> i1
+                  "hw.output"(%0) : (i1) -> ()
+                }) {sym_name = "top"} : () -> ()
+            "#,
+        )?;
+        let ops = Vec::<internal::TopLevelItem>::from_pest(&mut parse_tree)?
+            .into_iter()
+            .filter_map(|item| match item {
+                internal::TopLevelItem::Operation(op) => Some(op),
+                internal::TopLevelItem::Other(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let expr = Expr::from(ops);
+
+        let Value::Op { args, .. } = &expr.binds[0].value else {
+            panic!("expected an operation");
+        };
+        let [Value::Thunk(thunk)] = args.as_slice() else {
+            panic!("expected a single region");
+        };
+        assert_eq!(thunk.addr, RegionKind::Graph);
+        assert_eq!(thunk.body.binds.len(), 1);
+        assert_eq!(
+            thunk.body.values,
+            vec![Value::Variable(Var::Var { id: "%0".into() })]
+        );
+
+        // The terminator's operand leaves the region as data, not control flow
+        let graph = expr.to_graph(false)?;
+        let region = graph.thunks().next().unwrap();
+        assert!(region
+            .graph_outputs()
+            .all(|edge| edge.weight().get_type() == WireType::Data));
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_results() -> Result<(), Box<dyn std::error::Error>> {
+        let mut parse_tree = MlirParser::parse(
+            Rule::toplevel,
+            r#"%result:2 = "foo_div"() : () -> (f32, i32)
+            "foo_use"(%result#1, %result#0) : (i32, f32) -> ()
+            "#,
+        )?;
+        let ops = Vec::<internal::TopLevelItem>::from_pest(&mut parse_tree)?
+            .into_iter()
+            .filter_map(|item| match item {
+                internal::TopLevelItem::Operation(op) => Some(op),
+                internal::TopLevelItem::Other(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let graph = Expr::from(ops).to_graph(false)?;
+
+        let div = graph
+            .operations()
+            .find(|op| op.weight().name == "foo_div")
+            .unwrap();
+        assert_eq!(div.number_of_outputs(), 2);
+
+        let monoidal = MonoidalGraph::from(&from_graph(&graph, Solver::default()));
+        assert!(monoidal.slices.iter().flat_map(|slice| &slice.ops).any(
+            |op| matches!(op, MonoidalOp::Operation { addr } if *addr == div)
+                && op.number_of_outputs() == 2
+        ));
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
 use std::{
+    borrow::Cow,
     fmt::{Debug, Display},
     hash::Hash,
 };
 
 use derivative::Derivative;
 
-use crate::{common::Matchable, hypergraph::traits::WithType, prettyprinter::PrettyPrint};
+use crate::{
+    arity::Arity,
+    common::{Matchable, Span},
+    hypergraph::traits::WithType,
+    prettyprinter::PrettyPrint,
+};
 
 pub mod chil;
 pub mod mlir;
@@ -15,6 +21,24 @@ pub(crate) fn span_into_str(span: pest::Span) -> &str {
     span.as_str()
 }
 
+/// The comments written on lines of their own directly before `pos` in `input`, without their
+/// leading `#`, so that they can be kept with whatever starts at `pos`.
+pub(crate) fn comments_before(input: &str, pos: usize) -> Vec<String> {
+    let mut lines = input[..pos].split('\n').rev();
+    // A comment at the end of a line of code belongs to that code instead
+    if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+        return Vec::new();
+    }
+    let mut comments = lines
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map_while(|line| line.strip_prefix('#'))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    comments.reverse();
+    comments
+}
+
 pub trait GetVar<V> {
     fn var(&self) -> &V;
     fn into_var(self) -> V;
@@ -47,6 +71,25 @@ pub enum CF<T: Language + ?Sized> {
     Brs(Vec<T::BlockAddr>),
 }
 
+/// Documentation of an operation, shown when hovering over it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OpDoc {
+    /// How the operation is written, such as `plus(x, y)`.
+    pub signature: Cow<'static, str>,
+    /// What the operation does.
+    pub summary: Cow<'static, str>,
+}
+
+impl OpDoc {
+    #[must_use]
+    pub const fn new(signature: &'static str, summary: &'static str) -> Self {
+        Self {
+            signature: Cow::Borrowed(signature),
+            summary: Cow::Borrowed(summary),
+        }
+    }
+}
+
 pub trait OpInfo<T: Language + ?Sized> {
     fn get_cf(&self) -> Option<CF<T>> {
         None
@@ -57,6 +100,18 @@ pub trait OpInfo<T: Language + ?Sized> {
     fn sym_name(&self) -> Option<T::Symbol> {
         None
     }
+    /// The numbers of inputs and outputs the operation expects, if the language knows them.
+    fn arity(&self) -> Option<Arity> {
+        None
+    }
+    /// What the operation does, if the language documents it.
+    fn doc(&self) -> Option<OpDoc> {
+        None
+    }
+    /// Where the operation was written, if the language keeps track.
+    fn span(&self) -> Option<Span> {
+        None
+    }
 }
 
 pub trait Language {
@@ -64,7 +119,9 @@ pub trait Language {
     type Var: Syntax + Fresh + From<Self::Symbol> + WithType;
     type Addr: Syntax;
     type BlockAddr: Syntax;
-    type VarDef: Syntax + GetVar<Self::Var>;
+    /// Definitions may know more about what a variable carries than its uses, such as whether
+    /// it only orders effects.
+    type VarDef: Syntax + GetVar<Self::Var> + WithType;
     type Symbol: Syntax;
 }
 
@@ -93,6 +150,9 @@ pub struct Expr<T: Language + ?Sized> {
 pub struct Bind<T: Language + ?Sized> {
     pub defs: Vec<T::VarDef>,
     pub value: Value<T>,
+    /// The comments written on the lines before the bind, which do not change its meaning.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    pub comments: Vec<String>,
 }
 
 #[derive(Derivative)]
@@ -122,6 +182,9 @@ pub struct Thunk<T: Language + ?Sized> {
     pub args: Vec<T::VarDef>,
     pub body: Expr<T>,
     pub blocks: Vec<Block<T>>,
+    /// The comments written on the lines before the thunk, which do not change its meaning.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    pub comments: Vec<String>,
 }
 
 #[derive(Derivative)]
@@ -168,6 +231,7 @@ impl<T: Language> Bind<T> {
         Bind {
             defs: self.defs.into_iter().map(Into::into).collect(),
             value: self.value.into(),
+            comments: self.comments,
         }
     }
 }
@@ -206,6 +270,7 @@ impl<T: Language> Thunk<T> {
             args: self.args.into_iter().map(Into::into).collect(),
             body: self.body.into(),
             blocks: self.blocks.into_iter().map(Block::into).collect(),
+            comments: self.comments,
         }
     }
 }
@@ -1,6 +1,7 @@
 #![allow(clippy::clone_on_copy)]
 
 use std::{
+    borrow::Cow,
     fmt::{Display, Write},
     str::FromStr,
 };
@@ -12,10 +13,12 @@ use pest_derive::Parser;
 #[cfg(test)]
 use serde::Serialize;
 
-use super::{span_into_str, Fresh, OpInfo};
+use super::{comments_before, span_into_str, Fresh, OpDoc, OpInfo};
 use crate::{
-    common::{Empty, Matchable, Unit},
+    arity::{Arity, Bounds},
+    common::{Empty, Matchable, Query, Unit},
     hypergraph::traits::{WireType, WithType},
+    interner::InternedStr,
 };
 
 pub struct Spartan;
@@ -133,32 +136,32 @@ impl FromStr for Op {
 }
 
 impl Matchable for Op {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         match self {
-            Self::Plus => query == "plus",
-            Self::Minus => query == "minus",
-            Self::Times => query == "times",
-            Self::Div => query == "div",
-            Self::Rem => query == "rem",
-            Self::And => query == "and",
-            Self::Or => query == "or",
-            Self::Not => query == "not",
-            Self::If => query == "if",
-            Self::Eq => query == "eq",
-            Self::Neq => query == "neq",
-            Self::Lt => query == "lt",
-            Self::Leq => query == "leq",
-            Self::Gt => query == "gt",
-            Self::Geq => query == "geq",
-            Self::App => query == "app",
-            Self::Lambda => query == "lambda",
-            Self::Atom => query == "atom",
-            Self::Deref => query == "deref",
-            Self::Assign => query == "assign",
-            Self::Tuple => query == "tuple",
-            Self::Detuple => query == "detuple",
-            Self::Bool(b) => b.to_string() == query,
-            Self::Number(n) => n.to_string() == query,
+            Self::Plus => query.matches("plus"),
+            Self::Minus => query.matches("minus"),
+            Self::Times => query.matches("times"),
+            Self::Div => query.matches("div"),
+            Self::Rem => query.matches("rem"),
+            Self::And => query.matches("and"),
+            Self::Or => query.matches("or"),
+            Self::Not => query.matches("not"),
+            Self::If => query.matches("if"),
+            Self::Eq => query.matches("eq"),
+            Self::Neq => query.matches("neq"),
+            Self::Lt => query.matches("lt"),
+            Self::Leq => query.matches("leq"),
+            Self::Gt => query.matches("gt"),
+            Self::Geq => query.matches("geq"),
+            Self::App => query.matches("app"),
+            Self::Lambda => query.matches("lambda"),
+            Self::Atom => query.matches("atom"),
+            Self::Deref => query.matches("deref"),
+            Self::Assign => query.matches("assign"),
+            Self::Tuple => query.matches("tuple"),
+            Self::Detuple => query.matches("detuple"),
+            Self::Bool(b) => query.matches(&b.to_string()),
+            Self::Number(n) => query.matches(&n.to_string()),
         }
     }
 }
@@ -184,12 +187,79 @@ impl<'pest> FromPest<'pest> for Op {
     }
 }
 
-impl OpInfo<Spartan> for Op {}
+impl OpInfo<Spartan> for Op {
+    fn arity(&self) -> Option<Arity> {
+        let (inputs, outputs) = match self {
+            Self::Plus
+            | Self::Minus
+            | Self::Times
+            | Self::Div
+            | Self::Rem
+            | Self::And
+            | Self::Or
+            | Self::Eq
+            | Self::Neq
+            | Self::Lt
+            | Self::Leq
+            | Self::Gt
+            | Self::Geq => (Bounds::exactly(2), Bounds::exactly(1)),
+            Self::Not | Self::Lambda | Self::Atom | Self::Deref => {
+                (Bounds::exactly(1), Bounds::exactly(1))
+            }
+            Self::If => (Bounds::exactly(3), Bounds::ANY),
+            Self::App => (Bounds::at_least(1), Bounds::ANY),
+            Self::Assign => (Bounds::exactly(2), Bounds::ANY),
+            Self::Tuple => (Bounds::ANY, Bounds::exactly(1)),
+            Self::Detuple => (Bounds::exactly(1), Bounds::ANY),
+            Self::Bool(_) | Self::Number(_) => (Bounds::exactly(0), Bounds::exactly(1)),
+        };
+        Some(Arity::new(inputs, outputs))
+    }
+
+    fn doc(&self) -> Option<OpDoc> {
+        let doc = match self {
+            Self::Plus => OpDoc::new("plus(x, y)", "The sum of x and y."),
+            Self::Minus => OpDoc::new("minus(x, y)", "The difference of x and y."),
+            Self::Times => OpDoc::new("times(x, y)", "The product of x and y."),
+            Self::Div => OpDoc::new("div(x, y)", "The quotient of x divided by y."),
+            Self::Rem => OpDoc::new("rem(x, y)", "The remainder of x divided by y."),
+            Self::And => OpDoc::new("and(x, y)", "Whether both x and y are true."),
+            Self::Or => OpDoc::new("or(x, y)", "Whether either of x and y is true."),
+            Self::Not => OpDoc::new("not(x)", "Whether x is false."),
+            Self::If => OpDoc::new(
+                "if(c, . t, . e)",
+                "Evaluates the thunk t if c is true, and the thunk e otherwise.",
+            ),
+            Self::Eq => OpDoc::new("eq(x, y)", "Whether x is equal to y."),
+            Self::Neq => OpDoc::new("neq(x, y)", "Whether x is not equal to y."),
+            Self::Lt => OpDoc::new("lt(x, y)", "Whether x is less than y."),
+            Self::Leq => OpDoc::new("leq(x, y)", "Whether x is at most y."),
+            Self::Gt => OpDoc::new("gt(x, y)", "Whether x is greater than y."),
+            Self::Geq => OpDoc::new("geq(x, y)", "Whether x is at least y."),
+            Self::App => OpDoc::new("app(f, x, ...)", "Applies the function f to the arguments."),
+            Self::Lambda => OpDoc::new("lambda(x . e)", "The function taking x to e."),
+            Self::Atom => OpDoc::new("atom(x)", "A new reference, holding x."),
+            Self::Deref => OpDoc::new("deref(r)", "The value held by the reference r."),
+            Self::Assign => OpDoc::new("assign(r, x)", "Makes the reference r hold x."),
+            Self::Tuple => OpDoc::new("tuple(x, ...)", "The tuple of the arguments."),
+            Self::Detuple => OpDoc::new("detuple(t)", "The components of the tuple t."),
+            Self::Bool(true) => OpDoc::new("true", "The boolean true."),
+            Self::Bool(false) => OpDoc::new("false", "The boolean false."),
+            Self::Number(n) => OpDoc {
+                signature: Cow::Owned(n.to_string()),
+                summary: Cow::Borrowed("A natural number."),
+            },
+        };
+        Some(doc)
+    }
+}
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, FromPest)]
 #[cfg_attr(test, derive(Serialize))]
 #[pest_ast(rule(Rule::variable))]
-pub struct Variable(#[pest_ast(outer(with(span_into_str), with(str::to_string)))] pub String);
+pub struct Variable(
+    #[pest_ast(outer(with(span_into_str), with(InternedStr::from)))] pub InternedStr,
+);
 
 impl WithType for Variable {
     fn get_type(&self) -> WireType {
@@ -204,8 +274,8 @@ impl From<Empty> for Variable {
 }
 
 impl Matchable for Variable {
-    fn is_match(&self, query: &str) -> bool {
-        self.0 == query
+    fn is_match(&self, query: &Query) -> bool {
+        query.matches(&self.0)
     }
 }
 
@@ -217,7 +287,7 @@ impl Display for Variable {
 
 impl Fresh for Variable {
     fn fresh(number: usize) -> Self {
-        Self(format!("?{number}"))
+        Self(format!("?{number}").into())
     }
 }
 
@@ -271,10 +341,12 @@ impl<'pest> FromPest<'pest> for Bind {
         if pair.as_rule() != Rule::bind {
             return Err(ConversionError::NoMatch);
         }
+        let span = pair.as_span();
         let mut inner = pair.into_inner();
         let bind = Bind {
             defs: FromPest::from_pest(&mut inner)?,
             value: FromPest::from_pest(&mut inner)?,
+            comments: comments_before(span.get_input(), span.start()),
         };
         if inner.next().is_some() {
             return Err(ConversionError::Extraneous {
@@ -349,12 +421,14 @@ impl<'pest> FromPest<'pest> for Thunk {
         if pair.as_rule() != Rule::thunk {
             return Err(ConversionError::NoMatch);
         }
+        let span = pair.as_span();
         let mut inner = pair.into_inner();
         let thunk = Thunk {
             addr: FromPest::from_pest(&mut inner)?,
             args: FromPest::from_pest(&mut inner)?,
             body: FromPest::from_pest(&mut inner)?,
             blocks: vec![],
+            comments: comments_before(span.get_input(), span.start()),
         };
         if inner.next().is_some() {
             return Err(ConversionError::Extraneous {
@@ -374,7 +448,8 @@ pub(crate) mod tests {
     use from_pest::FromPest;
     use pest::Parser;
 
-    use super::{Expr, Rule, SpartanParser};
+    use super::{Expr, Op, Rule, SpartanParser, Value};
+    use crate::{language::OpInfo, prettyprinter::PrettyPrint};
 
     pub fn parse_sd(raw_path: &str) -> (&str, Expr) {
         let path = Path::new(raw_path);
@@ -395,4 +470,47 @@ pub(crate) mod tests {
     fn check_parse(fixture: Fixture<(&str, Expr)>) {
         let (_name, _expr) = fixture.content();
     }
+
+    #[test]
+    fn comments_kept() {
+        let parse = |program: &str| {
+            let mut pairs = SpartanParser::parse(Rule::program, program).unwrap();
+            Expr::from_pest(&mut pairs).unwrap()
+        };
+        let expr = parse(
+            "# The sum
+bind x = plus(1, 2) in
+
+# A function
+bind f = lambda(y .
+  # Twice
+  bind z = times(y, x) in z) in
+app(f, x)",
+        );
+        let reparsed = parse(&expr.to_pretty());
+        assert_eq!(reparsed, expr);
+        assert_eq!(reparsed.binds[0].comments, [" The sum"]);
+        assert_eq!(reparsed.binds[1].comments, [" A function"]);
+        let Value::Op { args, .. } = &reparsed.binds[1].value else {
+            panic!("expected an operation");
+        };
+        let [Value::Thunk(thunk)] = args.as_slice() else {
+            panic!("expected a thunk");
+        };
+        assert_eq!(thunk.body.binds[0].comments, [" Twice"]);
+    }
+
+    #[test]
+    fn ops_documented() {
+        for name in [
+            "plus", "minus", "times", "div", "rem", "and", "or", "not", "if", "eq", "neq", "lt",
+            "leq", "gt", "geq", "app", "lambda", "atom", "deref", "assign", "tuple", "detuple",
+            "true", "false", "42",
+        ] {
+            let op = name.parse::<Op>().unwrap();
+            let doc = op.doc().unwrap();
+            // The signature is written the way the operation is in programs
+            assert!(doc.signature.starts_with(name), "{name}: {doc:?}");
+        }
+    }
 }
@@ -1,16 +1,25 @@
 #![allow(clippy::empty_docs)]
+pub mod arity;
 pub mod codeable;
 pub mod common;
 pub mod decompile;
 pub mod dot;
 pub mod examples;
+pub mod float;
 pub mod free_vars;
 pub mod graph;
 pub mod hypergraph;
 pub mod interactive;
+pub mod interner;
 pub mod language;
 pub mod lp;
 pub mod monoidal;
+pub mod outline;
 pub mod prettyprinter;
+pub mod profile;
+pub mod progress;
+pub mod rename;
+pub mod rewrite;
 pub mod selection;
+pub mod thumbnail;
 pub mod weak_map;
@@ -1,3 +1,24 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::LocalKey,
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        OnceLock,
+    },
+    thread,
+};
+
 use clap_derive::ValueEnum;
 #[cfg(feature = "gurobi")]
 use good_lp::solvers::lp_solvers::LpSolver;
@@ -5,15 +26,416 @@ use good_lp::{
     Constraint, Expression, IntoAffineExpression, ProblemVariables, ResolutionError, Solution,
     SolverModel, Variable, VariableDefinition,
 };
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use web_time::Instant;
 
 #[derive(Default)]
 pub struct LpProblem {
     problem: ProblemVariables,
+    variables: Vec<Variable>,
     constraints: Vec<Constraint>,
     objective: Expression,
 }
 
-#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+#[derive(Clone, Debug, Error)]
+pub enum LpError {
+    #[error("Resolution error: {0}")]
+    ResolutionError(#[from] ResolutionError),
+    #[error("Solver exceeded its time budget")]
+    BudgetExceeded,
+    #[error("Solve was cancelled")]
+    Cancelled,
+    #[error("Solver panicked")]
+    SolverPanicked,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The most solves left running after they were abandoned before new problems get no time to be
+/// solved in, as solvers cannot be interrupted and would otherwise pile up as the user moves from
+/// graph to graph.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_ABANDONED_SOLVES: usize = 4;
+
+/// The number of abandoned solves which are still running.
+#[cfg(not(target_arch = "wasm32"))]
+static ABANDONED_SOLVES: AtomicUsize = AtomicUsize::new(0);
+
+/// Threads kept to solve budgeted problems on, rather than starting a thread for each problem.
+///
+/// Problems are waited on from the threads of rayon's pool, so solving them there could leave
+/// every thread waiting on a problem which never starts. There are enough threads for each core
+/// to be solving a problem while the most abandoned solves are still running.
+#[cfg(not(target_arch = "wasm32"))]
+fn solver_pool() -> Option<&'static rayon::ThreadPool> {
+    static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(cores + MAX_ABANDONED_SOLVES)
+            .thread_name(|i| format!("solver-{i}"))
+            .build()
+            .inspect_err(|err| tracing::warn!("Failed to start solver threads: {err}"))
+            .ok()
+    })
+    .as_ref()
+}
+
+thread_local! {
+    static SOLUTION_LOG: RefCell<Option<SolutionLog>> = const { RefCell::new(None) };
+    static BUDGET: RefCell<Option<Budget>> = const { RefCell::new(None) };
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f`, catching any panic so that a solver falling over does not take its caller with it.
+pub fn catch_panics<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    let previous = CATCHING_PANICS.replace(true);
+    let result = catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.set(previous);
+    result
+}
+
+/// Whether a panic on this thread would be caught by [`catch_panics`], so a panic hook should not
+/// treat it as a crash.
+#[must_use]
+pub fn is_catching_panics() -> bool {
+    CATCHING_PANICS.get()
+}
+
+/// A shared flag used to abandon solves that are no longer needed.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A limit on how long the problems solved on the current thread may take altogether, counted
+/// from when the budget is first run, including the problems solved for it on other threads by
+/// [`map_with_budget`].
+///
+/// Problems which run out of time fail with [`LpError::BudgetExceeded`], leaving the caller
+/// to fall back to something cheaper. Solvers which fail outright are also noted, as callers
+/// fall back to another solver rather than giving up.
+#[derive(Clone, Debug)]
+pub struct Budget {
+    time: Duration,
+    /// When the budget was first run, which the time is counted from.
+    started: Option<Instant>,
+    cancel: CancellationToken,
+    exceeded: bool,
+    failures: Vec<String>,
+}
+
+impl Budget {
+    #[must_use]
+    pub const fn new(time: Duration, cancel: CancellationToken) -> Self {
+        Self {
+            time,
+            started: None,
+            cancel,
+            exceeded: false,
+            failures: Vec::new(),
+        }
+    }
+
+    /// A budget which never runs out, though solving can still be cancelled.
+    #[must_use]
+    pub const fn unlimited(cancel: CancellationToken) -> Self {
+        Self::new(Duration::MAX, cancel)
+    }
+
+    /// Whether any problem ran out of time.
+    #[must_use]
+    pub const fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    /// Why each solver which failed, rather than running out of time, did so.
+    #[must_use]
+    pub fn failures(&self) -> &[String] {
+        &self.failures
+    }
+
+    /// Run `f`, applying this budget to any problems it solves on this thread.
+    pub fn run<R>(mut self, f: impl FnOnce() -> R) -> (R, Self) {
+        self.started.get_or_insert_with(Instant::now);
+        let scope = Scope::enter(&BUDGET, self);
+        let result = f();
+        (result, scope.exit())
+    }
+
+    /// When the time runs out, if it ever does.
+    fn limits(&self) -> (Option<Instant>, CancellationToken) {
+        let deadline = self
+            .started
+            .and_then(|started| started.checked_add(self.time));
+        (deadline, self.cancel.clone())
+    }
+}
+
+/// A value put in a thread local for as long as a [`Budget`] is run or a [`SolutionLog`]
+/// recorded, which puts back what was there before when dropped, so that a panic does not leave
+/// it in place.
+struct Scope<T: 'static> {
+    key: &'static LocalKey<RefCell<Option<T>>>,
+    previous: Option<Option<T>>,
+}
+
+impl<T> Scope<T> {
+    fn enter(key: &'static LocalKey<RefCell<Option<T>>>, value: T) -> Self {
+        let previous = key.replace(Some(value));
+        Self {
+            key,
+            previous: Some(previous),
+        }
+    }
+
+    /// Puts back what was there before, returning the value given to [`Scope::enter`].
+    fn exit(mut self) -> T {
+        self.restore().unwrap()
+    }
+
+    fn restore(&mut self) -> Option<T> {
+        self.previous
+            .take()
+            .and_then(|previous| self.key.replace(previous))
+    }
+}
+
+impl<T> Drop for Scope<T> {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// What was logged for a problem, or for a group of items mapped over in parallel.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Entry {
+    /// The values of the variables of a solved problem.
+    Solution(Vec<f64>),
+    /// The entries logged for each of the items passed to [`map_with_budget`], which may have
+    /// been laid out on different threads.
+    Parallel(Vec<Vec<Entry>>),
+}
+
+/// A log of the solutions to the problems solved on the current thread, in order.
+///
+/// Solutions already in the log are replayed instead of solving the problem again,
+/// as long as they fit the problem. This relies on the same input always producing the
+/// same sequence of problems. The problems of each item mapped over in parallel are logged
+/// apart, so that their order does not depend on which threads they were solved on.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SolutionLog {
+    entries: Vec<Entry>,
+    #[serde(skip)]
+    replayed: usize,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl SolutionLog {
+    fn from_entries(entries: Vec<Entry>) -> Self {
+        Self {
+            entries,
+            ..Self::default()
+        }
+    }
+
+    /// Reads a log stored by [`SolutionLog::to_json`].
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Whether nothing has been logged, so every problem will be solved.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether any problem had to be solved rather than replayed.
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Run `f`, replaying and recording the solutions of any problems it solves on this thread.
+    pub fn record<R>(self, f: impl FnOnce() -> R) -> (R, Self) {
+        let scope = Scope::enter(&SOLUTION_LOG, self);
+        let result = f();
+        (result, scope.exit())
+    }
+
+    fn replay(&mut self, number_of_variables: usize) -> Option<Vec<f64>> {
+        match self.entries.get(self.replayed) {
+            Some(Entry::Solution(values)) if values.len() == number_of_variables => {
+                self.replayed += 1;
+                Some(values.clone())
+            }
+            _ => {
+                // The log no longer matches the problems being solved
+                self.entries.truncate(self.replayed);
+                None
+            }
+        }
+    }
+
+    fn push(&mut self, values: Vec<f64>) {
+        self.entries.push(Entry::Solution(values));
+        self.replayed += 1;
+        self.dirty = true;
+    }
+
+    /// A log for each of `items` items about to be mapped over in parallel, replaying what was
+    /// logged for them last time.
+    fn split(&mut self, items: usize) -> Vec<Self> {
+        match self.entries.get(self.replayed) {
+            Some(Entry::Parallel(logged)) if logged.len() == items => {
+                logged.iter().cloned().map(Self::from_entries).collect()
+            }
+            _ => {
+                self.entries.truncate(self.replayed);
+                (0..items).map(|_| Self::default()).collect()
+            }
+        }
+    }
+
+    /// Logs the problems solved for each of the items mapped over in parallel, in the order of
+    /// the items, after splitting the log for them.
+    fn join(&mut self, logs: Vec<Self>) {
+        // Nothing was replayed if there was no entry for the items
+        let mut dirty = self.entries.len() == self.replayed;
+        let logged = logs
+            .into_iter()
+            .map(|mut log| {
+                dirty |= log.dirty;
+                log.entries.truncate(log.replayed);
+                log.entries
+            })
+            .collect();
+        if dirty {
+            self.entries.truncate(self.replayed);
+            self.entries.push(Entry::Parallel(logged));
+            self.dirty = true;
+        }
+        self.replayed += 1;
+    }
+}
+
+/// Values of the variables of a solved or replayed problem.
+struct SolutionValues(HashMap<Variable, f64>);
+
+impl Solution for SolutionValues {
+    fn value(&self, variable: Variable) -> f64 {
+        self.0[&variable]
+    }
+}
+
+/// Runs `f` on each of `items`, spread across threads on native targets, applying the [`Budget`]
+/// and [`SolutionLog`] of this thread to the problems solved on the others.
+///
+/// Each item gets a log of its own, which are joined into the log of this thread in the order of
+/// the items, so that solutions are replayed to the same items whichever threads they run on.
+pub fn map_with_budget<I: Send, R: Send>(items: Vec<I>, f: impl Fn(I) -> R + Sync) -> Vec<R> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if items.len() > 1 {
+        use rayon::prelude::*;
+
+        let budget = BUDGET.with_borrow(Clone::clone);
+        let logs = SOLUTION_LOG
+            .with_borrow_mut(|log| log.as_mut().map(|log| log.split(items.len())))
+            .map_or_else(
+                || items.iter().map(|_| None).collect(),
+                |logs| logs.into_iter().map(Some).collect::<Vec<_>>(),
+            );
+        let outcomes: Vec<(R, Option<Budget>, Option<SolutionLog>)> = items
+            .into_par_iter()
+            .zip(logs)
+            .map(|(item, log)| {
+                let run = || match budget.clone() {
+                    Some(budget) => {
+                        let (result, budget) = budget.run(|| f(item));
+                        (result, Some(budget))
+                    }
+                    None => (f(item), None),
+                };
+                match log {
+                    Some(log) => {
+                        let ((result, budget), log) = log.record(run);
+                        (result, budget, Some(log))
+                    }
+                    None => {
+                        let (result, budget) = run();
+                        (result, budget, None)
+                    }
+                }
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(outcomes.len());
+        let mut budgets = Vec::new();
+        let mut logs = Vec::new();
+        for (result, budget, log) in outcomes {
+            results.push(result);
+            budgets.extend(budget);
+            logs.extend(log);
+        }
+        SOLUTION_LOG.with_borrow_mut(|parent| {
+            if let Some(parent) = parent {
+                parent.join(logs);
+            }
+        });
+        BUDGET.with_borrow_mut(|parent| {
+            if let Some(parent) = parent {
+                // Each thread started with the failures already noted here
+                let known = parent.failures.len();
+                for budget in budgets {
+                    parent.exceeded |= budget.exceeded;
+                    parent
+                        .failures
+                        .extend(budget.failures.into_iter().skip(known));
+                }
+            }
+        });
+        return results;
+    }
+    items.into_iter().map(f).collect()
+}
+
+/// Records that the budget for this thread ran out, returning the error to give up with.
+fn budget_exceeded() -> LpError {
+    BUDGET.with_borrow_mut(|budget| {
+        if let Some(budget) = budget {
+            budget.exceeded = true;
+        }
+    });
+    LpError::BudgetExceeded
+}
+
+/// Notes that `solver` failed on this thread, for the caller to report once it has recovered.
+fn solver_failed(solver: Solver, err: &LpError) {
+    tracing::warn!("{solver:?} failed: {err}");
+    BUDGET.with_borrow_mut(|budget| {
+        if let Some(budget) = budget {
+            budget.failures.push(format!("{solver:?} failed: {err}"));
+        }
+    });
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub enum Solver {
     Clarabel,
     #[cfg(feature = "gurobi")]
@@ -26,13 +448,29 @@ pub enum Solver {
     Microlp,
 }
 
+impl Solver {
+    /// A different solver to try when this one runs out of time or fails. Fallbacks are written
+    /// in Rust, so they work wherever the first choice of solver does not.
+    #[must_use]
+    pub const fn fallback(self) -> Self {
+        match self {
+            Self::Microlp => Self::Clarabel,
+            _ => Self::Microlp,
+        }
+    }
+}
+
 impl LpProblem {
     pub fn add_variable(&mut self, var_def: VariableDefinition) -> Variable {
-        self.problem.add(var_def)
+        let variable = self.problem.add(var_def);
+        self.variables.push(variable);
+        variable
     }
 
     pub fn add_variables(&mut self, var_def: VariableDefinition, len: usize) -> Vec<Variable> {
-        self.problem.add_vector(var_def, len)
+        let variables = self.problem.add_vector(var_def, len);
+        self.variables.extend(variables.iter().copied());
+        variables
     }
 
     pub fn add_objective(&mut self, objective: impl IntoAffineExpression) {
@@ -43,19 +481,137 @@ impl LpProblem {
         self.constraints.push(constraint);
     }
 
-    pub fn minimise(self, s: Solver) -> Result<Box<dyn Solution>, ResolutionError> {
+    /// Minimise the objective, giving up if the [`Budget`] for this thread runs out.
+    pub fn minimise(self, s: Solver) -> Result<Box<dyn Solution>, LpError> {
+        self.minimise_internal(s, true)
+    }
+
+    /// Minimise the objective, however long it takes.
+    pub fn minimise_ignoring_budget(self, s: Solver) -> Result<Box<dyn Solution>, LpError> {
+        self.minimise_internal(s, false)
+    }
+
+    fn minimise_internal(self, s: Solver, budgeted: bool) -> Result<Box<dyn Solution>, LpError> {
+        let variables = self.variables.clone();
+
+        let replayed = SOLUTION_LOG
+            .with_borrow_mut(|log| log.as_mut().and_then(|log| log.replay(variables.len())));
+        if let Some(values) = replayed {
+            return Ok(Box::new(SolutionValues(
+                variables.into_iter().zip(values).collect(),
+            )));
+        }
+
+        let budget = budgeted
+            .then(|| BUDGET.with_borrow(|budget| budget.as_ref().map(Budget::limits)))
+            .flatten();
+        let values = match budget {
+            // Out of time already, so the caller only wants its fallback and a solve is not started
+            Some((Some(deadline), _)) if deadline <= Instant::now() => {
+                return Err(budget_exceeded())
+            }
+            Some((deadline, cancel)) => self.solve_within(s, deadline, &cancel),
+            None => catch_panics(|| self.solve(s))
+                .map_or(Err(LpError::SolverPanicked), |values| Ok(values?)),
+        };
+        let values = values.inspect_err(|err| {
+            if matches!(err, LpError::ResolutionError(_) | LpError::SolverPanicked) {
+                solver_failed(s, err);
+            }
+        })?;
+
+        SOLUTION_LOG.with_borrow_mut(|log| {
+            if let Some(log) = log {
+                log.push(values.clone());
+            }
+        });
+
+        Ok(Box::new(SolutionValues(
+            variables.into_iter().zip(values).collect(),
+        )))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn solve_within(
+        self,
+        s: Solver,
+        deadline: Option<Instant>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<f64>, LpError> {
+        if ABANDONED_SOLVES.load(Ordering::SeqCst) >= MAX_ABANDONED_SOLVES {
+            return Err(budget_exceeded());
+        }
+        let (sender, receiver) = mpsc::channel();
+        // Set by whichever of the solve finishing or it being abandoned comes first
+        let done = Arc::new(AtomicBool::new(false));
+        // Solvers cannot be interrupted, so an abandoned solve is left to finish on its own
+        let solve = {
+            let done = done.clone();
+            move || {
+                let _ = sender.send(catch_panics(|| self.solve(s)));
+                if done.swap(true, Ordering::SeqCst) {
+                    ABANDONED_SOLVES.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        };
+        match solver_pool() {
+            Some(pool) => pool.spawn(solve),
+            None => {
+                thread::spawn(solve);
+            }
+        }
+        let abandon = |err| {
+            if !done.swap(true, Ordering::SeqCst) {
+                ABANDONED_SOLVES.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(err)
+        };
+
+        loop {
+            if cancel.is_cancelled() {
+                return abandon(LpError::Cancelled);
+            }
+            let remaining = deadline.map_or(Duration::MAX, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            if remaining.is_zero() {
+                return abandon(budget_exceeded());
+            }
+            match receiver.recv_timeout(remaining.min(POLL_INTERVAL)) {
+                Ok(Ok(result)) => return Ok(result?),
+                Ok(Err(_)) | Err(RecvTimeoutError::Disconnected) => {
+                    return Err(LpError::SolverPanicked)
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+
+    // Threads are not available on the web, so the budget cannot be enforced there
+    #[cfg(target_arch = "wasm32")]
+    fn solve_within(
+        self,
+        s: Solver,
+        _deadline: Option<Instant>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<f64>, LpError> {
+        if cancel.is_cancelled() {
+            return Err(LpError::Cancelled);
+        }
+        catch_panics(|| self.solve(s)).map_or(Err(LpError::SolverPanicked), |values| Ok(values?))
+    }
+
+    fn solve(self, s: Solver) -> Result<Vec<f64>, ResolutionError> {
         fn run_model<S: SolverModel<Error = ResolutionError>>(
             mut model: S,
             constraints: Vec<Constraint>,
-        ) -> Result<Box<dyn Solution>, ResolutionError>
-        where
-            S::Solution: 'static,
-        {
+            variables: &[Variable],
+        ) -> Result<Vec<f64>, ResolutionError> {
             for c in constraints {
                 model.add_constraint(c);
             }
             let sln = model.solve()?;
-            Ok(Box::new(sln))
+            Ok(variables.iter().map(|var| sln.value(*var)).collect())
         }
 
         let to_solve = self.problem.minimise(self.objective);
@@ -64,17 +620,19 @@ impl LpProblem {
             Solver::Clarabel => run_model(
                 to_solve.using(good_lp::solvers::clarabel::clarabel),
                 self.constraints,
+                &self.variables,
             ),
             #[cfg(feature = "gurobi")]
             Solver::Gurobi => {
                 let solver = LpSolver(good_lp::solvers::lp_solvers::GurobiSolver::new());
-                run_model(to_solve.using(solver), self.constraints)
+                run_model(to_solve.using(solver), self.constraints, &self.variables)
             }
 
             #[cfg(feature = "highs")]
             Solver::Highs => run_model(
                 to_solve.using(good_lp::solvers::highs::highs),
                 self.constraints,
+                &self.variables,
             ),
             #[cfg(feature = "cbc")]
             Solver::Cbc => run_model(
@@ -85,11 +643,108 @@ impl LpProblem {
                     prob
                 }),
                 self.constraints,
+                &self.variables,
             ),
             Solver::Microlp => run_model(
                 to_solve.using(good_lp::solvers::microlp::microlp),
                 self.constraints,
+                &self.variables,
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+
+    use good_lp::{variable, Solution};
+
+    use super::{
+        catch_panics, map_with_budget, Budget, CancellationToken, LpError, LpProblem, SolutionLog,
+        Solver, BUDGET, SOLUTION_LOG,
+    };
+
+    /// The smallest `x` of at least `lower`.
+    fn smallest(lower: u32) -> Result<f64, super::LpError> {
+        let mut problem = LpProblem::default();
+        let x = problem.add_variable(variable().min(lower));
+        problem.add_objective(x);
+        Ok(problem.minimise(Solver::Microlp)?.value(x))
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn recording_runs_in_parallel() -> Result<(), rayon::ThreadPoolBuildError> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build()?;
+        let arrived = AtomicUsize::new(0);
+        let (met, _) = pool.install(|| {
+            SolutionLog::default().record(|| {
+                map_with_budget(vec![(); 2], |()| {
+                    // Each item waits for the other, which only comes if it runs at the same time
+                    arrived.fetch_add(1, Ordering::SeqCst);
+                    let deadline = Instant::now() + Duration::from_secs(10);
+                    while arrived.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+                        std::thread::yield_now();
+                    }
+                    arrived.load(Ordering::SeqCst) == 2
+                })
+            })
+        });
+        assert_eq!(met, vec![true, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_solutions_replay_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        // The items solve different numbers of problems, of different sizes
+        let solve = || {
+            map_with_budget(vec![1, 2, 3], |lower| {
+                let mut problem = LpProblem::default();
+                let xs = problem.add_variables(variable().min(lower), lower as usize);
+                for x in &xs {
+                    problem.add_objective(*x);
+                }
+                let solution = problem.minimise(Solver::Microlp)?;
+                let sum = xs.iter().map(|x| solution.value(*x)).sum::<f64>();
+                Ok::<_, super::LpError>(sum + smallest(lower * 2)?)
+            })
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+        };
+        let (first, log) = SolutionLog::default().record(solve);
+        assert!(log.is_dirty());
+
+        let log = SolutionLog::from_json(&log.to_json()?)?;
+        let (second, log) = log.record(solve);
+        assert!(!log.is_dirty());
+        assert_eq!(first?, second?);
+        Ok(())
+    }
+
+    #[test]
+    fn budget_is_shared_by_problems() {
+        let budget = Budget::new(Duration::from_millis(200), CancellationToken::default());
+        let (result, budget) = budget.run(|| {
+            let first = smallest(1);
+            std::thread::sleep(Duration::from_millis(250));
+            (first, smallest(2))
+        });
+        assert!(matches!(result, (Ok(_), Err(LpError::BudgetExceeded))));
+        assert!(budget.is_exceeded());
+    }
+
+    #[test]
+    fn scopes_are_left_on_panic() {
+        let budget = Budget::unlimited(CancellationToken::default());
+        let result = catch_panics(|| {
+            SolutionLog::default().record(|| budget.run(|| panic!("solver fell over")))
+        });
+        assert!(result.is_err());
+        assert!(BUDGET.with_borrow(Option::is_none));
+        assert!(SOLUTION_LOG.with_borrow(Option::is_none));
+    }
+}
@@ -5,7 +5,7 @@ use tracing::debug;
 use super::{
     permutation::{advance_by, generate_permutation, PermutationOutput},
     wired_graph::{MonoidalWiredGraph, WiredOp},
-    MonoidalTerm, Slice,
+    MonoidalTerm, Simplification, Slice,
 };
 use crate::{
     common::{Direction, InOut, InOutIter, Link},
@@ -360,6 +360,15 @@ impl<T: Ctx> Extend<Slice<MonoidalOp<T>>> for MonoidalGraphBuilder<T> {
 
 impl<T: Ctx> From<&MonoidalWiredGraph<T>> for MonoidalGraph<T> {
     fn from(graph: &MonoidalWiredGraph<T>) -> Self {
+        Self::from_wired(graph, Simplification::default())
+    }
+}
+
+impl<T: Ctx> MonoidalGraph<T> {
+    /// Inserts the swaps, cups, caps and deletions which line up the slices of `graph`, merging
+    /// slices of identities unless `simplification` is [`Simplification::None`].
+    #[must_use]
+    pub fn from_wired(graph: &MonoidalWiredGraph<T>, simplification: Simplification) -> Self {
         debug!("Input graph {:#?}", graph);
         let graph_inputs: Vec<Link<T>> = graph
             .free_inputs
@@ -443,17 +452,17 @@ impl<T: Ctx> From<&MonoidalWiredGraph<T>> for MonoidalGraph<T> {
         // Perform sanity check
         graph.check_in_out_count();
 
-        // Perform local optimisations to graph
-        graph.squash_layers();
+        if simplification != Simplification::None {
+            // Perform local optimisations to graph
+            graph.squash_layers();
 
-        // Recheck sanity
-        graph.check_in_out_count();
+            // Recheck sanity
+            graph.check_in_out_count();
+        }
 
         graph
     }
-}
 
-impl<T: Ctx> MonoidalGraph<T> {
     /// Perform local optimisations on a `MonoidalGraph` to try to shrink the number of layers
     fn squash_layers(&mut self) {
         self.slices = std::mem::take(&mut self.slices)
@@ -566,4 +575,75 @@ mod tests {
     //     assert_eq!(Slice::permutation_to_swaps(permutation), result);
     //     Ok(())
     // }
+
+    use std::sync::Arc;
+
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::{MonoidalGraph, MonoidalOp};
+    use crate::{
+        language::spartan::{Expr, Rule, SpartanParser},
+        lp::Solver,
+        monoidal::{wired_graph::from_graph_with_progress, Simplification},
+    };
+
+    #[test]
+    fn simplification_levels() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind x = plus(a, a) in bind y = times(x, a) in bind z = minus(y, a) in tuple(x, z)",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let copies = |simplification| {
+            let term = from_graph_with_progress(
+                &graph,
+                Solver::default(),
+                simplification,
+                Arc::new(|_, _| ()),
+            );
+            // Translating checks the slices line up
+            MonoidalGraph::from_wired(&term, simplification)
+                .slices
+                .iter()
+                .flat_map(|slice| &slice.ops)
+                .filter(|op| matches!(op, MonoidalOp::Copy { copies, .. } if *copies > 1))
+                .count()
+        };
+        for simplification in Simplification::ALL {
+            assert_eq!(
+                simplification.name().parse::<Simplification>(),
+                Ok(simplification)
+            );
+        }
+        // `a` is copied four times, in a cascade unless copies are folded
+        assert!(copies(Simplification::HideIdentities) > copies(Simplification::FoldCopies));
+        assert_eq!(
+            copies(Simplification::None),
+            copies(Simplification::HideIdentities)
+        );
+        copies(Simplification::Full);
+        Ok(())
+    }
+
+    #[test]
+    fn crossings_minimised() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, "bind x = minus(a, b) in x")?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let mut term = from_graph_with_progress(
+            &graph,
+            Solver::default(),
+            Simplification::Full,
+            Arc::new(|_, _| ()),
+        );
+        assert_eq!(term.crossings(), 0);
+
+        // Swapping the inputs crosses them over on their way to `minus`
+        term.free_inputs.reverse();
+        assert_eq!(term.crossings(), 1);
+        term.minimise_crossings(1);
+        assert_eq!(term.crossings(), 0);
+        MonoidalGraph::from_wired(&term, Simplification::Full);
+        Ok(())
+    }
 }
@@ -1,12 +1,13 @@
 use std::{collections::HashMap, fmt::Debug, hash::Hash};
 
+use clap_derive::ValueEnum;
 use derivative::Derivative;
 use num::rational::Ratio;
 
 use crate::{
     common::{Direction, InOut, InOutIter, Link},
     hypergraph::generic::Ctx,
-    monoidal::permutation::{generate_permutation, PermutationOutput},
+    monoidal::permutation::{count_crossings, generate_permutation, PermutationOutput},
 };
 
 pub mod graph;
@@ -15,6 +16,60 @@ pub mod wired_graph;
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+/// How far the translation of a hypergraph into a monoidal graph simplifies it, trading
+/// faithfulness to the structure of the hypergraph for readability. Each level also makes the
+/// simplifications of the levels before it.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub enum Simplification {
+    /// Keep every identity, copy and swap of the translation.
+    None,
+    /// Merge slices of identities into their neighbours.
+    #[default]
+    HideIdentities,
+    /// Split each copied wire once, where it is produced, rather than once per slice using it.
+    FoldCopies,
+    /// Reorder the operations of each slice until no more swaps can be removed.
+    Full,
+}
+
+impl Simplification {
+    pub const ALL: [Self; 4] = [
+        Self::None,
+        Self::HideIdentities,
+        Self::FoldCopies,
+        Self::Full,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::HideIdentities => "hide-identities",
+            Self::FoldCopies => "fold-copies",
+            Self::Full => "full",
+        }
+    }
+}
+
+impl std::fmt::Display for Simplification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Simplification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name() == s)
+            .ok_or_else(|| format!("unknown simplification level {s:?}"))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Derivative)]
 #[derivative(Default(bound = ""))]
 pub struct Slice<O> {
@@ -101,6 +156,70 @@ impl<T: Ctx, O: InOut + Debug> MonoidalTerm<T, O> {
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
 impl<O: InOutIter + PartialEq + Eq + Hash + Clone + Debug> MonoidalTerm<O::T, O> {
+    fn input_links(&self) -> impl Iterator<Item = Link<O::T>> + '_ {
+        self.free_inputs
+            .iter()
+            .chain(self.bound_inputs.iter())
+            .map(|edge| (edge.clone(), Direction::Forward))
+    }
+
+    fn output_links(&self) -> impl Iterator<Item = Link<O::T>> + '_ {
+        self.free_outputs
+            .iter()
+            .chain(self.bound_outputs.iter())
+            .map(|edge| (edge.clone(), Direction::Forward))
+    }
+
+    /// The number of pairs of wires which cross between consecutive slices of the term, counting
+    /// those between the inputs and the first slice, and between the last slice and the outputs
+    #[must_use]
+    pub fn crossings(&self) -> usize {
+        let mut above: Vec<Link<O::T>> = self.input_links().collect();
+        let mut crossings = 0;
+        for slice in &self.slices {
+            crossings += count_crossings::<O::T>(above.into_iter(), slice.input_links());
+            above = slice.output_links().collect();
+        }
+        crossings + count_crossings::<O::T>(above.into_iter(), self.output_links())
+    }
+
+    /// Alternates sweeps up and down the term, reordering the operations of each slice by where
+    /// their wires go in the slices on either side, for at most `passes` passes or until a pass
+    /// removes no more crossings. Keeps the order with the fewest crossings seen.
+    pub fn minimise_crossings(&mut self, passes: usize) {
+        let mut best = (
+            self.crossings(),
+            self.slices.clone(),
+            self.free_inputs.clone(),
+        );
+        for _ in 0..passes {
+            self.minimise_swaps(true);
+            self.minimise_swaps_downwards();
+            let crossings = self.crossings();
+            if crossings >= best.0 {
+                break;
+            }
+            best = (crossings, self.slices.clone(), self.free_inputs.clone());
+        }
+        (_, self.slices, self.free_inputs) = best;
+    }
+
+    /// Reorder the operations on each slice from the top down, so that each slice follows the
+    /// order just chosen for the slice above it
+    fn minimise_swaps_downwards(&mut self) {
+        for i in 0..self.slices.len() {
+            let above: Vec<_> = match i.checked_sub(1) {
+                Some(j) => self.slices[j].output_links().collect(),
+                None => self.input_links().collect(),
+            };
+            let below: Vec<_> = match self.slices.get(i + 1) {
+                Some(slice) => slice.input_links().collect(),
+                None => self.output_links().collect(),
+            };
+            self.slices[i].minimise_swaps(above.into_iter(), below.into_iter());
+        }
+    }
+
     /// Reorder the operations on each slice of a monoidal term to attempt to reduce the amount of swapping
     pub fn minimise_swaps(&mut self, use_above: bool) {
         let mut edges_below = Box::new(
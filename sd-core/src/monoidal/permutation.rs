@@ -82,3 +82,42 @@ pub(crate) fn generate_permutation<'a, T: Ctx>(
 
     out
 }
+
+/// Counts the pairs of items of `start` which cross on their way to `end`, ignoring those which
+/// are deleted or paired up in between
+pub(crate) fn count_crossings<'a, T: Ctx>(
+    start: impl Iterator<Item = Link<T>> + 'a,
+    end: impl Iterator<Item = Link<T>> + 'a,
+) -> usize {
+    let mut destinations: Vec<usize> = generate_permutation::<T>(start, end)
+        .into_iter()
+        .filter_map(|(_, output)| Option::<usize>::from(output))
+        .collect();
+    sort_counting_inversions(&mut destinations)
+}
+
+/// Sorts `items` by merging, returning how many pairs of them were out of order
+fn sort_counting_inversions(items: &mut [usize]) -> usize {
+    if items.len() < 2 {
+        return 0;
+    }
+    let (left, right) = items.split_at_mut(items.len() / 2);
+    let mut inversions = sort_counting_inversions(left) + sort_counting_inversions(right);
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if right[j] < left[i] {
+            // Everything left in `left` is out of order with this item
+            inversions += left.len() - i;
+            merged.push(right[j]);
+            j += 1;
+        } else {
+            merged.push(left[i]);
+            i += 1;
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    items.copy_from_slice(&merged);
+    inversions
+}
@@ -1,12 +1,18 @@
-use std::{cmp::Reverse, collections::HashMap};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use derivative::Derivative;
 use good_lp::{variable, Expression, Variable};
 use indexmap::IndexMap;
 use itertools::Itertools;
-use tracing::debug;
+use tracing::{debug, info, warn};
+use web_time::Instant;
 
-use super::{MonoidalTerm, Slice};
+use super::{MonoidalTerm, Simplification, Slice};
 use crate::{
     common::{Direction, InOut, InOutIter, Link},
     hypergraph::{
@@ -14,9 +20,17 @@ use crate::{
         traits::{Graph, NodeLike},
         utils::normalised_targets,
     },
-    lp::{LpProblem, Solver},
+    lp::{map_with_budget, LpError, LpProblem, Solver},
+    progress::{Callback, Tracker},
 };
 
+/// The most passes of crossing minimisation made for [`Simplification::Full`], which stops early
+/// once a pass removes no crossings.
+const MAX_SWAP_PASSES: usize = 100;
+
+/// The most passes of crossing minimisation made below [`Simplification::Full`].
+const SWAP_PASSES: usize = 10;
+
 /// A `MonoidalWiredGraph` stores the operations of a hypergraph layer by layer
 /// It stores the copies of the graph, but does not store deletions, cups, or caps
 ///
@@ -128,8 +142,9 @@ struct MonoidalWiredGraphBuilder<T: Ctx> {
     /// Edges that have been connected to an output but not all their inputs.
     /// Each is mapped to a `BacklinkData`
     backlinks: HashMap<T::Edge, usize>,
-    /// Lp solver
-    solver: Solver,
+    simplification: Simplification,
+    /// Counts the nodes placed so far
+    progress: Tracker,
 }
 
 impl<T: Ctx> MonoidalWiredGraphBuilder<T> {
@@ -146,6 +161,38 @@ impl<T: Ctx> MonoidalWiredGraphBuilder<T> {
     /// Insert copies and identities so that `edge` is ready to output to at `layer`
     fn prepare_input(&mut self, edge: &T::Edge, layer: usize) {
         let mut layers = self.open_edges.swap_remove(edge).unwrap_or_default();
+        if self.simplification >= Simplification::FoldCopies {
+            // Copy the edge as many times as it is needed just below where it is produced, and
+            // run the copies alongside each other down to where they are used
+            let lowest = layers.iter().copied().min().unwrap_or(layer);
+            for current_layer in lowest..layer {
+                let copies = layers.iter().filter(|x| **x <= current_layer).count();
+                if current_layer + 1 == layer {
+                    self.add_op(
+                        Slice {
+                            ops: vec![WiredOp::Copy {
+                                addr: edge.clone(),
+                                copies,
+                            }],
+                        },
+                        current_layer,
+                    );
+                } else {
+                    for _ in 0..copies {
+                        self.add_op(
+                            Slice {
+                                ops: vec![WiredOp::Copy {
+                                    addr: edge.clone(),
+                                    copies: 1,
+                                }],
+                            },
+                            current_layer,
+                        );
+                    }
+                }
+            }
+            return;
+        }
         layers.sort_by_key(|x| Reverse(*x));
         if let Some(mut current_layer) = layers.pop() {
             while current_layer < layer {
@@ -200,13 +247,18 @@ impl<T: Ctx> MonoidalWiredGraphBuilder<T> {
         }
     }
 
-    /// Inserts a node of a hypergraph into the builder
+    /// Inserts a node of a hypergraph into the builder, taking the body of a thunk from `bodies`
     /// This prepares all the inputs of the node and inserts relevant backlinks
-    fn insert_operation(&mut self, node: &Node<T>, node_layer: usize) {
+    fn insert_operation(
+        &mut self,
+        node: &Node<T>,
+        node_layer: usize,
+        bodies: &mut HashMap<T::Thunk, MonoidalWiredGraph<T>>,
+    ) {
         let wired_op = match node {
             Node::Operation(op) => WiredOp::Operation { addr: op.clone() },
             Node::Thunk(thunk) => WiredOp::Thunk {
-                body: from_graph(thunk, self.solver),
+                body: bodies.remove(thunk).unwrap(),
                 addr: thunk.clone(),
             },
         };
@@ -249,10 +301,123 @@ impl<T: Ctx> MonoidalWiredGraphBuilder<T> {
     }
 }
 
+/// Layers for `nodes` which satisfy the constraints of [`from_graph_tracked`] without solving
+/// them, found by placing each node just above the nodes its outputs go to.
+fn longest_path_layers<T: Ctx>(nodes: &IndexMap<Node<T>, Variable>) -> (Vec<usize>, usize) {
+    let mut layers = vec![1; nodes.len()];
+    for (i, node) in nodes.keys().enumerate() {
+        let targets = node
+            .outputs()
+            .map(|edge| normalised_targets::<T>(&edge, node.backlink().as_ref()))
+            .collect_vec();
+        for targets in &targets {
+            // Leave room for a copy if the edge has several targets
+            let offset = usize::from(targets.len() > 1);
+            for target in targets {
+                let layer = match target {
+                    Endpoint::Node(target_node) => match nodes.get_index_of(target_node) {
+                        Some(j) if j < i => layers[j] + offset + 1,
+                        _ => continue,
+                    },
+                    Endpoint::Boundary(_) => 1 + offset,
+                };
+                layers[i] = layers[i].max(layer);
+            }
+        }
+        // Nodes later in the order which this one feeds must not be below it
+        for target in targets.iter().flatten() {
+            if let Endpoint::Node(target_node) = target {
+                if let Some(j) = nodes.get_index_of(target_node).filter(|j| *j > i) {
+                    layers[j] = layers[j].max(layers[i]);
+                }
+            }
+        }
+    }
+    let max = layers.iter().max().map_or(1, |layer| layer + 1);
+    (layers, max)
+}
+
+/// The time spent solving the layering problems of a graph and its thunks, added up across the
+/// threads solving them.
+#[derive(Default)]
+struct SolveTime(AtomicU64);
+
+impl SolveTime {
+    fn add(&self, time: Duration) {
+        let nanos = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+        self.0.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn total(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
+
+pub fn from_graph<G: Graph>(graph: &G, solver: Solver) -> MonoidalWiredGraph<G::Ctx> {
+    from_graph_timed(
+        graph,
+        solver,
+        Simplification::default(),
+        &Tracker::default(),
+    )
+}
+
+/// Like [`from_graph`], simplifying the graph as far as `simplification`, and telling `progress`
+/// how many of the nodes of the graph, including those inside thunks, have been placed out of
+/// how many in total.
+pub fn from_graph_with_progress<G: Graph>(
+    graph: &G,
+    solver: Solver,
+    simplification: Simplification,
+    progress: Callback,
+) -> MonoidalWiredGraph<G::Ctx> {
+    from_graph_timed(
+        graph,
+        solver,
+        simplification,
+        &Tracker::new(progress, num_nodes(graph)),
+    )
+}
+
+/// Like [`from_graph_tracked`], logging how long it took and how much of that time was spent
+/// solving, which is more than the time taken when thunks are solved on several threads.
+fn from_graph_timed<G: Graph>(
+    graph: &G,
+    solver: Solver,
+    simplification: Simplification,
+    progress: &Tracker,
+) -> MonoidalWiredGraph<G::Ctx> {
+    let start = Instant::now();
+    let solving = SolveTime::default();
+    let term = from_graph_tracked(graph, solver, simplification, progress, &solving);
+    info!(
+        "Layering took {:?}, with {:?} spent solving across all threads",
+        start.elapsed(),
+        solving.total()
+    );
+    term
+}
+
+fn num_nodes<G: Graph>(graph: &G) -> usize {
+    graph
+        .nodes()
+        .map(|node| match node {
+            Node::Operation(_) => 1,
+            Node::Thunk(thunk) => 1 + num_nodes(&thunk),
+        })
+        .sum()
+}
+
 #[allow(clippy::too_many_lines)]
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::cast_sign_loss)]
-pub fn from_graph<G: Graph>(graph: &G, solver: Solver) -> MonoidalWiredGraph<G::Ctx> {
+fn from_graph_tracked<G: Graph>(
+    graph: &G,
+    solver: Solver,
+    simplification: Simplification,
+    progress: &Tracker,
+    solving: &SolveTime,
+) -> MonoidalWiredGraph<G::Ctx> {
     let mut problem = LpProblem::default();
     let max = problem.add_variable(variable().min(0.5));
     let nodes: IndexMap<Node<G::Ctx>, Variable> = graph
@@ -329,19 +494,53 @@ pub fn from_graph<G: Graph>(graph: &G, solver: Solver) -> MonoidalWiredGraph<G::
     }
     problem.add_objective(max);
 
-    let soln = problem.minimise(solver).unwrap();
+    let start = Instant::now();
+    let solution = problem.minimise(solver);
+    solving.add(start.elapsed());
+    let (layers, max_layer): (Vec<usize>, usize) = match solution {
+        Ok(soln) => (
+            nodes
+                .values()
+                .map(|var| soln.value(*var).floor() as usize)
+                .collect(),
+            soln.value(max).floor() as usize,
+        ),
+        Err(LpError::BudgetExceeded | LpError::Cancelled) => {
+            debug!("Falling back to a longest path layering");
+            longest_path_layers(&nodes)
+        }
+        // The failure has already been noted against the budget, if there is one
+        Err(err) => {
+            warn!("Falling back to a longest path layering: {err}");
+            longest_path_layers(&nodes)
+        }
+    };
 
-    let mut builder = MonoidalWiredGraphBuilder::<G::Ctx>::default();
+    let mut builder = MonoidalWiredGraphBuilder::<G::Ctx> {
+        simplification,
+        progress: progress.clone(),
+        ..Default::default()
+    };
     let outputs: Vec<Edge<G::Ctx>> = graph.graph_outputs().collect();
 
     for edge in &outputs {
         builder.open_edges.entry(edge.clone()).or_default().push(0);
     }
 
-    for (node, var) in nodes {
+    // The body of each thunk is laid out independently of the others and of this graph, so they
+    // can be solved at the same time
+    let mut bodies: HashMap<_, _> = map_with_budget(graph.thunks().collect(), |thunk| {
+        let body = from_graph_tracked(&thunk, solver, simplification, progress, solving);
+        (thunk, body)
+    })
+    .into_iter()
+    .collect();
+
+    for (node, layer) in nodes.into_keys().zip(layers) {
         debug!("Node recieved: {node:#?}");
         // Use topsorted graph here
-        builder.insert_operation(&node, soln.value(var).floor() as usize);
+        builder.insert_operation(&node, layer, &mut bodies);
+        builder.progress.step();
     }
 
     let (backlinked_edges, other_edges): (Vec<_>, Vec<_>) = builder
@@ -376,7 +575,7 @@ pub fn from_graph<G: Graph>(graph: &G, solver: Solver) -> MonoidalWiredGraph<G::
         }
     }
 
-    let final_height = soln.value(max).floor() as usize + 1;
+    let final_height = max_layer + 1;
 
     // Connect up global inputs
     for edge in other_edges {
@@ -395,9 +594,11 @@ pub fn from_graph<G: Graph>(graph: &G, solver: Solver) -> MonoidalWiredGraph<G::
 
     // We can minimise swaps, keeping "compound terms" together
     graph.minimise_swaps(false);
-    for _ in 0..10 {
-        graph.minimise_swaps(true);
-    }
+    graph.minimise_crossings(if simplification == Simplification::Full {
+        MAX_SWAP_PASSES
+    } else {
+        SWAP_PASSES
+    });
 
     // After this we can flatten the "compound terms"
     graph.flatten_graph()
@@ -0,0 +1,121 @@
+//! Textual outlines of the structure of a graph.
+
+use std::fmt::Display;
+
+use indexmap::IndexMap;
+
+use crate::hypergraph::{
+    generic::{Edge, Operation, Thunk, Weight},
+    traits::{Graph, WithWeight},
+};
+
+/// Writes a Markdown outline of `graph` under the heading `title`.
+///
+/// The outline is a nested list with an entry for the graph and each thunk inside it, giving its
+/// inputs, its number of outputs, and how many times each operation occurs directly inside it.
+pub fn to_markdown<G: Graph>(graph: &G, title: &str) -> String
+where
+    Edge<G::Ctx>: WithWeight,
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Edge<G::Ctx>>: Display,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    let mut markdown = format!("# {title}\n\n");
+    entry(&mut markdown, graph, "program", 0);
+    markdown
+}
+
+fn entry<G: Graph>(markdown: &mut String, graph: &G, name: &str, depth: usize)
+where
+    Edge<G::Ctx>: WithWeight,
+    Operation<G::Ctx>: WithWeight,
+    Thunk<G::Ctx>: WithWeight,
+    Weight<Edge<G::Ctx>>: Display,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Thunk<G::Ctx>>: Display,
+{
+    // The inputs of the program are its free variables, those of a thunk are its arguments
+    let inputs = if depth == 0 {
+        graph.free_graph_inputs()
+    } else {
+        graph.bound_graph_inputs()
+    }
+    .map(|edge| edge.weight().to_string())
+    .collect::<Vec<_>>();
+    let mut operations = IndexMap::<String, usize>::new();
+    for op in graph.operations() {
+        *operations.entry(op.weight().to_string()).or_default() += 1;
+    }
+    operations.sort_keys();
+
+    markdown.push_str(&format!(
+        "{:indent$}- **{name}**: {}",
+        "",
+        plural(inputs.len(), "input"),
+        indent = 2 * depth,
+    ));
+    let names = inputs
+        .iter()
+        .filter(|input| !input.is_empty())
+        .map(|input| format!("`{input}`"))
+        .collect::<Vec<_>>();
+    if !names.is_empty() {
+        markdown.push_str(&format!(" ({})", names.join(", ")));
+    }
+    markdown.push_str(&format!(
+        ", {}, {}",
+        plural(graph.graph_outputs().count(), "output"),
+        plural(operations.values().sum(), "operation"),
+    ));
+    if !operations.is_empty() {
+        let counts = operations
+            .iter()
+            .map(|(op, count)| format!("{count} × `{op}`"))
+            .collect::<Vec<_>>();
+        markdown.push_str(&format!(" ({})", counts.join(", ")));
+    }
+    markdown.push('\n');
+
+    for thunk in graph.thunks() {
+        let name = match thunk.weight().to_string() {
+            weight if weight.is_empty() => "thunk".to_owned(),
+            weight => format!("thunk `{weight}`"),
+        };
+        entry(markdown, &thunk, &name, depth + 1);
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::to_markdown;
+    use crate::language::spartan::{Expr, Rule, SpartanParser};
+
+    #[test]
+    fn outline() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = plus(y z . bind a = plus(x, y) in bind b = plus(a, z) in b) in f",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let outline = to_markdown(&graph, "Example");
+        let lines = outline.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "# Example");
+        assert!(lines[2].starts_with("- **program**: 1 input (`x`), 1 output, 1 operation"));
+        assert!(lines[3].starts_with("  - **thunk"));
+        assert!(lines[3].ends_with("2 inputs (`y`, `z`), 1 output, 2 operations (2 × `plus`)"));
+        Ok(())
+    }
+}
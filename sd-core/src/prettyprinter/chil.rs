@@ -1,9 +1,9 @@
 use pretty::RcDoc;
 
-use super::{list, paran_list, PrettyPrint};
+use super::{comments, list, paran_list, PrettyPrint};
 use crate::language::chil::{
     Addr, BaseType, Bind, Expr, FunctionType, GenericType, Identifier, Op, Thunk, TupleType, Type,
-    Value, Variable, VariableDef,
+    UnknownType, Value, Variable, VariableDef,
 };
 
 const INDENTATION: isize = 2;
@@ -19,7 +19,8 @@ impl PrettyPrint for Expr {
 
 impl PrettyPrint for Bind {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text("def")
+        comments(&self.comments)
+            .append(RcDoc::text("def"))
             .append(RcDoc::space())
             .append(self.defs[0].to_doc())
             .append(RcDoc::space())
@@ -68,13 +69,20 @@ impl PrettyPrint for Type {
             Self::Generic(gty) => gty.to_doc(),
             Self::Tuple(tty) => tty.to_doc(),
             Self::Function(fty) => fty.to_doc(),
+            Self::Unknown(uty) => uty.to_doc(),
         }
     }
 }
 
 impl PrettyPrint for BaseType {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
+    }
+}
+
+impl PrettyPrint for UnknownType {
+    fn to_doc(&self) -> RcDoc<'_, ()> {
+        RcDoc::text(self.0.as_str())
     }
 }
 
@@ -138,7 +146,8 @@ impl PrettyPrint for Value {
 
 impl PrettyPrint for Thunk {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text("thunk")
+        comments(&self.comments)
+            .append(RcDoc::text("thunk"))
             .append(RcDoc::space())
             .append(self.addr.to_doc())
             .append(RcDoc::space())
@@ -160,7 +169,7 @@ impl PrettyPrint for Thunk {
 
 impl PrettyPrint for Op {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
     }
 }
 
@@ -172,7 +181,7 @@ impl PrettyPrint for Addr {
 
 impl PrettyPrint for Identifier {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
     }
 }
 
@@ -0,0 +1,247 @@
+//! Pretty-printing as HTML, for documentation pages which cross-reference exported diagrams.
+
+use std::{collections::HashSet, fmt::Write};
+
+use super::PrettyPrint;
+use crate::language::{Bind, Block, Expr, GetVar, Language, Thunk, Value};
+
+const INDENTATION: usize = 4;
+
+const STYLE: &str = ".sd-code a { color: inherit; } \
+                     .sd-code .binder { font-weight: bold; } \
+                     .sd-code .free { font-style: italic; } \
+                     .sd-code :target { background: #ffe08a; }";
+
+/// Writes `expr` as an HTML `pre` element, which can also be embedded in Markdown.
+///
+/// The layout follows the spartan printer, with the operations, variables and addresses of other
+/// languages printed as their own printers would. Each binder has an `id` of the form `var-n`
+/// which its occurrences link to, while free variables are left unlinked. Each thunk has an `id`
+/// made from its address, or of the form `thunk-n` if it has none.
+pub fn to_html<T: Language>(expr: &Expr<T>) -> String {
+    let mut printer = Printer::<T>::new();
+    printer.out.push_str("<pre class=\"sd-code\">");
+    printer.expr(expr);
+    printer.out.push_str("</pre>\n");
+    printer.out
+}
+
+/// Writes a standalone HTML page with the heading `title`, containing `expr` as by [`to_html`].
+pub fn to_html_page<T: Language>(expr: &Expr<T>, title: &str) -> String {
+    let title = escape(title);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{}</body>\n</html>\n",
+        to_html(expr)
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+struct Printer<T: Language> {
+    out: String,
+    indent: usize,
+    /// The variables in scope, innermost last, with the number of their binder.
+    scope: Vec<(T::Var, usize)>,
+    binders: usize,
+    thunks: HashSet<String>,
+}
+
+impl<T: Language> Printer<T> {
+    fn new() -> Self {
+        Self {
+            out: String::new(),
+            indent: 0,
+            scope: Vec::new(),
+            binders: 0,
+            thunks: HashSet::new(),
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat(' ').take(self.indent));
+    }
+
+    fn text(&mut self, text: &str) {
+        self.out.push_str(&escape(text));
+    }
+
+    fn paren_list<U>(&mut self, items: &[U], mut item: impl FnMut(&mut Self, &U)) {
+        self.out.push('(');
+        for (i, u) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            item(self, u);
+        }
+        self.out.push(')');
+    }
+
+    /// Writes a binder, returning its number. It is up to the caller to bring it into scope.
+    fn def(&mut self, def: &T::VarDef) -> usize {
+        let n = self.binders;
+        self.binders += 1;
+        let _ = write!(
+            self.out,
+            "<span class=\"binder\" id=\"var-{n}\">{}</span>",
+            escape(&def.to_pretty())
+        );
+        n
+    }
+
+    fn variable(&mut self, var: &T::Var) {
+        let text = escape(&var.to_pretty());
+        let binder = self
+            .scope
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == var)
+            .map(|(_, n)| *n);
+        let _ = match binder {
+            Some(n) => write!(self.out, "<a href=\"#var-{n}\">{text}</a>"),
+            None => write!(self.out, "<span class=\"free\">{text}</span>"),
+        };
+    }
+
+    fn thunk_id(&mut self, addr: &T::Addr) -> String {
+        let addr = addr
+            .to_pretty()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        let id = (!addr.is_empty())
+            .then(|| format!("thunk-{addr}"))
+            .filter(|id| !self.thunks.contains(id))
+            .unwrap_or_else(|| format!("thunk-{}", self.thunks.len()));
+        self.thunks.insert(id.clone());
+        id
+    }
+
+    fn expr(&mut self, expr: &Expr<T>) {
+        for bind in &expr.binds {
+            self.bind(bind);
+        }
+        match expr.values.as_slice() {
+            [value] => self.value(value),
+            values => self.paren_list(values, Self::value),
+        }
+    }
+
+    fn bind(&mut self, bind: &Bind<T>) {
+        self.out.push_str("bind ");
+        let mut binders = Vec::new();
+        if let [def] = bind.defs.as_slice() {
+            binders.push(self.def(def));
+        } else {
+            self.paren_list(&bind.defs, |printer, def| binders.push(printer.def(def)));
+        }
+        self.out.push_str(" = ");
+        // The definitions are only in scope after the value
+        self.value(&bind.value);
+        self.out.push_str(" in");
+        self.newline();
+        self.scope
+            .extend(bind.defs.iter().map(|def| def.var().clone()).zip(binders));
+    }
+
+    fn value(&mut self, value: &Value<T>) {
+        match value {
+            Value::Variable(var) => self.variable(var),
+            Value::Thunk(thunk) => self.thunk(thunk),
+            Value::Op { op, args } => {
+                self.text(&op.to_pretty());
+                if !args.is_empty() {
+                    self.paren_list(args, Self::value);
+                }
+            }
+        }
+    }
+
+    fn thunk(&mut self, thunk: &Thunk<T>) {
+        let id = self.thunk_id(&thunk.addr);
+        let _ = write!(self.out, "<span class=\"thunk\" id=\"{id}\">");
+        // Everything bound inside the thunk goes out of scope at its end
+        let depth = self.scope.len();
+        for def in &thunk.args {
+            let n = self.def(def);
+            self.out.push(' ');
+            self.scope.push((def.var().clone(), n));
+        }
+        self.out.push('.');
+        if thunk.body.binds.is_empty() && thunk.blocks.is_empty() {
+            self.out.push(' ');
+            self.expr(&thunk.body);
+        } else {
+            self.indent += INDENTATION;
+            self.newline();
+            self.expr(&thunk.body);
+            for block in &thunk.blocks {
+                self.block(block);
+            }
+            self.indent -= INDENTATION;
+            self.newline();
+        }
+        self.scope.truncate(depth);
+        self.out.push_str("</span>");
+    }
+
+    fn block(&mut self, block: &Block<T>) {
+        self.newline();
+        self.text(&block.addr.to_pretty());
+        if !block.args.is_empty() {
+            self.paren_list(&block.args, |printer, def| {
+                let n = printer.def(def);
+                printer.scope.push((def.var().clone(), n));
+            });
+        }
+        self.out.push(':');
+        self.indent += INDENTATION;
+        self.newline();
+        self.expr(&block.expr);
+        self.indent -= INDENTATION;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::to_html;
+    use crate::language::spartan::{Expr, Rule, SpartanParser};
+
+    #[test]
+    fn links() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = y . bind a = plus(x, y) in a in bind b = f in b",
+        )?;
+        let html = to_html(&Expr::from_pest(&mut pairs)?);
+        assert_eq!(
+            html,
+            "<pre class=\"sd-code\">\
+             bind <span class=\"binder\" id=\"var-0\">f</span> = \
+             <span class=\"thunk\" id=\"thunk-0\">\
+             <span class=\"binder\" id=\"var-1\">y</span> .\n    \
+             bind <span class=\"binder\" id=\"var-2\">a</span> = \
+             plus(<span class=\"free\">x</span>, <a href=\"#var-1\">y</a>) in\n    \
+             <a href=\"#var-2\">a</a>\n</span> in\n\
+             bind <span class=\"binder\" id=\"var-3\">b</span> = <a href=\"#var-0\">f</a> in\n\
+             <a href=\"#var-3\">b</a></pre>\n"
+        );
+        Ok(())
+    }
+}
@@ -1,21 +1,21 @@
 use pretty::RcDoc;
 
 use super::PrettyPrint;
-use crate::language::mlir::{BlockAddr, Expr, Op, Thunk, Var};
+use crate::language::mlir::{BlockAddr, Expr, Op, RegionKind, Thunk, Var};
 
 impl PrettyPrint for Op {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.name).append(&self.attributes)
+        RcDoc::text(self.name.as_str()).append(&self.attributes)
     }
 }
 
 impl PrettyPrint for Var {
     fn to_doc(&self) -> RcDoc<'_, ()> {
         match self {
-            Var::Var { id } => RcDoc::text(id),
-            Var::VarIdx { id, index } => {
-                RcDoc::text(id).append("#").append(RcDoc::as_string(index))
-            }
+            Var::Var { id } => RcDoc::text(id.as_str()),
+            Var::VarIdx { id, index } => RcDoc::text(id.as_str())
+                .append("#")
+                .append(RcDoc::as_string(index)),
             Var::Symbol(s) => s.to_doc(),
         }
     }
@@ -23,7 +23,13 @@ impl PrettyPrint for Var {
 
 impl PrettyPrint for BlockAddr {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
+    }
+}
+
+impl PrettyPrint for RegionKind {
+    fn to_doc(&self) -> RcDoc<'_, ()> {
+        RcDoc::nil()
     }
 }
 
@@ -4,6 +4,7 @@ use itertools::Either;
 use pretty::RcDoc;
 
 pub mod chil;
+pub mod html;
 pub mod mlir;
 pub mod spartan;
 
@@ -30,6 +31,15 @@ pub fn paran_list<'a, T: 'a + PrettyPrint>(ts: impl IntoIterator<Item = &'a T>)
     RcDoc::text("(").append(list(ts)).append(RcDoc::text(")"))
 }
 
+/// Comments, each on a line of its own, to go before whatever they were written before.
+pub fn comments(comments: &[String]) -> RcDoc<'_, ()> {
+    RcDoc::concat(comments.iter().map(|comment| {
+        RcDoc::text("#")
+            .append(RcDoc::text(comment.as_str()))
+            .append(RcDoc::hardline())
+    }))
+}
+
 impl<T: PrettyPrint> PrettyPrint for Vec<T> {
     fn to_doc(&self) -> RcDoc<'_, ()> {
         list(self)
@@ -1,6 +1,6 @@
 use pretty::RcDoc;
 
-use super::{paran_list, PrettyPrint};
+use super::{comments, paran_list, PrettyPrint};
 use crate::language::spartan::{Bind, Expr, Op, Thunk, Value, Variable};
 
 impl PrettyPrint for Expr {
@@ -19,7 +19,8 @@ impl PrettyPrint for Expr {
 
 impl PrettyPrint for Bind {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text("bind")
+        comments(&self.comments)
+            .append(RcDoc::text("bind"))
             .append(RcDoc::space())
             .append(if self.defs.len() == 1 {
                 self.defs[0].to_doc()
@@ -38,7 +39,7 @@ impl PrettyPrint for Bind {
 
 impl PrettyPrint for Variable {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::text(&self.0)
+        RcDoc::text(self.0.as_str())
     }
 }
 
@@ -91,7 +92,11 @@ impl PrettyPrint for Op {
 
 impl PrettyPrint for Thunk {
     fn to_doc(&self) -> RcDoc<'_, ()> {
-        RcDoc::intersperse(self.args.iter().map(PrettyPrint::to_doc), RcDoc::space())
+        comments(&self.comments)
+            .append(RcDoc::intersperse(
+                self.args.iter().map(PrettyPrint::to_doc),
+                RcDoc::space(),
+            ))
             .append(RcDoc::space())
             .append(RcDoc::text("."))
             .append(if self.body.binds.is_empty() {
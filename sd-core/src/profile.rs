@@ -0,0 +1,157 @@
+//! Numeric annotations of nodes, such as execution counts or costs from a profiler, read from a
+//! sidecar file which names each node by its [`NodeAddress`].
+//!
+//! The file is either a JSON object from addresses to numbers, for example
+//! `{"%3/add#1": 1200, "%3/mul": 40}`, or CSV with an address and a number on each line, for
+//! example `%3/add#1,1200`. CSV files may start with a header line, and blank lines and lines
+//! starting with `#` are ignored.
+
+use std::{collections::HashMap, fmt::Display};
+
+use indexmap::IndexMap;
+use thiserror::Error;
+
+use crate::hypergraph::{
+    address::{addresses, AddressParseError, NodeAddress},
+    generic::{Node, Operation, Thunk, Weight},
+    traits::{Graph, WithWeight},
+};
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Line {0}: expected `ADDRESS,VALUE`")]
+    Line(usize),
+    #[error("Line {0}: invalid value `{1}`")]
+    Value(usize, String),
+    #[error(transparent)]
+    Address(#[from] AddressParseError),
+}
+
+/// A value for each of the nodes named in a sidecar file.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Profile(pub IndexMap<NodeAddress, f64>);
+
+impl Profile {
+    /// Reads a profile as JSON if it is an object, and as CSV otherwise.
+    pub fn parse(source: &str) -> Result<Self, ProfileError> {
+        if source.trim_start().starts_with('{') {
+            Self::parse_json(source)
+        } else {
+            Self::parse_csv(source)
+        }
+    }
+
+    pub fn parse_json(source: &str) -> Result<Self, ProfileError> {
+        serde_json::from_str::<IndexMap<String, f64>>(source)?
+            .into_iter()
+            .map(|(address, value)| Ok((address.parse()?, value)))
+            .collect::<Result<_, ProfileError>>()
+            .map(Self)
+    }
+
+    pub fn parse_csv(source: &str) -> Result<Self, ProfileError> {
+        let mut values = IndexMap::new();
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        for (index, (line, text)) in lines.enumerate() {
+            // Addresses may contain commas, values never do
+            let (address, value) = text.rsplit_once(',').ok_or(ProfileError::Line(line))?;
+            let value = value.trim();
+            match value.parse() {
+                Ok(value) => {
+                    values.insert(address.trim().parse()?, value);
+                }
+                Err(_) if index == 0 => {} // A header
+                Err(_) => return Err(ProfileError::Value(line, value.to_owned())),
+            }
+        }
+        Ok(Self(values))
+    }
+
+    /// The smallest and largest values, unless the profile is empty.
+    #[must_use]
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.0.values().fold(None, |range, &value| match range {
+            None => Some((value, value)),
+            Some((min, max)) => Some((min.min(value), max.max(value))),
+        })
+    }
+
+    /// The heat of each node of `graph` with a value, from 0 for the smallest value to 1 for the
+    /// largest. Values for addresses which are not in the graph are ignored.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn heat<G: Graph>(&self, graph: &G) -> HashMap<Node<G::Ctx>, f32>
+    where
+        Operation<G::Ctx>: WithWeight,
+        Thunk<G::Ctx>: WithWeight,
+        Weight<Operation<G::Ctx>>: Display,
+        Weight<Thunk<G::Ctx>>: Display,
+    {
+        let Some((min, max)) = self.range() else {
+            return HashMap::new();
+        };
+        let addresses = addresses(graph);
+        self.0
+            .iter()
+            .filter_map(|(address, &value)| {
+                let heat = if max > min {
+                    (value - min) / (max - min)
+                } else {
+                    1.0
+                };
+                Some((addresses.get(address)?.clone(), heat as f32))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Profile, ProfileError};
+    use crate::{
+        hypergraph::{
+            generic::Node,
+            traits::{Graph, WithWeight},
+        },
+        language::chil::parser,
+    };
+
+    #[test]
+    fn formats_agree() {
+        let csv = Profile::parse("address,count\n# comment\nadd, 10\n\nmul#1,30\n").unwrap();
+        let json = Profile::parse(r#"{"add": 10, "mul#1": 30}"#).unwrap();
+        assert_eq!(csv, json);
+        assert_eq!(csv.range(), Some((10.0, 30.0)));
+
+        assert!(matches!(
+            Profile::parse("add,10\nmul,lots"),
+            Err(ProfileError::Value(2, _))
+        ));
+        assert!(matches!(Profile::parse("add"), Err(ProfileError::Line(1))));
+    }
+
+    #[test]
+    fn heat_of_nodes() {
+        let graph = parser::parse("def %0 = add(%1, %2)\ndef %3 = mul(%0, %0)\noutput %3")
+            .unwrap()
+            .to_graph(false)
+            .unwrap();
+        let profile = Profile::parse("add,5\nmul,25\nsub,15").unwrap();
+        let heat = profile.heat(&graph);
+        assert_eq!(heat.len(), 2);
+        let heat_of = |name: &str| {
+            graph
+                .operations()
+                .find(|op| op.weight().0 == name)
+                .and_then(|op| heat.get(&Node::Operation(op)).copied())
+        };
+        assert_eq!(heat_of("add"), Some(0.0));
+        assert_eq!(heat_of("mul"), Some(1.0));
+    }
+}
@@ -0,0 +1,71 @@
+//! Reporting how far through a long-running conversion we are, so that a large input shows
+//! movement rather than appearing hung.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// A callback told the number of items processed so far, and the total number of items.
+pub type Callback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Counts the items processed by a conversion, reporting to a callback as it goes.
+#[derive(Clone, Default)]
+pub(crate) struct Tracker {
+    callback: Option<Callback>,
+    done: Arc<AtomicUsize>,
+    total: usize,
+}
+
+impl Tracker {
+    pub(crate) fn new(callback: Callback, total: usize) -> Self {
+        callback(0, total);
+        Self {
+            callback: Some(callback),
+            done: Arc::default(),
+            total,
+        }
+    }
+
+    /// Records that an item has been processed.
+    pub(crate) fn step(&self) {
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        // Only report each percent, as the callback may be expensive
+        if done >= self.total || percent(done, self.total) != percent(done - 1, self.total) {
+            callback(done.min(self.total), self.total);
+        }
+    }
+}
+
+const fn percent(done: usize, total: usize) -> usize {
+    done * 100 / total
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::Tracker;
+
+    #[test]
+    fn reports_each_percent() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let tracker = Tracker::new(
+            Arc::new({
+                let reports = reports.clone();
+                move |done, total| reports.lock().unwrap().push((done, total))
+            }),
+            1000,
+        );
+        for _ in 0..1000 {
+            tracker.step();
+        }
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 101);
+        assert_eq!(reports.first(), Some(&(0, 1000)));
+        assert_eq!(reports.last(), Some(&(1000, 1000)));
+    }
+}
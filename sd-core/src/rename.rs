@@ -0,0 +1,308 @@
+//! Renaming a variable of a program everywhere it is bound and used, such as from a wire of its
+//! diagram. Each wire carries the variable it was built from in its weight, and as programs which
+//! shadow variables are rejected, the variable alone says where it is bound.
+
+use std::fmt::Display;
+
+use pest::Parser;
+use thiserror::Error;
+
+use crate::{
+    language::{
+        chil::{Chil, Identifier, Variable, VariableDef},
+        spartan::{self, Spartan, SpartanParser},
+        Expr, GetVar, Language, Value,
+    },
+    prettyprinter::PrettyPrint,
+};
+
+/// Languages whose variables can be renamed and the program printed back as code.
+pub trait Rename: Language {
+    /// Whether `name` can name a variable, so that the code printed parses again.
+    fn is_valid_name(name: &str) -> bool;
+
+    /// `var` called `name` instead.
+    fn rename_var(var: &Self::Var, name: &str) -> Self::Var;
+
+    /// `def` called `name` instead, keeping whatever else it says about the variable.
+    fn rename_def(def: &Self::VarDef, name: &str) -> Self::VarDef;
+}
+
+/// Where a variable is bound in a program.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binder {
+    /// Defined by a binding.
+    Bind,
+    /// An argument of a thunk.
+    ThunkArg,
+    /// An argument of one of the blocks of a thunk.
+    BlockArg,
+    /// Bound nowhere, so an input of the whole program.
+    Free,
+}
+
+impl Display for Binder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bind => f.write_str("bound by a binding"),
+            Self::ThunkArg => f.write_str("an argument of a thunk"),
+            Self::BlockArg => f.write_str("an argument of a block"),
+            Self::Free => f.write_str("free"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum RenameError {
+    #[error("`{0}` is not a valid variable name")]
+    InvalidName(String),
+    #[error("`{0}` is not in the program")]
+    NotFound(String),
+    #[error("`{0}` is already the name of another variable")]
+    Taken(String),
+}
+
+impl<T: Language> Expr<T> {
+    /// Where `var` is bound in the expression, or `None` if it does not occur in it.
+    #[must_use]
+    pub fn binder(&self, var: &T::Var) -> Option<Binder> {
+        let mut binder = None;
+        self.visit_vars(&mut |other, site| {
+            if other == var && binder.map_or(true, |binder| binder == Binder::Free) {
+                binder = Some(site.unwrap_or(Binder::Free));
+            }
+        });
+        binder
+    }
+
+    /// Calls `f` on every variable bound or used in the expression, with where it is bound for
+    /// the definitions and `None` for the uses.
+    fn visit_vars(&self, f: &mut impl FnMut(&T::Var, Option<Binder>)) {
+        for bind in &self.binds {
+            for def in &bind.defs {
+                f(def.var(), Some(Binder::Bind));
+            }
+            bind.value.visit_vars(f);
+        }
+        for value in &self.values {
+            value.visit_vars(f);
+        }
+    }
+}
+
+impl<T: Rename> Expr<T> {
+    /// A copy of the expression with `var` called `name` wherever it is bound and used.
+    pub fn rename_var(&self, var: &T::Var, name: &str) -> Result<Self, RenameError> {
+        if !T::is_valid_name(name) {
+            return Err(RenameError::InvalidName(name.to_owned()));
+        }
+        if self.binder(var).is_none() {
+            return Err(RenameError::NotFound(var.to_pretty()));
+        }
+        let renamed = T::rename_var(var, name);
+        if renamed == *var {
+            return Ok(self.clone());
+        }
+        // Merging two variables into one would change what the program means
+        let mut taken = false;
+        self.visit_vars(&mut |other, _| taken |= *other == renamed);
+        if taken {
+            return Err(RenameError::Taken(name.to_owned()));
+        }
+
+        let mut expr = self.clone();
+        expr.rename_occurrences(var, &renamed, name);
+        Ok(expr)
+    }
+
+    fn rename_occurrences(&mut self, from: &T::Var, to: &T::Var, name: &str) {
+        for bind in &mut self.binds {
+            rename_defs::<T>(&mut bind.defs, from, name);
+            bind.value.rename_occurrences(from, to, name);
+        }
+        for value in &mut self.values {
+            value.rename_occurrences(from, to, name);
+        }
+    }
+}
+
+impl<T: Language> Value<T> {
+    fn visit_vars(&self, f: &mut impl FnMut(&T::Var, Option<Binder>)) {
+        match self {
+            Value::Variable(var) => f(var, None),
+            Value::Thunk(thunk) => {
+                for arg in &thunk.args {
+                    f(arg.var(), Some(Binder::ThunkArg));
+                }
+                thunk.body.visit_vars(f);
+                for block in &thunk.blocks {
+                    for arg in &block.args {
+                        f(arg.var(), Some(Binder::BlockArg));
+                    }
+                    block.expr.visit_vars(f);
+                }
+            }
+            Value::Op { args, .. } => {
+                for arg in args {
+                    arg.visit_vars(f);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Rename> Value<T> {
+    fn rename_occurrences(&mut self, from: &T::Var, to: &T::Var, name: &str) {
+        match self {
+            Value::Variable(var) => {
+                if var == from {
+                    *var = to.clone();
+                }
+            }
+            Value::Thunk(thunk) => {
+                rename_defs::<T>(&mut thunk.args, from, name);
+                thunk.body.rename_occurrences(from, to, name);
+                for block in &mut thunk.blocks {
+                    rename_defs::<T>(&mut block.args, from, name);
+                    block.expr.rename_occurrences(from, to, name);
+                }
+            }
+            Value::Op { args, .. } => {
+                for arg in args {
+                    arg.rename_occurrences(from, to, name);
+                }
+            }
+        }
+    }
+}
+
+fn rename_defs<T: Rename>(defs: &mut [T::VarDef], from: &T::Var, name: &str) {
+    for def in defs {
+        if def.var() == from {
+            *def = T::rename_def(def, name);
+        }
+    }
+}
+
+impl Rename for Spartan {
+    fn is_valid_name(name: &str) -> bool {
+        SpartanParser::parse(spartan::Rule::variable, name)
+            .is_ok_and(|pairs| pairs.as_str() == name)
+    }
+
+    fn rename_var(_: &Self::Var, name: &str) -> Self::Var {
+        spartan::Variable(name.into())
+    }
+
+    fn rename_def(def: &Self::VarDef, name: &str) -> Self::VarDef {
+        Self::rename_var(def, name)
+    }
+}
+
+impl Rename for Chil {
+    /// Names run up to the bracket before their address, so cannot contain one or spaces, and
+    /// would be read as an address if they started like one.
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with(['@', '%'])
+            && !name.contains(|c: char| c == '(' || c.is_whitespace())
+    }
+
+    /// The variable keeps its address, which tells it apart from others of the same name.
+    fn rename_var(var: &Self::Var, name: &str) -> Self::Var {
+        Variable {
+            name: Some(Identifier(name.into())),
+            addr: var.addr.clone(),
+        }
+    }
+
+    fn rename_def(def: &Self::VarDef, name: &str) -> Self::VarDef {
+        VariableDef {
+            var: Self::rename_var(&def.var, name),
+            r#type: def.r#type.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::{Binder, Rename, RenameError};
+    use crate::{
+        language::{
+            chil::Chil,
+            spartan::{Expr, Rule, SpartanParser, Variable},
+        },
+        prettyprinter::PrettyPrint,
+    };
+
+    fn parse(source: &str) -> Result<Expr, Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, source)?;
+        Ok(Expr::from_pest(&mut pairs)?)
+    }
+
+    fn rename(source: &str, var: &str, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(parse(source)?
+            .rename_var(&Variable(var.into()), name)?
+            .to_pretty())
+    }
+
+    const PROGRAM: &str = "bind a = plus(x, y) in bind f = z . times(a, z) in f";
+
+    #[test]
+    fn binders() -> Result<(), Box<dyn std::error::Error>> {
+        let expr = parse(PROGRAM)?;
+        let binder = |var: &str| expr.binder(&Variable(var.into()));
+        assert_eq!(binder("a"), Some(Binder::Bind));
+        assert_eq!(binder("z"), Some(Binder::ThunkArg));
+        assert_eq!(binder("x"), Some(Binder::Free));
+        assert_eq!(binder("w"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn renames_binders_and_uses() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            rename(PROGRAM, "a", "sum")?,
+            parse("bind sum = plus(x, y) in bind f = z . times(sum, z) in f")?.to_pretty()
+        );
+        assert_eq!(
+            rename(PROGRAM, "z", "w")?,
+            parse("bind a = plus(x, y) in bind f = w . times(a, w) in f")?.to_pretty()
+        );
+        assert_eq!(
+            rename(PROGRAM, "x", "u")?,
+            parse("bind a = plus(u, y) in bind f = z . times(a, z) in f")?.to_pretty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_bad_renames() -> Result<(), Box<dyn std::error::Error>> {
+        let expr = parse(PROGRAM)?;
+        let error = |var: &str, name: &str| expr.rename_var(&Variable(var.into()), name).err();
+        assert_eq!(error("a", "y"), Some(RenameError::Taken("y".to_owned())));
+        assert_eq!(
+            error("a", "in"),
+            Some(RenameError::InvalidName("in".to_owned()))
+        );
+        assert_eq!(
+            error("a", "1a"),
+            Some(RenameError::InvalidName("1a".to_owned()))
+        );
+        assert_eq!(error("w", "v"), Some(RenameError::NotFound("w".to_owned())));
+        Ok(())
+    }
+
+    #[test]
+    fn chil_names() {
+        assert!(Chil::is_valid_name("x_1"));
+        assert!(Chil::is_valid_name("Float_tangentAdd"));
+        assert!(!Chil::is_valid_name(""));
+        assert!(!Chil::is_valid_name("%1"));
+        assert!(!Chil::is_valid_name("f(x)"));
+        assert!(!Chil::is_valid_name("two words"));
+    }
+}
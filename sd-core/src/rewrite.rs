@@ -0,0 +1,387 @@
+//! Rewriting programs by equations between values, such as `plus(x, 0) = x`, for exploratory
+//! reasoning about the diagrams they draw. Rewrites change the source, which is then recompiled.
+
+use std::collections::HashMap;
+
+use derivative::Derivative;
+use from_pest::{ConversionError, FromPest, Void};
+use indexmap::IndexSet;
+use pest::Parser;
+use thiserror::Error;
+
+use crate::language::{
+    spartan::{self, Spartan, SpartanParser},
+    Expr, GetVar, Language, Value,
+};
+
+/// Laws of the built-in Spartan operations.
+const SPARTAN_LAWS: &str = include_str!("theories/spartan.theory");
+
+#[derive(Clone, Debug, Error)]
+pub enum RuleError {
+    #[error("variable {0} on the right-hand side is not bound on the left-hand side")]
+    Unbound(String),
+
+    #[error("the left-hand side cannot be a variable, as it would match everywhere")]
+    Variable,
+
+    #[error("rules cannot contain thunks")]
+    Thunk,
+
+    #[error("each side must have exactly one output")]
+    Outputs,
+
+    #[error("each bind must define exactly one variable")]
+    Destructuring,
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum TheoryError {
+    #[error("Theory parsing error:\n{0}")]
+    Parse(#[from] Box<pest::error::Error<spartan::Rule>>),
+
+    #[error("Conversion error:\n{0}")]
+    Conversion(#[from] ConversionError<Void>),
+
+    #[error("rule {0}: {1}")]
+    Rule(String, RuleError),
+}
+
+/// An equation between two values, used from left to right. The variables of the left-hand side
+/// stand for any value, those appearing more than once for equal values.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct RewriteRule<T: Language> {
+    pub name: String,
+    pub lhs: Value<T>,
+    pub rhs: Value<T>,
+}
+
+/// Where a value sits in an expression, one step at a time from the top.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Step {
+    /// The value of a bind.
+    Bind(usize),
+    /// An output of the expression.
+    Output(usize),
+    /// An argument of an operation.
+    Arg(usize),
+    /// The body of a thunk.
+    Body,
+}
+
+/// A value of a program to which a rule applies.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct Match<T: Language> {
+    /// The index of the rule which applies.
+    pub rule: usize,
+    pub path: Vec<Step>,
+    pub before: Value<T>,
+    pub after: Value<T>,
+}
+
+impl<T: Language> RewriteRule<T> {
+    pub fn new(name: String, lhs: Value<T>, rhs: Value<T>) -> Result<Self, RuleError> {
+        if matches!(lhs, Value::Variable(_)) {
+            return Err(RuleError::Variable);
+        }
+        let (mut bound, mut used) = (IndexSet::new(), IndexSet::new());
+        variables(&lhs, &mut bound)?;
+        variables(&rhs, &mut used)?;
+        if let Some(var) = used.difference(&bound).next() {
+            return Err(RuleError::Unbound(var.to_string()));
+        }
+        Ok(Self { name, lhs, rhs })
+    }
+
+    /// The result of rewriting `value` by this rule, if it applies.
+    #[must_use]
+    pub fn apply(&self, value: &Value<T>) -> Option<Value<T>> {
+        let mut substitution = HashMap::new();
+        unify(&self.lhs, value, &mut substitution).then(|| substitute(&self.rhs, &substitution))
+    }
+
+    /// A rule between two programs, each with a single output, whose bindings are inlined.
+    pub fn from_exprs(name: String, lhs: Expr<T>, rhs: Expr<T>) -> Result<Self, RuleError> {
+        Self::new(name, inline(lhs)?, inline(rhs)?)
+    }
+}
+
+impl RewriteRule<Spartan> {
+    /// Laws of arithmetic and logic which hold of the built-in Spartan operations.
+    #[must_use]
+    pub fn spartan_laws() -> Vec<Self> {
+        Self::parse_theory(SPARTAN_LAWS).expect("built-in laws should be well formed")
+    }
+
+    /// Parses a file of rules, each written `rule name: lhs = rhs` with both sides Spartan
+    /// programs.
+    pub fn parse_theory(source: &str) -> Result<Vec<Self>, TheoryError> {
+        let pairs = SpartanParser::parse(spartan::Rule::theory, source).map_err(Box::new)?;
+        pairs
+            .filter(|pair| pair.as_rule() == spartan::Rule::equation)
+            .map(|pair| {
+                let mut inner = pair.into_inner();
+                let name = inner
+                    .next()
+                    .map(|name| name.as_str().to_owned())
+                    .unwrap_or_default();
+                let lhs = spartan::Expr::from_pest(&mut inner)?;
+                let rhs = spartan::Expr::from_pest(&mut inner)?;
+                Self::from_exprs(name.clone(), lhs, rhs).map_err(|err| TheoryError::Rule(name, err))
+            })
+            .collect()
+    }
+}
+
+/// The single output of `expr`, with the values bound to variables substituted for them.
+fn inline<T: Language>(expr: Expr<T>) -> Result<Value<T>, RuleError> {
+    let mut substitution = HashMap::new();
+    for bind in expr.binds {
+        let [def] = <[T::VarDef; 1]>::try_from(bind.defs).map_err(|_| RuleError::Destructuring)?;
+        let value = substitute(&bind.value, &substitution);
+        substitution.insert(def.into_var(), value);
+    }
+    let [value] = <[Value<T>; 1]>::try_from(expr.values).map_err(|_| RuleError::Outputs)?;
+    Ok(substitute(&value, &substitution))
+}
+
+/// Collects the variables of a rule's side.
+fn variables<T: Language>(value: &Value<T>, vars: &mut IndexSet<T::Var>) -> Result<(), RuleError> {
+    match value {
+        Value::Variable(var) => {
+            vars.insert(var.clone());
+        }
+        Value::Thunk(_) => return Err(RuleError::Thunk),
+        Value::Op { args, .. } => {
+            for arg in args {
+                variables(arg, vars)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `value` is an instance of `pattern`, extending `substitution` to make it so.
+fn unify<T: Language>(
+    pattern: &Value<T>,
+    value: &Value<T>,
+    substitution: &mut HashMap<T::Var, Value<T>>,
+) -> bool {
+    match (pattern, value) {
+        (Value::Variable(var), _) => match substitution.get(var) {
+            Some(bound) => bound == value,
+            None => {
+                substitution.insert(var.clone(), value.clone());
+                true
+            }
+        },
+        (
+            Value::Op { op, args },
+            Value::Op {
+                op: value_op,
+                args: value_args,
+            },
+        ) => {
+            op == value_op
+                && args.len() == value_args.len()
+                && args
+                    .iter()
+                    .zip(value_args)
+                    .all(|(arg, value_arg)| unify(arg, value_arg, substitution))
+        }
+        _ => false,
+    }
+}
+
+fn substitute<T: Language>(
+    pattern: &Value<T>,
+    substitution: &HashMap<T::Var, Value<T>>,
+) -> Value<T> {
+    match pattern {
+        Value::Variable(var) => substitution
+            .get(var)
+            .cloned()
+            .unwrap_or_else(|| pattern.clone()),
+        Value::Thunk(_) => pattern.clone(),
+        Value::Op { op, args } => Value::Op {
+            op: op.clone(),
+            args: args
+                .iter()
+                .map(|arg| substitute(arg, substitution))
+                .collect(),
+        },
+    }
+}
+
+impl<T: Language> Expr<T> {
+    /// Every value in the expression, including those inside thunks, to which one of `rules`
+    /// applies.
+    #[must_use]
+    pub fn matches(&self, rules: &[RewriteRule<T>]) -> Vec<Match<T>> {
+        let mut matches = Vec::new();
+        self.collect_matches(rules, &mut Vec::new(), &mut matches);
+        matches
+    }
+
+    fn collect_matches(
+        &self,
+        rules: &[RewriteRule<T>],
+        path: &mut Vec<Step>,
+        matches: &mut Vec<Match<T>>,
+    ) {
+        let values = self
+            .binds
+            .iter()
+            .enumerate()
+            .map(|(i, bind)| (Step::Bind(i), &bind.value))
+            .chain(
+                self.values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (Step::Output(i), value)),
+            );
+        for (step, value) in values {
+            path.push(step);
+            value.collect_matches(rules, path, matches);
+            path.pop();
+        }
+    }
+
+    /// A copy of the expression with `rewrite` applied, unless it is not a match in this
+    /// expression.
+    #[must_use]
+    pub fn rewrite(&self, rewrite: &Match<T>) -> Option<Self> {
+        let mut expr = self.clone();
+        let value = expr.value_mut(&rewrite.path)?;
+        if *value != rewrite.before {
+            return None;
+        }
+        *value = rewrite.after.clone();
+        Some(expr)
+    }
+
+    fn value_mut(&mut self, path: &[Step]) -> Option<&mut Value<T>> {
+        let (step, rest) = path.split_first()?;
+        let value = match step {
+            Step::Bind(i) => &mut self.binds.get_mut(*i)?.value,
+            Step::Output(i) => self.values.get_mut(*i)?,
+            Step::Arg(_) | Step::Body => return None,
+        };
+        value.at_mut(rest)
+    }
+}
+
+impl<T: Language> Value<T> {
+    fn collect_matches(
+        &self,
+        rules: &[RewriteRule<T>],
+        path: &mut Vec<Step>,
+        matches: &mut Vec<Match<T>>,
+    ) {
+        for (index, rule) in rules.iter().enumerate() {
+            if let Some(after) = rule.apply(self) {
+                matches.push(Match {
+                    rule: index,
+                    path: path.clone(),
+                    before: self.clone(),
+                    after,
+                });
+            }
+        }
+        match self {
+            Self::Variable(_) => {}
+            // Blocks are left alone, as they are only found in languages without rules
+            Self::Thunk(thunk) => {
+                path.push(Step::Body);
+                thunk.body.collect_matches(rules, path, matches);
+                path.pop();
+            }
+            Self::Op { args, .. } => {
+                for (i, arg) in args.iter().enumerate() {
+                    path.push(Step::Arg(i));
+                    arg.collect_matches(rules, path, matches);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    fn at_mut(&mut self, path: &[Step]) -> Option<&mut Self> {
+        let Some((step, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        match (self, step) {
+            (Self::Op { args, .. }, Step::Arg(i)) => args.get_mut(*i)?.at_mut(rest),
+            (Self::Thunk(thunk), Step::Body) => thunk.body.value_mut(rest),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::RewriteRule;
+    use crate::{
+        language::spartan::{Expr, Rule, SpartanParser},
+        prettyprinter::PrettyPrint,
+    };
+
+    fn parse(source: &str) -> Result<Expr, Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(Rule::program, source)?;
+        Ok(Expr::from_pest(&mut pairs)?)
+    }
+
+    #[test]
+    fn rewrite_laws() -> Result<(), Box<dyn std::error::Error>> {
+        let laws = RewriteRule::spartan_laws();
+        let expr =
+            parse("bind a = plus(x, 0) in bind f = y . times(1, not(not(y))) in tuple(a, f)")?;
+        let matches = expr.matches(&laws);
+        let names: Vec<_> = matches.iter().map(|m| laws[m.rule].name.as_str()).collect();
+        assert_eq!(names, ["plus-zero", "one-times", "not-not"]);
+
+        let rewritten = expr.rewrite(&matches[2]).ok_or("match not found")?;
+        assert_eq!(
+            rewritten.to_pretty(),
+            parse("bind a = plus(x, 0) in bind f = y . times(1, y) in tuple(a, f)")?.to_pretty()
+        );
+        // Stale once the value has changed
+        assert!(rewritten.rewrite(&matches[2]).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn theory() -> Result<(), Box<dyn std::error::Error>> {
+        let rules = RewriteRule::parse_theory(
+            "# Distributivity\n\
+             rule distribute: bind a = plus(y, z) in times(x, a) = \
+             plus(times(x, y), times(x, z))",
+        )?;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].name, "distribute");
+        let expr = parse("times(2, plus(n, 1))")?;
+        let matches = expr.matches(&rules);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].after.to_pretty(),
+            "plus(times(2, n), times(2, 1))"
+        );
+
+        assert!(RewriteRule::parse_theory("rule bad: plus(x, 0) = y").is_err());
+        assert!(RewriteRule::parse_theory("rule bad: (x, y) = (y, x)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_variables() -> Result<(), Box<dyn std::error::Error>> {
+        let laws = RewriteRule::spartan_laws();
+        assert_eq!(parse("if(c, x, x)")?.matches(&laws).len(), 1);
+        assert!(parse("if(c, x, y)")?.matches(&laws).is_empty());
+        Ok(())
+    }
+}
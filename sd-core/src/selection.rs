@@ -69,6 +69,11 @@ where
         self.0.values_mut().for_each(|selected| *selected = false);
     }
 
+    /// Select all nodes.
+    pub fn select_all(&mut self) {
+        self.0.values_mut().for_each(|selected| *selected = true);
+    }
+
     /// Iterator of selected nodes.
     #[must_use]
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = Node<T>> + Clone + '_ {
@@ -0,0 +1,245 @@
+//! Tiny pictures of graphs, without labels, for recognising them at a glance in lists of graphs.
+//!
+//! Nothing is solved to draw a thumbnail: the graph is layered by rank, and each slice is packed
+//! from the left, so a thumbnail is quick to make even for graphs which take a while to lay out.
+
+use std::time::Duration;
+
+use crate::{
+    common::InOut,
+    hypergraph::{generic::Ctx, traits::Graph},
+    lp::{Budget, CancellationToken, Solver},
+    monoidal::{
+        graph::{MonoidalGraph, MonoidalOp},
+        wired_graph::from_graph,
+    },
+};
+
+/// The gap between slices, for the wires between them.
+const GAP: f32 = 0.5;
+/// The height of an operation.
+const NODE_HEIGHT: f32 = 0.6;
+/// The space between the border of a thunk and its body.
+const PADDING: f32 = 0.25;
+/// The space around the picture, in pixels.
+const MARGIN: usize = 1;
+
+/// A greyscale picture of a graph, where each pixel is how much of it is covered by the graph.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    /// The coverage of each pixel, row by row from the top left.
+    pub pixels: Vec<u8>,
+}
+
+impl Thumbnail {
+    /// Draws `graph` to fit within a square of `size` pixels, keeping its aspect ratio.
+    #[must_use]
+    pub fn new<G: Graph>(graph: &G, size: usize) -> Self {
+        // With no time to solve in, the monoidal term falls back to a layering by rank
+        let budget = Budget::new(Duration::ZERO, CancellationToken::default());
+        let (term, _) = budget.run(|| from_graph(graph, Solver::default()));
+        Self::from_monoidal(&MonoidalGraph::from(&term), size)
+    }
+
+    /// Draws a monoidal graph to fit within a square of `size` pixels, keeping its aspect ratio.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn from_monoidal<T: Ctx>(graph: &MonoidalGraph<T>, size: usize) -> Self {
+        let mut strokes = Vec::new();
+        let (width, height) = draw(graph, 0.0, 0.0, &mut strokes);
+
+        let inner = size.saturating_sub(2 * MARGIN).max(1) as f32;
+        let scale = inner / width.max(height).max(1.0);
+        let mut thumbnail = Self {
+            width: (width * scale).ceil() as usize + 2 * MARGIN,
+            height: (height * scale).ceil() as usize + 2 * MARGIN,
+            pixels: Vec::new(),
+        };
+        thumbnail.pixels = vec![0; thumbnail.width * thumbnail.height];
+        let to_pixels = |(x, y): (f32, f32)| (x * scale + MARGIN as f32, y * scale + MARGIN as f32);
+        for stroke in strokes {
+            match stroke {
+                Stroke::Line(from, to) => thumbnail.line(to_pixels(from), to_pixels(to)),
+                Stroke::Box(min, max) => thumbnail.fill(to_pixels(min), to_pixels(max)),
+                Stroke::Frame(min, max) => {
+                    let (min, max) = (to_pixels(min), to_pixels(max));
+                    thumbnail.line(min, (max.0, min.1));
+                    thumbnail.line((max.0, min.1), max);
+                    thumbnail.line(max, (min.0, max.1));
+                    thumbnail.line((min.0, max.1), min);
+                }
+            }
+        }
+        thumbnail
+    }
+
+    /// The thumbnail as RGBA pixels of the given colour, which are transparent where the graph
+    /// does not cover them.
+    #[must_use]
+    pub fn to_rgba(&self, [r, g, b]: [u8; 3]) -> Vec<u8> {
+        self.pixels.iter().flat_map(|&a| [r, g, b, a]).collect()
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn plot(&mut self, x: f32, y: f32) {
+        let (x, y) = (x.max(0.0) as usize, y.max(0.0) as usize);
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = u8::MAX;
+        }
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn line(&mut self, (x0, y0): (f32, f32), (x1, y1): (f32, f32)) {
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let t = i as f32 / steps as f32;
+            self.plot(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn fill(&mut self, (x0, y0): (f32, f32), (x1, y1): (f32, f32)) {
+        for y in y0 as usize..=(y1 as usize) {
+            for x in x0 as usize..=(x1 as usize) {
+                self.plot(x as f32, y as f32);
+            }
+        }
+    }
+}
+
+/// Something to draw, in the units of the layout rather than pixels.
+enum Stroke {
+    Line((f32, f32), (f32, f32)),
+    Box((f32, f32), (f32, f32)),
+    Frame((f32, f32), (f32, f32)),
+}
+
+/// Positions for `n` ports spread evenly across `width`, starting from `x`.
+#[allow(clippy::cast_precision_loss)]
+fn spread(x: f32, width: f32, n: usize) -> impl Iterator<Item = f32> {
+    let step = width / n.max(1) as f32;
+    (0..n).map(move |i| x + step * (i as f32 + 0.5))
+}
+
+/// Draws `graph` with its top left corner at `(x, y)`, returning its width and height.
+#[allow(clippy::cast_precision_loss)]
+fn draw<T: Ctx>(graph: &MonoidalGraph<T>, x: f32, y: f32, strokes: &mut Vec<Stroke>) -> (f32, f32) {
+    let inputs = graph.free_inputs.len() + graph.bound_inputs.len();
+    let mut width = inputs.max(1) as f32;
+    let mut wires: Vec<f32> = spread(x, inputs as f32, inputs).collect();
+    let mut v = y;
+
+    for slice in &graph.slices {
+        let top = v + GAP;
+        let mut h = x;
+        let mut height = NODE_HEIGHT;
+        let mut ports = Vec::new();
+        // The outputs of each op, with where they start, to extend to the bottom of the slice
+        let mut outputs = Vec::new();
+
+        for op in &slice.ops {
+            let (ni, no) = (op.number_of_inputs(), op.number_of_outputs());
+            let op_width;
+            let op_height;
+            match op {
+                MonoidalOp::Thunk { body, .. } => {
+                    let (w, body_height) = draw(body, h + PADDING, top + PADDING, strokes);
+                    op_width = w + 2.0 * PADDING;
+                    op_height = body_height + 2.0 * PADDING;
+                    strokes.push(Stroke::Frame((h, top), (h + op_width, top + op_height)));
+                }
+                MonoidalOp::Operation { .. } => {
+                    op_width = ni.max(no).max(1) as f32;
+                    op_height = NODE_HEIGHT;
+                    strokes.push(Stroke::Box(
+                        (h + 0.1, top),
+                        (h + op_width - 0.1, top + op_height),
+                    ));
+                }
+                MonoidalOp::Swap { out_to_in, .. } => {
+                    op_width = ni as f32;
+                    op_height = NODE_HEIGHT;
+                    let ins: Vec<f32> = spread(h, op_width, ni).collect();
+                    for (out, &i) in spread(h, op_width, no).zip(out_to_in) {
+                        strokes.push(Stroke::Line((ins[i], top), (out, top + op_height)));
+                    }
+                }
+                MonoidalOp::Copy { .. }
+                | MonoidalOp::Backlink { .. }
+                | MonoidalOp::Cup { .. }
+                | MonoidalOp::Cap { .. } => {
+                    op_width = ni.max(no).max(1) as f32;
+                    op_height = NODE_HEIGHT;
+                    // Drawn as their ports joined at the middle, which is all a thumbnail can show
+                    let middle = (h + op_width / 2.0, top + op_height / 2.0);
+                    for port in spread(h, op_width, ni) {
+                        strokes.push(Stroke::Line((port, top), middle));
+                    }
+                    for port in spread(h, op_width, no) {
+                        strokes.push(Stroke::Line(middle, (port, top + op_height)));
+                    }
+                }
+            }
+            ports.extend(spread(h, op_width, ni));
+            outputs.extend(spread(h, op_width, no).map(|port| (port, top + op_height)));
+            h += op_width;
+            height = height.max(op_height);
+        }
+
+        for (&from, &to) in wires.iter().zip(&ports) {
+            strokes.push(Stroke::Line((from, v), (to, top)));
+        }
+        for &(port, start) in &outputs {
+            if start < top + height {
+                strokes.push(Stroke::Line((port, start), (port, top + height)));
+            }
+        }
+        wires = outputs.into_iter().map(|(port, _)| port).collect();
+        width = width.max(h - x);
+        v = top + height;
+    }
+
+    let bottom = v + GAP;
+    for &wire in &wires {
+        strokes.push(Stroke::Line((wire, v), (wire, bottom)));
+    }
+    (width, bottom - y)
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+
+    use super::Thumbnail;
+    use crate::language::spartan::{Expr, Rule, SpartanParser};
+
+    #[test]
+    fn fits() -> Result<(), Box<dyn std::error::Error>> {
+        let mut pairs = SpartanParser::parse(
+            Rule::program,
+            "bind f = y z . bind a = plus(x, y) in bind b = plus(a, z) in b in \
+             bind c = app(f, x, x) in times(c, c)",
+        )?;
+        let graph = Expr::from_pest(&mut pairs)?.to_graph(false)?;
+        let thumbnail = Thumbnail::new(&graph, 32);
+        assert!(thumbnail.width <= 32 && thumbnail.height <= 32);
+        assert_eq!(thumbnail.pixels.len(), thumbnail.width * thumbnail.height);
+        assert!(thumbnail.pixels.iter().any(|&pixel| pixel > 0));
+        assert_eq!(
+            thumbnail.to_rgba([1, 2, 3]).len(),
+            4 * thumbnail.pixels.len()
+        );
+        Ok(())
+    }
+}
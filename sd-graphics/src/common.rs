@@ -1,12 +1,19 @@
-use egui::{Pos2, Rounding};
+use std::fmt::Display;
+
+use egui::{ecolor::Hsva, Color32, Pos2, Rounding};
 use flo_curves::Coord2;
-use sd_core::hypergraph::{
-    self,
-    adapter::{collapse::CollapseOperation, cut::CutOperation},
-    generic::{Ctx, Node, Operation},
-    subgraph::SubOperation,
-    traits::Graph,
-    Weight,
+use sd_core::{
+    common::Query,
+    hypergraph::{
+        self,
+        adapter::{collapse::CollapseOperation, cut::CutOperation, fold::FoldOperation},
+        generic::{self, Ctx, Edge, Node, Operation},
+        subgraph::SubOperation,
+        traits::{Graph, WithWeight},
+        utils::derived_thunk_name,
+        Weight,
+    },
+    language::OpDoc,
 };
 
 pub const RADIUS_ARG: f32 = 0.05;
@@ -14,6 +21,12 @@ pub const RADIUS_COPY: f32 = 0.1;
 pub const TOLERANCE: f32 = 0.3;
 pub const TEXT_SIZE: f32 = 0.28;
 pub const RADIUS_OPERATION: f32 = 0.2;
+/// How far the tick between the captured values and the arguments of a thunk reaches into it.
+pub const SEPARATOR_HEIGHT: f32 = 0.25;
+/// The length of the dashes, and the gaps between them, of wires which only order effects.
+pub const ORDERING_DASH: f32 = 0.08;
+/// How far below the bottom of the diagram the names of its outputs are drawn.
+pub const OUTPUT_LABEL_OFFSET: f32 = 0.3;
 
 // pub trait ContainsPoint {
 //     // Check if a point lies on a line or curve (with the given tolerance).
@@ -38,6 +51,82 @@ pub const RADIUS_OPERATION: f32 = 0.2;
 //     }
 // }
 
+/// Text sizes (in points) at which operation labels are progressively revealed.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LabelVisibility {
+    /// Labels are hidden when they would be drawn at or below this size.
+    pub min_text_size: f32,
+    /// Operations show the types of their outputs from this size.
+    pub type_text_size: f32,
+    /// Labels are abbreviated when they would be drawn below this size.
+    pub full_text_size: f32,
+}
+
+impl Default for LabelVisibility {
+    fn default() -> Self {
+        Self {
+            min_text_size: 5.0,
+            type_text_size: 7.5,
+            full_text_size: 10.0,
+        }
+    }
+}
+
+impl LabelVisibility {
+    /// The text of `label` to show at the given text size, if any.
+    #[must_use]
+    pub fn reveal(self, label: &str, text_size: f32) -> Option<&str> {
+        if text_size <= self.min_text_size {
+            None
+        } else if text_size < self.full_text_size {
+            // Drop namespaces such as MLIR dialect prefixes
+            Some(
+                label
+                    .rsplit('.')
+                    .next()
+                    .filter(|short| !short.is_empty())
+                    .unwrap_or(label),
+            )
+        } else {
+            Some(label)
+        }
+    }
+
+    /// Whether operations show the types of their outputs at the given text size.
+    #[must_use]
+    pub fn reveals_types(self, text_size: f32) -> bool {
+        text_size > self.min_text_size && text_size >= self.type_text_size
+    }
+}
+
+/// A coloured tag drawn on the operations and thunks which match a query.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Badge {
+    pub query: Query,
+    pub text: String,
+    pub color: Color32,
+}
+
+/// The colour of wires carrying values of the type `name`, picked by hashing the name so that
+/// each type keeps its colour across programs and sessions.
+#[must_use]
+pub fn type_color(name: &str, dark_mode: bool) -> Color32 {
+    // FNV-1a, as the standard library's hashers are not stable across releases
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let hue = (hash % 360) as f32 / 360.0;
+    let value = if dark_mode { 0.9 } else { 0.65 };
+    Hsva::new(hue, 0.7, value, 1.0).into()
+}
+
+/// The colour of `heat` on a scale from blue for 0 through green and yellow to red for 1.
+#[must_use]
+pub fn heat_color(heat: f32) -> Color32 {
+    let hue = (1.0 - heat.clamp(0.0, 1.0)) * 2.0 / 3.0;
+    Hsva::new(hue, 0.8, 0.9, 1.0).into()
+}
+
 pub(crate) fn to_coord2(pos2: Pos2) -> Coord2 {
     Coord2(f64::from(pos2.x), f64::from(pos2.y))
 }
@@ -74,11 +163,24 @@ impl ShapeKind {
 
 pub trait Shapeable {
     fn to_shape(&self) -> ShapeKind;
+
+    /// A label to show when the weight of the operation displays as nothing.
+    fn fallback_label(&self) -> Option<String> {
+        None
+    }
+
+    /// Documentation to show when hovering over the operation.
+    fn doc(&self) -> Option<OpDoc> {
+        None
+    }
 }
 
 impl<G: Graph> Shapeable for CollapseOperation<G>
 where
-    Operation<G::Ctx>: Shapeable,
+    Operation<G::Ctx>: Shapeable + WithWeight,
+    Edge<G::Ctx>: WithWeight,
+    generic::Weight<Edge<G::Ctx>>: Display,
+    generic::Weight<Operation<G::Ctx>>: Display,
 {
     fn to_shape(&self) -> ShapeKind {
         match self.inner() {
@@ -86,6 +188,21 @@ where
             Node::Thunk(_) => ShapeKind::Square,
         }
     }
+
+    /// Collapsed thunks without names are named after their contents.
+    fn fallback_label(&self) -> Option<String> {
+        match self.inner() {
+            Node::Operation(op) => op.fallback_label(),
+            Node::Thunk(thunk) => derived_thunk_name::<G::Ctx>(thunk),
+        }
+    }
+
+    fn doc(&self) -> Option<OpDoc> {
+        match self.inner() {
+            Node::Operation(op) => op.doc(),
+            Node::Thunk(_) => None,
+        }
+    }
 }
 
 impl<G: Graph> Shapeable for CutOperation<G>
@@ -99,6 +216,47 @@ where
             Self::Store { .. } => ShapeKind::BulletDown,
         }
     }
+
+    fn fallback_label(&self) -> Option<String> {
+        match self {
+            Self::Inner { op, .. } => op.fallback_label(),
+            Self::Reuse { .. } | Self::Store { .. } => None,
+        }
+    }
+
+    /// Reuses of folded constants are documented as the constant.
+    fn doc(&self) -> Option<OpDoc> {
+        match self {
+            Self::Inner { op, .. } => op.doc(),
+            Self::Reuse { .. } => self.folded()?.doc(),
+            Self::Store { .. } => None,
+        }
+    }
+}
+
+impl<G: Graph> Shapeable for FoldOperation<G>
+where
+    Operation<G::Ctx>: Shapeable,
+{
+    fn to_shape(&self) -> ShapeKind {
+        self.inner()[0].to_shape()
+    }
+
+    /// Folded chains are labelled by the weights of their operations, so only operations on their
+    /// own fall back.
+    fn fallback_label(&self) -> Option<String> {
+        match self {
+            Self::Inner { op, .. } => op.fallback_label(),
+            Self::Chain { .. } => None,
+        }
+    }
+
+    fn doc(&self) -> Option<OpDoc> {
+        match self {
+            Self::Inner { op, .. } => op.doc(),
+            Self::Chain { .. } => None,
+        }
+    }
 }
 
 impl<T: Ctx> Shapeable for SubOperation<T>
@@ -108,10 +266,22 @@ where
     fn to_shape(&self) -> ShapeKind {
         self.inner().to_shape()
     }
+
+    fn fallback_label(&self) -> Option<String> {
+        self.inner().fallback_label()
+    }
+
+    fn doc(&self) -> Option<OpDoc> {
+        self.inner().doc()
+    }
 }
 
 impl<W: Weight> Shapeable for hypergraph::Operation<W> {
     fn to_shape(&self) -> ShapeKind {
         ShapeKind::Squircle
     }
+
+    fn doc(&self) -> Option<OpDoc> {
+        W::operation_doc(&self.weight())
+    }
 }
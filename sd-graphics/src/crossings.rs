@@ -0,0 +1,85 @@
+//! Counting wire crossings, to show where a layout breaks down.
+
+use std::cmp::Reverse;
+
+use egui::{Pos2, Rect};
+use itertools::Itertools;
+use sd_core::hypergraph::generic::Ctx;
+
+use crate::layout::{Layout, Node, NodeOffset};
+
+/// A slice of a layout in which wires cross.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hotspot {
+    /// The region covered by the swaps of the slice, in layout coordinates.
+    pub rect: Rect,
+    pub crossings: usize,
+}
+
+/// The slices of `layout`, including those inside thunks, where wires cross, worst first.
+#[must_use]
+pub fn hotspots<T: Ctx>(layout: &Layout<T>) -> Vec<Hotspot> {
+    fn helper<T: Ctx>(layout: &Layout<T>, hotspots: &mut Vec<Hotspot>) {
+        for (slice, (before, after)) in layout.nodes.iter().zip(layout.wires.iter().tuple_windows())
+        {
+            let mut rect = Rect::NOTHING;
+            let mut crossings = 0;
+            for NodeOffset {
+                node,
+                inputs,
+                outputs,
+            } in slice
+            {
+                match node {
+                    Node::Swap {
+                        v_top,
+                        v_bot,
+                        out_to_in,
+                        ..
+                    } => {
+                        crossings += inversions(out_to_in);
+                        for wire in &before[inputs.clone()] {
+                            rect.extend_with(Pos2::new(wire.h, *v_top));
+                        }
+                        for wire in &after[outputs.clone()] {
+                            rect.extend_with(Pos2::new(wire.h, *v_bot));
+                        }
+                    }
+                    Node::Thunk { layout, .. } => helper(layout, hotspots),
+                    Node::Atom { .. } => {}
+                }
+            }
+            if crossings > 0 {
+                hotspots.push(Hotspot { rect, crossings });
+            }
+        }
+    }
+
+    let mut hotspots = Vec::new();
+    helper(layout, &mut hotspots);
+    hotspots.sort_by_key(|hotspot| Reverse(hotspot.crossings));
+    hotspots
+}
+
+/// The number of pairs of wires which cross in a swap, where output `i` comes from input
+/// `out_to_in[i]`.
+fn inversions(out_to_in: &[usize]) -> usize {
+    out_to_in
+        .iter()
+        .tuple_combinations()
+        .filter(|(a, b)| a > b)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inversions;
+
+    #[test]
+    fn count_inversions() {
+        assert_eq!(inversions(&[0, 1, 2]), 0);
+        assert_eq!(inversions(&[1, 0]), 1);
+        assert_eq!(inversions(&[2, 0, 1]), 2);
+        assert_eq!(inversions(&[3, 2, 1, 0]), 6);
+    }
+}
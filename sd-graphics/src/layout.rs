@@ -5,7 +5,7 @@ use std::{
 
 use derivative::Derivative;
 use egui::Vec2;
-use good_lp::{variable, Expression, ResolutionError, Solution, Variable};
+use good_lp::{variable, Expression, Solution, Variable};
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
 use sd_core::{
@@ -14,7 +14,7 @@ use sd_core::{
         generic::{Ctx, Weight},
         traits::{Graph, NodeLike, WithWeight},
     },
-    lp::{LpProblem, Solver},
+    lp::{LpError, LpProblem, Solver},
     monoidal::graph::{MonoidalGraph, MonoidalOp},
 };
 #[cfg(test)]
@@ -31,7 +31,7 @@ use crate::{
 #[derive(Clone, Debug, Error)]
 pub enum LayoutError {
     #[error("An error occurred when solving the problem: {0}")]
-    ResolutionError(#[from] ResolutionError),
+    LpError(#[from] LpError),
 }
 
 #[derive(Clone, Derivative)]
@@ -363,6 +363,25 @@ impl<T: Ctx> Layout<T> {
     }
 }
 
+/// How far an atom for `op`, which is neither a thunk nor a swap, extends either side of its
+/// centre, and what kind of atom it is.
+fn atom<T: Ctx>(op: &MonoidalOp<T>) -> (f32, AtomType<T>)
+where
+    Weight<T::Operation>: Display,
+{
+    match op {
+        MonoidalOp::Cup { .. } => (0.0, AtomType::Cup),
+        MonoidalOp::Cap { .. } => (0.0, AtomType::Cap),
+        MonoidalOp::Operation { addr } => (
+            (addr.weight().to_string().chars().count().saturating_sub(1) as f32 / 2.0)
+                * RADIUS_OPERATION,
+            AtomType::Op(addr.clone()),
+        ),
+        MonoidalOp::Copy { copies, .. } if *copies != 1 => (0.0, AtomType::Copy),
+        _ => (0.0, AtomType::Id),
+    }
+}
+
 #[allow(clippy::too_many_lines)]
 fn h_layout_internal<T: Ctx>(
     graph: &MonoidalGraph<T>,
@@ -457,39 +476,15 @@ where
                         v_bot: (),
                         out_to_in: out_to_in.clone(),
                     },
-                    MonoidalOp::Cup { .. } => Node::Atom {
-                        h_pos: problem.add_variable(variable().min(0.0)),
-                        v_pos: (),
-                        extra_size: 0.0,
-                        atype: AtomType::Cup,
-                    },
-                    MonoidalOp::Cap { .. } => Node::Atom {
-                        h_pos: problem.add_variable(variable().min(0.0)),
-                        v_pos: (),
-                        extra_size: 0.0,
-                        atype: AtomType::Cap,
-                    },
-                    MonoidalOp::Operation { addr } => Node::Atom {
-                        h_pos: problem.add_variable(variable().min(0.0)),
-                        v_pos: (),
-                        extra_size: (addr.weight().to_string().chars().count().saturating_sub(1)
-                            as f32
-                            / 2.0)
-                            * RADIUS_OPERATION,
-                        atype: AtomType::Op(addr.clone()),
-                    },
-                    MonoidalOp::Copy { copies, .. } if *copies != 1 => Node::Atom {
-                        h_pos: problem.add_variable(variable().min(0.0)),
-                        v_pos: (),
-                        extra_size: 0.0,
-                        atype: AtomType::Copy,
-                    },
-                    _ => Node::Atom {
-                        h_pos: problem.add_variable(variable().min(0.0)),
-                        v_pos: (),
-                        extra_size: 0.0,
-                        atype: AtomType::Id,
-                    },
+                    _ => {
+                        let (extra_size, atype) = atom(op);
+                        Node::Atom {
+                            h_pos: problem.add_variable(variable().min(0.0)),
+                            v_pos: (),
+                            extra_size,
+                            atype,
+                        }
+                    }
                 };
                 let node_offset = NodeOffset {
                     node,
@@ -888,19 +883,53 @@ pub fn layout<T: Ctx>(graph: &MonoidalGraph<T>, solver: Solver) -> Result<Layout
 where
     Weight<T::Operation>: Display,
 {
-    let mut problem = LpProblem::default();
+    layout_internal(graph, solver, false)
+}
+
+/// The solver used by [`solve_deterministic`]. It is written in Rust, so it is available and
+/// gives the same solutions on every platform.
+pub const DETERMINISTIC_SOLVER: Solver = Solver::Microlp;
+
+/// How many steps each unit is divided into when [`solve_deterministic`] snaps positions.
+const GRID: f64 = 1000.0;
+
+/// Lays out `graph` identically on every platform, whichever solvers are compiled in, so that
+/// layouts can be compared against ones stored earlier.
+///
+/// Every problem is solved by [`DETERMINISTIC_SOLVER`], ignoring any [`sd_core::lp::Budget`] so
+/// that running out of time cannot change the result. Positions are snapped to a grid, so that
+/// ties between equally good positions are settled the same way whatever rounding the solver did
+/// on the way.
+pub fn solve_deterministic<T: Ctx>(graph: &MonoidalGraph<T>) -> Result<Layout<T>, LayoutError>
+where
+    Weight<T::Operation>: Display,
+{
+    layout_internal(graph, DETERMINISTIC_SOLVER, true)
+}
 
+fn layout_internal<T: Ctx>(
+    graph: &MonoidalGraph<T>,
+    solver: Solver,
+    deterministic: bool,
+) -> Result<Layout<T>, LayoutError>
+where
+    Weight<T::Operation>: Display,
+{
     let now = Instant::now();
     info!("Calculating horizontal layout");
-    let layout = h_layout_internal(graph, &mut problem);
-    problem.add_objective(layout.h_max);
-    let h_solution = problem.minimise(solver)?;
+    let (layout, h_solution) = minimise_with_fallback(solver, deterministic, |problem| {
+        let layout = h_layout_internal(graph, problem);
+        problem.add_objective(layout.h_max);
+        layout
+    })?;
 
-    problem = LpProblem::default();
     info!("Calculating vertical layout");
-    let v_layout = v_layout_internal(&mut problem, HLayout::from_solution_h(layout, &*h_solution));
-    problem.add_objective(v_layout.v_max);
-    let v_solution = problem.minimise(solver)?;
+    let h_layout = HLayout::from_solution_h(layout, &*h_solution);
+    let (v_layout, v_solution) = minimise_with_fallback(solver, deterministic, |problem| {
+        let v_layout = v_layout_internal(problem, h_layout.clone());
+        problem.add_objective(v_layout.v_max);
+        v_layout
+    })?;
 
     let layout_complete = Layout::from_solution_v(v_layout, &*v_solution);
 
@@ -915,11 +944,235 @@ where
     Ok(layout_complete)
 }
 
+/// Lays out `graph` without solving anything, as a draft to show while [`layout`] runs.
+///
+/// Each slice is given a rank of its own, and its nodes are placed side by side from the left,
+/// leaving wires to bend between them however far they need to.
+#[must_use]
+pub fn draft_layout<T: Ctx>(graph: &MonoidalGraph<T>) -> Layout<T>
+where
+    Weight<T::Operation>: Display,
+{
+    draft_layout_internal(graph, 0.0, 0.0)
+}
+
+/// The height of the gap between slices in a draft layout.
+const DRAFT_WIRE_LENGTH: f32 = 1.0;
+
+#[allow(clippy::cast_precision_loss)]
+fn draft_layout_internal<T: Ctx>(graph: &MonoidalGraph<T>, h_min: f32, v_min: f32) -> Layout<T>
+where
+    Weight<T::Operation>: Display,
+{
+    // Positions for `n` wires a unit apart, centred on `center`
+    fn spread(center: f32, n: usize) -> impl Iterator<Item = f32> {
+        let start = center - n.saturating_sub(1) as f32 / 2.0;
+        (0..n).map(move |i| start + i as f32)
+    }
+
+    // Positions for `n` ports, taken from `hs` as far as they go
+    fn ports<'a>(hs: impl Iterator<Item = &'a f32>, n: usize, center: f32) -> Vec<f32> {
+        hs.copied()
+            .chain(std::iter::repeat(center))
+            .take(n)
+            .collect()
+    }
+
+    let mut h_max = h_min + 1.0;
+    let mut v = v_min;
+    let mut row: Vec<(f32, T::Edge)> = graph
+        .free_inputs
+        .iter()
+        .chain(&graph.bound_inputs)
+        .enumerate()
+        .map(|(i, addr)| (h_min + 0.5 + i as f32, addr.clone()))
+        .collect();
+    let mut nodes = Vec::default();
+    let mut wires = Vec::default();
+
+    for slice in &graph.slices {
+        let top = v + DRAFT_WIRE_LENGTH;
+        if let Some((h, _)) = row.last() {
+            h_max = h_max.max(h + 0.5);
+        }
+        wires.push(
+            row.into_iter()
+                .map(|(h, addr)| WireData {
+                    h,
+                    v_min: v,
+                    v_max: top,
+                    addr,
+                })
+                .collect(),
+        );
+
+        let mut x = h_min + 0.5;
+        let mut height: f32 = 1.0;
+        let mut outputs = Vec::new();
+        let mut input_offset = 0;
+        let mut output_offset = 0;
+        let mut ns = slice
+            .ops
+            .iter()
+            .map(|op| {
+                let (ni, no) = (op.number_of_inputs(), op.number_of_outputs());
+                let node = match op {
+                    MonoidalOp::Thunk { body, addr } => {
+                        let layout = draft_layout_internal(body, x, top + 0.5);
+                        let center = (layout.h_min + layout.h_max) / 2.0;
+                        height = height.max(layout.height() + 1.0);
+                        x = layout.h_max;
+                        let inputs = ports(layout.inputs(), ni, center);
+                        let thunk_outputs = ports(layout.outputs(), no, center);
+                        outputs.extend(&thunk_outputs);
+                        Node::Thunk {
+                            addr: addr.clone(),
+                            layout,
+                            inputs,
+                            outputs: thunk_outputs,
+                        }
+                    }
+                    MonoidalOp::Swap { out_to_in, .. } => {
+                        let width = no.saturating_sub(1) as f32;
+                        let center = x + width / 2.0;
+                        outputs.extend(spread(center, no));
+                        x += width;
+                        Node::Swap {
+                            h_pos: center,
+                            v_top: top,
+                            v_bot: top,
+                            out_to_in: out_to_in.clone(),
+                        }
+                    }
+                    _ => {
+                        let (extra_size, atype) = atom(op);
+                        let width = (2.0 * extra_size).max(no.saturating_sub(1) as f32);
+                        let center = x + width / 2.0;
+                        outputs.extend(spread(center, no));
+                        x += width;
+                        Node::Atom {
+                            h_pos: center,
+                            v_pos: top,
+                            extra_size,
+                            atype,
+                        }
+                    }
+                };
+                x += 1.0;
+                let node_offset = NodeOffset {
+                    node,
+                    inputs: input_offset..input_offset + ni,
+                    outputs: output_offset..output_offset + no,
+                };
+                input_offset += ni;
+                output_offset += no;
+                node_offset
+            })
+            .collect_vec();
+        h_max = h_max.max(x - 0.5);
+
+        // Atoms and swaps fill the height of the slice, which is only known once its thunks are
+        let bottom = top + height;
+        for n in &mut ns {
+            match &mut n.node {
+                Node::Atom { v_pos, .. } => *v_pos = top + height / 2.0,
+                Node::Swap { v_bot, .. } => *v_bot = bottom,
+                Node::Thunk { .. } => {}
+            }
+        }
+        nodes.push(ns);
+
+        row = outputs
+            .into_iter()
+            .zip(slice.output_links().map(|link| link.0))
+            .collect();
+        v = bottom;
+    }
+
+    if let Some((h, _)) = row.last() {
+        h_max = h_max.max(h + 0.5);
+    }
+    let v_max = v + DRAFT_WIRE_LENGTH;
+    wires.push(
+        row.into_iter()
+            .map(|(h, addr)| WireData {
+                h,
+                v_min: v,
+                v_max,
+                addr,
+            })
+            .collect(),
+    );
+
+    LayoutInternal {
+        h_min,
+        h_max,
+        v_min,
+        v_max,
+        nodes,
+        wires,
+    }
+}
+
+/// Builds and solves a problem, rebuilding it for the fallback solver if the first
+/// attempt runs out of time or fails. Deterministic solves take as long as they need, never fall
+/// back, and snap their solutions to the grid.
+fn minimise_with_fallback<L>(
+    solver: Solver,
+    deterministic: bool,
+    build: impl Fn(&mut LpProblem) -> L,
+) -> Result<(L, Box<dyn Solution>), LpError> {
+    let mut problem = LpProblem::default();
+    let layout = build(&mut problem);
+    if deterministic {
+        let solution = problem.minimise_ignoring_budget(solver)?;
+        return Ok((layout, Box::new(Snapped(solution))));
+    }
+    match problem.minimise(solver) {
+        Err(LpError::BudgetExceeded | LpError::ResolutionError(_) | LpError::SolverPanicked) => {
+            let fallback = solver.fallback();
+            info!("Falling back to {fallback:?}");
+            let mut problem = LpProblem::default();
+            let layout = build(&mut problem);
+            Ok((layout, problem.minimise_ignoring_budget(fallback)?))
+        }
+        result => Ok((layout, result?)),
+    }
+}
+
+/// A solution with every value snapped to the nearest point of the grid.
+struct Snapped(Box<dyn Solution>);
+
+impl Solution for Snapped {
+    fn value(&self, variable: Variable) -> f64 {
+        // Adding zero turns negative zero into zero, which is printed differently
+        (self.0.value(variable) * GRID).round() / GRID + 0.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use sd_core::{examples, lp::Solver};
+    use std::{ffi::OsStr, path::Path};
+
+    use dir_test::{dir_test, Fixture};
+    use from_pest::FromPest;
+    use pest::Parser;
+    use sd_core::{
+        common::InOut,
+        examples,
+        language::{
+            chil,
+            mlir::{
+                self,
+                internal::{MlirParser, Rule as MlirRule, TopLevelItem},
+            },
+            spartan::{self, Rule as SpartanRule, SpartanParser},
+        },
+        lp::Solver,
+        monoidal::{graph::MonoidalGraph, wired_graph::from_graph},
+    };
 
-    use super::layout;
+    use super::{draft_layout, layout, solve_deterministic, DETERMINISTIC_SOLVER};
 
     #[test]
     fn int() {
@@ -941,4 +1194,93 @@ mod tests {
             insta::assert_ron_snapshot!(layout(&examples::thunk(), Solver::default()).expect("Layout failed"));
         });
     }
+
+    #[test]
+    fn draft() {
+        let graph = examples::thunk();
+        let draft = draft_layout(&graph);
+        assert_eq!(draft.nodes.len(), graph.slices.len());
+        assert_eq!(draft.wires.len(), graph.slices.len() + 1);
+        for (slice, wires) in graph.slices.iter().zip(&draft.wires[1..]) {
+            assert_eq!(wires.len(), slice.number_of_outputs());
+        }
+        assert!(draft.width() > 0.0 && draft.height() > 0.0);
+    }
+
+    /// Reads the example at `path`, returning its language, its name and its source.
+    fn read(path: &str) -> (&str, &str, String) {
+        let source = std::fs::read_to_string(path).expect("Failed to read example");
+        let path = Path::new(path);
+        let name = path.file_stem().and_then(OsStr::to_str).unwrap();
+        let lang = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+        (lang, name, source)
+    }
+
+    /// Compares the deterministic layout of a compiled example against the stored one, or the
+    /// reason it does not compile against the stored reason.
+    macro_rules! assert_golden {
+        ($name:expr, $graph:expr) => {
+            match $graph {
+                Ok(graph) => {
+                    let graph = MonoidalGraph::from(&from_graph(&graph, DETERMINISTIC_SOLVER));
+                    let layout = solve_deterministic(&graph).expect("Layout failed");
+                    insta::with_settings!({sort_maps => true}, {
+                        insta::assert_ron_snapshot!($name, layout);
+                    });
+                }
+                Err(err) => insta::assert_snapshot!($name, err),
+            }
+        };
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    #[dir_test(dir: "$CARGO_MANIFEST_DIR/../examples", glob: "**/*", loader: read, postfix: "golden_layout")]
+    // Until the golden snapshots are generated with `cargo insta test --accept -- --ignored` and
+    // committed, there is nothing to compare against
+    #[dir_test_attr(
+        #[ignore = "golden layout snapshots have not been generated yet"]
+    )]
+    fn golden_layouts(fixture: Fixture<(&str, &str, String)>) {
+        let (lang, name, source) = fixture.content();
+        let name = format!("golden_{name}.{lang}");
+        match *lang {
+            "sd" => assert_golden!(
+                name,
+                SpartanParser::parse(SpartanRule::program, source)
+                    .map_err(|err| err.to_string())
+                    .and_then(|mut pairs| {
+                        spartan::Expr::from_pest(&mut pairs).map_err(|err| err.to_string())
+                    })
+                    .and_then(|expr| expr.to_graph(false).map_err(|err| err.to_string()))
+            ),
+            "chil" => assert_golden!(
+                name,
+                chil::parser::parse(source)
+                    .map_err(|err| err.to_string())
+                    .and_then(|expr| expr.to_graph(false).map_err(|err| err.to_string()))
+            ),
+            "mlir" => assert_golden!(
+                name,
+                MlirParser::parse(MlirRule::toplevel, source)
+                    .map_err(|err| err.to_string())
+                    .and_then(|mut pairs| {
+                        Vec::<TopLevelItem>::from_pest(&mut pairs).map_err(|err| err.to_string())
+                    })
+                    .and_then(|items| {
+                        let ops: Vec<mlir::internal::Operation> = items
+                            .into_iter()
+                            .filter_map(|item| match item {
+                                TopLevelItem::Operation(op) => Some(op),
+                                TopLevelItem::Other(_) => None,
+                            })
+                            .collect();
+                        mlir::Expr::from(ops)
+                            .to_graph(false)
+                            .map_err(|err| err.to_string())
+                    })
+            ),
+            // Other files, such as notes on the examples, have no layout
+            _ => {}
+        }
+    }
 }
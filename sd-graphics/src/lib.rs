@@ -1,7 +1,13 @@
 pub mod common;
+pub mod crossings;
 pub mod intervals;
 pub mod layout;
+pub mod png;
 pub mod render;
 pub mod renderable;
 pub mod shape;
+pub mod spatial_index;
 pub mod svg;
+pub(crate) mod text;
+pub mod theme;
+pub mod tikz;
@@ -0,0 +1,284 @@
+//! Exporting diagrams as png images, for documents and tools which cannot show svg.
+//!
+//! The image uses the same scale and default colours as svg exports, on a white background, with
+//! labels set in the monospace font bundled with egui.
+
+use std::io;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use egui::{Color32, FontDefinitions, Pos2, Rect, Rounding};
+use sd_core::hypergraph::generic::Ctx;
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+use crate::{
+    common::SEPARATOR_HEIGHT,
+    shape::{Shape, Shapes},
+};
+
+/// The size of labels, in pixels, as in svg exports.
+const FONT_SIZE: f32 = 16.0;
+
+const GRAY: Color32 = Color32::from_rgb(128, 128, 128);
+const LIGHT_GRAY: Color32 = Color32::from_rgb(211, 211, 211);
+
+/// Where a line of text is placed vertically relative to the point it is drawn at.
+#[derive(Clone, Copy)]
+enum Baseline {
+    Middle,
+    Hanging,
+}
+
+/// The image being drawn, with the font its labels are set in.
+struct Canvas<'a> {
+    pixmap: Pixmap,
+    font: FontRef<'a>,
+}
+
+impl Canvas<'_> {
+    fn fill(&mut self, path: Option<tiny_skia::Path>, color: Color32) {
+        if let Some(path) = path {
+            self.pixmap.fill_path(
+                &path,
+                &paint(color),
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn stroke(&mut self, path: Option<tiny_skia::Path>, color: Color32) {
+        if let Some(path) = path {
+            self.pixmap.stroke_path(
+                &path,
+                &paint(color),
+                &Stroke::default(),
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    /// Draws `text` centred horizontally on `pos`.
+    fn text(&mut self, text: &str, pos: Pos2, baseline: Baseline, color: Color32) {
+        let scale = PxScale::from(FONT_SIZE);
+        let font = &self.font;
+        let scaled = font.as_scaled(scale);
+        let width: f32 = text
+            .chars()
+            .map(|c| scaled.h_advance(font.glyph_id(c)))
+            .sum();
+        let y = match baseline {
+            Baseline::Middle => pos.y + (scaled.ascent() + scaled.descent()) / 2.0,
+            Baseline::Hanging => pos.y + scaled.ascent(),
+        };
+        let mut x = pos.x - width / 2.0;
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            let glyph = id.with_scale_and_position(scale, point(x, y));
+            x += scaled.h_advance(id);
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|dx, dy, coverage| {
+                let pixel = tiny_skia::Rect::from_xywh(
+                    bounds.min.x + dx as f32,
+                    bounds.min.y + dy as f32,
+                    1.0,
+                    1.0,
+                );
+                if let Some(pixel) = pixel {
+                    let paint = paint(color.gamma_multiply(coverage.clamp(0.0, 1.0)));
+                    self.pixmap
+                        .fill_rect(pixel, &paint, Transform::identity(), None);
+                }
+            });
+        }
+    }
+}
+
+fn paint(color: Color32) -> Paint<'static> {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+/// The outline of `rect` with its corners rounded by `rounding`.
+fn rounded_rect(rect: Rect, rounding: Rounding) -> Option<tiny_skia::Path> {
+    let (left, top, right, bottom) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+    let mut path = PathBuilder::new();
+    path.move_to(left + rounding.nw, top);
+    path.line_to(right - rounding.ne, top);
+    path.quad_to(right, top, right, top + rounding.ne);
+    path.line_to(right, bottom - rounding.se);
+    path.quad_to(right, bottom, right - rounding.se, bottom);
+    path.line_to(left + rounding.sw, bottom);
+    path.quad_to(left, bottom, left, bottom - rounding.sw);
+    path.line_to(left, top + rounding.nw);
+    path.quad_to(left, top, left + rounding.nw, top);
+    path.close();
+    path.finish()
+}
+
+fn line(start: Pos2, end: Pos2) -> Option<tiny_skia::Path> {
+    let mut path = PathBuilder::new();
+    path.move_to(start.x, start.y);
+    path.line_to(end.x, end.y);
+    path.finish()
+}
+
+impl<T: Ctx> Shape<T> {
+    fn draw_png(&self, canvas: &mut Canvas) {
+        match self {
+            Self::Operation {
+                center,
+                radius,
+                label,
+                kind,
+                fill,
+                stroke,
+                ..
+            } => {
+                let x_size = radius * (label.chars().count().max(1) as f32 + 1.0);
+                let outline = rounded_rect(
+                    Rect::from_center_size(*center, egui::vec2(x_size, radius * 2.0)),
+                    kind.into_rounding(*radius),
+                );
+                let stroke = stroke.map_or(Color32::BLACK, |stroke| stroke.color);
+                canvas.fill(outline.clone(), fill.unwrap_or(Color32::WHITE));
+                canvas.stroke(outline, stroke);
+                canvas.text(label, *center, Baseline::Middle, stroke);
+            }
+            Self::CircleFilled { center, radius, .. } => {
+                canvas.fill(
+                    PathBuilder::from_circle(center.x, center.y, *radius),
+                    Color32::BLACK,
+                );
+            }
+            Self::Rectangle {
+                rect, separator, ..
+            } => {
+                canvas.stroke(rounded_rect(*rect, Rounding::ZERO), GRAY);
+                if let Some(x) = separator {
+                    canvas.stroke(
+                        line(
+                            Pos2::new(*x, rect.min.y),
+                            Pos2::new(*x, rect.min.y + SEPARATOR_HEIGHT * Shapes::<T>::SCALE),
+                        ),
+                        LIGHT_GRAY,
+                    );
+                }
+            }
+            Self::Line { start, end, .. } => canvas.stroke(line(*start, *end), Color32::BLACK),
+            Self::CubicBezier { points, .. } => {
+                let mut path = PathBuilder::new();
+                path.move_to(points[0].x, points[0].y);
+                path.cubic_to(
+                    points[1].x,
+                    points[1].y,
+                    points[2].x,
+                    points[2].y,
+                    points[3].x,
+                    points[3].y,
+                );
+                canvas.stroke(path.finish(), Color32::BLACK);
+            }
+            Self::OutputLabel { center, label, .. } => {
+                canvas.text(label, *center, Baseline::Hanging, GRAY);
+            }
+            // Arrows only show where thunks can be expanded on screen
+            Self::Arrow { .. } => {}
+        }
+    }
+}
+
+impl<T: Ctx> Shapes<T> {
+    /// Write the diagram to `writer` as a png image, at the scale of svg exports.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn write_png(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        let (width, height) = (self.size.x * Self::SCALE, self.size.y * Self::SCALE);
+        let mut pixmap = Pixmap::new(width.ceil().max(1.0) as u32, height.ceil().max(1.0) as u32)
+            .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("a {width}x{height} image is too big to draw"),
+            )
+        })?;
+        pixmap.fill(tiny_skia::Color::WHITE);
+
+        let fonts = FontDefinitions::default();
+        let font = FontRef::try_from_slice(&fonts.font_data["Hack"].font)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let mut canvas = Canvas { pixmap, font };
+
+        let scale = self.svg_scale();
+        for shape in &self.shapes {
+            let mut shape = shape.clone();
+            shape.apply_transform(&scale);
+            shape.draw_png(&mut canvas);
+        }
+
+        let pixmap = canvas.pixmap;
+        let mut encoder = png::Encoder::new(writer, pixmap.width(), pixmap.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut image = encoder.write_header()?;
+        let data: Vec<u8> = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+        image.write_image_data(&data)?;
+        image.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{vec2, Pos2};
+    use sd_core::examples::{DummyCtx, DummyEdge};
+
+    use crate::shape::{Shape, Shapes};
+
+    #[test]
+    fn draws_shapes() -> Result<(), Box<dyn std::error::Error>> {
+        // On the centres of pixels, so that they are drawn solid
+        let shapes: Shapes<DummyCtx> = Shapes::new(
+            vec![
+                Shape::Line {
+                    start: Pos2::new(1.01, 0.0),
+                    end: Pos2::new(1.01, 2.0),
+                    addr: DummyEdge,
+                    fanout: 1,
+                },
+                Shape::CircleFilled {
+                    center: Pos2::new(0.51, 1.51),
+                    radius: 0.1,
+                    addr: DummyEdge,
+                    coord: [0, 0],
+                    copy: true,
+                },
+            ],
+            vec2(2.0, 2.0),
+        );
+        let mut bytes = Vec::new();
+        shapes.write_png(&mut bytes)?;
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info()?;
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image)?;
+        assert_eq!((info.width, info.height), (100, 100));
+        let pixel = |x: usize, y: usize| &image[(y * 100 + x) * 4..][..4];
+        assert_eq!(pixel(10, 10), [255, 255, 255, 255]);
+        assert_eq!(pixel(50, 10), [0, 0, 0, 255]);
+        assert_eq!(pixel(25, 75), [0, 0, 0, 255]);
+        Ok(())
+    }
+}
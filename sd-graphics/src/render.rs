@@ -1,91 +1,726 @@
-use std::fmt::Display;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+};
 
-use egui::{emath::RectTransform, show_tooltip_at_pointer, Id, Pos2, Rect, Response};
+use egui::{
+    emath::RectTransform,
+    epaint::{CubicBezierShape, PathShape},
+    show_tooltip_at_pointer, vec2, Align2, Color32, FontId, Id, Pos2, Rect, Response, Rounding,
+    Stroke, Vec2,
+};
 use indexmap::IndexSet;
 use itertools::Itertools;
 use sd_core::{
     codeable::Codeable,
-    common::Matchable,
+    common::{Matchable, Query},
     hypergraph::{
-        generic::{Ctx, Edge, Operation, Thunk, Weight},
+        generic::{Ctx, Edge, Endpoint, Node, Operation, Thunk, Weight},
         subgraph::ExtensibleEdge,
-        traits::{Graph, NodeLike, WithType, WithWeight},
+        traits::{EdgeLike, Graph, NodeLike, WireType, WithType, WithWeight},
     },
     prettyprinter::PrettyPrint,
 };
 
 use crate::{
-    common::{Shapeable, RADIUS_ARG, RADIUS_COPY, RADIUS_OPERATION},
+    common::{
+        heat_color, type_color, Badge, LabelVisibility, Shapeable, OUTPUT_LABEL_OFFSET, RADIUS_ARG,
+        RADIUS_COPY, RADIUS_OPERATION, TOLERANCE,
+    },
     layout::{AtomType, Layout, NodeOffset},
     renderable::RenderableGraph,
-    shape::Shape,
+    shape::{Shape, Shapes},
+    text::label_shape,
+    theme::Theme,
 };
 
-#[allow(clippy::needless_collect)]
+/// The radius, in points on the screen, of the marks on the ports of the operation under the
+/// pointer.
+const PORT_RADIUS: f32 = 4.0;
+/// The size of the indices next to the marks on the ports.
+const PORT_TEXT_SIZE: f32 = 10.0;
+
+/// Renders the diagram, leaving out wires which only order effects unless `show_ordering`,
+/// colouring wires by the types of their values if `type_colors`, and drawing the wires into
+/// copies as one thick wire which thins as it splits towards the consumers if `bundle_copies`.
+/// The elements are coloured by the [`Theme`] of the context of `ui`.
+#[allow(clippy::too_many_arguments)]
 pub fn render<G>(
     graph: &mut G,
     ui: &egui::Ui,
-    shapes: &[Shape<G::Ctx>],
+    shapes: &mut Shapes<G::Ctx>,
     response: &Response,
     to_screen: RectTransform,
-    search: Option<&str>,
+    search: Option<&Query>,
+    labels: LabelVisibility,
+    show_ordering: bool,
+    type_colors: bool,
+    bundle_copies: bool,
 ) -> Vec<egui::Shape>
 where
     G: RenderableGraph,
     Edge<G::Ctx>: Codeable,
-    Operation<G::Ctx>: Codeable + Matchable,
+    Operation<G::Ctx>: Codeable + Matchable + Shapeable,
     Thunk<G::Ctx>: Matchable,
-    Weight<Edge<G::Ctx>>: WithType,
+    Weight<Edge<G::Ctx>>: Display + WithType,
+    Weight<Thunk<G::Ctx>>: Display,
 {
-    let viewport = *to_screen.from();
-
     let mut highlight_op = None;
+    let mut highlight_thunk = None;
     let mut highlight_edges = IndexSet::default();
 
-    let id = Id::new(graph.key());
-    let shapes_vec: Vec<_> = shapes
-        .iter()
-        .filter(|shape| viewport.intersects(shape.bounding_box()))
-        .map(|shape| {
-            let mut s = shape.clone();
-            s.apply_transform(&to_screen);
-            s.collect_highlights(
-                graph,
-                id,
-                ui,
-                response,
-                &to_screen,
-                search,
-                &mut highlight_op,
-                &mut highlight_edges,
-            );
-            s
+    // Only shapes near the pointer need precise hit-testing
+    let near_pointer = response
+        .hover_pos()
+        .map(|pos| {
+            shapes
+                .index
+                .query_point(to_screen.inverse().transform_pos(pos), TOLERANCE)
         })
-        .collect();
+        .unwrap_or_default();
 
-    // Show hover tooltips.
-    let labels = match highlight_op {
+    // Geometry only needs recomputing when the view moves, styles are updated in place
+    shapes
+        .frame
+        .update(&shapes.shapes, &shapes.index, to_screen);
+
+    let theme = Theme::get(ui.ctx());
+    let id = Id::new(graph.key());
+    for (i, shape) in shapes.frame.iter_mut() {
+        shape.collect_highlights(
+            graph,
+            id,
+            ui,
+            response,
+            &to_screen,
+            search,
+            &theme,
+            near_pointer.binary_search(&i).is_ok(),
+            &mut highlight_op,
+            &mut highlight_thunk,
+            &mut highlight_edges,
+        );
+    }
+
+    // Label the ports of the operation under the pointer
+    let ports = highlight_op.as_ref().and_then(|op| {
+        let rect = shapes
+            .frame
+            .iter_mut()
+            .find_map(|(_, shape)| match &*shape {
+                Shape::Operation { addr, .. } if addr == op => Some(shape.bounding_box()),
+                _ => None,
+            })?;
+        Some(Ports::new(op, rect, response.hover_pos()))
+    });
+
+    // Show hover tooltips, naming the thunk under the pointer when nothing inside it is hovered.
+    let mut doc = None;
+    let tooltips = match highlight_op {
         Some(op) => {
             highlight_edges.extend(op.inputs().chain(op.outputs()));
+            doc = op.doc();
             vec![op.code().to_pretty()]
         }
+        None if highlight_edges.is_empty() => highlight_thunk
+            .map(|thunk| thunk.weight().to_string())
+            .filter(|name| !name.is_empty())
+            .map(|name| format!("Thunk {name}"))
+            .into_iter()
+            .collect(),
         None => highlight_edges
             .iter()
-            .map(|edge| edge.code().to_pretty())
+            .map(|edge| match edge.weight().type_name() {
+                Some(name) => format!("{}\n\nType: {name}", edge.code().to_pretty()),
+                None => edge.code().to_pretty(),
+            })
             .collect(),
     };
-    for label in labels {
+    for tooltip in tooltips {
         show_tooltip_at_pointer(ui.ctx(), egui::Id::new("hover_tooltip"), |ui| {
-            ui.label(label)
+            ui.label(tooltip);
+            if let Some(doc) = &doc {
+                ui.separator();
+                ui.monospace(doc.signature.as_ref());
+                ui.label(doc.summary.as_ref());
+            }
+            if let Some(ports) = &ports {
+                ui.separator();
+                ports.ui(ui);
+            }
+        });
+    }
+
+    let mut result: Vec<_> = shapes
+        .frame
+        .iter_mut()
+        .map(|(_, shape)| {
+            shape.to_egui_shape(
+                ui,
+                &to_screen,
+                &highlight_edges,
+                labels,
+                &theme,
+                show_ordering,
+                type_colors,
+                bundle_copies,
+            )
+        })
+        .collect();
+    if let Some(ports) = &ports {
+        result.extend(ports.shapes(ui));
+    }
+    result
+}
+
+/// The inputs and outputs of the operation under the pointer, with the names of the variables on
+/// their wires, to debug operations given the wrong number of arguments.
+struct Ports {
+    /// Where each input is marked along the top of the operation, and its name.
+    inputs: Vec<(Pos2, String)>,
+    /// Where each output is marked along the bottom of the operation, and its name.
+    outputs: Vec<(Pos2, String)>,
+    /// The port nearest the pointer, as whether it is an output and its index.
+    hovered: Option<(bool, usize)>,
+}
+
+impl Ports {
+    /// The ports of `op`, drawn in `rect` on the screen, spread out in the order of its wires.
+    fn new<O>(op: &O, rect: Rect, pointer: Option<Pos2>) -> Self
+    where
+        O: NodeLike,
+        Weight<Edge<O::Ctx>>: Display + WithType,
+    {
+        let spread = |edges: Vec<Edge<O::Ctx>>, y: f32| {
+            let count = edges.len() as f32;
+            edges
+                .iter()
+                .enumerate()
+                .map(|(i, edge)| {
+                    let x = rect.left() + rect.width() * (i as f32 + 0.5) / count;
+                    (Pos2::new(x, y), port_name(edge))
+                })
+                .collect::<Vec<_>>()
+        };
+        let inputs = spread(op.inputs().collect(), rect.top());
+        let outputs = spread(op.outputs().collect(), rect.bottom());
+
+        // The upper half of the operation picks out an input and the lower half an output
+        let hovered = pointer.and_then(|pointer| {
+            let output = pointer.y > rect.center().y;
+            let ports = if output { &outputs } else { &inputs };
+            let index = ports
+                .iter()
+                .map(|(pos, _)| (pos.x - pointer.x).abs())
+                .position_min_by(f32::total_cmp)?;
+            Some((output, index))
         });
+
+        Self {
+            inputs,
+            outputs,
+            hovered,
+        }
+    }
+
+    /// Lists the ports in the tooltip, emphasising the one nearest the pointer.
+    fn ui(&self, ui: &mut egui::Ui) {
+        for (output, title, ports) in [
+            (false, "Inputs", &self.inputs),
+            (true, "Outputs", &self.outputs),
+        ] {
+            ui.strong(format!("{title} ({})", ports.len()));
+            for (i, (_, name)) in ports.iter().enumerate() {
+                let text = egui::RichText::new(format!("{i}: {name}")).monospace();
+                if self.hovered == Some((output, i)) {
+                    ui.label(
+                        text.strong()
+                            .background_color(ui.visuals().selection.bg_fill),
+                    );
+                } else {
+                    ui.label(text);
+                }
+            }
+        }
+    }
+
+    /// Marks each port with its index, circling the one nearest the pointer.
+    fn shapes(&self, ui: &egui::Ui) -> Vec<egui::Shape> {
+        let visuals = ui.visuals();
+        let mut result = Vec::new();
+        for (output, ports) in [(false, &self.inputs), (true, &self.outputs)] {
+            for (i, (pos, _)) in ports.iter().enumerate() {
+                let (radius, stroke) = if self.hovered == Some((output, i)) {
+                    (PORT_RADIUS * 1.5, visuals.selection.stroke)
+                } else {
+                    (PORT_RADIUS, visuals.widgets.noninteractive.fg_stroke)
+                };
+                result.push(egui::Shape::circle_filled(
+                    *pos,
+                    radius,
+                    visuals.extreme_bg_color,
+                ));
+                result.push(egui::Shape::circle_stroke(*pos, radius, stroke));
+
+                let (align, offset) = if output {
+                    (Align2::CENTER_TOP, vec2(0.0, radius))
+                } else {
+                    (Align2::CENTER_BOTTOM, vec2(0.0, -radius))
+                };
+                result.push(label_shape(
+                    ui,
+                    &i.to_string(),
+                    PORT_TEXT_SIZE,
+                    align,
+                    *pos + offset,
+                    stroke.color,
+                ));
+            }
+        }
+        result
+    }
+}
+
+/// The name of the variable carried by `edge`, with its type if known.
+fn port_name<E>(edge: &E) -> String
+where
+    E: WithWeight,
+    Weight<E>: Display + WithType,
+{
+    let weight = edge.weight();
+    let name = match weight.to_string() {
+        name if name.is_empty() => "_".to_owned(),
+        name => name,
+    };
+    match weight.type_name() {
+        Some(type_name) => format!("{name}: {type_name}"),
+        None => name,
+    }
+}
+
+/// Draws the badges on the top right corner of the visible operations and thunks they match,
+/// using the screen positions from the last call to [`render`].
+pub fn render_badges<T>(ui: &egui::Ui, shapes: &mut Shapes<T>, badges: &[Badge]) -> Vec<egui::Shape>
+where
+    T: Ctx,
+    T::Operation: Matchable,
+    T::Thunk: Matchable,
+{
+    const PADDING: f32 = 2.0;
+
+    let mut result = Vec::new();
+    if badges.is_empty() {
+        return result;
     }
+    for (_, shape) in shapes.frame.iter_mut() {
+        let mut anchor = match shape {
+            Shape::Operation { center, radius, .. } => {
+                *center + vec2(*radius, -*radius) * std::f32::consts::FRAC_1_SQRT_2
+            }
+            Shape::Rectangle { rect, .. } => rect.right_top(),
+            _ => continue,
+        };
+        for badge in badges.iter().filter(|badge| shape.is_match(&badge.query)) {
+            let galley = ui.fonts(|fonts| {
+                fonts.layout_no_wrap(
+                    badge.text.clone(),
+                    FontId::proportional(10.0),
+                    Color32::WHITE,
+                )
+            });
+            let rect = Align2::LEFT_CENTER
+                .anchor_size(anchor, galley.size())
+                .expand(PADDING);
+            result.push(egui::Shape::rect_filled(
+                rect,
+                Rounding::same(PADDING * 2.0),
+                badge.color,
+            ));
+            result.push(egui::Shape::galley(
+                rect.min + vec2(PADDING, PADDING),
+                galley,
+                Color32::WHITE,
+            ));
+            anchor.x = rect.max.x + PADDING;
+        }
+    }
+    result
+}
 
-    shapes_vec
-        .into_iter()
-        .map(|shape| shape.into_egui_shape(ui, &to_screen, &highlight_edges))
+/// Outlines each visible operation in the colour `color` gives it, if any, using the screen
+/// positions from the last call to [`render`].
+pub fn render_operation_colors<T: Ctx>(
+    shapes: &mut Shapes<T>,
+    color: impl Fn(&T::Operation) -> Option<Color32>,
+) -> Vec<egui::Shape> {
+    const WIDTH: f32 = 3.0;
+
+    shapes
+        .frame
+        .iter_mut()
+        .filter_map(|(_, shape)| match &*shape {
+            Shape::Operation {
+                addr, radius, kind, ..
+            } => {
+                let color = color(addr)?;
+                Some(egui::Shape::rect_stroke(
+                    shape.bounding_box().expand(WIDTH),
+                    kind.into_rounding(*radius + WIDTH),
+                    Stroke::new(WIDTH, color),
+                ))
+            }
+            _ => None,
+        })
         .collect()
 }
 
+/// Highlights the operations, thunks and wires of `path`, such as the paths between two nodes,
+/// and dims the rest of the diagram, using the screen positions from the last call to [`render`].
+pub fn render_path<T: Ctx>(
+    ui: &egui::Ui,
+    shapes: &mut Shapes<T>,
+    path: &IndexSet<Node<T>>,
+) -> Vec<egui::Shape> {
+    const WIDTH: f32 = 2.0;
+
+    let highlight = ui.visuals().selection.stroke;
+    let dim = ui.visuals().faint_bg_color.gamma_multiply(0.75);
+    // Wires are on the path when they join two of its nodes
+    let on_path = |edge: &T::Edge| {
+        edge.source()
+            .into_node()
+            .is_some_and(|node| path.contains(&node))
+            && edge
+                .targets()
+                .filter_map(Endpoint::into_node)
+                .any(|node| path.contains(&node))
+    };
+    let wire = |edge: &T::Edge| {
+        if on_path(edge) {
+            Stroke::new(WIDTH, highlight.color)
+        } else {
+            Stroke::new(WIDTH, dim)
+        }
+    };
+
+    shapes
+        .frame
+        .iter_mut()
+        .filter_map(|(_, shape)| match &*shape {
+            Shape::Line {
+                start, end, addr, ..
+            } => Some(egui::Shape::line_segment([*start, *end], wire(addr))),
+            Shape::CubicBezier { points, addr, .. } => Some(egui::Shape::CubicBezier(
+                CubicBezierShape::from_points_stroke(
+                    *points,
+                    false,
+                    Color32::TRANSPARENT,
+                    wire(addr),
+                ),
+            )),
+            Shape::CircleFilled {
+                center,
+                radius,
+                addr,
+                ..
+            } => Some(egui::Shape::circle_filled(
+                *center,
+                *radius,
+                wire(addr).color,
+            )),
+            Shape::Operation {
+                addr, radius, kind, ..
+            } => {
+                let rounding = kind.into_rounding(*radius);
+                Some(if path.contains(&Node::Operation(addr.clone())) {
+                    egui::Shape::rect_stroke(shape.bounding_box(), rounding, highlight)
+                } else {
+                    egui::Shape::rect_filled(shape.bounding_box(), rounding, dim)
+                })
+            }
+            Shape::Rectangle { rect, addr, .. } => {
+                let stroke = if path.contains(&Node::Thunk(addr.clone())) {
+                    highlight
+                } else {
+                    Stroke::new(WIDTH, dim)
+                };
+                Some(egui::Shape::rect_stroke(*rect, Rounding::ZERO, stroke))
+            }
+            Shape::Arrow { .. } | Shape::OutputLabel { .. } => None,
+        })
+        .collect()
+}
+
+/// Fills each visible operation with the colour of its heat, from 0 to 1, if it has one, using
+/// the screen positions from the last call to [`render`].
+pub fn render_heat<T: Ctx>(
+    shapes: &mut Shapes<T>,
+    heat: &HashMap<Node<T>, f32>,
+) -> Vec<egui::Shape> {
+    shapes
+        .frame
+        .iter_mut()
+        .filter_map(|(_, shape)| match &*shape {
+            Shape::Operation {
+                addr, radius, kind, ..
+            } => {
+                let heat = heat.get(&Node::Operation(addr.clone()))?;
+                Some(egui::Shape::rect_filled(
+                    shape.bounding_box(),
+                    kind.into_rounding(*radius),
+                    heat_color(*heat).gamma_multiply(0.6),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Draws the scale of [`render_heat`] in the top right corner of `rect`, from `min` to `max`.
+pub fn render_heat_legend(ui: &egui::Ui, rect: Rect, min: f64, max: f64) -> Vec<egui::Shape> {
+    const STEPS: usize = 16;
+    const SIZE: Vec2 = vec2(120.0, 10.0);
+    const PADDING: f32 = 8.0;
+
+    let bar = Rect::from_min_size(
+        rect.right_top() + vec2(-PADDING - SIZE.x, PADDING * 5.0),
+        SIZE,
+    );
+    let step = SIZE.x / STEPS as f32;
+    let mut result = (0..STEPS)
+        .map(|i| {
+            egui::Shape::rect_filled(
+                Rect::from_min_size(bar.min + vec2(step * i as f32, 0.0), vec2(step, SIZE.y)),
+                Rounding::ZERO,
+                heat_color(i as f32 / (STEPS - 1) as f32),
+            )
+        })
+        .collect::<Vec<_>>();
+    let text_color = ui.visuals().text_color();
+    ui.fonts(|fonts| {
+        for (pos, align, value) in [
+            (bar.left_bottom(), Align2::LEFT_TOP, min),
+            (bar.right_bottom(), Align2::RIGHT_TOP, max),
+        ] {
+            result.push(egui::Shape::text(
+                fonts,
+                pos + vec2(0.0, 2.0),
+                align,
+                value,
+                FontId::proportional(12.0),
+                text_color,
+            ));
+        }
+    });
+    result
+}
+
+/// Draws a legend of the colours of the types of the visible wires in the bottom right corner of
+/// `rect`, using the screen positions from the last call to [`render`].
+pub fn render_type_legend<T>(ui: &egui::Ui, shapes: &mut Shapes<T>, rect: Rect) -> Vec<egui::Shape>
+where
+    T: Ctx,
+    Weight<T::Edge>: WithType,
+{
+    const LINE: f32 = 16.0;
+    const PADDING: f32 = 8.0;
+
+    let names = shapes
+        .frame
+        .iter_mut()
+        .filter_map(|(_, shape)| match shape {
+            Shape::Line { addr, .. } | Shape::CubicBezier { addr, .. } => Some(addr.weight()),
+            _ => None,
+        })
+        .filter(|weight| weight.get_type() == WireType::Data)
+        .filter_map(|weight| weight.type_name())
+        .collect::<BTreeSet<_>>();
+
+    let dark_mode = ui.visuals().dark_mode;
+    let text_color = ui.visuals().text_color();
+    let mut result = Vec::new();
+    let mut pos = rect.right_bottom() - vec2(PADDING, PADDING + LINE * names.len() as f32);
+    for name in names {
+        let color = type_color(&name, dark_mode);
+        let text = ui.fonts(|fonts| {
+            egui::Shape::text(
+                fonts,
+                pos + vec2(-LINE * 1.5, LINE / 2.0),
+                Align2::RIGHT_CENTER,
+                &name,
+                FontId::proportional(12.0),
+                text_color,
+            )
+        });
+        result.push(egui::Shape::line_segment(
+            [pos + vec2(-LINE, LINE / 2.0), pos + vec2(0.0, LINE / 2.0)],
+            Stroke::new(2.0, color),
+        ));
+        result.push(text);
+        pos.y += LINE;
+    }
+    result
+}
+
+/// Draws the whole diagram small in the bottom left corner of `rect`, with the part of it shown on
+/// screen, `viewport`, outlined. Returns the shapes and the transform from the diagram to the
+/// minimap, whose target is the area the minimap covers.
+pub fn render_minimap<T: Ctx>(
+    ui: &egui::Ui,
+    shapes: &Shapes<T>,
+    rect: Rect,
+    viewport: Rect,
+) -> (Vec<egui::Shape>, RectTransform) {
+    const MAX_SIZE: Vec2 = vec2(200.0, 150.0);
+    const PADDING: f32 = 8.0;
+
+    let scale = (MAX_SIZE / shapes.size.max(vec2(1.0, 1.0))).min_elem();
+    let minimap = Rect::from_min_size(
+        rect.left_bottom() + vec2(PADDING, -PADDING - shapes.size.y * scale),
+        shapes.size * scale,
+    );
+    let to_minimap = RectTransform::from_to(Rect::from_min_size(Pos2::ZERO, shapes.size), minimap);
+
+    let visuals = ui.visuals();
+    let color = visuals.weak_text_color();
+    let mut result = vec![
+        egui::Shape::rect_filled(
+            minimap.expand(PADDING / 2.0),
+            Rounding::same(2.0),
+            visuals.extreme_bg_color,
+        ),
+        egui::Shape::rect_stroke(
+            minimap.expand(PADDING / 2.0),
+            Rounding::same(2.0),
+            visuals.window_stroke(),
+        ),
+    ];
+    // Only the outlines of the diagram are drawn, as every wire would be lost at this size
+    for shape in &shapes.shapes {
+        match shape {
+            Shape::Operation { .. } => {
+                let rect = to_minimap.transform_rect(shape.bounding_box());
+                result.push(egui::Shape::rect_filled(
+                    Rect::from_center_size(rect.center(), rect.size().max(vec2(1.0, 1.0))),
+                    Rounding::ZERO,
+                    color,
+                ));
+            }
+            Shape::Rectangle { rect, .. } => {
+                result.push(egui::Shape::rect_stroke(
+                    to_minimap.transform_rect(*rect),
+                    Rounding::ZERO,
+                    Stroke::new(0.5, color),
+                ));
+            }
+            _ => {}
+        }
+    }
+    result.push(egui::Shape::rect_stroke(
+        to_minimap.transform_rect(viewport).intersect(minimap),
+        Rounding::ZERO,
+        visuals.selection.stroke,
+    ));
+    (result, to_minimap)
+}
+
+/// Highlights the `worst` slices with the most wire crossings, labelled with their counts.
+pub fn render_hotspots<T: Ctx>(
+    ui: &egui::Ui,
+    shapes: &Shapes<T>,
+    to_screen: RectTransform,
+    worst: usize,
+) -> Vec<egui::Shape> {
+    let color = ui.visuals().error_fg_color;
+    let mut result = Vec::new();
+    for hotspot in shapes.hotspots.iter().take(worst) {
+        let rect = to_screen.transform_rect(hotspot.rect).expand(4.0);
+        result.push(egui::Shape::rect_filled(
+            rect,
+            Rounding::same(4.0),
+            color.gamma_multiply(0.15),
+        ));
+        result.push(egui::Shape::rect_stroke(
+            rect,
+            Rounding::same(4.0),
+            egui::Stroke::new(1.0, color),
+        ));
+        result.push(ui.fonts(|fonts| {
+            egui::Shape::text(
+                fonts,
+                rect.right_top(),
+                Align2::LEFT_TOP,
+                format!(" {} crossings", hotspot.crossings),
+                FontId::proportional(12.0),
+                color,
+            )
+        }));
+    }
+    result
+}
+
+/// Draws each control-flow edge as a dashed arrow straight from the operation which branches to
+/// the block it branches to, on top of the dataflow diagram.
+pub fn render_control_flow<T: Ctx>(
+    ui: &egui::Ui,
+    shapes: &Shapes<T>,
+    to_screen: RectTransform,
+) -> Vec<egui::Shape>
+where
+    Weight<T::Edge>: WithType,
+{
+    const ARROW_SIZE: f32 = 6.0;
+
+    // Blocks are drawn as boxes when expanded and as operations when collapsed
+    let targets = shapes
+        .shapes
+        .iter()
+        .filter_map(|shape| match shape {
+            Shape::Rectangle { rect, addr, .. } => {
+                Some((Node::Thunk(addr.clone()), rect.center_top()))
+            }
+            Shape::Operation { center, addr, .. } => Some((Node::Operation(addr.clone()), *center)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let stroke = Stroke::new(1.5, ui.visuals().warn_fg_color);
+    let mut result = Vec::new();
+    for shape in &shapes.shapes {
+        let Shape::Operation { center, addr, .. } = shape else {
+            continue;
+        };
+        for edge in addr.outputs() {
+            if edge.weight().get_type() != WireType::ControlFlow {
+                continue;
+            }
+            for target in edge.targets() {
+                let Some(end) = (match target {
+                    Endpoint::Node(node) => targets.get(&node),
+                    Endpoint::Boundary(_) => None,
+                }) else {
+                    continue;
+                };
+                let start = to_screen.transform_pos(*center);
+                let end = to_screen.transform_pos(*end);
+                if (end - start).length() < ARROW_SIZE {
+                    continue;
+                }
+                let direction = (end - start).normalized();
+                result.extend(egui::Shape::dashed_line(&[start, end], stroke, 6.0, 4.0));
+                let back = end - direction * ARROW_SIZE;
+                let side = direction.rot90() * ARROW_SIZE / 2.0;
+                result.push(egui::Shape::Path(PathShape::convex_polygon(
+                    vec![end, back + side, back - side],
+                    stroke.color,
+                    Stroke::NONE,
+                )));
+            }
+        }
+    }
+    result
+}
+
 #[allow(clippy::too_many_lines)]
 pub fn generate_shapes<T>(shapes: &mut Vec<Shape<T>>, layout: &Layout<T>, arrows: bool)
 where
@@ -93,6 +728,7 @@ where
     T::Edge: ExtensibleEdge,
     T::Operation: Shapeable,
     Weight<T::Operation>: Display,
+    Weight<T::Edge>: Display,
 {
     if arrows {
         // Source
@@ -109,7 +745,7 @@ where
             }
         }
 
-        // Target
+        // Target, or the name of the output if the wire leaves the whole graph
         for wire in layout.output_wires() {
             let targets = wire.addr.extend_targets().collect::<Vec<_>>();
             if !targets.is_empty() {
@@ -121,16 +757,28 @@ where
                     stroke: None,
                     height: 0.1,
                 });
+            } else {
+                let label = wire.addr.weight().to_string();
+                if !label.is_empty() {
+                    shapes.push(Shape::OutputLabel {
+                        center: Pos2::new(wire.h, layout.v_max + OUTPUT_LABEL_OFFSET),
+                        label,
+                        addr: wire.addr.clone(),
+                    });
+                }
             }
         }
     }
 
+    let fanouts = fanouts(layout);
+
     // Wires
-    for wire in layout.wires.iter().flat_map(|x| x.iter()) {
+    for (wire, &fanout) in layout.wires.iter().flatten().zip(fanouts.iter().flatten()) {
         shapes.push(Shape::Line {
             start: Pos2::new(wire.h, wire.v_min),
             end: Pos2::new(wire.h, wire.v_max),
             addr: wire.addr.clone(),
+            fanout,
         });
     }
 
@@ -152,6 +800,8 @@ where
         {
             let x_ins = &before[inputs.clone()];
             let x_outs = &after[outputs.clone()];
+            let fan_ins = &fanouts[i][inputs.clone()];
+            let fan_outs = &fanouts[i + 1][outputs.clone()];
 
             match node {
                 crate::layout::Node::Atom {
@@ -170,11 +820,15 @@ where
                                     start,
                                     end,
                                     addr: wire_in.addr.clone(),
+                                    fanout: 1,
                                 });
                             }
                             (
                                 vec![],
-                                vec![x_outs[0].clone(), x_outs.last().unwrap().clone()],
+                                vec![
+                                    (x_outs[0].clone(), fan_outs[0]),
+                                    (x_outs.last().unwrap().clone(), *fan_outs.last().unwrap()),
+                                ],
                             )
                         }
                         AtomType::Cup => {
@@ -185,29 +839,44 @@ where
                                     start,
                                     end,
                                     addr: wire_in.addr.clone(),
+                                    fanout: 1,
                                 });
                             }
                             (
-                                vec![x_ins[0].clone(), x_ins.last().unwrap().clone()],
+                                vec![
+                                    (x_ins[0].clone(), fan_ins[0]),
+                                    (x_ins.last().unwrap().clone(), *fan_ins.last().unwrap()),
+                                ],
                                 vec![],
                             )
                         }
-                        _ => (x_ins.to_vec(), x_outs.to_vec()),
+                        _ => (
+                            x_ins.iter().cloned().zip(fan_ins.iter().copied()).collect(),
+                            x_outs
+                                .iter()
+                                .cloned()
+                                .zip(fan_outs.iter().copied())
+                                .collect(),
+                        ),
                     };
 
-                    for wire in x_ins_rem {
+                    for (wire, fanout) in x_ins_rem {
                         let input = Pos2::new(wire.h, wire.v_max);
                         shapes.push(Shape::CubicBezier {
                             points: vertical_out_horizontal_in(input, center),
                             addr: wire.addr.clone(),
+                            fanout,
+                            swap: false,
                         });
                     }
 
-                    for wire in x_outs_rem {
+                    for (wire, fanout) in x_outs_rem {
                         let output = Pos2::new(wire.h, wire.v_min);
                         shapes.push(Shape::CubicBezier {
                             points: horizontal_out_vertical_in(center, output),
                             addr: wire.addr.clone(),
+                            fanout,
+                            swap: false,
                         });
                     }
 
@@ -218,13 +887,20 @@ where
                                 radius: RADIUS_COPY,
                                 addr: x_ins[0].addr.clone(),
                                 coord: [j, i],
+                                copy: true,
                             });
                         }
                         AtomType::Op(addr) => {
+                            let label = match addr.weight().to_string() {
+                                label if label.is_empty() => {
+                                    addr.fallback_label().unwrap_or_default()
+                                }
+                                label => label,
+                            };
                             shapes.push(Shape::Operation {
                                 center,
                                 addr: addr.clone(),
-                                label: addr.weight().to_string(),
+                                label,
                                 kind: addr.to_shape(),
                                 radius: RADIUS_OPERATION,
                                 fill: None,
@@ -246,6 +922,8 @@ where
                         shapes.push(Shape::CubicBezier {
                             points: vertical_out_vertical_in(in_wire, out_wire),
                             addr: x_ins[*in_idx].addr.clone(),
+                            fanout: fan_ins[*in_idx],
+                            swap: true,
                         });
                     }
                 }
@@ -255,21 +933,25 @@ where
                     inputs,
                     outputs,
                 } => {
-                    for (outer, inner) in x_ins.iter().zip(inputs) {
+                    for ((outer, inner), &fanout) in x_ins.iter().zip(inputs).zip(fan_ins) {
                         let start = Pos2::new(outer.h, outer.v_max);
                         let end = Pos2::new(*inner, layout.v_min);
                         shapes.push(Shape::CubicBezier {
                             points: vertical_out_vertical_in(start, end),
                             addr: outer.addr.clone(),
+                            fanout,
+                            swap: false,
                         });
                     }
 
-                    for (outer, inner) in x_outs.iter().zip(outputs) {
+                    for ((outer, inner), &fanout) in x_outs.iter().zip(outputs).zip(fan_outs) {
                         let start = Pos2::new(*inner, layout.v_max);
                         let end = Pos2::new(outer.h, outer.v_min);
                         shapes.push(Shape::CubicBezier {
                             points: vertical_out_vertical_in(start, end),
                             addr: outer.addr.clone(),
+                            fanout,
+                            swap: false,
                         });
                     }
 
@@ -277,10 +959,24 @@ where
                         Pos2::new(layout.h_min, layout.v_min),
                         Pos2::new(layout.h_max, layout.v_max),
                     );
+                    // The body takes the captured values first, so they line up with `inputs`,
+                    // and then the arguments
+                    let arguments = addr.bound_graph_inputs().count();
+                    let last_capture = inputs.iter().copied().reduce(f32::max);
+                    let first_argument = layout
+                        .inputs()
+                        .rev()
+                        .take(arguments)
+                        .copied()
+                        .reduce(f32::min);
+                    let separator = last_capture
+                        .zip(first_argument)
+                        .map(|(capture, argument)| (capture + argument) / 2.0);
                     shapes.push(Shape::Rectangle {
                         rect: thunk_rect,
                         addr: addr.clone(),
                         stroke: None,
+                        separator,
                     });
 
                     for (edge, &x) in addr.bound_graph_inputs().rev().zip(layout.inputs().rev()) {
@@ -290,6 +986,7 @@ where
                             radius: RADIUS_ARG,
                             addr: edge,
                             coord: [j, i],
+                            copy: false,
                         });
                     }
                     for (edge, &x) in addr.bound_graph_outputs().rev().zip(layout.outputs().rev()) {
@@ -298,6 +995,7 @@ where
                             radius: RADIUS_ARG,
                             addr: edge,
                             coord: [j, i],
+                            copy: false,
                         });
                     }
 
@@ -308,6 +1006,46 @@ where
     }
 }
 
+/// How many consumers the value on each wire of `layout` reaches, through the copies, identities
+/// and swaps below it, indexed like `layout.wires`.
+fn fanouts<T: Ctx>(layout: &Layout<T>) -> Vec<Vec<usize>> {
+    let mut fanouts: Vec<Vec<usize>> = layout
+        .wires
+        .iter()
+        .map(|wires| vec![1; wires.len()])
+        .collect();
+    for (i, slice) in layout.nodes.iter().enumerate().rev() {
+        for NodeOffset {
+            node,
+            inputs,
+            outputs,
+        } in slice
+        {
+            match node {
+                crate::layout::Node::Atom {
+                    atype: AtomType::Copy,
+                    ..
+                } => {
+                    fanouts[i][inputs.start] = outputs.clone().map(|k| fanouts[i + 1][k]).sum();
+                }
+                crate::layout::Node::Atom {
+                    atype: AtomType::Id,
+                    ..
+                } => {
+                    fanouts[i][inputs.start] = fanouts[i + 1][outputs.start];
+                }
+                crate::layout::Node::Swap { out_to_in, .. } => {
+                    for (out_idx, in_idx) in out_to_in.iter().enumerate() {
+                        fanouts[i][inputs.start + in_idx] = fanouts[i + 1][outputs.start + out_idx];
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    fanouts
+}
+
 fn vertical_out_horizontal_in(start: Pos2, end: Pos2) -> [Pos2; 4] {
     [
         start,
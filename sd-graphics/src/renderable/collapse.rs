@@ -35,6 +35,19 @@ impl<G: RenderableGraph> RenderableGraph for CollapseGraph<G> {
         }
     }
 
+    // Inner operations: delegate to inner graph.
+    // Collapsed thunks: expand the thunk.
+    fn alt_clicked_operation(&mut self, op: CollapseOperation<G>) {
+        match op.into_inner() {
+            Node::Operation(op) => {
+                self.inner_mut().alt_clicked_operation(op);
+            }
+            Node::Thunk(thunk) => {
+                self.toggle(&thunk);
+            }
+        }
+    }
+
     // Either collapse the thunk [click] or delegate to inner graph [right-click].
     fn clicked_thunk(&mut self, thunk: CollapseThunk<G>, primary: bool) {
         if primary {
@@ -29,6 +29,19 @@ impl<G: RenderableGraph> RenderableGraph for CutGraph<G> {
         }
     }
 
+    // Inner operations: delegate to inner graph.
+    // Store and reuse operations: toggle the edge.
+    fn alt_clicked_operation(&mut self, op: CutOperation<G>) {
+        match op.into_inner() {
+            Either::Left(op) => {
+                self.inner_mut().alt_clicked_operation(op);
+            }
+            Either::Right(edge) => {
+                self.toggle(&edge);
+            }
+        }
+    }
+
     // Delegate to inner graph.
     fn clicked_thunk(&mut self, thunk: CutThunk<G>, primary: bool) {
         self.inner_mut().clicked_thunk(thunk.into_inner(), primary);
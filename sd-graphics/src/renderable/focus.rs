@@ -0,0 +1,38 @@
+use sd_core::hypergraph::{
+    adapter::focus::FocusGraph,
+    generic::{Edge, Node, Operation, Thunk},
+};
+
+use super::RenderableGraph;
+
+impl<G: RenderableGraph> RenderableGraph for FocusGraph<G> {
+    // Delegate to inner graph.
+    fn selected(&self, node: Node<G::Ctx>) -> bool {
+        self.inner().selected(node)
+    }
+
+    // Delegate to inner graph.
+    fn clicked_edge(&mut self, edge: Edge<G::Ctx>) {
+        self.inner_mut().clicked_edge(edge);
+    }
+
+    // Delegate to inner graph.
+    fn clicked_operation(&mut self, op: Operation<G::Ctx>, primary: bool) {
+        self.inner_mut().clicked_operation(op, primary);
+    }
+
+    // Delegate to inner graph.
+    fn alt_clicked_operation(&mut self, op: Operation<G::Ctx>) {
+        self.inner_mut().alt_clicked_operation(op);
+    }
+
+    // Delegate to inner graph.
+    fn clicked_thunk(&mut self, thunk: Thunk<G::Ctx>, primary: bool) {
+        self.inner_mut().clicked_thunk(thunk, primary);
+    }
+
+    // Delegate to inner graph.
+    fn extend(&mut self, nodes: impl Iterator<Item = Node<G::Ctx>>) {
+        self.inner_mut().extend(nodes);
+    }
+}
@@ -0,0 +1,58 @@
+use sd_core::hypergraph::{
+    adapter::fold::{FoldEdge, FoldGraph, FoldNode, FoldOperation, FoldThunk},
+    generic::Node,
+};
+
+use super::RenderableGraph;
+
+impl<G: RenderableGraph> RenderableGraph for FoldGraph<G> {
+    // Folded chains are selected when all their operations are.
+    fn selected(&self, node: FoldNode<G>) -> bool {
+        node.into_inner()
+            .into_iter()
+            .all(|node| self.inner().selected(node))
+    }
+
+    // Delegate to inner graph.
+    fn clicked_edge(&mut self, edge: FoldEdge<G>) {
+        self.inner_mut().clicked_edge(edge.into_inner());
+    }
+
+    // Folded chains: either unfold the chain [click] or select its operations [right-click].
+    // Other operations: delegate to inner graph.
+    fn clicked_operation(&mut self, op: FoldOperation<G>, primary: bool) {
+        let ops = op.inner();
+        if primary && ops.len() > 1 {
+            self.toggle(&ops[0]);
+            return;
+        }
+        let selected = ops
+            .iter()
+            .all(|op| self.inner().selected(Node::Operation(op.clone())));
+        for op in ops {
+            if self.inner().selected(Node::Operation(op.clone())) == selected {
+                self.inner_mut().clicked_operation(op.clone(), primary);
+            }
+        }
+    }
+
+    // Fold or unfold the chain of the operation, delegating to inner graph for operations in no
+    // chain.
+    fn alt_clicked_operation(&mut self, op: FoldOperation<G>) {
+        let op = &op.inner()[0];
+        if !self.toggle(op) {
+            self.inner_mut().alt_clicked_operation(op.clone());
+        }
+    }
+
+    // Delegate to inner graph.
+    fn clicked_thunk(&mut self, thunk: FoldThunk<G>, primary: bool) {
+        self.inner_mut().clicked_thunk(thunk.into_inner(), primary);
+    }
+
+    // Delegate to inner graph.
+    fn extend(&mut self, nodes: impl Iterator<Item = FoldNode<G>>) {
+        self.inner_mut()
+            .extend(nodes.flat_map(FoldNode::into_inner));
+    }
+}
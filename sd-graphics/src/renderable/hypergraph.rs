@@ -14,6 +14,9 @@ impl<W: Weight> RenderableGraph for Hypergraph<W> {
     // Noop
     fn clicked_operation(&mut self, _op: Operation<W>, _primary: bool) {}
 
+    // Noop
+    fn alt_clicked_operation(&mut self, _op: Operation<W>) {}
+
     // Noop
     fn clicked_thunk(&mut self, _thunk: Thunk<W>, _primary: bool) {}
 
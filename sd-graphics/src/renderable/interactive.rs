@@ -18,6 +18,10 @@ impl<G: RenderableGraph> RenderableGraph for InteractiveGraph<G> {
         self.0.clicked_operation(op, primary);
     }
 
+    fn alt_clicked_operation(&mut self, op: Operation<Self::Ctx>) {
+        self.0.alt_clicked_operation(op);
+    }
+
     fn clicked_thunk(&mut self, thunk: Thunk<Self::Ctx>, primary: bool) {
         self.0.clicked_thunk(thunk, primary);
     }
@@ -40,6 +44,10 @@ impl<T: Ctx> RenderableGraph for InteractiveSubgraph<T> {
         self.0.clicked_operation(op, primary);
     }
 
+    fn alt_clicked_operation(&mut self, op: Operation<Self::Ctx>) {
+        self.0.alt_clicked_operation(op);
+    }
+
     fn clicked_thunk(&mut self, thunk: Thunk<Self::Ctx>, primary: bool) {
         self.0.clicked_thunk(thunk, primary);
     }
@@ -5,6 +5,8 @@ use sd_core::hypergraph::{
 
 mod collapse;
 mod cut;
+mod focus;
+mod fold;
 mod hypergraph;
 mod interactive;
 mod selectable;
@@ -15,6 +17,7 @@ pub trait RenderableGraph: Graph {
     fn selected(&self, node: Node<Self::Ctx>) -> bool;
     fn clicked_edge(&mut self, edge: Edge<Self::Ctx>);
     fn clicked_operation(&mut self, op: Operation<Self::Ctx>, primary: bool);
+    fn alt_clicked_operation(&mut self, op: Operation<Self::Ctx>);
     fn clicked_thunk(&mut self, thunk: Thunk<Self::Ctx>, primary: bool);
     fn extend(&mut self, nodes: impl Iterator<Item = Node<Self::Ctx>>);
 }
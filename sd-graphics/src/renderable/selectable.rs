@@ -21,6 +21,11 @@ impl<G: RenderableGraph> RenderableGraph for SelectableGraph<G> {
         *self.selected_mut(&Node::Operation(op)) ^= true;
     }
 
+    // Toggle the selection.
+    fn alt_clicked_operation(&mut self, op: Operation<G::Ctx>) {
+        *self.selected_mut(&Node::Operation(op)) ^= true;
+    }
+
     // Toggle the selection.
     fn clicked_thunk(&mut self, thunk: Thunk<G::Ctx>, _primary: bool) {
         *self.selected_mut(&Node::Thunk(thunk)) ^= true;
@@ -19,6 +19,11 @@ impl<T: Ctx> RenderableGraph for Subgraph<T> {
         self.remove(&Node::Operation(op.into_inner()));
     }
 
+    // Remove from the subgraph.
+    fn alt_clicked_operation(&mut self, op: SubOperation<T>) {
+        self.remove(&Node::Operation(op.into_inner()));
+    }
+
     // Remove from the subgraph.
     fn clicked_thunk(&mut self, thunk: SubThunk<T>, _primary: bool) {
         self.remove(&Node::Thunk(thunk.into_inner()));
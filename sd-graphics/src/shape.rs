@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use derivative::Derivative;
 use egui::{
     emath::RectTransform,
@@ -7,16 +9,24 @@ use egui::{
 use flo_curves::bezier::{solve_curve_for_t_along_axis, Curve};
 use indexmap::IndexSet;
 use sd_core::{
-    common::Matchable,
+    common::{Matchable, Query},
     hypergraph::{
         generic::{Ctx, Node, Weight},
-        traits::{Keyable, WireType, WithType, WithWeight},
+        traits::{Keyable, NodeLike, WireType, WithType, WithWeight},
     },
+    monoidal::Simplification,
 };
 
 use crate::{
-    common::{to_coord2, ShapeKind, TEXT_SIZE, TOLERANCE},
+    common::{
+        to_coord2, type_color, LabelVisibility, ShapeKind, ORDERING_DASH, SEPARATOR_HEIGHT,
+        TEXT_SIZE, TOLERANCE,
+    },
+    crossings::Hotspot,
     renderable::RenderableGraph,
+    spatial_index::SpatialIndex,
+    text::label_shape,
+    theme::Theme,
 };
 
 #[derive(Derivative)]
@@ -26,21 +36,31 @@ pub enum Shape<T: Ctx> {
         start: Pos2,
         end: Pos2,
         addr: T::Edge,
+        /// How many consumers the value on the wire reaches, through the copies below it.
+        fanout: usize,
     },
     CubicBezier {
         points: [Pos2; 4],
         addr: T::Edge,
+        fanout: usize,
+        /// Whether the wire crosses others in a swap.
+        swap: bool,
     },
     Rectangle {
         rect: Rect,
         addr: T::Thunk,
         stroke: Option<Stroke>,
+        /// Where the captured values on the top edge give way to the arguments, if the thunk has
+        /// both.
+        separator: Option<f32>,
     },
     CircleFilled {
         center: Pos2,
         radius: f32,
         addr: T::Edge,
         coord: [usize; 2],
+        /// Whether the dot is where a value is copied, rather than a thunk argument or output.
+        copy: bool,
     },
     Operation {
         center: Pos2,
@@ -59,11 +79,137 @@ pub enum Shape<T: Ctx> {
         stroke: Option<Stroke>,
         height: f32,
     },
+    /// The name of an output of the whole diagram, beneath its bottom boundary.
+    OutputLabel {
+        center: Pos2,
+        label: String,
+        addr: T::Edge,
+    },
 }
 
 pub struct Shapes<T: Ctx> {
     pub shapes: Vec<Shape<T>>,
     pub size: Vec2,
+    pub index: SpatialIndex,
+    pub frame: FrameCache<T>,
+    /// Whether the layout was approximated because solving it ran out of time.
+    pub approximate: bool,
+    /// Whether the layout is a draft, made without solving anything, to show until the solved
+    /// layout is ready.
+    pub draft: bool,
+    /// Whether the layout was made without solving anything, as the graph looked too big to
+    /// solve quickly.
+    pub heuristic: bool,
+    /// How long laying out the graph took.
+    pub layout_time: std::time::Duration,
+    /// The slices where wires cross, worst first.
+    pub hotspots: Vec<Hotspot>,
+    /// How far the graph was simplified before it was laid out.
+    pub simplification: Simplification,
+    /// Why the graph could not be laid out, in which case there are no shapes.
+    pub error: Option<String>,
+}
+
+impl<T: Ctx> Shapes<T> {
+    #[must_use]
+    pub fn new(shapes: Vec<Shape<T>>, size: Vec2) -> Self {
+        let index = SpatialIndex::new(shapes.iter().map(Shape::bounding_box));
+        Self {
+            shapes,
+            size,
+            index,
+            frame: FrameCache::default(),
+            approximate: false,
+            draft: false,
+            heuristic: false,
+            layout_time: std::time::Duration::ZERO,
+            hotspots: Vec::new(),
+            simplification: Simplification::default(),
+            error: None,
+        }
+    }
+
+    /// Where each operation and thunk is laid out, in the coordinates of the diagram.
+    #[must_use]
+    pub fn node_bounds(&self) -> HashMap<Node<T>, Rect> {
+        self.shapes
+            .iter()
+            .filter_map(|shape| match shape {
+                Shape::Operation { addr, .. } => {
+                    Some((Node::Operation(addr.clone()), shape.bounding_box()))
+                }
+                Shape::Rectangle { addr, rect, .. } => Some((Node::Thunk(addr.clone()), *rect)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The edge of the wire or output label at `pos`, in the coordinates of the diagram.
+    #[must_use]
+    pub fn edge_at(&self, pos: Pos2) -> Option<T::Edge> {
+        // Wires drawn later are on top
+        self.index
+            .query_point(pos, TOLERANCE)
+            .into_iter()
+            .rev()
+            .find_map(|i| match &self.shapes[i] {
+                shape @ (Shape::Line { addr, .. }
+                | Shape::CubicBezier { addr, .. }
+                | Shape::OutputLabel { addr, .. })
+                    if shape.contains_point(pos, TOLERANCE) =>
+                {
+                    Some(addr.clone())
+                }
+                _ => None,
+            })
+    }
+}
+
+/// The visible shapes transformed to screen coordinates for the last rendered frame.
+///
+/// These are kept between frames so that changes to highlighting or selection
+/// only need to restyle the shapes rather than regenerate them.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct FrameCache<T: Ctx> {
+    transform: Option<RectTransform>,
+    shapes: Vec<(usize, Shape<T>)>,
+}
+
+impl<T: Ctx> FrameCache<T> {
+    /// Recompute the visible shapes if the transform has changed since the last frame.
+    pub(crate) fn update(
+        &mut self,
+        shapes: &[Shape<T>],
+        index: &SpatialIndex,
+        transform: RectTransform,
+    ) {
+        let unchanged = self
+            .transform
+            .is_some_and(|old| old.from() == transform.from() && old.to() == transform.to());
+        if unchanged {
+            return;
+        }
+
+        let viewport = *transform.from();
+        self.shapes = index
+            .query(viewport)
+            .into_iter()
+            .map(|i| (i, &shapes[i]))
+            .filter(|(_, shape)| viewport.intersects(shape.bounding_box()))
+            .map(|(i, shape)| {
+                let mut shape = shape.clone();
+                shape.apply_transform(&transform);
+                (i, shape)
+            })
+            .collect();
+        self.transform = Some(transform);
+    }
+
+    /// Iterate over the visible shapes along with their index in the full list of shapes.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut Shape<T>)> {
+        self.shapes.iter_mut().map(|(i, shape)| (*i, shape))
+    }
 }
 
 impl<T: Ctx> Shape<T> {
@@ -78,14 +224,22 @@ impl<T: Ctx> Shape<T> {
                     *point = transform.transform_pos(*point);
                 }
             }
-            Shape::Rectangle { rect, .. } => {
+            Shape::Rectangle {
+                rect, separator, ..
+            } => {
                 *rect = transform.transform_rect(*rect);
+                if let Some(x) = separator {
+                    *x = transform.transform_pos(Pos2::new(*x, 0.0)).x;
+                }
             }
             Shape::CircleFilled { center, radius, .. }
             | Shape::Operation { center, radius, .. } => {
                 *center = transform.transform_pos(*center);
                 *radius *= transform.scale().min_elem(); // NOTE(calintat): should this be length?
             }
+            Shape::OutputLabel { center, .. } => {
+                *center = transform.transform_pos(*center);
+            }
             Shape::Arrow { center, height, .. } => {
                 *center = transform.transform_pos(*center);
                 *height *= transform.scale().min_elem();
@@ -102,8 +256,11 @@ impl<T: Ctx> Shape<T> {
         ui: &egui::Ui,
         response: &Response,
         transform: &RectTransform,
-        search: Option<&str>,
+        search: Option<&Query>,
+        theme: &Theme,
+        near_pointer: bool,
         highlight_op: &mut Option<T::Operation>,
+        highlight_thunk: &mut Option<T::Thunk>,
         highlight_edges: &mut IndexSet<T::Edge>,
     ) where
         G: RenderableGraph<Ctx = T>,
@@ -115,10 +272,12 @@ impl<T: Ctx> Shape<T> {
 
         let bounding_box = self.bounding_box();
 
-        if let Some(hover_pos) = response.hover_pos() {
+        if let Some(hover_pos) = response.hover_pos().filter(|_| near_pointer) {
             if self.contains_point(hover_pos, tolerance) {
                 match self {
-                    Shape::Line { addr, .. } | Shape::CubicBezier { addr, .. } => {
+                    Shape::Line { addr, .. }
+                    | Shape::CubicBezier { addr, .. }
+                    | Shape::OutputLabel { addr, .. } => {
                         highlight_edges.insert(addr.clone());
                     }
                     _ => {}
@@ -126,7 +285,7 @@ impl<T: Ctx> Shape<T> {
             }
         }
         match self {
-            Shape::Line { .. } | Shape::CubicBezier { .. } => {}
+            Shape::Line { .. } | Shape::CubicBezier { .. } | Shape::OutputLabel { .. } => {}
             Shape::CircleFilled { addr, coord, .. } => {
                 let circle_response = ui.interact(
                     bounding_box.intersect(bounds),
@@ -151,17 +310,24 @@ impl<T: Ctx> Shape<T> {
                     .interact_selectable(&thunk_response, selected)
                     .fg_stroke;
                 if !selected {
-                    new_stroke.color = new_stroke.color.gamma_multiply(0.35);
+                    new_stroke.color = theme
+                        .thunk_border
+                        .unwrap_or_else(|| new_stroke.color.gamma_multiply(0.35));
                 }
                 if search_match {
                     new_stroke.color = Color32::LIGHT_RED;
                 }
                 *stroke = Some(new_stroke);
 
+                // Nested thunks are drawn after those around them, so the innermost one wins
+                if thunk_response.hovered() {
+                    *highlight_thunk = Some(addr.clone());
+                }
                 if thunk_response.clicked() {
                     graph.clicked_thunk(addr.clone(), true);
                 }
-                if thunk_response.secondary_clicked() {
+                // Long presses stand in for right clicks on touch screens
+                if thunk_response.secondary_clicked() || thunk_response.long_touched() {
                     graph.clicked_thunk(addr.clone(), false);
                 }
             }
@@ -176,21 +342,28 @@ impl<T: Ctx> Shape<T> {
                     Sense::click(),
                 );
                 if op_response.clicked() {
-                    graph.clicked_operation(addr.clone(), true);
+                    if ui.input(|i| i.modifiers.alt) {
+                        graph.alt_clicked_operation(addr.clone());
+                    } else {
+                        graph.clicked_operation(addr.clone(), true);
+                    }
                 }
-                if op_response.secondary_clicked() {
+                if op_response.secondary_clicked() || op_response.long_touched() {
                     graph.clicked_operation(addr.clone(), false);
                 }
-                *fill = Some(
-                    ui.style()
-                        .interact_selectable(&op_response, selected)
-                        .bg_fill,
-                );
-                *stroke = Some(
-                    ui.style()
-                        .interact_selectable(&op_response, selected)
-                        .fg_stroke,
-                );
+                let visuals = ui.style().interact_selectable(&op_response, selected);
+                let mut new_stroke = visuals.fg_stroke;
+                *fill = Some(visuals.bg_fill);
+                // The theme only colours operations at rest, so hovering and selecting still show
+                if !selected && !op_response.hovered() {
+                    if let Some(color) = theme.node_fill {
+                        *fill = Some(color);
+                    }
+                    if let Some(color) = theme.node_stroke {
+                        new_stroke.color = color;
+                    }
+                }
+                *stroke = Some(new_stroke);
                 if search_match {
                     *fill = Some(Color32::DARK_RED);
                     stroke.as_mut().unwrap().color = Color32::LIGHT_RED;
@@ -212,9 +385,9 @@ impl<T: Ctx> Shape<T> {
                     Sense::click(),
                 );
 
-                if arrow_response.hovered() {
-                    *stroke = Some(ui.style().interact(&arrow_response).fg_stroke);
-                }
+                *stroke = arrow_response
+                    .hovered()
+                    .then(|| ui.style().interact(&arrow_response).fg_stroke);
 
                 if arrow_response.clicked() {
                     graph.extend(to_add.iter().cloned());
@@ -223,12 +396,18 @@ impl<T: Ctx> Shape<T> {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[allow(clippy::too_many_lines)]
-    pub(crate) fn into_egui_shape(
-        self,
+    pub(crate) fn to_egui_shape(
+        &self,
         ui: &egui::Ui,
         transform: &RectTransform,
         highlight_edges: &IndexSet<T::Edge>,
+        labels: LabelVisibility,
+        theme: &Theme,
+        show_ordering: bool,
+        type_colors: bool,
+        bundle: bool,
     ) -> egui::Shape
     where
         Weight<T::Edge>: WithType,
@@ -236,14 +415,19 @@ impl<T: Ctx> Shape<T> {
         let default_stroke = ui.visuals().noninteractive().fg_stroke;
         let fg_stroke = ui.visuals().widgets.hovered.fg_stroke;
 
-        let wire_stroke = |highlighted: bool, wire_type: WireType| -> Stroke {
+        let wire_stroke = |highlighted: bool, weight: &Weight<T::Edge>| -> Stroke {
+            let wire_type = weight.get_type();
             let mut stroke = if highlighted {
                 fg_stroke
             } else {
                 default_stroke
             };
             match wire_type {
-                WireType::Data => {}
+                WireType::Data => {
+                    if let Some(color) = theme.edge.filter(|_| !highlighted) {
+                        stroke.color = color;
+                    }
+                }
                 WireType::ControlFlow => {
                     if highlighted {
                         stroke.color = Color32::YELLOW;
@@ -258,74 +442,159 @@ impl<T: Ctx> Shape<T> {
                         stroke.color = Color32::DARK_GREEN;
                     }
                 }
+                WireType::Ordering => {
+                    stroke.width /= 2.0;
+                }
+            }
+            if type_colors && wire_type == WireType::Data {
+                if let Some(name) = weight.type_name() {
+                    stroke.color = type_color(&name, ui.visuals().dark_mode);
+                }
             }
             stroke
         };
 
+        // Wires which only order effects are drawn thin and dashed, if at all
+        let dashed = |points: &[Pos2], stroke: Stroke| {
+            let dash = ORDERING_DASH * transform.scale().min_elem();
+            egui::Shape::Vec(egui::Shape::dashed_line(points, stroke, dash, dash))
+        };
+
         match self {
-            Shape::Line { start, end, addr } => {
-                let stroke = wire_stroke(highlight_edges.contains(&addr), addr.weight().get_type());
-                egui::Shape::line_segment([start, end], stroke)
+            Shape::Line {
+                start,
+                end,
+                addr,
+                fanout,
+            } => {
+                let weight = addr.weight();
+                let wire_type = weight.get_type();
+                let mut stroke = wire_stroke(highlight_edges.contains(addr), &weight);
+                if bundle {
+                    stroke.width *= bundle_width(*fanout);
+                }
+                match wire_type {
+                    WireType::Ordering if !show_ordering => egui::Shape::Noop,
+                    WireType::Ordering => dashed(&[*start, *end], stroke),
+                    _ => egui::Shape::line_segment([*start, *end], stroke),
+                }
             }
-            Shape::CubicBezier { points, addr } => {
-                let stroke = wire_stroke(highlight_edges.contains(&addr), addr.weight().get_type());
+            Shape::CubicBezier {
+                points,
+                addr,
+                fanout,
+                swap,
+            } => {
+                let weight = addr.weight();
+                let wire_type = weight.get_type();
+                let highlighted = highlight_edges.contains(addr);
+                let mut stroke = wire_stroke(highlighted, &weight);
+                if bundle {
+                    stroke.width *= bundle_width(*fanout);
+                }
+                if let Some(color) = theme.swap.filter(|_| *swap && !highlighted) {
+                    stroke.color = color;
+                }
 
                 let bezier = CubicBezierShape::from_points_stroke(
-                    points,
+                    *points,
                     false,
                     Color32::TRANSPARENT,
                     stroke,
                 );
-                egui::Shape::CubicBezier(bezier)
+                match wire_type {
+                    WireType::Ordering if !show_ordering => egui::Shape::Noop,
+                    WireType::Ordering => dashed(&bezier.flatten(None), stroke),
+                    _ => egui::Shape::CubicBezier(bezier),
+                }
             }
-            Shape::Rectangle { rect, stroke, .. } => egui::Shape::Rect(RectShape::new(
+            Shape::Rectangle {
                 rect,
-                Rounding::ZERO,
-                Color32::default(),
-                stroke.unwrap_or(default_stroke),
-            )),
+                stroke,
+                separator,
+                ..
+            } => {
+                let stroke = stroke.unwrap_or(default_stroke);
+                let shape = egui::Shape::Rect(RectShape::new(
+                    *rect,
+                    Rounding::ZERO,
+                    Color32::default(),
+                    stroke,
+                ));
+                let Some(x) = separator else {
+                    return shape;
+                };
+                let height = SEPARATOR_HEIGHT * transform.scale().min_elem();
+                let separator = egui::Shape::line_segment(
+                    [
+                        Pos2::new(*x, rect.min.y),
+                        Pos2::new(*x, rect.min.y + height),
+                    ],
+                    Stroke::new(stroke.width, stroke.color.gamma_multiply(0.5)),
+                );
+                egui::Shape::Vec(vec![shape, separator])
+            }
             Shape::CircleFilled {
                 center,
                 radius,
                 addr,
+                copy,
                 ..
             } => {
-                let stroke = wire_stroke(highlight_edges.contains(&addr), addr.weight().get_type());
-                egui::Shape::circle_filled(center, radius, stroke.color)
+                let highlighted = highlight_edges.contains(addr);
+                let color = match theme.copy {
+                    Some(color) if *copy && !highlighted => color,
+                    _ => wire_stroke(highlighted, &addr.weight()).color,
+                };
+                egui::Shape::circle_filled(*center, *radius, color)
             }
             Shape::Operation {
                 center,
                 radius,
+                addr,
                 label,
                 kind,
                 fill,
                 stroke,
-                ..
             } => {
                 let rect = egui::Shape::Rect(RectShape::new(
                     Rect::from_center_size(
-                        center,
-                        radius * vec2(label.chars().count().max(1) as f32 + 1.0, 2.0),
+                        *center,
+                        *radius * vec2(label.chars().count().max(1) as f32 + 1.0, 2.0),
                     ),
-                    kind.into_rounding(radius),
+                    kind.into_rounding(*radius),
                     fill.unwrap_or_default(),
                     stroke.unwrap_or(default_stroke),
                 ));
                 let text_size: f32 = TEXT_SIZE * transform.scale().min_elem();
-                if text_size <= 5.0 {
+                let Some(label) = labels.reveal(label, text_size) else {
                     return rect;
+                };
+                let text = label_shape(
+                    ui,
+                    label,
+                    text_size,
+                    Align2::CENTER_CENTER,
+                    *center,
+                    ui.visuals().strong_text_color(),
+                );
+                let type_names: Vec<_> = addr
+                    .outputs()
+                    .filter_map(|edge| edge.weight().type_name())
+                    .collect();
+                if type_names.is_empty() || !labels.reveals_types(text_size) {
+                    return egui::Shape::Vec(vec![rect, text]);
                 }
-                let text = ui.fonts(|fonts| {
-                    egui::Shape::text(
-                        fonts,
-                        center,
-                        Align2::CENTER_CENTER,
-                        label,
-                        egui::FontId::monospace(text_size),
-                        ui.visuals().strong_text_color(),
-                    )
-                });
-                egui::Shape::Vec(vec![rect, text])
+                // Beneath the operation, as its box is sized for the name alone
+                let types = label_shape(
+                    ui,
+                    &type_names.join(", "),
+                    text_size * 0.75,
+                    Align2::CENTER_TOP,
+                    *center + vec2(0.0, *radius),
+                    ui.visuals().weak_text_color(),
+                );
+                egui::Shape::Vec(vec![rect, text, types])
             }
             Shape::Arrow {
                 center,
@@ -334,6 +603,7 @@ impl<T: Ctx> Shape<T> {
                 stroke,
                 ..
             } => {
+                let (center, upwards, height) = (*center, *upwards, *height);
                 let stroke = stroke.unwrap_or(default_stroke);
 
                 let fill = stroke.color;
@@ -357,6 +627,22 @@ impl<T: Ctx> Shape<T> {
                     stroke,
                 })
             }
+            Shape::OutputLabel {
+                center,
+                label,
+                addr,
+            } => {
+                let text_size: f32 = TEXT_SIZE * transform.scale().min_elem();
+                let Some(label) = labels.reveal(label, text_size) else {
+                    return egui::Shape::Noop;
+                };
+                let color = if highlight_edges.contains(addr) {
+                    fg_stroke.color
+                } else {
+                    ui.visuals().weak_text_color()
+                };
+                label_shape(ui, label, text_size, Align2::CENTER_TOP, *center, color)
+            }
         }
     }
 
@@ -367,11 +653,12 @@ impl<T: Ctx> Shape<T> {
             Shape::Rectangle { rect, .. } => rect.center(),
             Shape::CircleFilled { center, .. }
             | Shape::Operation { center, .. }
-            | Shape::Arrow { center, .. } => *center,
+            | Shape::Arrow { center, .. }
+            | Shape::OutputLabel { center, .. } => *center,
         }
     }
 
-    pub(crate) fn bounding_box(&self) -> Rect {
+    pub fn bounding_box(&self) -> Rect {
         match self {
             Shape::Line { start, end, .. } => Rect::from_two_pos(*start, *end),
             Shape::CubicBezier { points, .. } => Rect::from_points(points),
@@ -391,6 +678,11 @@ impl<T: Ctx> Shape<T> {
             Shape::Arrow { center, height, .. } => {
                 Rect::from_center_size(*center, Vec2::splat(*height * 5.0))
             }
+            // Only an estimate, as the size of the text depends on the zoom
+            Shape::OutputLabel { center, label, .. } => Rect::from_center_size(
+                *center,
+                vec2(label.chars().count() as f32 * TEXT_SIZE, TEXT_SIZE * 2.0),
+            ),
         }
     }
 
@@ -420,17 +712,25 @@ impl<T: Ctx> Shape<T> {
                 f64::from(tolerance),
             )
             .is_some(),
+            Shape::OutputLabel { .. } => self.bounding_box().expand(tolerance).contains(point),
             _ => false,
         }
     }
 }
 
+/// How many times thicker than usual a bundled wire reaching `fanout` consumers is drawn, so that
+/// the wire out of a copy thins as it splits towards the consumers.
+#[allow(clippy::cast_precision_loss)]
+fn bundle_width(fanout: usize) -> f32 {
+    1.0 + (fanout.max(1) as f32).log2()
+}
+
 impl<T: Ctx> Matchable for Shape<T>
 where
     T::Operation: Matchable,
     T::Thunk: Matchable,
 {
-    fn is_match(&self, query: &str) -> bool {
+    fn is_match(&self, query: &Query) -> bool {
         match self {
             Self::Operation { addr, .. } => addr.is_match(query),
             Self::Rectangle { addr, .. } => addr.is_match(query),
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use egui::{Pos2, Rect, Vec2};
+
+/// A uniform grid over the bounding boxes of shapes, used to avoid scanning every shape
+/// when culling to the viewport or hit-testing the pointer.
+#[derive(Clone, Debug)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    /// Union of all indexed bounding boxes.
+    bounds: Rect,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialIndex {
+    /// Build an index over the given bounding boxes, identified by their position in the iterator.
+    pub fn new(bounding_boxes: impl Iterator<Item = Rect>) -> Self {
+        let bounding_boxes: Vec<Rect> = bounding_boxes.collect();
+        let bounds = bounding_boxes
+            .iter()
+            .fold(Rect::NOTHING, |bounds, rect| bounds.union(*rect));
+        let mut index = Self {
+            cell_size: Self::cell_size(bounds, bounding_boxes.len()),
+            bounds,
+            cells: HashMap::new(),
+        };
+        for (i, rect) in bounding_boxes.into_iter().enumerate() {
+            for cell in index.cells_of(rect) {
+                index.cells.entry(cell).or_default().push(i);
+            }
+        }
+        index
+    }
+
+    /// Side length of a grid cell, in layout coordinates, so that there are about as many cells
+    /// covering `bounds` as there are shapes, however large the diagram is.
+    #[allow(clippy::cast_precision_loss)]
+    fn cell_size(bounds: Rect, shapes: usize) -> f32 {
+        let size = bounds.width().max(bounds.height()) / (shapes as f32).sqrt();
+        if size.is_finite() && size > 0.0 {
+            size
+        } else {
+            // No shapes, or every shape is a point
+            1.0
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn cell_of(&self, pos: Pos2) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cells_of(&self, rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        let (x_min, y_min) = self.cell_of(rect.min);
+        let (x_max, y_max) = self.cell_of(rect.max);
+        (x_min..=x_max).flat_map(move |x| (y_min..=y_max).map(move |y| (x, y)))
+    }
+
+    /// Indices of shapes whose bounding boxes may intersect `rect`, in ascending order.
+    #[must_use]
+    pub fn query(&self, rect: Rect) -> Vec<usize> {
+        // Clamp to the indexed region so zooming far out does not visit empty cells
+        let rect = rect.intersect(self.bounds);
+        if rect.width() < 0.0 || rect.height() < 0.0 {
+            return Vec::new();
+        }
+        let mut result: Vec<usize> = self
+            .cells_of(rect)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /// Indices of shapes whose bounding boxes may lie within `radius` of `pos`, in ascending order.
+    #[must_use]
+    pub fn query_point(&self, pos: Pos2, radius: f32) -> Vec<usize> {
+        self.query(Rect::from_center_size(pos, Vec2::splat(radius * 2.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2, Rect};
+
+    use super::SpatialIndex;
+
+    /// `side` by `side` unit squares, `spacing` apart.
+    #[allow(clippy::cast_precision_loss)]
+    fn grid(side: usize, spacing: f32) -> Vec<Rect> {
+        (0..side * side)
+            .map(|i| {
+                let min = pos2((i % side) as f32 * spacing, (i / side) as f32 * spacing);
+                Rect::from_min_size(min, vec2(1.0, 1.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn whole_diagram_viewport() {
+        for spacing in [0.5, 2.0, 1000.0] {
+            let mut rects = grid(10, spacing);
+            // A thunk around everything
+            rects.push(
+                rects
+                    .iter()
+                    .fold(Rect::NOTHING, |bounds, rect| bounds.union(*rect)),
+            );
+            let index = SpatialIndex::new(rects.iter().copied());
+            let all: Vec<usize> = (0..rects.len()).collect();
+
+            assert_eq!(index.query(index.bounds), all);
+            // Zoomed out beyond the diagram
+            assert_eq!(index.query(index.bounds.expand(1e6)), all);
+            assert!(index.cells.len() <= rects.len() * 4);
+        }
+    }
+
+    #[test]
+    fn finds_shapes_under_point() {
+        let rects = grid(10, 1000.0);
+        let index = SpatialIndex::new(rects.iter().copied());
+        let centre = rects[42].center();
+        assert!(index.query_point(centre, 0.1).contains(&42));
+        assert!(index.query_point(pos2(-10.0, -10.0), 0.1).is_empty());
+    }
+
+    #[test]
+    fn empty() {
+        let index = SpatialIndex::new(std::iter::empty());
+        assert!(index.query(Rect::EVERYTHING).is_empty());
+    }
+}
@@ -1,11 +1,16 @@
-use egui::{emath::RectTransform, Pos2, Rect};
+use std::{io, num::NonZeroUsize, thread};
+
+use egui::{emath::RectTransform, Color32, Pos2, Rect};
 use sd_core::hypergraph::generic::Ctx;
 use svg::{
     node::element::{path::Data, Circle, Group, Line, Path, Rectangle, Text},
-    Document, Node,
+    Node,
 };
 
-use crate::shape::{Shape, Shapes};
+use crate::{
+    common::{ShapeKind, SEPARATOR_HEIGHT},
+    shape::{Shape, Shapes},
+};
 
 impl<T: Ctx> Shape<T> {
     pub(crate) fn to_svg(&self) -> Box<dyn Node> {
@@ -14,11 +19,20 @@ impl<T: Ctx> Shape<T> {
                 center,
                 radius,
                 label,
+                kind,
+                fill,
+                stroke,
                 ..
             } => {
                 let x_size = radius * (label.chars().count().max(1) as f32 + 1.0);
+                let fill = fill.map_or_else(|| "white".to_owned(), to_svg_color);
+                let stroke = stroke.map_or_else(|| "black".to_owned(), |s| to_svg_color(s.color));
                 Box::new(
                     Group::new()
+                        .set(
+                            "class",
+                            format!("operation {} {}", kind.class(), css_class("op-", label)),
+                        )
                         .add(
                             Rectangle::new()
                                 .set("x", center.x - x_size / 2.0)
@@ -27,12 +41,13 @@ impl<T: Ctx> Shape<T> {
                                 .set("height", radius * 2.0)
                                 .set("rx", *radius)
                                 .set("ry", *radius)
-                                .set("fill", "white")
-                                .set("stroke", "black")
+                                .set("fill", fill)
+                                .set("stroke", stroke.clone())
                                 .set("stroke-width", 1),
                         )
                         .add(
                             Text::new(html_escape::encode_text(label))
+                                .set("fill", stroke)
                                 .set("x", center.x)
                                 .set("y", center.y)
                                 .set("font-size", 16)
@@ -47,24 +62,44 @@ impl<T: Ctx> Shape<T> {
                     .set("cx", center.x)
                     .set("cy", center.y)
                     .set("r", *radius)
+                    .set("class", "dot")
                     .set("fill", "black"),
             ),
-            Self::Rectangle { rect, .. } => Box::new(
-                Rectangle::new()
+            Self::Rectangle {
+                rect, separator, ..
+            } => {
+                let rectangle = Rectangle::new()
                     .set("x", rect.min.x)
                     .set("y", rect.min.y)
                     .set("width", rect.width())
                     .set("height", rect.height())
+                    .set("class", "thunk")
                     .set("fill", "none")
                     .set("stroke", "gray")
-                    .set("stroke-width", 1),
-            ),
+                    .set("stroke-width", 1);
+                match separator {
+                    Some(x) => Box::new(
+                        Group::new().add(rectangle).add(
+                            Line::new()
+                                .set("x1", *x)
+                                .set("y1", rect.min.y)
+                                .set("x2", *x)
+                                .set("y2", rect.min.y + SEPARATOR_HEIGHT * Shapes::<T>::SCALE)
+                                .set("class", "thunk-separator")
+                                .set("stroke", "lightgray")
+                                .set("stroke-width", 1),
+                        ),
+                    ),
+                    None => Box::new(rectangle),
+                }
+            }
             Self::Line { start, end, .. } => Box::new(
                 Line::new()
                     .set("x1", start.x)
                     .set("y1", start.y)
                     .set("x2", end.x)
                     .set("y2", end.y)
+                    .set("class", "wire")
                     .set("stroke", "black")
                     .set("stroke-width", "1"),
             ),
@@ -81,37 +116,185 @@ impl<T: Ctx> Shape<T> {
                     ));
                 Path::new()
                     .set("d", data)
+                    .set("class", "wire")
                     .set("fill", "none")
                     .set("stroke", "black")
                     .set("stroke-width", 1)
             }),
+            Self::OutputLabel { center, label, .. } => Box::new(
+                Text::new(html_escape::encode_text(label))
+                    .set("class", "output-label")
+                    .set("fill", "gray")
+                    .set("x", center.x)
+                    .set("y", center.y)
+                    .set("font-size", 16)
+                    .set("font-family", "monospace")
+                    .set("text-anchor", "middle")
+                    .set("dominant-baseline", "hanging"),
+            ),
             Self::Arrow { .. } => {
-                panic!("Arrows should not be in svgs")
+                unreachable!("arrows are left out of svgs")
             }
         }
     }
 }
 
+fn to_svg_color(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+impl ShapeKind {
+    const fn class(self) -> &'static str {
+        match self {
+            Self::Squircle => "squircle",
+            Self::Square => "square",
+            Self::BulletUp => "bullet-up",
+            Self::BulletDown => "bullet-down",
+        }
+    }
+}
+
+/// A CSS class made from `prefix` and `name`, with characters not allowed in class names replaced
+/// by `_`.
+fn css_class(prefix: &str, name: &str) -> String {
+    let name = name.replace(
+        |c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "_",
+    );
+    format!("{prefix}{name}")
+}
+
 impl<T: Ctx> Shapes<T> {
-    const SCALE: f32 = 50.0;
+    pub(crate) const SCALE: f32 = 50.0;
 
-    #[must_use]
-    pub fn to_svg(&self) -> Document {
-        let mut document = Document::new()
-            .set("width", self.size.x * Self::SCALE)
-            .set("height", self.size.y * Self::SCALE);
+    /// Number of shapes converted to svg elements by a single thread at a time.
+    const CHUNK_SIZE: usize = 4096;
 
-        let scale = RectTransform::from_to(
+    pub(crate) fn svg_scale(&self) -> RectTransform {
+        RectTransform::from_to(
             Rect::from_min_size(Pos2::ZERO, self.size / Self::SCALE),
             Rect::from_min_size(Pos2::ZERO, self.size),
-        );
+        )
+    }
+
+    /// The svg document, as [`Self::write_svg`] writes it without a stylesheet.
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_svg(&mut bytes, None)
+            .expect("writing to a vector cannot fail");
+        String::from_utf8(bytes).expect("svg documents are utf-8")
+    }
+
+    /// Write the svg document to `writer` without building it in memory.
+    ///
+    /// Elements are generated for chunks of shapes in parallel,
+    /// and written out in order as soon as each batch of chunks is done.
+    /// Where threads cannot be spawned, as on the web, they are generated on this thread.
+    ///
+    /// The elements carry CSS classes by kind: `operation` (together with the kind of shape, such
+    /// as `squircle`, and `op-` followed by the label), `thunk`, `thunk-separator` (the tick
+    /// between the captured values and arguments of a thunk), `wire`, `dot` and `output-label` (the
+    /// names of the outputs of the diagram). Rules in `stylesheet` take precedence over the
+    /// default colours.
+    #[allow(clippy::needless_collect)]
+    pub fn write_svg(
+        &self,
+        writer: &mut impl io::Write,
+        stylesheet: Option<&str>,
+    ) -> io::Result<()> {
+        let scale = self.svg_scale();
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
 
-        for shape in &self.shapes {
-            let mut shape = shape.clone();
-            shape.apply_transform(&scale);
-            document = document.add(shape.to_svg());
+        // The view box lets the figure be scaled to fit when it is embedded in a paper or slides
+        let (width, height) = (self.size.x * Self::SCALE, self.size.y * Self::SCALE);
+        writeln!(
+            writer,
+            r#"<svg height="{height}" width="{width}" viewBox="0 0 {width} {height}" xmlns="http://www.w3.org/2000/svg">"#,
+        )?;
+        // So that the figure can be laid out the same way again
+        writeln!(writer, "<!-- simplification: {} -->", self.simplification)?;
+        if let Some(stylesheet) = stylesheet {
+            writeln!(
+                writer,
+                "<style><![CDATA[\n{}\n]]></style>",
+                stylesheet.replace("]]>", "]]]]><![CDATA[>")
+            )?;
         }
 
-        document
+        for batch in self.shapes.chunks(Self::CHUNK_SIZE * threads) {
+            if threads == 1 {
+                writer.write_all(chunk_to_svg(batch, &scale).as_bytes())?;
+                continue;
+            }
+            let chunks: Vec<String> = thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .chunks(Self::CHUNK_SIZE)
+                    .map(|chunk| scope.spawn(move || chunk_to_svg(chunk, &scale)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("svg export thread panicked"))
+                    .collect()
+            });
+            for chunk in chunks {
+                writer.write_all(chunk.as_bytes())?;
+            }
+        }
+
+        writeln!(writer, "</svg>")
+    }
+}
+
+/// The svg elements for `shapes`, one to a line, placed by `scale`.
+fn chunk_to_svg<T: Ctx>(shapes: &[Shape<T>], scale: &RectTransform) -> String {
+    let mut out = String::new();
+    // Arrows only show where thunks can be expanded on screen
+    for shape in shapes
+        .iter()
+        .filter(|shape| !matches!(shape, Shape::Arrow { .. }))
+    {
+        let mut shape = shape.clone();
+        shape.apply_transform(scale);
+        out.push_str(&shape.to_svg().to_string());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{vec2, Pos2};
+    use sd_core::examples::{DummyCtx, DummyEdge};
+
+    use super::css_class;
+    use crate::shape::{Shape, Shapes};
+
+    #[test]
+    fn css_classes() {
+        assert_eq!(css_class("op-", "plus"), "op-plus");
+        assert_eq!(css_class("op-", "bool/and"), "op-bool_and");
+        assert_eq!(css_class("op-", "arith.addi"), "op-arith_addi");
+    }
+
+    #[test]
+    fn documents_match_streamed_svg() -> Result<(), Box<dyn std::error::Error>> {
+        let shapes: Shapes<DummyCtx> = Shapes::new(
+            vec![Shape::Line {
+                start: Pos2::new(0.5, 0.0),
+                end: Pos2::new(0.5, 1.0),
+                addr: DummyEdge,
+                fanout: 1,
+            }],
+            vec2(1.0, 1.0),
+        );
+        let mut bytes = Vec::new();
+        shapes.write_svg(&mut bytes, None)?;
+        let document = shapes.to_svg();
+        assert_eq!(document, String::from_utf8(bytes)?);
+        assert!(document.starts_with(r#"<svg height="50" width="50" viewBox="0 0 50 50""#));
+        assert!(document.contains("<!-- simplification:"));
+        assert!(document.ends_with("</svg>\n"));
+        Ok(())
     }
 }
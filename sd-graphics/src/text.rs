@@ -0,0 +1,111 @@
+use std::{collections::HashMap, sync::Arc};
+
+use egui::{
+    epaint::{Mesh, Vertex},
+    vec2, Align2, Color32, FontId, Galley, Id, Pos2,
+};
+
+/// Maximum number of galleys kept around between frames.
+const CAPACITY: usize = 4096;
+
+/// Number of discrete text sizes per doubling of the zoom level.
+const STEPS_PER_OCTAVE: f32 = 8.0;
+
+/// Galleys for operation labels, keyed by label and quantised text size.
+///
+/// Galleys are laid out with a placeholder colour, so they can be reused across themes. They
+/// refer to glyphs in the font atlas, so are only valid for the atlas size and pixels per point
+/// they were laid out with.
+#[derive(Clone, Default)]
+struct GalleyCache {
+    fonts: ([usize; 2], u32),
+    galleys: HashMap<(String, u32), Arc<Galley>>,
+}
+
+/// Round a text size to one of a fixed set of sizes, so that labels do not need to be laid out
+/// again on every frame while zooming.
+fn quantise(text_size: f32) -> f32 {
+    ((text_size.log2() * STEPS_PER_OCTAVE).round() / STEPS_PER_OCTAVE).exp2()
+}
+
+/// Lay out `label` at the quantised `text_size`, reusing a previous layout where possible.
+fn label_galley(ui: &egui::Ui, label: &str, text_size: f32) -> Arc<Galley> {
+    let key = (label.to_owned(), text_size.to_bits());
+    let fonts = (
+        ui.fonts(|fonts| fonts.font_image_size()),
+        ui.ctx().pixels_per_point().to_bits(),
+    );
+    let id = Id::new("label_galleys");
+
+    if let Some(galley) = ui.data_mut(|data| {
+        let cache = data.get_temp_mut_or_default::<GalleyCache>(id);
+        if cache.fonts != fonts {
+            cache.fonts = fonts;
+            cache.galleys.clear();
+        }
+        cache.galleys.get(&key).cloned()
+    }) {
+        return galley;
+    }
+
+    let galley = ui.fonts(|fonts| {
+        fonts.layout_no_wrap(
+            label.to_owned(),
+            FontId::monospace(text_size),
+            Color32::PLACEHOLDER,
+        )
+    });
+
+    ui.data_mut(|data| {
+        let cache = data.get_temp_mut_or_default::<GalleyCache>(id);
+        if cache.galleys.len() >= CAPACITY {
+            cache.galleys.clear();
+        }
+        cache.galleys.insert(key, galley.clone());
+    });
+
+    galley
+}
+
+/// The shape of `label` at `text_size`, placed at `pos` by `align`.
+///
+/// The label is laid out at a nearby quantised size and its mesh scaled to `text_size`, so that
+/// labels grow smoothly while zooming.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn label_shape(
+    ui: &egui::Ui,
+    label: &str,
+    text_size: f32,
+    align: Align2,
+    pos: Pos2,
+    color: Color32,
+) -> egui::Shape {
+    let quantised = quantise(text_size);
+    let galley = label_galley(ui, label, quantised);
+    let scale = text_size / quantised;
+    let origin = align.anchor_size(pos, galley.size() * scale).min;
+
+    // Glyphs are given in texels of the font atlas, as the tessellator normalises them for text
+    let [width, height] = ui.fonts(|fonts| fonts.font_image_size());
+    let uv_normaliser = vec2(1.0 / width as f32, 1.0 / height as f32);
+
+    let mut mesh = Mesh::default();
+    for row in &galley.rows {
+        let mesh_row = &row.visuals.mesh;
+        #[allow(clippy::cast_possible_truncation)]
+        let offset = mesh.vertices.len() as u32;
+        mesh.indices
+            .extend(mesh_row.indices.iter().map(|index| index + offset));
+        mesh.vertices
+            .extend(mesh_row.vertices.iter().map(|vertex| Vertex {
+                pos: origin + vertex.pos.to_vec2() * scale,
+                uv: (vertex.uv.to_vec2() * uv_normaliser).to_pos2(),
+                color: if vertex.color == Color32::PLACEHOLDER {
+                    color
+                } else {
+                    vertex.color
+                },
+            }));
+    }
+    egui::Shape::mesh(mesh)
+}
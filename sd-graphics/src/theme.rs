@@ -0,0 +1,61 @@
+use egui::{Color32, Id};
+
+/// Colours for the elements of diagrams. Each one which is not given follows the egui visuals,
+/// so that a theme only needs to set the colours it changes.
+///
+/// The theme in use is kept in the egui context, like the visuals, so that every diagram drawn
+/// with the context picks it up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Theme {
+    /// The fill of operations which are neither hovered nor selected.
+    pub node_fill: Option<Color32>,
+    /// The outline of operations which are neither hovered nor selected.
+    pub node_stroke: Option<Color32>,
+    /// The colour of wires carrying data.
+    pub edge: Option<Color32>,
+    /// The outline of thunks which are not selected.
+    pub thunk_border: Option<Color32>,
+    /// The colour of the wires crossing each other in swaps.
+    pub swap: Option<Color32>,
+    /// The colour of the dots where values are copied.
+    pub copy: Option<Color32>,
+}
+
+impl Theme {
+    /// The theme in use in `ctx`.
+    #[must_use]
+    pub fn get(ctx: &egui::Context) -> Self {
+        ctx.data(|data| data.get_temp(Self::id()))
+            .unwrap_or_default()
+    }
+
+    /// Uses this theme for the diagrams drawn with `ctx`.
+    pub fn set(self, ctx: &egui::Context) {
+        ctx.data_mut(|data| data.insert_temp(Self::id(), self));
+    }
+
+    /// Each colour of the theme, with its key in theme files and a description.
+    #[must_use]
+    pub fn colors(&self) -> [(&'static str, &'static str, Option<Color32>); 6] {
+        let mut theme = *self;
+        theme
+            .colors_mut()
+            .map(|(key, name, color)| (key, name, *color))
+    }
+
+    /// Each colour of the theme, with its key in theme files and a description, to change it.
+    pub fn colors_mut(&mut self) -> [(&'static str, &'static str, &mut Option<Color32>); 6] {
+        [
+            ("node_fill", "Operation fill", &mut self.node_fill),
+            ("node_stroke", "Operation outline", &mut self.node_stroke),
+            ("edge", "Wires", &mut self.edge),
+            ("thunk_border", "Thunk outline", &mut self.thunk_border),
+            ("swap", "Swapped wires", &mut self.swap),
+            ("copy", "Copies", &mut self.copy),
+        ]
+    }
+
+    fn id() -> Id {
+        Id::new("sd_graphics_theme")
+    }
+}
@@ -0,0 +1,213 @@
+//! Exporting diagrams as TikZ pictures, to include in LaTeX documents.
+//!
+//! The picture uses the coordinates of the layout as centimetres, with y growing downwards as on
+//! screen, and draws each kind of element with a style set once at its top: `operation`,
+//! `op label`, `thunk`, `separator`, `wire`, `dot` and `output label`. A figure can be restyled by
+//! changing those, or adjusted by hand by moving the coordinates of the elements.
+//!
+//! Labels are written as they are shown, so those with symbols outside of ASCII need a LaTeX
+//! engine which understands Unicode.
+
+use std::io;
+
+use egui::{Color32, Pos2};
+use sd_core::hypergraph::generic::Ctx;
+
+use crate::{
+    common::{ShapeKind, SEPARATOR_HEIGHT},
+    shape::{Shape, Shapes},
+};
+
+/// The styles of the elements, matching the default colours of svg exports.
+const STYLES: &str = r"    operation/.style={draw, fill=white, line width=0.4pt},
+    op label/.style={font=\ttfamily\small, inner sep=0pt},
+    thunk/.style={draw=gray, line width=0.4pt},
+    separator/.style={draw=lightgray, line width=0.4pt},
+    wire/.style={draw, line width=0.4pt},
+    dot/.style={fill},
+    output label/.style={op label, text=gray, anchor=north, yshift=-2pt},";
+
+impl<T: Ctx> Shape<T> {
+    pub(crate) fn to_tikz(&self) -> String {
+        match self {
+            Self::Operation {
+                center,
+                radius,
+                label,
+                kind,
+                fill,
+                stroke,
+                ..
+            } => {
+                let half_width = radius * (label.chars().count().max(1) as f32 + 1.0) / 2.0;
+                let (left, right) = (center.x - half_width, center.x + half_width);
+                let (top, bottom) = (center.y - radius, center.y + radius);
+                let corner = |x, y| point(Pos2::new(x, y));
+                let r = number(*radius);
+                let outline = match kind {
+                    ShapeKind::Squircle => format!(
+                        "[rounded corners={r}cm] {} rectangle {}",
+                        corner(left, top),
+                        corner(right, bottom)
+                    ),
+                    ShapeKind::Square => {
+                        format!("{} rectangle {}", corner(left, top), corner(right, bottom))
+                    }
+                    ShapeKind::BulletUp => format!(
+                        "{} [rounded corners={r}cm] -- {} -- {} [sharp corners] -- {} -- cycle",
+                        corner(left, bottom),
+                        corner(left, top),
+                        corner(right, top),
+                        corner(right, bottom)
+                    ),
+                    ShapeKind::BulletDown => format!(
+                        "{} [rounded corners={r}cm] -- {} -- {} [sharp corners] -- {} -- cycle",
+                        corner(left, top),
+                        corner(left, bottom),
+                        corner(right, bottom),
+                        corner(right, top)
+                    ),
+                };
+                let mut style = "operation".to_owned();
+                if let Some(fill) = fill {
+                    style.push_str(&format!(", fill={}", to_tikz_color(*fill)));
+                }
+                let mut label_style = "op label".to_owned();
+                if let Some(stroke) = stroke {
+                    let color = to_tikz_color(stroke.color);
+                    style.push_str(&format!(", draw={color}"));
+                    label_style.push_str(&format!(", text={color}"));
+                }
+                format!(
+                    "\\draw[{style}] {outline};\n\\node[{label_style}] at {} {{{}}};",
+                    point(*center),
+                    escape(label)
+                )
+            }
+            Self::CircleFilled { center, radius, .. } => format!(
+                "\\fill[dot] {} circle[radius={}];",
+                point(*center),
+                number(*radius)
+            ),
+            Self::Rectangle {
+                rect, separator, ..
+            } => {
+                let thunk = format!(
+                    "\\draw[thunk] {} rectangle {};",
+                    point(rect.min),
+                    point(rect.max)
+                );
+                match separator {
+                    Some(x) => format!(
+                        "{thunk}\n\\draw[separator] {} -- {};",
+                        point(Pos2::new(*x, rect.min.y)),
+                        point(Pos2::new(*x, rect.min.y + SEPARATOR_HEIGHT))
+                    ),
+                    None => thunk,
+                }
+            }
+            Self::Line { start, end, .. } => {
+                format!("\\draw[wire] {} -- {};", point(*start), point(*end))
+            }
+            Self::CubicBezier { points, .. } => format!(
+                "\\draw[wire] {} .. controls {} and {} .. {};",
+                point(points[0]),
+                point(points[1]),
+                point(points[2]),
+                point(points[3])
+            ),
+            Self::OutputLabel { center, label, .. } => format!(
+                "\\node[output label] at {} {{{}}};",
+                point(*center),
+                escape(label)
+            ),
+            Self::Arrow { .. } => {
+                unreachable!("arrows are left out of TikZ pictures")
+            }
+        }
+    }
+}
+
+/// A coordinate rounded to a hundredth of a millimetre, which is as fine as anyone can see.
+fn number(x: f32) -> String {
+    let x = format!("{x:.3}");
+    let x = x.trim_end_matches('0').trim_end_matches('.');
+    match x {
+        "-0" => "0".to_owned(),
+        _ => x.to_owned(),
+    }
+}
+
+fn point(pos: Pos2) -> String {
+    format!("({}, {})", number(pos.x), number(pos.y))
+}
+
+fn to_tikz_color(color: Color32) -> String {
+    format!(
+        "{{rgb,255:red,{};green,{};blue,{}}}",
+        color.r(),
+        color.g(),
+        color.b()
+    )
+}
+
+/// `text` with the characters which mean something to LaTeX escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<T: Ctx> Shapes<T> {
+    /// Write the diagram to `writer` as a TikZ picture, to `\input` into a LaTeX document which
+    /// loads the `tikz` package.
+    pub fn write_tikz(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        // So that the figure can be laid out the same way again
+        writeln!(writer, "% simplification: {}", self.simplification)?;
+        writeln!(writer, "\\begin{{tikzpicture}}[x=1cm, y=-1cm,\n{STYLES}\n]")?;
+        // Arrows only show where thunks can be expanded on screen
+        for shape in self
+            .shapes
+            .iter()
+            .filter(|shape| !matches!(shape, Shape::Arrow { .. }))
+        {
+            writeln!(writer, "{}", shape.to_tikz())?;
+        }
+        writeln!(writer, "\\end{{tikzpicture}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, number};
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number(1.0), "1");
+        assert_eq!(number(0.25), "0.25");
+        assert_eq!(number(2.123_456), "2.123");
+        assert_eq!(number(-0.000_1), "0");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(escape("plus"), "plus");
+        assert_eq!(escape("bool/and"), "bool/and");
+        assert_eq!(escape("x_1 & y#2"), "x\\_1 \\& y\\#2");
+        assert_eq!(
+            escape("{a^b}\\~"),
+            "\\{a\\textasciicircum{}b\\}\\textbackslash{}\\textasciitilde{}"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use eframe::egui::Color32;
+use sd_core::{common::Query, hypergraph::diff::Change};
+use sd_graphics::common::Badge;
+
+use crate::diff::change_color;
+
+/// How an external differ says a node was changed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ChangeTag {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl ChangeTag {
+    const fn text(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Modified => "modified",
+        }
+    }
+
+    const fn color(self) -> Color32 {
+        change_color(match self {
+            Self::Added => Change::Added,
+            Self::Removed => Change::Removed,
+            Self::Modified => Change::Changed,
+        })
+    }
+}
+
+impl FromStr for ChangeTag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "added" => Ok(Self::Added),
+            "removed" => Ok(Self::Removed),
+            "modified" => Ok(Self::Modified),
+            _ => bail!("unknown change tag {s:?}, expected added, removed or modified"),
+        }
+    }
+}
+
+/// Parses a sidecar file produced by an external differ into badges to draw on the graph.
+///
+/// Each line is a node address followed by a change tag, for example `%3 modified`. A node is
+/// tagged if it matches the address exactly, rather than containing it as a search would. Blank
+/// lines and lines starting with `#` are ignored.
+pub(crate) fn parse_annotations(source: &str) -> anyhow::Result<Vec<Badge>> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let (address, tag) = line
+                .rsplit_once(char::is_whitespace)
+                .ok_or_else(|| anyhow!("expected `ADDRESS TAG`, found {line:?}"))
+                .and_then(|(address, tag)| Ok((address.trim(), tag.parse::<ChangeTag>()?)))
+                .with_context(|| format!("line {}", i + 1))?;
+            Ok(Badge {
+                query: Query::Exact(address.to_owned()),
+                text: tag.text().to_owned(),
+                color: tag.color(),
+            })
+        })
+        .collect()
+}
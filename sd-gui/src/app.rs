@@ -1,12 +1,19 @@
 use std::{
+    collections::BTreeSet,
+    panic::AssertUnwindSafe,
     sync::{
         mpsc::{channel, Receiver, Sender},
-        Arc, Mutex,
+        Arc, Mutex, PoisonError,
     },
     task::Poll,
+    time::Duration,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
 use anyhow::anyhow;
+use clap::ValueEnum;
 use eframe::{
     egui::{self, FontDefinitions},
     emath::{Align, Align2},
@@ -15,27 +22,126 @@ use eframe::{
 use egui_notify::Toasts;
 use poll_promise::Promise;
 use sd_core::{
-    common::Direction,
-    dot::{dot_to_graph, DotSettings},
+    common::{Direction, Query},
+    dot::DotSettings,
     language::mlir::MlirSettings,
     lp::Solver,
+    monoidal::Simplification,
+    profile::Profile,
+};
+use sd_graphics::{
+    common::{type_color, Badge, LabelVisibility},
+    theme::Theme,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    annotations::parse_annotations,
+    diagnostics::write_bundle,
+    diff::DiffView,
+    export::{export_html, ExportDialog, ExportFormat, PendingExport},
+    watch::FileWatcher,
+};
 use crate::{
+    autosave::Autosave,
+    bookmarks::Bookmarks,
     code_generator::clear_code_cache,
     code_ui::code_ui,
-    graph_ui::GraphUi,
-    parser::{parse, ParseError, ParseOutput, UiLanguage},
-    selection::Selection,
-    shape_generator::clear_shape_cache,
-    squiggly_line::show_parse_error,
+    crash::{install_hook, take_panic, Panic},
+    disk_cache::CacheKey,
+    files::{FileSearch, Files, OpenFile},
+    graph_ui::{GraphUi, Overlays},
+    help::{self, Help},
+    parser::{parse_with_warnings, ParseError, ParseOutput, UiLanguage},
+    progress,
+    recorder::{Action, Recorder},
+    rename::RenameVariable,
+    rewrites::Rewrites,
+    selection::{locate, Promotion, Selection, SelectionRequest},
+    session::{self, SavedSelection},
+    settings,
+    shape_generator::{
+        clear_shape_cache, set_simplification, simplification, take_solver_failures,
+    },
+    squiggly_line::{show_chil_parse_error, show_compile_error, show_parse_error},
+    theme::ThemeEditor,
+    tutorial::{Step, Tutorial},
+    view_script::ViewScript,
+};
+#[cfg(target_arch = "wasm32")]
+use crate::{
+    pen,
+    web_api::{self, Request},
 };
+#[cfg(target_arch = "wasm32")]
+use sd_core::interactive::Toggle;
 
 #[derive(Debug, Clone)]
 enum Message {
     Compile,
     SetLanguage(UiLanguage),
+    OpenFile(OpenFile),
     ParseError(ParseError),
+    ParseWarnings(Vec<String>),
+}
+
+/// The number of nodes and edges beyond which the browser may freeze while laying out a graph.
+const SIZE_BUDGET: usize = 5_000;
+
+/// How long to wait after an edit before recompiling, when compiling as the program is typed.
+const LIVE_COMPILE_DELAY: Duration = Duration::from_millis(750);
+
+/// The number of nodes and edges drawn at first when large programs are collapsed on opening.
+const AUTO_COLLAPSE_BUDGET: usize = 2_000;
+
+/// The deepest cone of reachable nodes the depth slider goes up to.
+const MAX_CONE_DEPTH: usize = 20;
+
+/// A change made to the list of selections from the selection panel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SelectionEdit {
+    /// Gives the selection being renamed its new name.
+    Rename,
+    CancelRename,
+    Duplicate(usize),
+    /// Swaps the selection at the index with the one after it.
+    Swap(usize),
+    Delete(usize),
+}
+
+/// Whether a freshly compiled graph may be laid out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SizeCheck {
+    Unchecked,
+    /// The graph, of the given size, is over the budget and the user has not yet chosen what to do.
+    OverBudget(usize),
+    Accepted,
+}
+
+/// The search typed into the find panel.
+#[derive(Debug)]
+struct Find {
+    text: String,
+    /// The parsed text, or why it is not a valid regular expression.
+    query: Result<Query, String>,
+    /// Which of the matches to pan to next.
+    offset: usize,
+}
+
+impl Find {
+    fn new(text: String) -> Self {
+        let query = Query::parse(&text).map_err(|err| err.to_string());
+        Self {
+            text,
+            query,
+            offset: 0,
+        }
+    }
+
+    /// The query to search for, unless nothing valid has been typed yet.
+    fn query(&self) -> Option<&Query> {
+        self.query.as_ref().ok().filter(|_| !self.text.is_empty())
+    }
 }
 
 pub struct App {
@@ -43,17 +149,80 @@ pub struct App {
     tx: Sender<Message>,
     rx: Receiver<Message>,
     about: bool,
+    help: Help,
     editor: bool,
     code: Arc<Mutex<String>>,
     last_parse: Option<Arc<Mutex<Promise<Option<ParseOutput>>>>>,
     last_parse_error: Option<ParseError>,
+    /// Whether to recompile the program shortly after each edit which parses.
+    live_compile: bool,
+    /// When the code was last edited, if it has not been compiled since.
+    edited: Option<f64>,
+    /// The code last compiled and its language, to undo the edits made to it since.
+    compiled: Option<(String, UiLanguage)>,
     language: UiLanguage,
     dot_settings: DotSettings,
     mlir_settings: MlirSettings,
+    /// Whether to float bindings into the thunks which use them, and shared ones out, for display.
+    float_lets: bool,
+    /// The top-level definitions of a Chil program to draw as single operations.
+    collapsed_clusters: BTreeSet<String>,
     graph_ui: Option<Promise<anyhow::Result<GraphUi>>>,
+    size_check: SizeCheck,
     selections: Vec<Selection>,
-    find: Option<(String, usize)>,
+    /// The direction and depth of the cone of reachable nodes being selected, while its window
+    /// is open.
+    cone: Option<(Direction, usize)>,
+    /// The index of the selection being renamed, and its new name so far.
+    renaming: Option<(usize, String)>,
+    /// The variable of the program being renamed, after double-clicking a wire carrying it.
+    variable_rename: Option<RenameVariable>,
+    find: Option<Find>,
+    /// A query to find once the file being compiled is shown, after jumping to it from a search
+    /// across files.
+    pending_find: Option<String>,
+    /// A view script to apply to the file being compiled once it is shown.
+    pending_view: Option<ViewScript>,
+    /// Selections to restore once the file being compiled is shown.
+    pending_selections: Vec<SavedSelection>,
+    files: Files,
+    file_search: Option<FileSearch>,
+    bookmarks: Bookmarks,
+    recorder: Recorder,
+    rewrites: Rewrites,
+    labels: LabelVisibility,
+    /// Change tags from an external differ, drawn on the nodes they refer to.
+    badges: Vec<Badge>,
+    /// Values for nodes from a profiler, drawn as a heatmap over the operations.
+    profile: Option<Arc<Profile>>,
+    overlays: Overlays,
+    /// The colours of the elements of diagrams, over those of the light or dark mode.
+    theme: Theme,
+    theme_editor: ThemeEditor,
+    /// How many nodes and edges to draw at most when a program is opened, expanding thunks
+    /// breadth first, or none to expand every thunk.
+    auto_collapse: Option<usize>,
     toasts: Toasts,
+    tutorial: Tutorial,
+    autosave: Autosave,
+    /// A program left behind by a session that crashed, which the user has not yet restored or
+    /// discarded.
+    recovered: Option<(String, UiLanguage)>,
+    /// The message and bug report of the last panic, until the user dismisses it.
+    crash: Option<(String, String)>,
+    /// Whether the source should go in the diagnostic bundle, while its dialog is open.
+    diagnostics: Option<bool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    export: Option<ExportDialog>,
+    /// Drawings being written to files, until they are done.
+    #[cfg(not(target_arch = "wasm32"))]
+    exports: Vec<PendingExport>,
+    /// The window comparing an earlier version of the program with the code being edited.
+    #[cfg(not(target_arch = "wasm32"))]
+    diff: Option<DiffView>,
+    /// The files the program was opened from on the command line, reloaded when they change.
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher: Option<FileWatcher>,
     solver: Solver,
 }
 
@@ -64,12 +233,6 @@ impl App {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
-        // Load previous app state (if any).
-        // Note that you must enable the `persistence` feature for this to work.
-        // if let Some(storage) = cc.storage {
-        //     return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
-        // }
-
         let font_name = "mono_font".to_owned();
 
         let mut font_definitions = FontDefinitions::default();
@@ -87,27 +250,94 @@ impl App {
 
         cc.egui_ctx.set_fonts(font_definitions);
 
+        install_hook();
+        #[cfg(target_arch = "wasm32")]
+        {
+            web_api::install(&cc.egui_ctx);
+            pen::install(&cc.egui_ctx);
+        }
+
         let (tx, rx) = channel();
-        Self {
+        let (autosave, recovered) = Autosave::recover();
+        let mut app = Self {
             tx,
             rx,
             about: Default::default(),
+            help: Help::default(),
             editor: Default::default(),
             code: Arc::default(),
             last_parse: Option::default(),
             last_parse_error: Option::default(),
+            live_compile: false,
+            edited: None,
+            compiled: None,
             language: UiLanguage::default(),
             dot_settings: DotSettings::default(),
-            mlir_settings: MlirSettings::default(),
+            mlir_settings: settings::load_mlir(),
+            float_lets: false,
+            collapsed_clusters: BTreeSet::default(),
             graph_ui: Option::default(),
+            size_check: SizeCheck::Unchecked,
             selections: Vec::default(),
+            cone: None,
+            renaming: None,
+            variable_rename: None,
             find: None,
+            pending_find: None,
+            pending_view: None,
+            pending_selections: Vec::default(),
+            files: Files::default(),
+            file_search: None,
+            bookmarks: Bookmarks::default(),
+            recorder: Recorder::default(),
+            rewrites: Rewrites::new(solver),
+            labels: LabelVisibility::default(),
+            badges: Vec::default(),
+            profile: None,
+            overlays: Overlays::default(),
+            theme: cc.storage.map(session::load_theme).unwrap_or_default(),
+            theme_editor: ThemeEditor::default(),
+            auto_collapse: Some(AUTO_COLLAPSE_BUDGET),
             toasts: Toasts::default(),
+            // Without storage the tour could never be marked as seen, so it is not forced on every
+            // launch
+            tutorial: Tutorial::new(cc.storage.map_or(true, session::load_tour_seen)),
+            autosave,
+            recovered,
+            crash: None,
+            diagnostics: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            export: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            exports: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            diff: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            watcher: None,
             solver,
+        };
+
+        // Pick up where the last session left off, unless a program is opened on the command line
+        if let Some((code, view, selections)) = cc.storage.and_then(session::load) {
+            // A crash since the session was last saved leaves the same program in the backup
+            if app
+                .recovered
+                .as_ref()
+                .is_some_and(|(recovered, _)| *recovered == code)
+            {
+                app.recovered = None;
+                app.autosave.clear();
+            }
+            let language = view.language.unwrap_or_default();
+            app.set_view(&code, language, view);
+            app.pending_selections = selections;
         }
+        app
     }
 
     pub fn set_file(&mut self, code: &str, language: Option<UiLanguage>) {
+        self.pending_view = None;
+        self.pending_selections.clear();
         *self.code.lock().unwrap() = code.to_string();
         if let Some(language) = language {
             self.tx
@@ -119,30 +349,433 @@ impl App {
             .expect("failed to send message");
     }
 
+    /// Shows `code`, collapsing and selecting its nodes and moving the view as recorded in `view`
+    /// once it has been compiled.
+    pub fn set_view(&mut self, code: &str, language: UiLanguage, view: ViewScript) {
+        if let Some(level) = view.simplification {
+            set_simplification(level);
+        }
+        self.set_file(code, Some(language));
+        self.pending_view = Some(view);
+    }
+
+    /// Reloads the program from `paths`, which are in `language`, whenever they change on disk.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watch(&mut self, ctx: &egui::Context, paths: Vec<PathBuf>, language: UiLanguage) {
+        match FileWatcher::new(paths, language, ctx) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(err) => {
+                self.toasts
+                    .error(format!("Failed to watch the program: {err}"));
+            }
+        }
+    }
+
+    /// Reloads the program if the files it was opened from have changed, keeping the view.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn reload(&mut self, ctx: &egui::Context) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if !watcher.poll(ctx) {
+            return;
+        }
+        let (name, language) = (watcher.name(), watcher.language());
+        match watcher.read() {
+            // Touching a file without changing it is not worth laying it out again
+            Ok(code) if code == *self.code.lock().unwrap() => {}
+            Ok(code) => {
+                let view = self.view_script();
+                self.set_view(&code, language, view);
+                self.toasts.info(format!("Reloaded {name}"));
+            }
+            Err(err) => {
+                self.toasts
+                    .error(format!("Failed to reload {name}: {err:#}"));
+            }
+        }
+    }
+
+    /// Takes an action on the graph, recording it if a macro is being recorded.
+    /// Returns false if there was nothing for the action to act on.
+    fn apply(&mut self, action: &Action) -> bool {
+        let Some(graph_ui) = finished_mut(&mut self.graph_ui) else {
+            return false;
+        };
+        match action {
+            Action::Toggle(toggle) => {
+                if !graph_ui.toggle(toggle) {
+                    return false;
+                }
+            }
+            Action::SetExpandedAll(expanded) => {
+                graph_ui.set_expanded_all(*expanded);
+                graph_ui.reset();
+            }
+            Action::ExtendSelection(direction) => graph_ui.extend_selection(*direction),
+            Action::ClearSelection => {
+                clear_code_cache();
+                graph_ui.clear_selection();
+            }
+            Action::SaveSelection => {
+                if let Some(sel) = Selection::from_graph(
+                    graph_ui,
+                    format!("Selection {}", self.selections.len()),
+                    self.solver,
+                ) {
+                    self.selections.push(sel);
+                }
+                graph_ui.clear_selection();
+            }
+        }
+        self.recorder.record(action.clone());
+        true
+    }
+
+    /// Replays the recorded macro on the graph shown.
+    fn replay(&mut self) {
+        let actions = self.recorder.actions().to_vec();
+        let missed = actions.iter().filter(|action| !self.apply(action)).count();
+        if missed > 0 {
+            self.toasts.warning(format!(
+                "{missed} of the {} recorded actions had no node to act on",
+                actions.len()
+            ));
+        }
+    }
+
+    /// Opens `file` alongside the other open files and shows it.
+    fn open_file(&mut self, file: OpenFile) {
+        let code = self.code.lock().unwrap().clone();
+        self.set_file(&file.code, Some(file.language));
+        self.files.open(file, &code);
+    }
+
+    /// Opens the code of a selection as a file, first keeping the program in the editor among the
+    /// open files if it was not opened from one.
+    fn open_selection(&mut self, file: OpenFile) {
+        if self.files.current().is_none() {
+            let code = self.code.lock().unwrap().clone();
+            if !code.trim().is_empty() {
+                let main = OpenFile {
+                    name: format!("main.{}", self.language.extension()),
+                    code: code.clone(),
+                    language: self.language,
+                };
+                self.files.open(main, &code);
+            }
+        }
+        self.open_file(file);
+    }
+
+    /// Shows the open file at `index`, keeping the edits made to the current one.
+    fn switch_file(&mut self, index: usize) {
+        let code = self.code.lock().unwrap().clone();
+        let file = self.files.switch(index, &code).clone();
+        self.set_file(&file.code, Some(file.language));
+    }
+
+    /// Finds the query of a search across files in the file it jumped to, once it is laid out.
+    fn pending_find(&mut self) {
+        if self.size_check != SizeCheck::Accepted {
+            return;
+        }
+        if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+            if let Some(text) = self.pending_find.take() {
+                let mut find = Find::new(text);
+                if let Some(query) = find.query() {
+                    graph_ui.expand_matches(query);
+                    graph_ui.find(query, 0);
+                    find.offset = 1;
+                }
+                self.find = Some(find);
+            }
+        }
+    }
+
+    /// Applies a view script to the file it was loaded with, once it is laid out.
+    fn pending_view(&mut self) {
+        if self.size_check != SizeCheck::Accepted {
+            return;
+        }
+        if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+            if let Some(view) = self.pending_view.take() {
+                let missed = view
+                    .toggles
+                    .iter()
+                    .filter(|toggle| !graph_ui.toggle(toggle))
+                    .count();
+                if missed > 0 {
+                    self.toasts.warning(format!(
+                        "{missed} of the {} nodes in the view script are not in the program",
+                        view.toggles.len()
+                    ));
+                }
+                if let Some(panzoom) = view.panzoom {
+                    graph_ui.set_panzoom(panzoom);
+                }
+            }
+        }
+    }
+
+    /// Restores the selections of the last session to the file they were saved with, once it is
+    /// laid out.
+    fn pending_selections(&mut self) {
+        if self.size_check != SizeCheck::Accepted || self.pending_selections.is_empty() {
+            return;
+        }
+        if let Some(graph_ui) = finished(&self.graph_ui) {
+            for (name, addresses, view) in std::mem::take(&mut self.pending_selections) {
+                if let Some(mut selection) =
+                    Selection::from_addresses(graph_ui, name, &addresses, self.solver)
+                {
+                    if let Some(view) = view {
+                        selection.set_view(view);
+                    }
+                    self.selections.push(selection);
+                }
+            }
+        }
+    }
+
+    /// The selections to save with the session, including those not yet restored.
+    fn saved_selections(&self) -> Vec<SavedSelection> {
+        self.selections
+            .iter()
+            .map(|selection| {
+                (
+                    selection.name().to_owned(),
+                    selection.addresses().to_vec(),
+                    selection.view(),
+                )
+            })
+            .chain(self.pending_selections.iter().cloned())
+            .collect()
+    }
+
+    /// A script which recreates the view of the graph shown, if any.
+    fn view_script(&self) -> ViewScript {
+        let graph_ui = finished(&self.graph_ui);
+        ViewScript {
+            file: self.files.current().map(|file| file.name.clone()),
+            language: Some(self.language),
+            toggles: graph_ui.map(GraphUi::toggles).unwrap_or_default(),
+            panzoom: graph_ui.map(GraphUi::panzoom),
+            simplification: Some(simplification()),
+        }
+    }
+
+    /// Saves a script which recreates the view of the graph shown.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_view(&mut self) {
+        if finished(&self.graph_ui).is_none() {
+            return;
+        }
+        let view = self.view_script();
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("View script", &["sdview"])
+            .set_file_name("view.sdview")
+            .save_file()
+        else {
+            return;
+        };
+        match std::fs::write(&path, view.to_string()) {
+            Ok(()) => self.toasts.info("Saved view script"),
+            Err(err) => self
+                .toasts
+                .error(format!("Failed to save view script: {err}")),
+        };
+    }
+
     fn code_edit_ui(&mut self, ui: &mut egui::Ui) {
         let text_edit_out = code_ui(ui, &mut *self.code.lock().unwrap(), self.language);
 
         if text_edit_out.response.changed() {
             tracing::trace!("code changed changed");
             self.trigger_parse(ui.ctx(), false);
+            if self.live_compile {
+                self.edited = Some(ui.input(|i| i.time));
+            }
         }
         if let Some(error) = &self.last_parse_error {
             match error {
-                ParseError::Chil(err) => show_parse_error(ui, err, &text_edit_out),
+                ParseError::Chil(err) => show_chil_parse_error(ui, err, &text_edit_out),
                 ParseError::Mlir(err) => show_parse_error(ui, err, &text_edit_out),
                 ParseError::Spartan(err) => show_parse_error(ui, err, &text_edit_out),
                 ParseError::Dot(_) | ParseError::Conversion(_) => (),
             }
         }
+        // Spans only point into the code they were compiled from
+        let compiled = self
+            .compiled
+            .as_ref()
+            .is_some_and(|(code, _)| code == text_edit_out.galley.text());
+        if let Some(Err(err)) = self.graph_ui.as_ref().and_then(Promise::ready) {
+            if compiled {
+                show_compile_error(ui, err, &text_edit_out);
+            }
+        }
     }
 
+    /// Lists the saved selections, with buttons to rename, duplicate, reorder and delete them.
     fn selection_ui(&mut self, ui: &mut egui::Ui) {
+        let mut edit = None;
+        let count = self.selections.len();
         ui.vertical(|ui| {
-            for selection in &mut self.selections {
-                let name = selection.name().to_owned();
-                ui.toggle_value(selection.displayed(), name);
+            for (i, selection) in self.selections.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    if let Some((_, name)) = self.renaming.as_mut().filter(|(j, _)| *j == i) {
+                        let response = ui.text_edit_singleline(name);
+                        if ui.input(|input| input.key_pressed(egui::Key::Escape)) {
+                            edit = Some(SelectionEdit::CancelRename);
+                        } else if response.lost_focus() {
+                            edit = Some(SelectionEdit::Rename);
+                        } else {
+                            response.request_focus();
+                        }
+                        return;
+                    }
+                    let name = selection.name().to_owned();
+                    ui.toggle_value(selection.displayed(), name);
+                    if ui.small_button("✏").on_hover_text("Rename").clicked() {
+                        self.renaming = Some((i, selection.name().to_owned()));
+                    }
+                    if ui.small_button("⧉").on_hover_text("Duplicate").clicked() {
+                        edit = Some(SelectionEdit::Duplicate(i));
+                    }
+                    if ui
+                        .add_enabled(i > 0, egui::Button::new("⏶").small())
+                        .on_hover_text("Move up")
+                        .clicked()
+                    {
+                        edit = Some(SelectionEdit::Swap(i - 1));
+                    }
+                    if ui
+                        .add_enabled(i + 1 < count, egui::Button::new("⏷").small())
+                        .on_hover_text("Move down")
+                        .clicked()
+                    {
+                        edit = Some(SelectionEdit::Swap(i));
+                    }
+                    if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                        edit = Some(SelectionEdit::Delete(i));
+                    }
+                });
+            }
+        });
+
+        match edit {
+            None => {}
+            Some(SelectionEdit::Rename) => {
+                if let Some((i, name)) = self.renaming.take() {
+                    let name = name.trim();
+                    if !name.is_empty() {
+                        self.selections[i].rename(name.to_owned());
+                    }
+                }
+            }
+            Some(SelectionEdit::CancelRename) => self.renaming = None,
+            Some(SelectionEdit::Duplicate(i)) => {
+                self.renaming = None;
+                let selection = &self.selections[i];
+                let name = format!("{} copy", selection.name());
+                if let Some(copy) = finished(&self.graph_ui)
+                    .and_then(|graph_ui| selection.duplicate(graph_ui, name, self.solver))
+                {
+                    self.selections.insert(i + 1, copy);
+                }
             }
+            Some(SelectionEdit::Swap(i)) => {
+                self.renaming = None;
+                self.selections.swap(i, i + 1);
+            }
+            Some(SelectionEdit::Delete(i)) => {
+                self.renaming = None;
+                self.selections.remove(i);
+            }
+        }
+    }
+
+    /// Lists the top-level definitions of a Chil program, to colour the operations by the
+    /// definition they were written in and to collapse definitions into single operations.
+    fn clusters_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.overlays.clusters, "Colour by definition")
+            .on_hover_text(
+                "Outline each operation in a colour for the definition it was written in",
+            );
+        let clusters = finished(&self.graph_ui)
+            .map(GraphUi::clusters)
+            .unwrap_or_default();
+        let mut changed = false;
+        if !self.collapsed_clusters.is_empty() && ui.button("Expand all").clicked() {
+            self.collapsed_clusters.clear();
+            changed = true;
+        }
+        ui.separator();
+        if clusters.is_empty() {
+            ui.weak("No definitions with operations");
+        }
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for cluster in clusters.iter() {
+                    let mut collapsed = self.collapsed_clusters.contains(&cluster.name);
+                    let name = egui::RichText::new(&cluster.name)
+                        .color(type_color(&cluster.name, ui.visuals().dark_mode));
+                    if ui
+                        .checkbox(&mut collapsed, name)
+                        .on_hover_text("Draw the definition as a single operation")
+                        .changed()
+                    {
+                        if collapsed {
+                            self.collapsed_clusters.insert(cluster.name.clone());
+                        } else {
+                            self.collapsed_clusters.remove(&cluster.name);
+                        }
+                        changed = true;
+                    }
+                }
+            });
+        if changed {
+            self.tx
+                .send(Message::Compile)
+                .expect("Failed to send message");
+        }
+    }
+
+    /// Shows the rewrites which apply to the parsed program, replacing the code if one is applied.
+    fn rewrites_ui(&mut self, ctx: &egui::Context) {
+        let parse = self.last_parse.clone();
+        // Left alone while a compilation holds it, rather than waiting
+        let guard = parse.as_ref().and_then(|parse| parse.try_lock().ok());
+        let expr = guard.as_ref().and_then(|promise| match promise.ready() {
+            Some(Some(ParseOutput::Spartan(expr))) => Some(expr),
+            _ => None,
         });
+        let code = self.code.lock().unwrap().clone();
+        if let Some(code) = self.rewrites.ui(ctx, &code, expr, self.labels) {
+            self.set_file(&code, None);
+        }
+    }
+
+    /// Splices the edited code of a selection back into the main buffer and recompiles.
+    fn promote(&mut self, promotion: &Promotion) {
+        let span = {
+            let mut code = self.code.lock().unwrap();
+            let span = locate(&code, &promotion.original);
+            if let Some(span) = &span {
+                code.replace_range(span.clone(), &promotion.replacement);
+            }
+            span
+        };
+        if span.is_some() {
+            self.tx
+                .send(Message::Compile)
+                .expect("failed to send message");
+        } else {
+            self.toasts
+                .error("Could not find the selection's code exactly once in the main buffer");
+        }
     }
 
     fn trigger_parse(&mut self, ctx: &egui::Context, send_error: bool) {
@@ -154,9 +787,13 @@ impl App {
         self.last_parse
             .replace(Arc::new(Mutex::new(crate::spawn!("parse", {
                 let guard = code.lock().unwrap();
-                let parsed = parse(&guard, language);
+                let parsed = parse_with_warnings(&guard, language);
                 match parsed {
-                    Ok(parse) => {
+                    Ok((parse, warnings)) => {
+                        if send_error && !warnings.is_empty() {
+                            tx.send(Message::ParseWarnings(warnings))
+                                .expect("failed to send message");
+                        }
                         ctx.request_repaint();
                         Some(parse)
                     }
@@ -171,52 +808,435 @@ impl App {
             }))));
     }
 
+    /// Records the edits typed since the last compile as one step to undo.
+    fn record_edit(&mut self) {
+        let code = self.code.lock().unwrap().clone();
+        let previous = self.compiled.replace((code.clone(), self.language));
+        let Some((before, language)) = previous else {
+            return;
+        };
+        // Undoing into the code of another language would not parse
+        if language == self.language {
+            self.rewrites.record_edit(before, code);
+        }
+    }
+
     fn trigger_compile(&mut self, ctx: &egui::Context) {
+        self.edited = None;
+        self.record_edit();
         clear_shape_cache();
         self.trigger_parse(ctx, true);
         {
             let parse = self.last_parse.as_ref().unwrap().clone();
             let ctx = ctx.clone();
             let dot_settings = self.dot_settings;
-            let mlir_settings = self.mlir_settings;
+            let mlir_settings = self.mlir_settings.clone();
+            let float_lets = self.float_lets;
+            let collapsed_clusters = self.collapsed_clusters.clone();
             let solver = self.solver;
+            let code = self.code.clone();
+            let language = self.language;
             self.graph_ui.replace(crate::spawn!("compile", {
+                let cache_key = CacheKey::new(
+                    &code.lock().unwrap(),
+                    language,
+                    (
+                        dot_settings,
+                        &mlir_settings,
+                        float_lets,
+                        &collapsed_clusters,
+                    ),
+                    solver,
+                );
                 let promise = parse.lock().unwrap();
                 let parse_output = promise
                     .block_until_ready()
                     .as_ref()
                     .ok_or_else(|| anyhow!("no parse"))?;
-                let compile = Ok(match parse_output {
-                    ParseOutput::Chil(expr) => {
-                        tracing::debug!("Converting chil to hypergraph...");
-                        GraphUi::new_chil(expr.to_graph(false)?, solver)
-                    }
-                    ParseOutput::Mlir(expr) => {
-                        tracing::debug!("Converting mlir to hypergraph...");
-                        GraphUi::new_mlir(expr.to_graph(mlir_settings.sym_name_linking)?, solver)
-                    }
-                    ParseOutput::Spartan(expr) => {
-                        tracing::debug!("Converting spartan to hypergraph...");
-                        GraphUi::new_spartan(expr.to_graph(false)?, solver)
-                    }
-                    ParseOutput::Dot(graph) => {
-                        tracing::debug!("Converting dot to hypergraph...");
-                        GraphUi::new_dot(dot_to_graph(graph, dot_settings)?, solver)
-                    }
-                });
+                let compile = GraphUi::from_parse(
+                    parse_output,
+                    dot_settings,
+                    &mlir_settings,
+                    float_lets,
+                    &collapsed_clusters,
+                    solver,
+                    Some(cache_key),
+                );
                 ctx.request_repaint();
                 compile
             }));
         }
 
+        self.size_check = SizeCheck::Unchecked;
         self.selections.clear();
+        self.renaming = None;
+        self.variable_rename = None;
         self.find = None;
+        self.bookmarks.clear();
+    }
+
+    /// Recompiles the program once it has parsed and no edits have been made for a while, when
+    /// compiling as the program is typed.
+    fn live_compile(&mut self, ctx: &egui::Context) {
+        let Some(edited) = self.edited else {
+            return;
+        };
+        let wait = LIVE_COMPILE_DELAY.as_secs_f64() - (ctx.input(|i| i.time) - edited);
+        if wait > 0.0 {
+            // Make sure a frame comes along to compile the edit even if the user stops typing
+            ctx.request_repaint_after(Duration::from_secs_f64(wait));
+            return;
+        }
+        let parsed = self
+            .last_parse
+            .as_ref()
+            .map(|parse| parse.lock().unwrap().ready().map(|parse| parse.is_some()));
+        match parsed {
+            Some(Some(true)) => self.trigger_compile(ctx),
+            // Still parsing
+            Some(None) => ctx.request_repaint_after(Duration::from_millis(50)),
+            // The squiggles already point out what does not parse
+            Some(Some(false)) | None => self.edited = None,
+        }
+    }
+
+    /// Checks the size of a freshly compiled graph, as laying out a large graph can freeze the
+    /// browser tab without warning, collapsing thunks of large graphs if enabled.
+    fn check_size(&mut self, ctx: &egui::Context) {
+        if self.size_check == SizeCheck::Unchecked {
+            if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+                let size = graph_ui.size();
+                // View scripts say which thunks to collapse themselves
+                let collapsed = self
+                    .auto_collapse
+                    .filter(|budget| size > *budget && self.pending_view.is_none())
+                    .map_or(0, |budget| graph_ui.collapse_to_budget(budget));
+                self.size_check = if collapsed > 0 {
+                    self.toasts.info(format!(
+                        "Collapsed {collapsed} thunks of this large program, which Expand all \
+                         shows"
+                    ));
+                    SizeCheck::Accepted
+                } else if cfg!(target_arch = "wasm32") && size > SIZE_BUDGET {
+                    SizeCheck::OverBudget(size)
+                } else {
+                    SizeCheck::Accepted
+                };
+            }
+        }
+
+        if let SizeCheck::OverBudget(size) = self.size_check {
+            egui::Window::new("Large graph")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, Vec2::default())
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "This graph has {size} nodes and edges, which may take the browser a \
+                         long time to lay out."
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Lay out anyway").clicked() {
+                            self.size_check = SizeCheck::Accepted;
+                        }
+                        if ui
+                            .button("Collapsed only")
+                            .on_hover_text("Lay out the top level, with every thunk collapsed")
+                            .clicked()
+                        {
+                            if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+                                graph_ui.set_expanded_all(false);
+                            }
+                            self.size_check = SizeCheck::Accepted;
+                        }
+                    });
+                });
+        }
+    }
+
+    /// Offers to restore the program from a session that crashed, and otherwise backs up the
+    /// editor buffer.
+    fn autosave(&mut self, ctx: &egui::Context) {
+        if self.recovered.is_none() && ctx.input(|i| i.viewport().close_requested()) {
+            self.autosave.clear();
+            return;
+        }
+
+        let Some((code, language)) = &self.recovered else {
+            self.autosave
+                .tick(ctx, &self.code.lock().unwrap(), self.language);
+            return;
+        };
+        let mut restore = false;
+        let mut discard = false;
+        egui::Window::new("Recover unsaved program")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::default())
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "The last session ended unexpectedly with a {} line {} program in the \
+                     editor.",
+                    code.lines().count(),
+                    language.name()
+                ));
+                ui.horizontal(|ui| {
+                    restore = ui.button("Restore").clicked();
+                    discard = ui.button("Discard").clicked();
+                });
+            });
+        if restore || discard {
+            // Left in place until now, in case this session crashes before the user decides
+            self.autosave.clear();
+            if let Some((code, language)) = self.recovered.take().filter(|_| restore) {
+                self.set_file(&code, Some(language));
+                self.editor = true;
+            }
+        }
+    }
+
+    /// Throws away everything a panic may have left in a broken state, such as promises whose
+    /// threads died, keeping only the code.
+    fn recover_from(&mut self, panic: &Panic) {
+        // The code is locked while parsing, so a panic in the parser poisons it
+        let code = self
+            .code
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        self.crash = Some((panic.message.clone(), panic.report(&code, self.language)));
+        self.code = Arc::new(Mutex::new(code));
+
+        self.last_parse = None;
+        self.last_parse_error = None;
+        self.graph_ui = None;
+        self.size_check = SizeCheck::Unchecked;
+        self.selections.clear();
+        self.renaming = None;
+        self.variable_rename = None;
+        self.find = None;
+        self.pending_find = None;
+        self.pending_view = None;
+        self.pending_selections.clear();
+        clear_shape_cache();
+        clear_code_cache();
+    }
+
+    /// Forgets the program and everything shown for it, so that the next launch starts afresh.
+    fn clear_session(&mut self) {
+        self.code.lock().unwrap().clear();
+        self.last_parse = None;
+        self.last_parse_error = None;
+        self.graph_ui = None;
+        self.size_check = SizeCheck::Unchecked;
+        self.selections.clear();
+        self.renaming = None;
+        self.variable_rename = None;
+        self.find = None;
+        self.pending_find = None;
+        self.pending_view = None;
+        self.pending_selections.clear();
+        self.files = Files::default();
+        self.bookmarks.clear();
+        self.autosave.clear();
+    }
+
+    fn crash_ui(&mut self, ctx: &egui::Context) {
+        let Some((message, report)) = &self.crash else {
+            return;
+        };
+        let mut dismiss = false;
+        egui::Window::new("Something went wrong")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::default())
+            .show(ctx, |ui| {
+                ui.label(
+                    "SD Visualiser hit an internal error and has discarded the graph. Your code \
+                     has been kept.",
+                );
+                ui.monospace(message);
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Copy bug report")
+                        .on_hover_text("Includes the backtrace and the start of your code")
+                        .clicked()
+                    {
+                        ctx.output_mut(|o| o.copied_text.clone_from(report));
+                        self.toasts.info("Copied bug report");
+                    }
+                    ui.hyperlink_to(
+                        "Open an issue",
+                        concat!(env!("CARGO_PKG_REPOSITORY"), "/issues/new"),
+                    );
+                    dismiss = ui.button("Dismiss").clicked();
+                });
+            });
+        if dismiss {
+            self.crash = None;
+        }
+    }
+
+    /// Statistics about the current graph and how it was laid out, for diagnostic bundles.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stats(&self) -> String {
+        let mut stats = format!(
+            "version: {}\nsolver: {:?}\nlanguage: {}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.solver,
+            self.language.name()
+        );
+        if let Some(graph_ui) = finished(&self.graph_ui) {
+            stats.push_str(&format!("nodes and edges: {}\n", graph_ui.size()));
+            if let Some(time) = graph_ui.layout_time() {
+                stats.push_str(&format!("layout time: {:.3}s\n", time.as_secs_f64()));
+            }
+        }
+        stats
+    }
+
+    /// Opens the window selecting the cone of nodes reachable from the selection in `direction`,
+    /// replacing any cone being selected already.
+    fn start_cone(&mut self, direction: Direction) {
+        let Some(graph_ui) = finished_mut(&mut self.graph_ui) else {
+            return;
+        };
+        graph_ui.end_cone();
+        graph_ui.preview_cone(direction, 1);
+        self.cone = Some((direction, 1));
+    }
+
+    fn cone_ui(&mut self, ctx: &egui::Context) {
+        let Some((direction, depth)) = &mut self.cone else {
+            return;
+        };
+        let title = match direction {
+            Direction::Forward => "Forward cone",
+            Direction::Backward => "Backward cone",
+        };
+        let mut open = true;
+        let mut changed = false;
+        let mut select = false;
+        let mut cancel = false;
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Selects the nodes reachable from the selection in this many steps.");
+                changed = ui
+                    .add(egui::Slider::new(depth, 1..=MAX_CONE_DEPTH).text("Depth"))
+                    .changed();
+                ui.horizontal(|ui| {
+                    select = ui.button("Select").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        let (direction, depth) = (*direction, *depth);
+        let Some(graph_ui) = finished_mut(&mut self.graph_ui) else {
+            self.cone = None;
+            return;
+        };
+        if select || cancel || !open {
+            self.cone = None;
+            graph_ui.end_cone();
+            // Recorded as one step, to undo or replay, rather than each depth tried on the way
+            if select {
+                self.apply(&Action::ExtendSelection(Some((direction, depth))));
+            }
+        } else if changed {
+            graph_ui.preview_cone(direction, depth);
+        }
+    }
+
+    /// Shows the window renaming a variable, replacing the code with the renamed program and
+    /// recompiling it once confirmed.
+    fn rename_variable_ui(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.variable_rename else {
+            return;
+        };
+        let parse = self.last_parse.clone();
+        // Left alone while a compilation holds it, rather than waiting
+        let guard = parse.as_ref().and_then(|parse| parse.try_lock().ok());
+        let parse_output = guard
+            .as_ref()
+            .and_then(|promise| promise.ready())
+            .and_then(Option::as_ref);
+        let (open, renamed) = dialog.ui(ctx, parse_output);
+        if !open {
+            self.variable_rename = None;
+        }
+        if let Some(code) = renamed {
+            // Undone like a rewrite, as both replace the code
+            let before = self.code.lock().unwrap().clone();
+            self.rewrites.record(before, code.clone());
+            self.set_file(&code, None);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn diagnostics_ui(&mut self, ctx: &egui::Context) {
+        let Some(include_source) = &mut self.diagnostics else {
+            return;
+        };
+        let mut save = false;
+        let mut cancel = false;
+        egui::Window::new("Save diagnostic bundle")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::default())
+            .show(ctx, |ui| {
+                ui.label(
+                    "The bundle contains the log and statistics about the graph and its layout, \
+                     to attach to a bug report.",
+                );
+                ui.checkbox(include_source, "Include the source code");
+                ui.horizontal(|ui| {
+                    save = ui.button("Save…").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if save {
+            let include_source = *include_source;
+            self.diagnostics = None;
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Zip", &["zip"])
+                .set_file_name("diagnostics.zip")
+                .save_file()
+            {
+                let code = include_source.then(|| self.code.lock().unwrap().clone());
+                let name = format!("source.{}", self.language.extension());
+                let source = code.as_deref().map(|code| (name.as_str(), code));
+                match write_bundle(&path, &self.stats(), source) {
+                    Ok(()) => {
+                        self.toasts.info("Saved diagnostic bundle");
+                    }
+                    Err(err) => {
+                        self.toasts
+                            .error(format!("Failed to save diagnostic bundle: {err:#}"));
+                    }
+                }
+            }
+        } else if cancel {
+            self.diagnostics = None;
+        }
     }
-}
 
-impl eframe::App for App {
     #[allow(clippy::too_many_lines)]
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn frame(&mut self, ctx: &egui::Context) {
+        self.theme.set(ctx);
+
+        // Before the messages, as setting the code sends some
+        #[cfg(target_arch = "wasm32")]
+        for request in web_api::take_requests() {
+            match request {
+                Request::SetCode(code, language) => self.set_file(&code, Some(language)),
+                Request::GetSvg(reply) => {
+                    reply.send(finished(&self.graph_ui).and_then(GraphUi::svg).as_deref());
+                }
+            }
+        }
+
         // process messages sent asynchronously
         while let Ok(message) = self.rx.try_recv() {
             tracing::debug!("Got asynchronous message {message:?}");
@@ -225,11 +1245,18 @@ impl eframe::App for App {
                 Message::SetLanguage(language) => {
                     self.language = language;
                 }
+                Message::OpenFile(file) => self.open_file(file),
                 Message::ParseError(err) => {
                     self.toasts.error(err.to_string());
                     tracing::debug!("{}", err);
                     self.last_parse_error.replace(err);
                 }
+                Message::ParseWarnings(warnings) => {
+                    for warning in warnings {
+                        tracing::debug!("{}", warning);
+                        self.toasts.warning(warning);
+                    }
+                }
             }
         }
 
@@ -261,17 +1288,11 @@ impl eframe::App for App {
                         ui.add_enabled($enabled, egui::Button::new($label))
                             .clicked()
                     };
-                    ($label:literal, $shortcut:expr) => {{
-                        button!($label, egui::Modifiers::NONE, $shortcut, enabled = true)
-                    }};
-                    ($label:literal, $shortcut:expr, enabled = $enabled:expr) => {{
-                        button!($label, egui::Modifiers::NONE, $shortcut, enabled = $enabled)
-                    }};
-                    ($label:literal, $modifiers:expr, $shortcut:expr) => {{
-                        button!($label, $modifiers, $shortcut, enabled = true)
+                    ($label:literal, shortcut = $shortcut:expr) => {{
+                        button!($label, shortcut = $shortcut, enabled = true)
                     }};
-                    ($label:literal, $modifiers:expr, $shortcut:expr, enabled = $enabled:expr) => {{
-                        let shortcut = egui::KeyboardShortcut::new($modifiers, $shortcut);
+                    ($label:literal, shortcut = $shortcut:expr, enabled = $enabled:expr) => {{
+                        let shortcut = $shortcut;
                         ui.add_enabled(
                             $enabled,
                             egui::Button::new($label).shortcut_text(ctx.format_shortcut(&shortcut)),
@@ -283,11 +1304,20 @@ impl eframe::App for App {
                 ui.visuals_mut().button_frame = false;
                 ui.style_mut().wrap = Some(false);
 
-                if ui.selectable_label(self.editor, "Editor").clicked() {
+                let editor = ui.selectable_label(self.editor, "Editor");
+                self.tutorial.target(Step::Editor, editor.rect);
+                if editor.clicked() {
                     self.editor = !self.editor;
                 };
 
                 egui::widgets::global_dark_light_mode_buttons(ui);
+                if ui
+                    .selectable_label(self.theme_editor.open, "Theme")
+                    .on_hover_text("Choose the colours of the diagram")
+                    .clicked()
+                {
+                    self.theme_editor.open = !self.theme_editor.open;
+                }
 
                 ui.separator();
 
@@ -333,50 +1363,256 @@ impl eframe::App for App {
                                 .send(Message::Compile)
                                 .expect("Failed to send message");
                         }
+                        let filter = &self.mlir_settings.filter;
+                        if !filter.drop.is_empty() {
+                            ui.weak(format!("Dropping {}", filter.drop.join(", ")))
+                                .on_hover_text("Set in the settings file");
+                        }
+                        if !filter.collapse.is_empty() {
+                            ui.weak(format!("Collapsing {}", filter.collapse.join(", ")))
+                                .on_hover_text("Set in the settings file");
+                        }
+                    });
+                }
+
+                if self.language != UiLanguage::Dot {
+                    ui.menu_button("Normalise", |ui| {
+                        if ui
+                            .selectable_label(self.float_lets, "Float bindings")
+                            .on_hover_text(
+                                "Move bindings into the only thunk using them, \
+                                 and share those repeated across thunks",
+                            )
+                            .clicked()
+                        {
+                            self.float_lets = !self.float_lets;
+                            self.tx
+                                .send(Message::Compile)
+                                .expect("Failed to send message");
+                        }
                     });
                 }
 
-                if button!("Import file", egui::Modifiers::COMMAND, egui::Key::O) {
+                if self.language == UiLanguage::Spartan
+                    && ui
+                        .selectable_label(self.rewrites.open, "Rewrites")
+                        .on_hover_text("List the places where rewrite rules apply")
+                        .clicked()
+                {
+                    self.rewrites.open = !self.rewrites.open;
+                }
+
+                if self.language == UiLanguage::Chil {
+                    ui.menu_button("Clusters", |ui| self.clusters_ui(ui));
+                }
+
+                ui.menu_button("Labels", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.labels.min_text_size, 0.0..=20.0)
+                            .text("Hide below"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut self.labels.type_text_size, 0.0..=40.0)
+                            .text("Show types from"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut self.labels.full_text_size, 0.0..=40.0)
+                            .text("Abbreviate below"),
+                    );
+                    if ui.button("Reset").clicked() {
+                        self.labels = LabelVisibility::default();
+                    }
+                });
+
+                ui.menu_button("Simplify", |ui| {
+                    let current = simplification();
+                    for (level, hint) in [
+                        (Simplification::None, "Draw every identity, copy and swap"),
+                        (
+                            Simplification::HideIdentities,
+                            "Merge slices of plain wires into their neighbours",
+                        ),
+                        (
+                            Simplification::FoldCopies,
+                            "Copy each wire once, rather than in a cascade",
+                        ),
+                        (
+                            Simplification::Full,
+                            "Also reorder operations until no more crossings can be removed",
+                        ),
+                    ] {
+                        if ui
+                            .radio(current == level, level.name())
+                            .on_hover_text(hint)
+                            .clicked()
+                        {
+                            set_simplification(level);
+                        }
+                    }
+                    ui.separator();
+                    let mut auto_collapse = self.auto_collapse.is_some();
+                    if ui
+                        .checkbox(&mut auto_collapse, "Collapse large programs")
+                        .on_hover_text(
+                            "When a program is opened, expand its thunks breadth first only \
+                             while the diagram stays within this many nodes and wires",
+                        )
+                        .changed()
+                    {
+                        self.auto_collapse = auto_collapse.then_some(AUTO_COLLAPSE_BUDGET);
+                    }
+                    if let Some(budget) = &mut self.auto_collapse {
+                        ui.add(
+                            egui::DragValue::new(budget)
+                                .clamp_range(100..=100_000)
+                                .speed(10.0)
+                                .suffix(" nodes and wires"),
+                        );
+                    }
+                });
+
+                ui.menu_button("Solver", |ui| {
+                    for &solver in Solver::value_variants() {
+                        if ui
+                            .radio(self.solver == solver, format!("{solver:?}"))
+                            .clicked()
+                            && self.solver != solver
+                        {
+                            self.solver = solver;
+                            self.rewrites.set_solver(solver);
+                            self.tx
+                                .send(Message::Compile)
+                                .expect("Failed to send message");
+                        }
+                    }
+                    ui.separator();
+                    ui.weak(format!(
+                        "Falls back to {:?} if it fails or runs out of time",
+                        self.solver.fallback()
+                    ));
+                });
+
+                ui.menu_button("Overlays", |ui| {
+                    ui.checkbox(&mut self.overlays.crossings, "Wire crossings")
+                        .on_hover_text("Highlight the slices where the most wires cross");
+                    ui.checkbox(&mut self.overlays.control_flow, "Control flow")
+                        .on_hover_text("Draw arrows from each branch to the blocks it targets");
+                    ui.checkbox(&mut self.overlays.minimap, "Minimap")
+                        .on_hover_text(
+                        "Show the whole diagram in a corner, to pan by clicking or dragging on it",
+                    );
+                    ui.checkbox(&mut self.overlays.type_colors, "Wire types")
+                        .on_hover_text("Colour wires by the types of their values, with a legend");
+                    ui.checkbox(&mut self.overlays.bundle_copies, "Bundle copies")
+                        .on_hover_text(
+                            "Draw a copied value as one thick wire which splits near its consumers",
+                        );
+                    ui.checkbox(&mut self.overlays.hide_ordering, "Hide ordering wires")
+                        .on_hover_text(
+                            "Leave out the dashed wires which only order effects, without \
+                             passing data",
+                        );
+                    ui.checkbox(&mut self.overlays.paths, "Paths")
+                        .on_hover_text(
+                            "Highlight the paths between two selected nodes, dimming the rest",
+                        );
+                    ui.add_enabled_ui(self.overlays.paths, |ui| {
+                        ui.checkbox(&mut self.overlays.flat_paths, "Paths at selection depth")
+                            .on_hover_text(
+                                "Highlight the thunks the paths go through rather than the \
+                                 nodes inside them",
+                            );
+                    });
+                });
+
+                if button!("Import file", shortcut = help::IMPORT) {
                     #[cfg(not(target_arch = "wasm32"))]
                     if let Some(path) = rfd::FileDialog::new().pick_file() {
-                        let language = match path.extension() {
-                            Some(ext) if ext == "sd" => Some(UiLanguage::Spartan),
-                            Some(ext) if ext == "chil" => Some(UiLanguage::Chil),
-                            Some(_) | None => None,
-                        };
-                        self.set_file(
-                            &std::fs::read_to_string(path)
-                                .expect("file picker returned invalid path"),
+                        let name = path
+                            .file_name()
+                            .unwrap_or(path.as_os_str())
+                            .to_string_lossy()
+                            .into_owned();
+                        let code = std::fs::read_to_string(&path)
+                            .expect("file picker returned invalid path");
+                        let language = UiLanguage::guess(&name, &code).unwrap_or(self.language);
+                        self.open_file(OpenFile {
+                            name,
+                            code,
                             language,
-                        );
+                        });
                     }
 
                     #[cfg(target_arch = "wasm32")]
                     {
                         let task = rfd::AsyncFileDialog::new().pick_file();
                         let tx = self.tx.clone();
-                        let code = self.code.clone();
+                        let current = self.language;
                         wasm_bindgen_futures::spawn_local(async move {
                             let file = task.await.unwrap();
                             tracing::trace!("got file name {:?}", file.file_name());
-                            let language = match file.file_name().split('.').last() {
-                                Some("chil") => Some(UiLanguage::Chil),
-                                Some("sd") => Some(UiLanguage::Spartan),
-                                Some(_) | None => None,
-                            };
                             let contents = file.read().await;
-                            if let Ok(string) = String::from_utf8(contents) {
-                                *code.lock().unwrap() = string.to_owned();
-                                if let Some(language) = language {
-                                    tx.send(Message::SetLanguage(language))
-                                        .expect("failed to send message");
-                                }
-                                tx.send(Message::Compile).expect("failed to send message");
+                            if let Ok(code) = String::from_utf8(contents) {
+                                let name = file.file_name();
+                                let language = UiLanguage::guess(&name, &code).unwrap_or(current);
+                                tx.send(Message::OpenFile(OpenFile {
+                                    name,
+                                    code,
+                                    language,
+                                }))
+                                .expect("failed to send message");
                             }
                         });
                     }
                 }
 
+                ui.menu_button("Files", |ui| {
+                    if let Some(index) = self.files.ui(ui) {
+                        ui.close_menu();
+                        self.switch_file(index);
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            !self.files.is_empty(),
+                            egui::Button::new("Find in open files…"),
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.file_search = Some(FileSearch::default());
+                    }
+                    if ui
+                        .add_enabled(self.files.can_link(), egui::Button::new("Link Chil files"))
+                        .on_hover_text(
+                            "Open the program made of every open Chil file, with the variables \
+                             used in each resolved to their definitions in the others",
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        let code = self.code.lock().unwrap().clone();
+                        match self.files.link_chil(&code) {
+                            Ok(file) => self.open_file(file),
+                            Err(err) => self
+                                .toasts
+                                .error(format!("Failed to link Chil files: {err}")),
+                        };
+                    }
+                    ui.separator();
+                    if ui
+                        .button("Clear session")
+                        .on_hover_text(
+                            "Close the program and the open files, so that they are not \
+                             restored on the next launch",
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.clear_session();
+                    }
+                });
+
                 ui.separator();
 
                 // will be true if any graph is currently being drawn
@@ -386,81 +1622,186 @@ impl eframe::App for App {
                 let has_selections = finished(&self.graph_ui)
                     .map(|graph_ui| !graph_ui.is_empty())
                     .unwrap_or_default();
+                if button!("Fit diagram", shortcut = help::RESET, enabled = ready) {
+                    if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+                        graph_ui.reset();
+                    }
+                }
                 if button!(
-                    "Reset",
-                    egui::Modifiers::COMMAND,
-                    egui::Key::Num0,
-                    enabled = ready
+                    "Fit selection",
+                    shortcut = help::FIT_SELECTION,
+                    enabled = ready && has_selections
                 ) {
                     if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
-                        graph_ui.reset();
+                        graph_ui.fit_selection();
                     }
                 }
-                if button!("Zoom In", egui::Key::Plus, enabled = ready) {
+                if button!("Zoom In", shortcut = help::ZOOM_IN, enabled = ready) {
                     if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
                         graph_ui.zoom_in();
                     }
                 }
-                if button!("Zoom Out", egui::Key::Minus, enabled = ready) {
+                if button!("Zoom Out", shortcut = help::ZOOM_OUT, enabled = ready) {
                     if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
                         graph_ui.zoom_out();
                     }
                 }
 
-                if button!(
-                    "Find",
-                    egui::Modifiers::COMMAND,
-                    egui::Key::F,
-                    enabled = ready
-                ) {
-                    self.find = Some((String::new(), 0));
+                if button!("Find", shortcut = help::FIND, enabled = ready) {
+                    self.find = Some(Find::new(String::new()));
                     find_request_focus = true;
                 }
 
+                if button!("Bookmark", shortcut = help::BOOKMARK, enabled = ready) {
+                    if let Some(graph_ui) = finished(&self.graph_ui) {
+                        self.bookmarks.add(graph_ui.panzoom());
+                    }
+                }
+
+                let macro_label = if self.recorder.is_recording() {
+                    "⏺ Macro"
+                } else {
+                    "Macro"
+                };
+                ui.menu_button(macro_label, |ui| {
+                    if self.recorder.ui(ui, ready) {
+                        self.replay();
+                    }
+                });
+                if ready && self.recorder.shortcut_pressed(ctx) {
+                    self.replay();
+                }
+
+                ui.add_enabled_ui(ready, |ui| {
+                    ui.menu_button("Bookmarks", |ui| {
+                        if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+                            let (jump, add) = self.bookmarks.ui(ui);
+                            if let Some(panzoom) = jump {
+                                graph_ui.set_panzoom(panzoom);
+                                ui.close_menu();
+                            }
+                            if add {
+                                self.bookmarks.add(graph_ui.panzoom());
+                            }
+                            if self.bookmarks.is_empty() {
+                                ui.weak("No bookmarks");
+                            }
+                        }
+                    });
+                });
+
                 if button!("Expand all", enabled = ready) {
+                    self.apply(&Action::SetExpandedAll(true));
+                }
+
+                if button!("Collapse all", enabled = ready) {
+                    self.apply(&Action::SetExpandedAll(false));
+                }
+
+                // Shows operations without inputs as literals at each of their uses
+                if button!("Fold constants", enabled = ready) {
                     if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
-                        graph_ui.set_expanded_all(true);
+                        graph_ui.set_constants_folded(true);
                         graph_ui.reset();
                     }
                 }
 
-                if button!("Collapse all", enabled = ready) {
+                if button!("Unfold constants", enabled = ready) {
+                    if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+                        graph_ui.set_constants_folded(false);
+                        graph_ui.reset();
+                    }
+                }
+
+                // Draws each straight line of operations as one node, shortening long programs
+                if button!("Fold chains", enabled = ready) {
                     if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
-                        graph_ui.set_expanded_all(false);
+                        graph_ui.set_chains_folded(true);
                         graph_ui.reset();
                     }
                 }
 
+                if button!("Unfold chains", enabled = ready) {
+                    if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
+                        graph_ui.set_chains_folded(false);
+                        graph_ui.reset();
+                    }
+                }
+
+                // Leave the shortcuts to the code editor while it has focus
+                let editing = ctx.memory(|memory| memory.focused().is_some());
+                let (can_undo, can_redo) = finished(&self.graph_ui)
+                    .map(|graph_ui| (graph_ui.can_undo(), graph_ui.can_redo()))
+                    .unwrap_or_default();
+                // Rewrites are older than any interaction with the graph they recompiled
+                let (can_undo_rewrite, can_redo_rewrite) = {
+                    let code = self.code.lock().unwrap();
+                    (self.rewrites.can_undo(&code), self.rewrites.can_redo(&code))
+                };
+                // Checked first, as the undo shortcut also matches when shift is held
+                let redo = ui
+                    .add_enabled(
+                        can_redo || can_redo_rewrite,
+                        egui::Button::new("Redo").shortcut_text(ctx.format_shortcut(&help::REDO)),
+                    )
+                    .clicked()
+                    || !editing && ui.input_mut(|i| i.consume_shortcut(&help::REDO));
+                let undo = ui
+                    .add_enabled(
+                        can_undo || can_undo_rewrite,
+                        egui::Button::new("Undo").shortcut_text(ctx.format_shortcut(&help::UNDO)),
+                    )
+                    .clicked()
+                    || !editing && ui.input_mut(|i| i.consume_shortcut(&help::UNDO));
+                if redo || undo {
+                    let code = self.code.lock().unwrap().clone();
+                    let graph_ui = finished_mut(&mut self.graph_ui);
+                    let rewritten = match graph_ui {
+                        Some(graph_ui) if redo && can_redo => {
+                            graph_ui.redo();
+                            None
+                        }
+                        Some(graph_ui) if undo && !redo && can_undo => {
+                            graph_ui.undo();
+                            None
+                        }
+                        _ if redo => self.rewrites.redo(&code),
+                        _ => self.rewrites.undo(&code),
+                    };
+                    if let Some(code) = rewritten {
+                        self.set_file(&code, None);
+                    }
+                }
+
                 ui.separator();
 
-                if button!("Compile", egui::Key::F5) {
+                let compile = ui.add(
+                    egui::Button::new("Compile").shortcut_text(ctx.format_shortcut(&help::COMPILE)),
+                );
+                self.tutorial.target(Step::Compile, compile.rect);
+                if compile.clicked() || ui.input_mut(|i| i.consume_shortcut(&help::COMPILE)) {
                     self.tx
                         .send(Message::Compile)
                         .expect("failed to send message");
                 }
+                if ui
+                    .selectable_label(self.live_compile, "Live")
+                    .on_hover_text("Recompile the program shortly after each edit which parses")
+                    .clicked()
+                {
+                    self.live_compile = !self.live_compile;
+                    self.edited = None;
+                }
 
                 if button!(
                     "Save selection",
-                    egui::Modifiers::COMMAND,
-                    egui::Key::S,
+                    shortcut = help::SAVE_SELECTION,
                     enabled = ready && has_selections
                 ) {
-                    if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
-                        if let Some(sel) = Selection::from_graph(
-                            graph_ui,
-                            format!("Selection {}", self.selections.len()),
-                            self.solver,
-                        ) {
-                            self.selections.push(sel);
-                        }
-                        graph_ui.clear_selection();
-                    }
+                    self.apply(&Action::SaveSelection);
                 }
                 if button!("Clear selection", enabled = ready && has_selections) {
-                    if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
-                        clear_code_cache();
-                        graph_ui.clear_selection();
-                    }
+                    self.apply(&Action::ClearSelection);
                 }
                 ui.add_enabled_ui(ready && has_selections, |ui| {
                     ui.menu_button("Extend selection", |ui| {
@@ -472,9 +1813,17 @@ impl eframe::App for App {
                             ("Backward", Some((Direction::Backward, usize::MAX))),
                         ] {
                             if ui.button(label).clicked() {
-                                if let Some(graph_ui) = finished_mut(&mut self.graph_ui) {
-                                    graph_ui.extend_selection(direction);
-                                }
+                                self.apply(&Action::ExtendSelection(direction));
+                            }
+                        }
+                        ui.separator();
+                        for (label, direction) in [
+                            ("Forward cone…", Direction::Forward),
+                            ("Backward cone…", Direction::Backward),
+                        ] {
+                            if ui.button(label).clicked() {
+                                ui.close_menu();
+                                self.start_cone(direction);
                             }
                         }
                     });
@@ -483,35 +1832,194 @@ impl eframe::App for App {
                 #[cfg(not(target_arch = "wasm32"))]
                 {
                     ui.separator();
-                    if button!("Export SVG", enabled = ready) {
-                        if let Some(graph_ui) = finished(&self.graph_ui) {
-                            let svg = graph_ui.export_svg();
-                            if let Some(path) = rfd::FileDialog::new().save_file() {
-                                let _ = std::fs::write(path, svg);
+                    ui.menu_button("File", |ui| {
+                        for (label, format) in [
+                            ("Export SVG…", ExportFormat::Svg),
+                            ("Export TikZ…", ExportFormat::Tikz),
+                            ("Export outline…", ExportFormat::Outline),
+                            ("Export dot…", ExportFormat::Dot),
+                        ] {
+                            if ui.add_enabled(ready, egui::Button::new(label)).clicked() {
+                                ui.close_menu();
+                                if let Some(graph_ui) = finished(&self.graph_ui) {
+                                    self.export = Some(ExportDialog::new(format, graph_ui));
+                                }
                             }
                         }
-                    }
+                        if ui
+                            .add_enabled(ready, egui::Button::new("Export view script…"))
+                            .on_hover_text(
+                                "Save the collapsed thunks, selection, pan and zoom, to recreate \
+                                 them with --view",
+                            )
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.export_view();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.language != UiLanguage::Dot,
+                                egui::Button::new("Export source as HTML…"),
+                            )
+                            .on_hover_text("With each variable linked to where it is bound")
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            let code = self.code.lock().unwrap().clone();
+                            export_html(&code, self.language, &mut self.toasts);
+                        }
+                        if ui.button("Load annotations…").clicked() {
+                            ui.close_menu();
+                            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                match std::fs::read_to_string(&path)
+                                    .map_err(anyhow::Error::from)
+                                    .and_then(|source| parse_annotations(&source))
+                                {
+                                    Ok(badges) => self.badges = badges,
+                                    Err(err) => self
+                                        .toasts
+                                        .error(format!("Failed to load annotations: {err:#}")),
+                                };
+                            }
+                        }
+                        if ui
+                            .add_enabled(
+                                !self.badges.is_empty(),
+                                egui::Button::new("Clear annotations"),
+                            )
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.badges.clear();
+                        }
+                        if ui.button("Load profile…").clicked() {
+                            ui.close_menu();
+                            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                match std::fs::read_to_string(&path)
+                                    .map_err(anyhow::Error::from)
+                                    .and_then(|source| Ok(Profile::parse(&source)?))
+                                {
+                                    Ok(profile) => self.profile = Some(Arc::new(profile)),
+                                    Err(err) => self
+                                        .toasts
+                                        .error(format!("Failed to load profile: {err:#}")),
+                                };
+                            }
+                        }
+                        if ui
+                            .add_enabled(self.profile.is_some(), egui::Button::new("Clear profile"))
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.profile = None;
+                        }
+                        let diffable = self.language != UiLanguage::Dot;
+                        if ui
+                            .add_enabled(diffable, egui::Button::new("Diff against file…"))
+                            .on_hover_text("Compare the program with another version of it")
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                match std::fs::read_to_string(&path) {
+                                    Ok(base) => {
+                                        let name = path
+                                            .file_name()
+                                            .unwrap_or(path.as_os_str())
+                                            .to_string_lossy()
+                                            .into_owned();
+                                        self.diff =
+                                            DiffView::new(name, base, self.language, self.solver);
+                                    }
+                                    Err(err) => self
+                                        .toasts
+                                        .error(format!("Failed to load diff base: {err:#}")),
+                                }
+                            }
+                        }
+                        if ui
+                            .add_enabled(diffable, egui::Button::new("Diff against this version"))
+                            .on_hover_text("Compare later edits with the code as it is now")
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            let base = self.code.lock().unwrap().clone();
+                            self.diff = DiffView::new(
+                                "earlier version".to_owned(),
+                                base,
+                                self.language,
+                                self.solver,
+                            );
+                        }
+                        ui.separator();
+                        if ui.button("Save diagnostic bundle…").clicked() {
+                            ui.close_menu();
+                            self.diagnostics = Some(false);
+                        }
+                    });
                 }
 
                 ui.separator();
+                if ui
+                    .selectable_label(self.help.open, "Help")
+                    .on_hover_text(format!(
+                        "Search the features and shortcuts ({})",
+                        ctx.format_shortcut(&help::HELP)
+                    ))
+                    .clicked()
+                {
+                    self.help.open = !self.help.open;
+                }
                 if ui.selectable_label(self.about, "About").clicked() {
                     self.about = !self.about;
                 };
+                if ui.button("Tour").clicked() {
+                    self.tutorial.start();
+                }
             });
         });
 
-        for selection in &mut self.selections {
-            selection.ui(ctx);
+        self.live_compile(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.reload(ctx);
+        for failure in take_solver_failures() {
+            self.toasts.warning(failure);
+        }
+        self.check_size(ctx);
+        self.pending_find();
+        self.pending_view();
+        self.pending_selections();
+        self.autosave(ctx);
+
+        let requests: Vec<_> = {
+            let code = self.code.lock().unwrap();
+            self.selections
+                .iter_mut()
+                .filter_map(|selection| selection.ui(ctx, self.labels, &code))
+                .collect()
+        };
+        for request in requests {
+            match request {
+                SelectionRequest::Promote(promotion) => self.promote(&promotion),
+                SelectionRequest::Open(file) => self.open_selection(file),
+            }
         }
 
-        egui::SidePanel::right("selection_panel").show(ctx, |ui| {
+        let selection_panel = egui::SidePanel::right("selection_panel").show(ctx, |ui| {
             egui::ScrollArea::vertical()
                 .id_source("selections")
                 .show(ui, |ui| self.selection_ui(ui));
         });
+        self.tutorial
+            .target(Step::Selections, selection_panel.response.rect);
+
+        if self.rewrites.open && self.language == UiLanguage::Spartan {
+            self.rewrites_ui(ctx);
+        }
 
         #[allow(clippy::redundant_closure_call)]
-        egui::CentralPanel::default().show(ctx, |ui| {
+        let central_panel = egui::CentralPanel::default().show(ctx, |ui| {
             macro_rules! optional_editor {
                 ($graph:expr) => {
                     if self.editor {
@@ -532,38 +2040,75 @@ impl eframe::App for App {
                     .as_mut()
                     .map(|p| p.poll_mut().map(Result::as_mut))
                 {
-                    Some(Poll::Ready(Ok(graph_ui))) => {
-                        graph_ui.ui(ui, self.find.as_ref().map(|x| x.0.as_str()));
+                    Some(Poll::Ready(Ok(_))) if self.size_check != SizeCheck::Accepted => {
+                        ui.centered_and_justified(|ui| ui.label("Waiting to lay out the graph"));
                     }
-                    Some(Poll::Pending) => {
-                        ui.centered_and_justified(eframe::egui::Ui::spinner);
+                    Some(Poll::Ready(Ok(graph_ui))) => {
+                        graph_ui.ui(
+                            ui,
+                            self.find.as_ref().and_then(Find::query),
+                            self.labels,
+                            &self.badges,
+                            self.profile.as_ref(),
+                            self.overlays,
+                        );
+                        let toggles = graph_ui.take_toggles();
+                        #[cfg(target_arch = "wasm32")]
+                        if toggles
+                            .iter()
+                            .any(|toggle| matches!(toggle, Toggle::Selected(_)))
+                        {
+                            web_api::selection_changed(&graph_ui.selected());
+                        }
+                        for toggle in toggles {
+                            self.recorder.record(Action::Toggle(toggle));
+                        }
+                        if let Some(variable) = graph_ui.take_rename_request() {
+                            self.variable_rename = Some(RenameVariable::new(variable));
+                        }
                     }
+                    Some(Poll::Pending) => progress::ui(ui),
                     Some(Poll::Ready(Err(_))) | None => { /* No pending successful compilation */ }
                 }
             });
         });
+        self.tutorial
+            .target(Step::Expand, central_panel.response.rect);
 
         let mut clear_find = false;
-        if let Some(((query, offset), graph_ui)) =
-            self.find.as_mut().zip(finished_mut(&mut self.graph_ui))
-        {
+        if let Some((find, graph_ui)) = self.find.as_mut().zip(finished_mut(&mut self.graph_ui)) {
             egui::Window::new("find_panel")
                 .movable(false)
                 .resizable(false)
                 .anchor(Align2::RIGHT_TOP, Vec2::default())
                 .title_bar(false)
                 .show(ctx, |ui| {
-                    let response = ui.text_edit_singleline(query);
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut find.text)
+                            .hint_text("Name, or /regular expression/"),
+                    );
                     if find_request_focus {
                         response.request_focus();
                     }
                     if response.changed() {
-                        *offset = 0;
+                        *find = Find::new(std::mem::take(&mut find.text));
                     }
+                    if let Err(err) = &find.query {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                    let enter =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
                     ui.horizontal(|ui| {
-                        if ui.button("Find").clicked() {
-                            graph_ui.find(query, *offset);
-                            *offset += 1;
+                        if ui.button("Find").clicked() || enter {
+                            if let Some(query) = find.query() {
+                                // Matches hidden in collapsed thunks are revealed before panning
+                                graph_ui.expand_matches(query);
+                                graph_ui.find(query, find.offset);
+                                find.offset += 1;
+                            }
+                            if enter {
+                                response.request_focus();
+                            }
                         }
                         if ui.button("Cancel").clicked() {
                             clear_find = true;
@@ -575,6 +2120,9 @@ impl eframe::App for App {
             self.find = None;
         }
 
+        self.help.ui(ctx);
+        self.theme_editor.ui(ctx, &mut self.theme, &mut self.toasts);
+
         if self.about {
             egui::Window::new("about")
                 .title_bar(false)
@@ -598,10 +2146,88 @@ impl eframe::App for App {
                 });
         }
 
+        if let Some(file_search) = &mut self.file_search {
+            let code = self.code.lock().unwrap().clone();
+            let (open, jump) = file_search.ui(
+                ctx,
+                &mut self.files,
+                &code,
+                &(self.dot_settings, self.mlir_settings.clone()),
+            );
+            if !open {
+                self.file_search = None;
+            }
+            if let Some((index, query)) = jump {
+                self.switch_file(index);
+                self.pending_find = Some(query);
+            }
+        }
+
+        self.cone_ui(ctx);
+        self.rename_variable_ui(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.diagnostics_ui(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dialog) = &mut self.export {
+            let open = finished(&self.graph_ui).is_some_and(|graph_ui| {
+                dialog.ui(ctx, graph_ui, &mut self.toasts, &mut self.exports)
+            });
+            if !open {
+                self.export = None;
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.exports.retain(|export| export.poll(&mut self.toasts));
+            if !self.exports.is_empty() {
+                ctx.request_repaint_after(Duration::from_millis(50));
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(view) = &mut self.diff {
+            let code = self.code.lock().unwrap().clone();
+            if !view.ui(ctx, &code, self.labels) {
+                self.diff = None;
+            }
+        }
+        self.tutorial.ui(ctx);
         self.toasts.show(ctx);
     }
 }
 
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Only caught natively, as panics abort on the web
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| self.frame(ctx)));
+        // Also covers panics in background threads, whose promises would panic when next polled
+        if let Some(panic) = take_panic() {
+            self.recover_from(&panic);
+        }
+        self.crash_ui(ctx);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn raw_input_hook(&mut self, _ctx: &egui::Context, raw_input: &mut egui::RawInput) {
+        raw_input.events.extend(pen::take_events());
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let code = self
+            .code
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        session::save(
+            storage,
+            &code,
+            &self.view_script(),
+            &self.saved_selections(),
+            &self.theme,
+            self.tutorial.seen(),
+        );
+    }
+}
+
 #[allow(clippy::inline_always)]
 #[inline(always)]
 fn finished<T, E>(promise: &Option<Promise<Result<T, E>>>) -> Option<&T>
@@ -0,0 +1,215 @@
+//! Backups of the editor buffer, taken every few seconds so that a crash does not lose unsaved
+//! work. Natively each running instance backs up to a file of its own in the temporary directory,
+//! held with a lock for as long as it runs, while on the web the backup is kept in the session
+//! storage of the tab. The backup is removed when the application is closed normally, so finding
+//! one on launch, unlocked, means the session it belonged to ended abruptly.
+
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::parser::UiLanguage;
+
+/// How long to wait after an edit before backing the buffer up.
+const INTERVAL: Duration = Duration::from_secs(5);
+
+const KEY: &str = "sd-visualiser-autosave";
+
+#[derive(Debug, Default)]
+pub(crate) struct Autosave {
+    saved: String,
+    last_save: f64,
+    slot: Slot,
+}
+
+impl Autosave {
+    /// Backups for this session, together with the program backed up by a session that did not
+    /// close normally, if there is one. Its backup is taken over by this session, so that no other
+    /// session offers to recover it too.
+    pub(crate) fn recover() -> (Self, Option<(String, UiLanguage)>) {
+        let (slot, backup) = Slot::recover();
+        let recovered = backup.and_then(|backup| {
+            let (name, code) = backup.split_once('\n')?;
+            let language = [
+                UiLanguage::Chil,
+                UiLanguage::Spartan,
+                UiLanguage::Mlir,
+                UiLanguage::Dot,
+            ]
+            .into_iter()
+            .find(|language| language.name() == name)?;
+            (!code.trim().is_empty()).then(|| (code.to_owned(), language))
+        });
+        let autosave = Self {
+            slot,
+            ..Self::default()
+        };
+        (autosave, recovered)
+    }
+
+    /// Backs up `code` if it has changed since the last backup and that backup is old enough.
+    pub(crate) fn tick(&mut self, ctx: &egui::Context, code: &str, language: UiLanguage) {
+        if code == self.saved {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        let wait = INTERVAL.as_secs_f64() - (now - self.last_save);
+        if wait > 0.0 {
+            // Make sure a frame comes along to save the edit even if the user stops typing
+            ctx.request_repaint_after(Duration::from_secs_f64(wait));
+            return;
+        }
+
+        if let Err(err) = self.slot.store(&format!("{}\n{code}", language.name())) {
+            tracing::warn!("Failed to autosave: {err}");
+        }
+        code.clone_into(&mut self.saved);
+        self.last_save = now;
+    }
+
+    /// Removes the backup, once the session is over or the user has dealt with it.
+    pub(crate) fn clear(&mut self) {
+        self.saved.clear();
+        if let Err(err) = self.slot.remove() {
+            tracing::warn!("Failed to remove autosave: {err}");
+        }
+    }
+}
+
+/// Where the backup of this session is kept: a file named after the process, next to a lock file
+/// which is held until the session ends.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+struct Slot {
+    /// The name of the files of the slot and its held lock, once claimed.
+    claimed: Option<(String, std::fs::File)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Slot {
+    fn dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(KEY)
+    }
+
+    fn backup(name: &str) -> std::path::PathBuf {
+        Self::dir().join(name).with_extension("backup")
+    }
+
+    fn lock_path(name: &str) -> std::path::PathBuf {
+        Self::dir().join(name).with_extension("lock")
+    }
+
+    /// Locks the slot `name`, unless another running session holds it.
+    fn lock(name: &str) -> Option<std::fs::File> {
+        let lock = std::fs::File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(Self::lock_path(name))
+            .ok()?;
+        lock.try_lock().ok()?;
+        Some(lock)
+    }
+
+    /// Takes over the slot of a session that ended abruptly, if there is one, with its backup.
+    fn recover() -> (Self, Option<String>) {
+        let Ok(entries) = std::fs::read_dir(Self::dir()) else {
+            return (Self::default(), None);
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != "backup")
+            {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if let Some(lock) = Self::lock(name) {
+                let backup = std::fs::read_to_string(&path).ok();
+                let slot = Self {
+                    claimed: Some((name.to_owned(), lock)),
+                };
+                return (slot, backup);
+            }
+        }
+        (Self::default(), None)
+    }
+
+    /// The name of the slot of this session, claiming one if it has none yet.
+    fn claim(&mut self) -> std::io::Result<&str> {
+        if self.claimed.is_none() {
+            std::fs::create_dir_all(Self::dir())?;
+            let name = std::process::id().to_string();
+            let lock = Self::lock(&name).ok_or_else(|| {
+                std::io::Error::other(format!("autosave slot {name} is held by another session"))
+            })?;
+            self.claimed = Some((name, lock));
+        }
+        Ok(self.claimed.as_ref().map_or("", |(name, _)| name))
+    }
+
+    fn store(&mut self, backup: &str) -> std::io::Result<()> {
+        let path = Self::backup(self.claim()?);
+        // Write to a fresh file and move it into place, so a crash mid-write leaves the old backup
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, backup)?;
+        std::fs::rename(tmp, path)
+    }
+
+    fn remove(&self) -> std::io::Result<()> {
+        let Some((name, _)) = &self.claimed else {
+            return Ok(());
+        };
+        match std::fs::remove_file(Self::backup(name)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some((name, lock)) = self.claimed.take() {
+            // Released first, as open files cannot be removed everywhere
+            drop(lock);
+            let _ = std::fs::remove_file(Self::lock_path(&name));
+        }
+    }
+}
+
+/// The backup of this session, in the session storage of the tab, which outlives reloads but is
+/// not shared with other tabs.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Default)]
+struct Slot;
+
+#[cfg(target_arch = "wasm32")]
+impl Slot {
+    fn storage() -> Option<eframe::web_sys::Storage> {
+        eframe::web_sys::window()?.session_storage().ok()?
+    }
+
+    fn recover() -> (Self, Option<String>) {
+        (
+            Self,
+            Self::storage().and_then(|storage| storage.get_item(KEY).ok()?),
+        )
+    }
+
+    fn store(&mut self, backup: &str) -> Result<(), String> {
+        Self::storage()
+            .ok_or_else(|| "session storage is unavailable".to_owned())?
+            .set_item(KEY, backup)
+            .map_err(|err| format!("{err:?}"))
+    }
+
+    fn remove(&self) -> Result<(), String> {
+        Self::storage()
+            .map_or(Ok(()), |storage| storage.remove_item(KEY))
+            .map_err(|err| format!("{err:?}"))
+    }
+}
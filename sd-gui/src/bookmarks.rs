@@ -0,0 +1,71 @@
+use eframe::egui;
+
+use crate::panzoom::Panzoom;
+
+/// A named viewport position in the diagram.
+#[derive(Clone, Debug)]
+pub struct Bookmark {
+    name: String,
+    panzoom: Panzoom,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+    /// Name to give the next bookmark.
+    new_name: String,
+}
+
+impl Bookmarks {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bookmarks.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.bookmarks.clear();
+    }
+
+    /// Add a bookmark at the given viewport, generating a name if none was given.
+    pub(crate) fn add(&mut self, panzoom: Panzoom) {
+        let name = match std::mem::take(&mut self.new_name).trim() {
+            "" => format!("Bookmark {}", self.bookmarks.len()),
+            name => name.to_owned(),
+        };
+        self.bookmarks.push(Bookmark { name, panzoom });
+    }
+
+    /// Show the quick-jump list.
+    /// Returns the viewport of a bookmark if the user clicked on it,
+    /// and whether the user asked for a bookmark at the current viewport.
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui) -> (Option<Panzoom>, bool) {
+        let mut jump = None;
+        let mut add = false;
+        let mut remove = None;
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_name).hint_text("Bookmark name"));
+            add = ui.button("Add").clicked();
+        });
+
+        for (i, bookmark) in self.bookmarks.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button(&bookmark.name).clicked() {
+                    jump = Some(bookmark.panzoom);
+                }
+                if ui
+                    .small_button("🗑")
+                    .on_hover_text("Remove bookmark")
+                    .clicked()
+                {
+                    remove = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = remove {
+            self.bookmarks.remove(i);
+        }
+
+        (jump, add)
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use sd_core::{
+    dot::{dot_to_graph, DotSettings},
+    hypergraph::{
+        generic::{Edge, Operation, Weight},
+        subgraph::ExtensibleEdge,
+        traits::Graph,
+    },
+    interactive::InteractiveGraph,
+    lp::Solver,
+};
+use sd_graphics::common::Shapeable;
+
+use crate::{
+    parser::{parse, ParseOutput, UiLanguage},
+    shape_generator::generate_shapes,
+};
+
+/// Parses, builds the hypergraph of, and lays out every program under `dir`, such as the `.sd`
+/// and `.chil` files a compiler emits, printing a table of how long each stage took for each
+/// program and of the errors met.
+///
+/// Fails if any program could not be checked, so that it can be run as part of a test suite.
+pub fn check_examples(dir: &Path, solver: Solver) -> anyhow::Result<()> {
+    let mut programs = Vec::new();
+    find_programs(dir, &mut programs)?;
+    programs.sort();
+
+    let reports = programs
+        .iter()
+        .map(|(path, language)| {
+            tracing::info!("Checking {}", path.display());
+            let mut report = Report::default();
+            if let Err(err) = check_program(path, *language, solver, &mut report) {
+                report.error = Some(err);
+            }
+            report
+        })
+        .collect::<Vec<_>>();
+
+    let names = programs
+        .iter()
+        .map(|(path, _)| path.strip_prefix(dir).unwrap_or(path).display().to_string())
+        .collect::<Vec<_>>();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .chain(["program".len()])
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:width$}  {:>10}  {:>10}  {:>10}  result",
+        "program", "parse", "build", "layout"
+    );
+    for (name, report) in names.iter().zip(&reports) {
+        println!(
+            "{name:width$}  {:>10}  {:>10}  {:>10}  {}",
+            Timing(report.parse),
+            Timing(report.build),
+            Timing(report.layout),
+            report.result()
+        );
+    }
+
+    let failures = reports
+        .iter()
+        .filter(|report| report.error.is_some())
+        .count();
+    println!();
+    if failures > 0 {
+        bail!("{failures} of {} programs failed", programs.len());
+    }
+    let total = reports
+        .iter()
+        .flat_map(|report| [report.parse, report.build, report.layout])
+        .flatten()
+        .sum::<Duration>();
+    println!(
+        "{} programs checked in {}",
+        programs.len(),
+        Timing(Some(total))
+    );
+    Ok(())
+}
+
+/// Collects the programs in `dir` whose language is known from their extension.
+fn find_programs(dir: &Path, programs: &mut Vec<(PathBuf, UiLanguage)>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_programs(&path, programs)?;
+        } else if let Some(language) = path
+            .extension()
+            .and_then(|ext| UiLanguage::from_extension(&ext.to_string_lossy()))
+        {
+            programs.push((path, language));
+        }
+    }
+    Ok(())
+}
+
+/// How far checking a program got, with the time taken by each stage it finished.
+#[derive(Debug, Default)]
+struct Report {
+    parse: Option<Duration>,
+    build: Option<Duration>,
+    layout: Option<Duration>,
+    /// Whether the layout was not solved exactly, as the graph was too big to solve in time.
+    approximate: bool,
+    error: Option<anyhow::Error>,
+}
+
+impl Report {
+    fn result(&self) -> String {
+        match &self.error {
+            // Only the first line, to keep the table readable
+            Some(err) => {
+                let stage = if self.parse.is_none() {
+                    "parse"
+                } else {
+                    "build"
+                };
+                let err = format!("{err:#}");
+                format!("{stage} error: {}", err.lines().next().unwrap_or_default())
+            }
+            None if self.approximate => "ok (approximate layout)".to_owned(),
+            None => "ok".to_owned(),
+        }
+    }
+}
+
+/// A duration in milliseconds, or a dash for a stage which was not reached.
+struct Timing(Option<Duration>);
+
+impl Display for Timing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(duration) => {
+                let ms = format!("{:.1} ms", duration.as_secs_f64() * 1000.0);
+                f.pad(&ms)
+            }
+            None => f.pad("-"),
+        }
+    }
+}
+
+fn check_program(
+    path: &Path,
+    language: UiLanguage,
+    solver: Solver,
+    report: &mut Report,
+) -> anyhow::Result<()> {
+    let code = fs::read_to_string(path)?;
+    let start = Instant::now();
+    let parse_output = parse(&code, language)?;
+    report.parse = Some(start.elapsed());
+
+    let start = Instant::now();
+    match parse_output {
+        ParseOutput::Chil(expr) => {
+            let graph = InteractiveGraph::new(expr.to_graph(false)?);
+            report.build = Some(start.elapsed());
+            lay_out(&graph, solver, report);
+        }
+        ParseOutput::Spartan(expr) => {
+            let graph = InteractiveGraph::new(expr.to_graph(false)?);
+            report.build = Some(start.elapsed());
+            lay_out(&graph, solver, report);
+        }
+        ParseOutput::Mlir(expr) => {
+            let graph = InteractiveGraph::new(expr.to_graph(false)?);
+            report.build = Some(start.elapsed());
+            lay_out(&graph, solver, report);
+        }
+        ParseOutput::Dot(graph) => {
+            let graph = InteractiveGraph::new(dot_to_graph(&graph, DotSettings::default())?);
+            report.build = Some(start.elapsed());
+            lay_out(&graph, solver, report);
+        }
+    }
+    Ok(())
+}
+
+/// Lays out `graph`, blocking until the layout is done.
+fn lay_out<G>(graph: &G, solver: Solver, report: &mut Report)
+where
+    G: Graph + 'static,
+    Edge<G::Ctx>: ExtensibleEdge,
+    Operation<G::Ctx>: Shapeable,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
+{
+    let start = Instant::now();
+    let shapes = generate_shapes(graph, solver, None);
+    let guard = shapes.lock().unwrap();
+    let shapes = guard.block_until_ready();
+    report.layout = Some(start.elapsed());
+    report.approximate = shapes.approximate || shapes.heuristic;
+}
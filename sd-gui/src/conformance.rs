@@ -0,0 +1,259 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use sd_core::{
+    dot::{dot_to_graph, DotSettings},
+    hypergraph::{
+        generic::Node,
+        traits::{Graph, NodeLike, WithWeight},
+        Hypergraph, Weight,
+    },
+    language::{Expr, Language},
+    prettyprinter::PrettyPrint,
+};
+
+use crate::parser::{parse, ParseOutput, UiLanguage};
+
+/// Extension of the files describing what each program in a suite should produce.
+const EXPECTED: &str = "expected";
+
+/// Checks every program in the conformance suite `suite` against its expectations.
+///
+/// A case is a program `NAME.EXT`, whose language is guessed from `EXT`, next to a file
+/// `NAME.EXT.expected` of `key: value` lines, each of which is optional:
+///
+/// - `nodes`: the number of operations and thunks, including those nested in thunks
+/// - `edges`: the number of edges, including those nested in thunks
+/// - `free`: a comma separated list of the free variables of the program
+/// - `decompiled`: the program decompiled from its graph, given by all remaining lines
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn check_conformance(suite: &Path) -> anyhow::Result<()> {
+    let mut cases = Vec::new();
+    find_cases(suite, &mut cases)?;
+    cases.sort();
+
+    let mut failures = 0;
+    for case in &cases {
+        match check_case(case) {
+            Ok(mismatches) if mismatches.is_empty() => println!("ok      {}", case.display()),
+            Ok(mismatches) => {
+                failures += 1;
+                println!("FAILED  {}", case.display());
+                for mismatch in mismatches {
+                    println!("        {mismatch}");
+                }
+            }
+            Err(err) => {
+                failures += 1;
+                println!("FAILED  {}\n        {err:#}", case.display());
+            }
+        }
+    }
+
+    if failures > 0 {
+        bail!("{failures} of {} conformance cases failed", cases.len());
+    }
+    println!("{} conformance cases passed", cases.len());
+    Ok(())
+}
+
+/// Collects the programs in `dir` which have expectations.
+fn find_cases(dir: &Path, cases: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_cases(&path, cases)?;
+        } else if path.extension().is_some_and(|ext| ext == EXPECTED) {
+            cases.push(path.with_extension(""));
+        }
+    }
+    Ok(())
+}
+
+/// Runs a single case, returning a description of each expectation it does not meet.
+fn check_case(program: &Path) -> anyhow::Result<Vec<String>> {
+    let language = program
+        .extension()
+        .and_then(|ext| UiLanguage::from_extension(&ext.to_string_lossy()))
+        .ok_or_else(|| anyhow!("cannot guess the language of {}", program.display()))?;
+    let mut expected = program.as_os_str().to_owned();
+    expected.push(format!(".{EXPECTED}"));
+    let expected = Expectations::parse(&fs::read_to_string(expected)?)?;
+    let code = fs::read_to_string(program)?;
+
+    let observed = match parse(&code, language)? {
+        ParseOutput::Chil(expr) => observe_expr(&expr)?,
+        ParseOutput::Spartan(expr) => observe_expr(&expr)?,
+        ParseOutput::Mlir(expr) => observe_expr(&expr)?,
+        ParseOutput::Dot(graph) => observe(&dot_to_graph(&graph, DotSettings::default())?, None),
+    };
+
+    let mut mismatches = Vec::new();
+    mismatch(&mut mismatches, "nodes", expected.nodes, observed.nodes);
+    mismatch(&mut mismatches, "edges", expected.edges, observed.edges);
+    mismatch(
+        &mut mismatches,
+        "free",
+        expected.free_vars.map(|vars| vars.join(", ")),
+        observed.free_vars.join(", "),
+    );
+    if let Some(decompiled) = expected.decompiled {
+        match observed.decompiled {
+            Some(observed) if observed.trim() == decompiled.trim() => {}
+            Some(observed) => mismatches.push(format!(
+                "decompiled: expected\n{decompiled}\n        found\n{observed}"
+            )),
+            None => mismatches.push(format!("decompiled: {language:?} cannot be decompiled")),
+        }
+    }
+    Ok(mismatches)
+}
+
+fn mismatch<T: PartialEq + Display>(
+    mismatches: &mut Vec<String>,
+    key: &str,
+    expected: Option<T>,
+    observed: T,
+) {
+    if let Some(expected) = expected.filter(|expected| *expected != observed) {
+        mismatches.push(format!("{key}: expected {expected}, found {observed}"));
+    }
+}
+
+/// What a case expects of the graph of its program.
+#[derive(Debug, Default)]
+struct Expectations {
+    nodes: Option<usize>,
+    edges: Option<usize>,
+    free_vars: Option<Vec<String>>,
+    decompiled: Option<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut expectations = Self::default();
+        let mut lines = source.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("expected `key: value`, found {line:?}"))?;
+            let value = value.trim();
+            match key.trim() {
+                "nodes" => expectations.nodes = Some(value.parse()?),
+                "edges" => expectations.edges = Some(value.parse()?),
+                "free" => {
+                    expectations.free_vars = Some(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|var| !var.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                    );
+                }
+                "decompiled" => {
+                    expectations.decompiled = Some(lines.by_ref().collect::<Vec<_>>().join("\n"));
+                }
+                key => bail!("unknown expectation {key:?}"),
+            }
+        }
+        Ok(expectations)
+    }
+}
+
+/// What was found in the graph of a program.
+struct Observed {
+    nodes: usize,
+    edges: usize,
+    free_vars: Vec<String>,
+    decompiled: Option<String>,
+}
+
+fn observe_expr<T: Language + 'static>(expr: &Expr<T>) -> anyhow::Result<Observed>
+where
+    Expr<T>: PrettyPrint,
+{
+    let graph = expr.to_graph(false)?;
+    let decompiled = Expr::<T>::decompile(&graph)?.to_pretty();
+    Ok(observe(&graph, Some(decompiled)))
+}
+
+fn observe<W: Weight>(graph: &Hypergraph<W>, decompiled: Option<String>) -> Observed
+where
+    W::EdgeWeight: Display,
+{
+    let (nodes, edges) = size(graph);
+    Observed {
+        nodes,
+        edges: edges + graph.number_of_free_graph_inputs(),
+        free_vars: graph
+            .free_graph_inputs()
+            .map(|edge| edge.weight().to_string())
+            .collect(),
+        decompiled,
+    }
+}
+
+/// Counts the nodes of `graph` and the edges bound in it, descending into thunks.
+fn size<G, W>(graph: &G) -> (usize, usize)
+where
+    G: Graph<Ctx = Hypergraph<W>>,
+    W: Weight,
+{
+    graph.nodes().fold(
+        (0, graph.number_of_bound_graph_inputs()),
+        |(nodes, edges), node| {
+            let (inner_nodes, inner_edges) = match &node {
+                Node::Operation(_) => (0, 0),
+                Node::Thunk(thunk) => size(thunk),
+            };
+            (
+                nodes + 1 + inner_nodes,
+                edges + node.number_of_outputs() + inner_edges,
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{check_case, check_conformance};
+
+    #[test]
+    fn suite() -> anyhow::Result<()> {
+        check_conformance(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../conformance"))
+    }
+
+    #[test]
+    fn mismatches_are_reported() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("sd-conformance-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let program = dir.join("op.sd");
+        fs::write(&program, "plus(a, b)")?;
+        fs::write(
+            dir.join("op.sd.expected"),
+            "nodes: 1\nfree: b, a\ndecompiled:\nplus(b, a)",
+        )?;
+        let mismatches = check_case(&program);
+        let conformance = check_conformance(&dir);
+        fs::remove_dir_all(&dir)?;
+
+        let mismatches = mismatches?;
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches[0].starts_with("free:"));
+        assert!(mismatches[1].starts_with("decompiled:"));
+        assert!(conformance.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//! Recording panics, so that the application can recover from them and help the user report them.
+
+use std::{
+    backtrace::Backtrace,
+    fmt::Write,
+    sync::{Mutex, PoisonError},
+};
+
+use sd_core::lp;
+
+use crate::parser::UiLanguage;
+
+/// How much of the program being visualised to include in a bug report.
+const MAX_INPUT: usize = 4_000;
+
+/// The first panic since the last call to [`take_panic`].
+static PANIC: Mutex<Option<Panic>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub(crate) struct Panic {
+    pub(crate) message: String,
+    thread: String,
+    backtrace: String,
+}
+
+impl Panic {
+    fn new(message: String) -> Self {
+        Self {
+            message,
+            thread: std::thread::current()
+                .name()
+                .unwrap_or("<unnamed>")
+                .to_owned(),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+
+    /// A bug report in Markdown, ready to be pasted into an issue.
+    pub(crate) fn report(&self, code: &str, language: UiLanguage) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "**Version:** {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(report, "**Language:** {}", language.name());
+        let _ = writeln!(report, "**Thread:** {}", self.thread);
+        let _ = writeln!(report, "**Panic:** {}\n", self.message);
+        let _ = writeln!(
+            report,
+            "<details><summary>Backtrace</summary>\n\n```\n{}\n```\n</details>\n",
+            self.backtrace.trim_end()
+        );
+        let input = match code.char_indices().nth(MAX_INPUT) {
+            Some((end, _)) => format!("{}\n… ({} more bytes)", &code[..end], code.len() - end),
+            None => code.to_owned(),
+        };
+        let _ = writeln!(
+            report,
+            "<details><summary>Input</summary>\n\n```\n{}\n```\n</details>",
+            input.trim_end()
+        );
+        report
+    }
+}
+
+/// Installs a panic hook which records each panic before handing it to the previous hook.
+/// Panics caught by the solvers are only logged, as the layout recovers from them on its own.
+pub(crate) fn install_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if lp::is_catching_panics() {
+            tracing::warn!("Solver panicked: {info}");
+            return;
+        }
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_owned())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_owned());
+        let panic = Panic::new(match info.location() {
+            Some(location) => format!("{message} ({location})"),
+            None => message,
+        });
+        tracing::error!("Panic in thread {}: {}", panic.thread, panic.message);
+        PANIC
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert(panic);
+        previous(info);
+    }));
+}
+
+/// The first panic recorded since this was last called, if any.
+pub(crate) fn take_panic() -> Option<Panic> {
+    PANIC.lock().unwrap_or_else(PoisonError::into_inner).take()
+}
+
+#[cfg(test)]
+mod tests {
+    use sd_core::lp::catch_panics;
+
+    use super::{install_hook, take_panic};
+
+    #[test]
+    fn caught_solver_panics_are_not_recorded() {
+        install_hook();
+        assert!(catch_panics(|| panic!("solver")).is_err());
+        assert!(take_panic().is_none());
+
+        assert!(std::thread::spawn(|| panic!("crash")).join().is_err());
+        assert!(take_panic().is_some_and(|panic| panic.message.starts_with("crash")));
+    }
+}
@@ -0,0 +1,68 @@
+//! Diagnostic bundles, which gather what is needed to reproduce a performance or layout bug into
+//! a single zip file.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use zip::{write::FileOptions, ZipWriter};
+
+/// How much of the log to keep, in bytes.
+const LOG_CAPACITY: usize = 1 << 20;
+
+/// The most recent log output, oldest first.
+static LOG: Mutex<String> = Mutex::new(String::new());
+
+/// A log destination which keeps the most recent output in memory for diagnostic bundles.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        log.push_str(&String::from_utf8_lossy(buf));
+        if log.len() > LOG_CAPACITY {
+            // Drop the oldest half, keeping whole lines
+            let mid = log.len() - LOG_CAPACITY / 2;
+            let cut = log[mid..].find('\n').map_or(log.len(), |i| mid + i + 1);
+            log.drain(..cut);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes a diagnostic bundle to `path`, containing the log, the `stats` of the current graph
+/// and, if given, the name and contents of the source file.
+pub(crate) fn write_bundle(
+    path: &Path,
+    stats: &str,
+    source: Option<(&str, &str)>,
+) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default();
+
+    zip.start_file("log.txt", options)?;
+    zip.write_all(
+        LOG.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_bytes(),
+    )?;
+
+    zip.start_file("stats.txt", options)?;
+    zip.write_all(stats.as_bytes())?;
+
+    if let Some((name, code)) = source {
+        zip.start_file(name, options)?;
+        zip.write_all(code.as_bytes())?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
@@ -0,0 +1,234 @@
+#![allow(clippy::inline_always)]
+
+use delegate::delegate;
+use eframe::egui::{self, Color32};
+use poll_promise::Promise;
+use sd_core::{
+    graph::SyntaxHypergraph,
+    hypergraph::{
+        diff::{diff, Change, Diff},
+        generic::{Key, Node, Operation},
+        traits::{Graph, Keyable},
+    },
+    interactive::InteractiveGraph,
+    language::{chil::Chil, mlir::Mlir, spartan::Spartan},
+    lp::Solver,
+};
+use sd_graphics::common::LabelVisibility;
+
+use crate::{
+    graph_ui::{GraphUiInternal, Overlays},
+    parser::UiLanguage,
+    selection::SelectionLanguage,
+};
+
+/// The colour of the nodes which have changed in the way given.
+pub(crate) const fn change_color(change: Change) -> Color32 {
+    match change {
+        Change::Added => Color32::from_rgb(0x2e, 0x7d, 0x32),
+        Change::Removed => Color32::from_rgb(0xc6, 0x28, 0x28),
+        Change::Changed => Color32::from_rgb(0xef, 0x6c, 0x00),
+    }
+}
+
+type DiffGraph<T> = InteractiveGraph<SyntaxHypergraph<T>>;
+type DiffCtx<T> = <DiffGraph<T> as Graph>::Ctx;
+type CompilePromise<T> = Promise<anyhow::Result<(SyntaxHypergraph<T>, SyntaxHypergraph<T>)>>;
+
+/// A window comparing an earlier version of the program with the code being edited, drawing
+/// both side by side with the nodes added, removed and changed in between highlighted.
+pub enum DiffView {
+    Chil(DiffInternal<Chil>),
+    Mlir(DiffInternal<Mlir>),
+    Spartan(DiffInternal<Spartan>),
+}
+
+impl DiffView {
+    /// Compares `base`, a version of the program called `name`, with the code being edited,
+    /// unless the language cannot be compiled on its own.
+    pub(crate) fn new(
+        name: String,
+        base: String,
+        language: UiLanguage,
+        solver: Solver,
+    ) -> Option<Self> {
+        match language {
+            UiLanguage::Chil => Some(Self::Chil(DiffInternal::new(name, base, solver))),
+            UiLanguage::Mlir => Some(Self::Mlir(DiffInternal::new(name, base, solver))),
+            UiLanguage::Spartan => Some(Self::Spartan(DiffInternal::new(name, base, solver))),
+            UiLanguage::Dot => None,
+        }
+    }
+
+    delegate! {
+        to match self {
+            Self::Chil(view) => view,
+            Self::Mlir(view) => view,
+            Self::Spartan(view) => view,
+        } {
+            pub(crate) fn ui(&mut self, ctx: &egui::Context, code: &str, labels: LabelVisibility) -> bool;
+        }
+    }
+}
+
+pub struct DiffInternal<T: SelectionLanguage> {
+    name: String,
+    base: String,
+    /// The code last compared with the base, which is recompiled whenever it is edited.
+    code: Option<String>,
+    compile: Option<CompilePromise<T>>,
+    compile_error: Option<String>,
+    /// The diagrams of the base and of the code.
+    graphs: Option<[GraphUiInternal<DiffGraph<T>>; 2]>,
+    /// The differences between the diagrams, as they were when last compared.
+    diff: Option<([Key<DiffGraph<T>>; 2], Diff<DiffCtx<T>>)>,
+    /// Whether expanding or collapsing a thunk in either diagram does the same to the thunk with
+    /// the same address in the other.
+    mirror: bool,
+    open: bool,
+    solver: Solver,
+}
+
+impl<T: SelectionLanguage> DiffInternal<T> {
+    fn new(name: String, base: String, solver: Solver) -> Self {
+        Self {
+            name,
+            base,
+            code: None,
+            compile: None,
+            compile_error: None,
+            graphs: None,
+            diff: None,
+            mirror: false,
+            open: true,
+            solver,
+        }
+    }
+
+    /// Draws the window comparing the base with `code`.
+    /// Returns whether the window is still open.
+    pub(crate) fn ui(&mut self, ctx: &egui::Context, code: &str, labels: LabelVisibility) -> bool {
+        if self.code.as_deref() != Some(code) {
+            self.code = Some(code.to_owned());
+            self.compile = Some(spawn_compile::<T>(self.base.clone(), code.to_owned(), ctx));
+        }
+        if let Some(result) = self.compile.take_if(|promise| promise.ready().is_some()) {
+            match result.block_and_take() {
+                Ok((before, after)) => {
+                    // Both diagrams keep their viewports across recompilations
+                    let panzooms = self
+                        .graphs
+                        .as_ref()
+                        .map(|graphs| graphs.each_ref().map(GraphUiInternal::panzoom));
+                    let mut graphs = [before, after].map(|graph| {
+                        GraphUiInternal::new(InteractiveGraph::new(graph), self.solver)
+                    });
+                    if let Some(panzooms) = panzooms {
+                        for (graph_ui, panzoom) in graphs.iter_mut().zip(panzooms) {
+                            graph_ui.set_panzoom(panzoom);
+                        }
+                    }
+                    self.graphs = Some(graphs);
+                    self.compile_error = None;
+                }
+                Err(err) => self.compile_error = Some(format!("{err:#}")),
+            }
+        }
+
+        egui::Window::new(format!("Diff against {}", self.name))
+            .id(egui::Id::new("diff"))
+            .open(&mut self.open)
+            .show(ctx, |ui| {
+                if let Some(error) = &self.compile_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                let Some([before, after]) = &mut self.graphs else {
+                    ui.weak("Compiling both versions");
+                    return;
+                };
+
+                let keys = [before.graph.key(), after.graph.key()];
+                if self
+                    .diff
+                    .as_ref()
+                    .map_or(true, |(compared, _)| *compared != keys)
+                {
+                    self.diff = Some((keys.clone(), diff(&before.graph, &after.graph)));
+                }
+                let Some((_, diff)) = &self.diff else {
+                    return;
+                };
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut self.mirror, "Mirror expansion")
+                        .on_hover_text("Expand and collapse the same thunks in both diagrams")
+                        .changed()
+                        && self.mirror
+                    {
+                        after.graph.mirror_expansion(&before.graph);
+                    }
+                    if diff.is_empty() {
+                        ui.weak("No differences");
+                    }
+                    for (change, text) in [
+                        (Change::Added, "added"),
+                        (Change::Removed, "removed"),
+                        (Change::Changed, "changed"),
+                    ] {
+                        let count = diff.count(change);
+                        if count > 0 {
+                            ui.colored_label(change_color(change), format!("{count} {text}"));
+                        }
+                    }
+                });
+
+                let color = |op: &Operation<DiffCtx<T>>| {
+                    let change = diff.changes.get(&Node::Operation(op.clone()))?;
+                    Some(change_color(*change))
+                };
+                ui.columns(2, |columns| {
+                    for ((ui, graph_ui), title) in columns
+                        .iter_mut()
+                        .zip([&mut *before, &mut *after])
+                        .zip([self.name.as_str(), "Current code"])
+                    {
+                        ui.strong(title);
+                        graph_ui.ui(
+                            ui,
+                            None,
+                            labels,
+                            &[],
+                            None,
+                            Overlays::default(),
+                            Some(&color),
+                        );
+                    }
+                });
+                // Follows whichever diagram was clicked
+                if self.mirror {
+                    if before.graph.key() != keys[0] {
+                        after.graph.mirror_expansion(&before.graph);
+                    } else if after.graph.key() != keys[1] {
+                        before.graph.mirror_expansion(&after.graph);
+                    }
+                }
+            });
+
+        self.open
+    }
+}
+
+/// Compiles the base and the code being edited, to compare them.
+fn spawn_compile<T: SelectionLanguage>(
+    base: String,
+    code: String,
+    ctx: &egui::Context,
+) -> CompilePromise<T> {
+    let ctx = ctx.clone();
+    crate::spawn!("diff_compile", {
+        let graphs = (T::compile(&base)?, T::compile(&code)?);
+        ctx.request_repaint();
+        Ok(graphs)
+    })
+}
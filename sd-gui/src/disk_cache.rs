@@ -0,0 +1,141 @@
+//! A persistent cache of layout solutions, keyed by a hash of the source they were computed from.
+//!
+//! Reopening a file that has been viewed before replays the stored solutions
+//! instead of solving the layout problems again. Only the layouts are stored, as parsing a program
+//! and building its graph take little time next to solving its layout.
+
+use std::{
+    fmt::{Debug, Write},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use sd_core::lp::{SolutionLog, Solver};
+use sha2::{Digest, Sha256};
+
+use crate::parser::UiLanguage;
+
+/// How many bytes of layouts to keep, dropping those used least recently beyond it.
+const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The extension of the files of the cache.
+const EXTENSION: &str = "json";
+
+/// A hash of everything a layout depends on. The hash is the same across builds and platforms, so
+/// the layouts stored by one run are found by the next.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    pub(crate) fn new(
+        source: &str,
+        language: UiLanguage,
+        settings: impl Debug,
+        solver: Solver,
+    ) -> Self {
+        let settings = format!("{settings:?}");
+        let solver = format!("{solver:?}");
+        let mut hasher = Sha256::new();
+        for part in [
+            env!("CARGO_PKG_VERSION"),
+            source,
+            language.name(),
+            settings.as_str(),
+            solver.as_str(),
+        ] {
+            // Prefixed by their lengths, so that no two sets of parts hash the same
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        Self(hasher.finalize().into())
+    }
+
+    fn path(self) -> Option<PathBuf> {
+        let mut name = String::with_capacity(2 * self.0.len());
+        for byte in self.0 {
+            let _ = write!(name, "{byte:02x}");
+        }
+        Some(cache_dir()?.join(name).with_extension(EXTENSION))
+    }
+}
+
+/// The platform cache directory for the application.
+fn cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var_os("LOCALAPPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var_os("HOME")?).join("Library/Caches")
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".cache")))?
+    };
+    Some(base.join("sd-visualiser"))
+}
+
+/// Load the solutions stored for `key`, if any.
+pub fn load(key: CacheKey) -> Option<SolutionLog> {
+    let path = key.path()?;
+    let contents = fs::read_to_string(&path).ok()?;
+    // Marks the layout as used, so that it is among the last to be dropped
+    let _ = fs::File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    match SolutionLog::from_json(&contents) {
+        Ok(log) => Some(log),
+        Err(err) => {
+            tracing::warn!("Ignoring corrupt layout cache: {err}");
+            None
+        }
+    }
+}
+
+/// Store the solutions logged for `key`.
+pub fn store(key: CacheKey, log: &SolutionLog) {
+    let Some(path) = key.path() else {
+        return;
+    };
+    let result = log
+        .to_json()
+        .map_err(std::io::Error::from)
+        .and_then(|contents| {
+            path.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&path, contents))
+        });
+    if let Err(err) = result {
+        tracing::warn!("Failed to write layout cache {}: {err}", path.display());
+    }
+    if let Some(dir) = path.parent() {
+        if let Err(err) = prune(dir) {
+            tracing::warn!("Failed to prune layout cache {}: {err}", dir.display());
+        }
+    }
+}
+
+/// Drops the layouts used least recently until those in `dir` take up at most [`MAX_SIZE`].
+fn prune(dir: &Path) -> std::io::Result<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+        {
+            let metadata = fs::metadata(&path)?;
+            entries.push((metadata.modified()?, metadata.len(), path));
+        }
+    }
+    let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+    entries.sort_unstable();
+    for (_, len, path) in entries {
+        if size <= MAX_SIZE {
+            break;
+        }
+        fs::remove_file(path)?;
+        size -= len;
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use std::{io, path::PathBuf};
+
+use eframe::egui::{self, Align2, Vec2};
+use egui_notify::Toasts;
+use poll_promise::Promise;
+use sd_core::{hypergraph::address::NodeAddress, prettyprinter::html::to_html_page};
+
+use crate::{
+    graph_ui::{Drawing, ExportScope, GraphUi},
+    parser::{parse, ParseOutput, UiLanguage},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ExportFormat {
+    Svg,
+    Tikz,
+    Outline,
+    Dot,
+}
+
+impl ExportFormat {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Svg => "SVG",
+            Self::Tikz => "TikZ",
+            Self::Outline => "outline",
+            Self::Dot => "dot",
+        }
+    }
+}
+
+/// A drawing being written to a file on another thread.
+pub(crate) struct PendingExport {
+    format: ExportFormat,
+    promise: Promise<io::Result<()>>,
+}
+
+impl PendingExport {
+    /// Reports a failed export once it is done, returning whether it is still running.
+    pub(crate) fn poll(&self, toasts: &mut Toasts) -> bool {
+        match self.promise.ready() {
+            None => true,
+            Some(Ok(())) => false,
+            Some(Err(err)) => {
+                toasts.error(format!("Failed to export {}: {err}", self.format.name()));
+                false
+            }
+        }
+    }
+}
+
+/// The dialog asking how much of the program to export.
+#[derive(Debug)]
+pub(crate) struct ExportDialog {
+    format: ExportFormat,
+    scope: ExportScope,
+    thunks: Vec<NodeAddress>,
+    /// CSS to style an svg with, read when exporting so that edits to it are picked up.
+    stylesheet: Option<PathBuf>,
+}
+
+impl ExportDialog {
+    pub(crate) fn new(format: ExportFormat, graph_ui: &GraphUi) -> Self {
+        Self {
+            format,
+            scope: ExportScope::default(),
+            thunks: graph_ui.thunks(),
+            stylesheet: None,
+        }
+    }
+
+    /// Shows the dialog, returning whether it should stay open. Drawings are written on another
+    /// thread, and added to `exports` to be reported on once they are done.
+    pub(crate) fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        graph_ui: &GraphUi,
+        toasts: &mut Toasts,
+        exports: &mut Vec<PendingExport>,
+    ) -> bool {
+        let mut export = false;
+        let mut cancel = false;
+        egui::Window::new(format!("Export {}", self.format.name()))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::default())
+            .show(ctx, |ui| {
+                ui.radio_value(&mut self.scope, ExportScope::Full, "Full program")
+                    .on_hover_text("With every thunk expanded");
+                ui.radio_value(&mut self.scope, ExportScope::Visible, "Current view")
+                    .on_hover_text("With collapsed thunks left collapsed");
+                ui.add_enabled_ui(!self.thunks.is_empty(), |ui| {
+                    ui.horizontal(|ui| {
+                        let selected = matches!(self.scope, ExportScope::Thunk(_));
+                        if ui.radio(selected, "Single thunk").clicked() && !selected {
+                            self.scope = ExportScope::Thunk(self.thunks[0].clone());
+                        }
+                        if let ExportScope::Thunk(address) = &mut self.scope {
+                            egui::ComboBox::from_id_source("export_thunk")
+                                .selected_text(address.to_string())
+                                .show_ui(ui, |ui| {
+                                    for thunk in &self.thunks {
+                                        ui.selectable_value(
+                                            address,
+                                            thunk.clone(),
+                                            thunk.to_string(),
+                                        );
+                                    }
+                                });
+                        }
+                    });
+                });
+                if self.format == ExportFormat::Svg {
+                    ui.add_space(4.0);
+                    ui.horizontal(|ui| {
+                        ui.label("Stylesheet:");
+                        match &self.stylesheet {
+                            Some(path) => {
+                                ui.monospace(
+                                    path.file_name()
+                                        .unwrap_or(path.as_os_str())
+                                        .to_string_lossy()
+                                        .into_owned(),
+                                );
+                                if ui.small_button("Clear").clicked() {
+                                    self.stylesheet = None;
+                                }
+                            }
+                            None => {
+                                ui.weak("default colours");
+                            }
+                        }
+                        if ui
+                            .small_button("Choose…")
+                            .on_hover_text(
+                                "CSS using the classes operation, thunk, wire and dot, and op- \
+                                 followed by the label of an operation",
+                            )
+                            .clicked()
+                        {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("CSS", &["css"])
+                                .pick_file()
+                            {
+                                self.stylesheet = Some(path);
+                            }
+                        }
+                    });
+                }
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    export = ui.button("Export…").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if export {
+            exports.extend(self.export(graph_ui, toasts));
+        }
+        !(export || cancel)
+    }
+
+    fn export(&self, graph_ui: &GraphUi, toasts: &mut Toasts) -> Option<PendingExport> {
+        let name = match &self.scope {
+            ExportScope::Thunk(address) => address
+                .0
+                .last()
+                .map_or("thunk", |step| step.label.as_str())
+                .replace(|c: char| !c.is_alphanumeric(), "_"),
+            ExportScope::Full | ExportScope::Visible => "diagram".to_owned(),
+        };
+        let (path, drawing) = match self.format {
+            ExportFormat::Svg => {
+                let path = rfd::FileDialog::new()
+                    .add_filter("SVG", &["svg"])
+                    .set_file_name(format!("{name}.svg"))
+                    .save_file()?;
+                let stylesheet = match &self.stylesheet {
+                    Some(stylesheet) => match std::fs::read_to_string(stylesheet) {
+                        Ok(stylesheet) => Some(stylesheet),
+                        Err(err) => {
+                            toasts.error(format!("Failed to export SVG: {err}"));
+                            return None;
+                        }
+                    },
+                    None => None,
+                };
+                (path, Drawing::Svg { stylesheet })
+            }
+            ExportFormat::Tikz => {
+                let path = rfd::FileDialog::new()
+                    .add_filter("TikZ", &["tex", "tikz"])
+                    .set_file_name(format!("{name}.tex"))
+                    .save_file()?;
+                (path, Drawing::Tikz)
+            }
+            ExportFormat::Outline => {
+                let path = rfd::FileDialog::new()
+                    .add_filter("Markdown", &["md"])
+                    .set_file_name(format!("{name}.md"))
+                    .save_file()?;
+                let title = path
+                    .file_stem()
+                    .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+                let Some(outline) = graph_ui.outline(&title, &self.scope) else {
+                    toasts.error("Failed to export outline: the thunk no longer exists");
+                    return None;
+                };
+                if let Err(err) = std::fs::write(&path, outline) {
+                    toasts.error(format!("Failed to export outline: {err}"));
+                }
+                return None;
+            }
+            ExportFormat::Dot => {
+                let path = rfd::FileDialog::new()
+                    .add_filter("Graphviz", &["dot", "gv"])
+                    .set_file_name(format!("{name}.dot"))
+                    .save_file()?;
+                let Some(dot) = graph_ui.dot(&self.scope) else {
+                    toasts.error("Failed to export dot: the thunk no longer exists");
+                    return None;
+                };
+                if let Err(err) = std::fs::write(&path, dot) {
+                    toasts.error(format!("Failed to export dot: {err}"));
+                }
+                return None;
+            }
+        };
+        // Drawings are laid out first, which can take a while for large programs
+        Some(PendingExport {
+            format: self.format,
+            promise: graph_ui.export_drawing(path, &self.scope, drawing),
+        })
+    }
+}
+
+/// Exports the program in `code` as an HTML page in which each variable links to its binder.
+pub(crate) fn export_html(code: &str, language: UiLanguage, toasts: &mut Toasts) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("HTML", &["html"])
+        .set_file_name("program.html")
+        .save_file()
+    else {
+        return;
+    };
+    let title = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let result = parse(code, language)
+        .map_err(anyhow::Error::from)
+        .and_then(|output| {
+            let html = match output {
+                ParseOutput::Chil(expr) => to_html_page(&expr, &title),
+                ParseOutput::Spartan(expr) => to_html_page(&expr, &title),
+                ParseOutput::Mlir(expr) => to_html_page(&expr, &title),
+                ParseOutput::Dot(_) => anyhow::bail!("dot graphs have no source to export"),
+            };
+            Ok(std::fs::write(&path, html)?)
+        });
+    if let Err(err) = result {
+        toasts.error(format!("Failed to export HTML: {err:#}"));
+    }
+}
@@ -0,0 +1,280 @@
+//! The files which have been opened, so that the user can switch between them and search all of
+//! them at once, for example to find every use of an operation across a multi-file Chil dump.
+
+use eframe::egui;
+use poll_promise::Promise;
+use sd_core::{
+    common::{Matchable, Query},
+    dot::{dot_to_graph, DotSettings},
+    hypergraph::{
+        generic::{Operation, Thunk},
+        traits::Graph,
+    },
+    interactive::InteractiveGraph,
+    language::{chil::module::parse_module, mlir::MlirSettings},
+    prettyprinter::PrettyPrint,
+};
+
+use crate::parser::{parse, ParseOutput, UiLanguage};
+
+/// The name of the file holding the open Chil files linked together.
+const LINKED: &str = "linked.chil";
+
+#[derive(Clone, Debug)]
+pub(crate) struct OpenFile {
+    pub(crate) name: String,
+    pub(crate) code: String,
+    pub(crate) language: UiLanguage,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Files {
+    files: Vec<OpenFile>,
+    /// The file shown in the editor, whose code in `files` is stale until it is stashed.
+    current: Option<usize>,
+}
+
+impl Files {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The file shown in the editor, if it was opened from a file.
+    pub(crate) fn current(&self) -> Option<&OpenFile> {
+        self.current.and_then(|current| self.files.get(current))
+    }
+
+    /// Opens `file`, replacing any open file of the same name, and makes it the current one.
+    /// `code` is the contents of the editor, which are kept for the previous file.
+    pub(crate) fn open(&mut self, file: OpenFile, code: &str) {
+        self.stash(code);
+        let index = match self.files.iter().position(|open| open.name == file.name) {
+            Some(index) => {
+                self.files[index] = file;
+                index
+            }
+            None => {
+                self.files.push(file);
+                self.files.len() - 1
+            }
+        };
+        self.current = Some(index);
+    }
+
+    /// Makes the file at `index` the current one, returning it so that it can be loaded into the
+    /// editor.
+    pub(crate) fn switch(&mut self, index: usize, code: &str) -> &OpenFile {
+        self.stash(code);
+        self.current = Some(index);
+        &self.files[index]
+    }
+
+    /// Closes the file at `index`. The editor keeps its contents if it was the current one.
+    pub(crate) fn close(&mut self, index: usize) {
+        self.files.remove(index);
+        self.current = match self.current {
+            Some(current) if current == index => None,
+            Some(current) if current > index => Some(current - 1),
+            current => current,
+        };
+    }
+
+    /// The open Chil files which are parts of a program, rather than their linked program.
+    fn chil_parts(&self) -> impl Iterator<Item = &OpenFile> {
+        self.files
+            .iter()
+            .filter(|file| file.language == UiLanguage::Chil && file.name != LINKED)
+    }
+
+    /// Whether there are several Chil files open to link together.
+    pub(crate) fn can_link(&self) -> bool {
+        self.chil_parts().nth(1).is_some()
+    }
+
+    /// Links the open Chil files into one program, resolving the variables each of them uses to
+    /// those defined in the others. `code` is the contents of the editor, which are kept for the
+    /// current file first.
+    pub(crate) fn link_chil(&mut self, code: &str) -> anyhow::Result<OpenFile> {
+        self.stash(code);
+        let expr = parse_module(
+            self.chil_parts()
+                .map(|file| (file.name.as_str(), file.code.as_str())),
+        )?;
+        Ok(OpenFile {
+            name: LINKED.to_owned(),
+            code: expr.to_pretty(),
+            language: UiLanguage::Chil,
+        })
+    }
+
+    /// Records the edits made to the current file.
+    fn stash(&mut self, code: &str) {
+        if let Some(file) = self.current.and_then(|current| self.files.get_mut(current)) {
+            code.clone_into(&mut file.code);
+        }
+    }
+
+    /// Shows the list of open files, returning the index of one to switch to.
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui) -> Option<usize> {
+        let mut switch = None;
+        let mut close = None;
+        for (i, file) in self.files.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui
+                    .selectable_label(self.current == Some(i), &file.name)
+                    .clicked()
+                {
+                    switch = Some(i);
+                }
+                if ui.small_button("🗙").on_hover_text("Close file").clicked() {
+                    close = Some(i);
+                }
+            });
+        }
+        if self.files.is_empty() {
+            ui.weak("No open files");
+        }
+        if let Some(i) = close {
+            self.close(i);
+        }
+        switch.filter(|i| Some(*i) != self.current)
+    }
+}
+
+/// The number of matches for a query in each open file, or why the file could not be searched.
+type Hits = Vec<(usize, Result<usize, String>)>;
+
+/// A search for a query across every open file.
+#[derive(Default)]
+pub(crate) struct FileSearch {
+    query: String,
+    /// Why the query is not a valid regular expression.
+    error: Option<String>,
+    hits: Option<(String, Promise<Hits>)>,
+}
+
+impl FileSearch {
+    /// Shows the search window, returning whether it should stay open, along with the index of a
+    /// file to switch to and the query to find in it if the user picked one of the results.
+    pub(crate) fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        files: &mut Files,
+        code: &str,
+        settings: &(DotSettings, MlirSettings),
+    ) -> (bool, Option<(usize, String)>) {
+        let mut open = true;
+        let mut jump = None;
+        egui::Window::new("Find in open files")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let response = ui.text_edit_singleline(&mut self.query);
+                    let enter =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("Search").clicked() || enter) && !self.query.is_empty() {
+                        match Query::parse(&self.query) {
+                            Ok(query) => {
+                                files.stash(code);
+                                self.error = None;
+                                self.hits = Some((
+                                    self.query.clone(),
+                                    search(files.files.clone(), query, settings.clone()),
+                                ));
+                            }
+                            Err(err) => self.error = Some(err.to_string()),
+                        }
+                    }
+                });
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                let Some((query, hits)) = &self.hits else {
+                    return;
+                };
+                let Some(hits) = hits.ready() else {
+                    ui.spinner();
+                    return;
+                };
+                ui.separator();
+                egui::Grid::new("file_search_hits").show(ui, |ui| {
+                    for (i, hits) in hits {
+                        let Some(file) = files.files.get(*i) else {
+                            continue;
+                        };
+                        match hits {
+                            Ok(0) => {
+                                ui.weak(&file.name);
+                                ui.weak("no matches");
+                            }
+                            Ok(count) => {
+                                if ui.link(&file.name).clicked() {
+                                    jump = Some((*i, query.clone()));
+                                }
+                                ui.label(format!(
+                                    "{count} {}",
+                                    if *count == 1 { "match" } else { "matches" }
+                                ));
+                            }
+                            Err(err) => {
+                                ui.weak(&file.name);
+                                ui.colored_label(ui.visuals().error_fg_color, "failed to parse")
+                                    .on_hover_text(err);
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        (open, jump)
+    }
+}
+
+/// Counts the matches for `query` in each of `files` in the background.
+fn search(
+    files: Vec<OpenFile>,
+    query: Query,
+    (dot_settings, mlir_settings): (DotSettings, MlirSettings),
+) -> Promise<Hits> {
+    crate::spawn!("file_search", {
+        files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let hits = count_matches(file, &query, dot_settings, &mlir_settings)
+                    .map_err(|err| format!("{err:#}"));
+                (i, hits)
+            })
+            .collect()
+    })
+}
+
+fn count_matches(
+    file: &OpenFile,
+    query: &Query,
+    dot_settings: DotSettings,
+    mlir_settings: &MlirSettings,
+) -> anyhow::Result<usize> {
+    Ok(match parse(&file.code, file.language)? {
+        ParseOutput::Chil(expr) => count(expr.to_graph(false)?, query),
+        ParseOutput::Spartan(expr) => count(expr.to_graph(false)?, query),
+        ParseOutput::Mlir(expr) => count(
+            mlir_settings
+                .filter
+                .apply(&expr)
+                .to_graph(mlir_settings.sym_name_linking)?,
+            query,
+        ),
+        ParseOutput::Dot(graph) => count(dot_to_graph(&graph, dot_settings)?, query),
+    })
+}
+
+/// The number of operations and thunks in `graph` matching `query`.
+fn count<G: Graph>(graph: G, query: &Query) -> usize
+where
+    Operation<G::Ctx>: Matchable,
+    Thunk<G::Ctx>: Matchable,
+{
+    InteractiveGraph::new(graph).search(query).len()
+}
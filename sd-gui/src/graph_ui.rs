@@ -1,53 +1,299 @@
 #![allow(clippy::inline_always)]
 
-use std::fmt::Display;
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use delegate::delegate;
 use eframe::{
     egui,
-    epaint::{Rounding, Shape},
+    epaint::{Color32, Pos2, Rounding, Shape},
 };
+use indexmap::IndexSet;
+use poll_promise::Promise;
 use sd_core::{
     codeable::Codeable,
-    common::{Direction, Matchable},
-    dot::DotWeight,
+    common::{Direction, Matchable, Query, Spanned},
+    dot::{dot_to_graph, graph_to_dot, DotSettings, DotWeight},
     graph::SyntaxHypergraph,
     hypergraph::{
-        generic::{Edge, Operation, Thunk, Weight},
+        address::{addresses, node_at, NodeAddress},
+        generic::{Ctx, Edge, Key, Node, Operation, Thunk, Weight},
+        reachability::NReachable,
         subgraph::ExtensibleEdge,
-        traits::{Graph, WithType},
+        traits::{Graph, NodeLike, WithType, WithWeight},
         Hypergraph,
     },
-    interactive::InteractiveGraph,
-    language::{chil::Chil, mlir::Mlir, spartan::Spartan},
+    interactive::{History, InteractiveGraph, SameState, Toggle},
+    language::{
+        chil::{
+            cluster::{self, cluster_of, Cluster},
+            Chil,
+        },
+        mlir::{Mlir, MlirSettings},
+        spartan::Spartan,
+        Expr, Language,
+    },
     lp::Solver,
+    outline::to_markdown,
+    profile::Profile,
+};
+use sd_graphics::{
+    common::{type_color, Badge, LabelVisibility, Shapeable},
+    renderable::RenderableGraph,
+    shape::Shapes,
 };
-use sd_graphics::{common::Shapeable, renderable::RenderableGraph};
 
-use crate::{panzoom::Panzoom, shape_generator::generate_shapes};
+use crate::{
+    disk_cache::CacheKey,
+    help,
+    navigation::{self, Step},
+    panzoom::{Panzoom, DEFAULT_ZOOM},
+    parser::ParseOutput,
+    progress,
+    rename::Variable,
+    shape_generator::{
+        cached_shapes, generate_draft_shapes, generate_exact_shapes, generate_shapes,
+    },
+};
+
+type ChilGraph = InteractiveGraph<SyntaxHypergraph<Chil>>;
 
 pub enum GraphUi {
-    Chil(GraphUiInternal<InteractiveGraph<SyntaxHypergraph<Chil>>>),
+    Chil(GraphUiInternal<ChilGraph>),
     Mlir(GraphUiInternal<InteractiveGraph<SyntaxHypergraph<Mlir>>>),
     Spartan(GraphUiInternal<InteractiveGraph<SyntaxHypergraph<Spartan>>>),
     Dot(GraphUiInternal<InteractiveGraph<Hypergraph<DotWeight>>>),
 }
 
+/// Evaluates `$body` with `$graph_ui` bound to the graph of whichever language is shown.
+macro_rules! each {
+    ($graph_ui:ident in $self:expr => $body:expr) => {
+        match $self {
+            GraphUi::Chil($graph_ui) => $body,
+            GraphUi::Mlir($graph_ui) => $body,
+            GraphUi::Spartan($graph_ui) => $body,
+            GraphUi::Dot($graph_ui) => $body,
+        }
+    };
+}
+
+/// Applies an interaction to whichever graph is shown, so that it can be undone.
+macro_rules! interact {
+    ($graph_ui:expr, $f:expr) => {
+        each!(graph_ui in $graph_ui => graph_ui.interact($f))
+    };
+}
+
+/// The furthest zooming to a node zooms in, relative to the default zoom.
+const NODE_ZOOM: f32 = 4.0;
+
+/// How many of the slices with the most wire crossings to highlight.
+const HOTSPOTS: usize = 5;
+
+/// How often an export checks whether the diagram has been laid out.
+const EXPORT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The stage of compilation in which the hypergraph is built from the parsed source.
+const BUILDING: &str = "Building graph";
+
+/// Extra information which can be drawn over the diagram.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) struct Overlays {
+    /// Whether to highlight the slices where the most wires cross.
+    pub(crate) crossings: bool,
+    /// Whether to draw control-flow edges as arrows from branches to the blocks they target.
+    pub(crate) control_flow: bool,
+    /// Whether to leave out wires which only order effects, such as those into a Chil `seq`.
+    pub(crate) hide_ordering: bool,
+    /// Whether to colour wires by the types of their values, for languages which annotate them.
+    pub(crate) type_colors: bool,
+    /// Whether to draw the wires into copies thicker the more consumers they reach, splitting
+    /// towards them.
+    pub(crate) bundle_copies: bool,
+    /// Whether to show the whole diagram in a corner, to pan by clicking or dragging on it.
+    pub(crate) minimap: bool,
+    /// Whether to outline the operations of Chil programs in a colour for the top-level
+    /// definition they were written in.
+    pub(crate) clusters: bool,
+    /// Whether to highlight the paths between two selected nodes, dimming the rest.
+    pub(crate) paths: bool,
+    /// Whether the paths between two selected nodes stay at the depth of the thunk containing
+    /// both, rather than going into the thunks along them.
+    pub(crate) flat_paths: bool,
+}
+
+/// How much of the program an export covers.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub(crate) enum ExportScope {
+    /// Every thunk, expanded.
+    Full,
+    /// What is currently shown, respecting which thunks are collapsed.
+    #[default]
+    Visible,
+    /// The full contents of a single thunk.
+    Thunk(NodeAddress),
+}
+
+/// The file format to draw a diagram in.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Drawing {
+    /// An svg document, styled by the CSS in `stylesheet`.
+    Svg { stylesheet: Option<String> },
+    /// A TikZ picture, for LaTeX documents.
+    Tikz,
+    /// A png image.
+    Png,
+}
+
+impl Drawing {
+    /// Streams `shapes` to a file at `path`.
+    fn write<T: Ctx>(self, shapes: &Shapes<T>, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match self {
+            Self::Svg { stylesheet } => shapes.write_svg(&mut writer, stylesheet.as_deref())?,
+            Self::Tikz => shapes.write_tikz(&mut writer)?,
+            Self::Png => shapes.write_png(&mut writer)?,
+        }
+        writer.flush()
+    }
+}
+
+/// Floats the bindings of `expr` into and out of thunks, if enabled, leaving the source as it is.
+fn floated<T: Language>(expr: Cow<'_, Expr<T>>, float_lets: bool) -> Cow<'_, Expr<T>> {
+    if float_lets {
+        tracing::debug!("Floating bindings...");
+        Cow::Owned(expr.float_lets())
+    } else {
+        expr
+    }
+}
+
 impl GraphUi {
-    pub(crate) fn new_chil(graph: SyntaxHypergraph<Chil>, solver: Solver) -> Self {
-        Self::Chil(GraphUiInternal::new(InteractiveGraph::new(graph), solver))
+    pub(crate) fn new_chil(
+        graph: SyntaxHypergraph<Chil>,
+        clusters: Vec<Cluster>,
+        solver: Solver,
+        cache_key: Option<CacheKey>,
+    ) -> Self {
+        let graph = InteractiveGraph::new(graph);
+        let preview = collapsed(&graph);
+        Self::Chil(
+            GraphUiInternal::new(graph, solver)
+                .with_cache_key(cache_key)
+                .with_clusters(clusters)
+                .with_preview(&preview),
+        )
+    }
+
+    pub(crate) fn new_mlir(
+        graph: SyntaxHypergraph<Mlir>,
+        solver: Solver,
+        cache_key: Option<CacheKey>,
+    ) -> Self {
+        let graph = InteractiveGraph::new(graph);
+        let preview = collapsed(&graph);
+        Self::Mlir(
+            GraphUiInternal::new(graph, solver)
+                .with_cache_key(cache_key)
+                .with_preview(&preview),
+        )
     }
 
-    pub(crate) fn new_mlir(graph: SyntaxHypergraph<Mlir>, solver: Solver) -> Self {
-        Self::Mlir(GraphUiInternal::new(InteractiveGraph::new(graph), solver))
+    pub(crate) fn new_spartan(
+        graph: SyntaxHypergraph<Spartan>,
+        solver: Solver,
+        cache_key: Option<CacheKey>,
+    ) -> Self {
+        let graph = InteractiveGraph::new(graph);
+        let preview = collapsed(&graph);
+        Self::Spartan(
+            GraphUiInternal::new(graph, solver)
+                .with_cache_key(cache_key)
+                .with_preview(&preview),
+        )
     }
 
-    pub(crate) fn new_spartan(graph: SyntaxHypergraph<Spartan>, solver: Solver) -> Self {
-        Self::Spartan(GraphUiInternal::new(InteractiveGraph::new(graph), solver))
+    pub(crate) fn new_dot(
+        graph: Hypergraph<DotWeight>,
+        solver: Solver,
+        cache_key: Option<CacheKey>,
+    ) -> Self {
+        let graph = InteractiveGraph::new(graph);
+        let preview = collapsed(&graph);
+        Self::Dot(
+            GraphUiInternal::new(graph, solver)
+                .with_cache_key(cache_key)
+                .with_preview(&preview),
+        )
     }
 
-    pub(crate) fn new_dot(graph: Hypergraph<DotWeight>, solver: Solver) -> Self {
-        Self::Dot(GraphUiInternal::new(InteractiveGraph::new(graph), solver))
+    /// Converts parsed source into a graph for display, collapsing the definitions of Chil
+    /// programs named in `collapsed_clusters` into single operations.
+    pub(crate) fn from_parse(
+        parse_output: &ParseOutput,
+        dot_settings: DotSettings,
+        mlir_settings: &MlirSettings,
+        float_lets: bool,
+        collapsed_clusters: &BTreeSet<String>,
+        solver: Solver,
+        cache_key: Option<CacheKey>,
+    ) -> anyhow::Result<Self> {
+        Ok(match parse_output {
+            ParseOutput::Chil(expr) => {
+                tracing::debug!("Converting chil to hypergraph...");
+                let clusters = cluster::clusters(expr);
+                let expr = if collapsed_clusters.is_empty() {
+                    Cow::Borrowed(expr)
+                } else {
+                    Cow::Owned(cluster::collapse(expr.clone(), collapsed_clusters))
+                };
+                let expr = floated(expr, float_lets);
+                Self::new_chil(
+                    expr.to_graph_with_progress(false, progress::reporter(BUILDING))?,
+                    clusters,
+                    solver,
+                    cache_key,
+                )
+            }
+            ParseOutput::Mlir(expr) => {
+                tracing::debug!("Converting mlir to hypergraph...");
+                let expr = if mlir_settings.filter.is_empty() {
+                    Cow::Borrowed(expr)
+                } else {
+                    Cow::Owned(mlir_settings.filter.apply(expr))
+                };
+                let expr = floated(expr, float_lets);
+                Self::new_mlir(
+                    expr.to_graph_with_progress(
+                        mlir_settings.sym_name_linking,
+                        progress::reporter(BUILDING),
+                    )?,
+                    solver,
+                    cache_key,
+                )
+            }
+            ParseOutput::Spartan(expr) => {
+                tracing::debug!("Converting spartan to hypergraph...");
+                let expr = floated(Cow::Borrowed(expr), float_lets);
+                Self::new_spartan(
+                    expr.to_graph_with_progress(false, progress::reporter(BUILDING))?,
+                    solver,
+                    cache_key,
+                )
+            }
+            ParseOutput::Dot(graph) => {
+                tracing::debug!("Converting dot to hypergraph...");
+                Self::new_dot(dot_to_graph(graph, dot_settings)?, solver, cache_key)
+            }
+        })
     }
 
     delegate! {
@@ -57,13 +303,19 @@ impl GraphUi {
             GraphUi::Spartan(graph_ui) => graph_ui,
         GraphUi::Dot(graph_ui) => graph_ui
         } {
-            pub(crate) fn ui(&mut self, ui: &mut egui::Ui, search: Option<&str>);
             pub(crate) const fn ready(&self) -> bool;
             pub(crate) fn reset(&mut self);
+            pub(crate) fn fit_selection(&mut self);
             pub(crate) fn zoom_in(&mut self);
             pub(crate) fn zoom_out(&mut self);
-            pub(crate) fn find(&mut self, query: &str, offset: usize);
-            pub(crate) fn export_svg(&self) -> String;
+            pub(crate) fn find(&mut self, query: &Query, offset: usize);
+            pub(crate) const fn panzoom(&self) -> Panzoom;
+            pub(crate) fn set_panzoom(&mut self, panzoom: Panzoom);
+            pub(crate) fn undo(&mut self);
+            pub(crate) fn redo(&mut self);
+            pub(crate) fn can_undo(&self) -> bool;
+            pub(crate) fn can_redo(&self) -> bool;
+            pub(crate) fn layout_time(&self) -> Option<Duration>;
         }
     }
 
@@ -75,11 +327,287 @@ impl GraphUi {
         GraphUi::Dot(graph_ui) => graph_ui.graph
         } {
             pub(crate) fn is_empty(&self) -> bool;
-            pub(crate) fn clear_selection(&mut self);
-            pub(crate) fn extend_selection(&mut self, direction: Option<(Direction, usize)>);
-            pub(crate) fn set_expanded_all(&mut self, expanded: bool);
         }
     }
+
+    pub(crate) fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        search: Option<&Query>,
+        labels: LabelVisibility,
+        badges: &[Badge],
+        profile: Option<&Arc<Profile>>,
+        overlays: Overlays,
+    ) {
+        self.breadcrumb_ui(ui);
+        match self {
+            Self::Chil(graph_ui) if overlays.clusters => {
+                let clusters = graph_ui.clusters.clone();
+                let dark_mode = ui.visuals().dark_mode;
+                let color = |op: &Operation<<ChilGraph as Graph>::Ctx>| {
+                    let cluster = &clusters[cluster_of(&clusters, op.weight().span()?)?];
+                    Some(type_color(&cluster.name, dark_mode))
+                };
+                graph_ui.ui(ui, search, labels, badges, profile, overlays, Some(&color));
+            }
+            _ => each!(graph_ui in self => {
+                graph_ui.ui(ui, search, labels, badges, profile, overlays, None);
+            }),
+        }
+        each!(graph_ui in self => {
+            if let Some(thunk) = graph_ui.focus_request.take() {
+                graph_ui.focus_on(Some(&thunk));
+            }
+        });
+    }
+
+    /// The trail from the whole program to the thunk focused on, through the thunks around it,
+    /// any of which can be clicked to focus on it instead. Nothing is shown without a focus.
+    fn breadcrumb_ui(&mut self, ui: &mut egui::Ui) {
+        each!(graph_ui in self => {
+            let path = graph_ui.graph.focus_path();
+            if path.is_empty() {
+                return;
+            }
+            let mut target = None;
+            ui.horizontal(|ui| {
+                if ui.link("Program").clicked() {
+                    target = Some(None);
+                }
+                for (i, thunk) in path.iter().enumerate() {
+                    ui.label("›");
+                    let label = thunk.weight().to_string();
+                    if i + 1 == path.len() {
+                        ui.strong(label);
+                    } else if ui.link(label).clicked() {
+                        target = Some(Some(thunk.clone()));
+                    }
+                }
+            });
+            if let Some(thunk) = target {
+                graph_ui.focus_on(thunk.as_ref());
+            }
+        });
+    }
+
+    /// The variable carried by the wire double-clicked since this was last called, to rename it,
+    /// for the languages whose programs can be printed back as code.
+    pub(crate) fn take_rename_request(&mut self) -> Option<Variable> {
+        match self {
+            Self::Chil(graph_ui) => graph_ui
+                .rename_request
+                .take()?
+                .weight()
+                .into_var()
+                .map(Variable::Chil),
+            Self::Spartan(graph_ui) => graph_ui
+                .rename_request
+                .take()?
+                .weight()
+                .into_var()
+                .map(Variable::Spartan),
+            Self::Mlir(_) | Self::Dot(_) => None,
+        }
+    }
+
+    /// The clusters of the operations of a Chil program by the top-level definitions they were
+    /// written in, which is empty for other languages.
+    pub(crate) fn clusters(&self) -> Arc<Vec<Cluster>> {
+        match self {
+            Self::Chil(graph_ui) => graph_ui.clusters.clone(),
+            _ => Arc::default(),
+        }
+    }
+
+    pub(crate) fn clear_selection(&mut self) {
+        interact!(self, |graph| graph.clear_selection());
+    }
+
+    pub(crate) fn extend_selection(&mut self, direction: Option<(Direction, usize)>) {
+        interact!(self, |graph| graph.extend_selection(direction));
+    }
+
+    /// Selects the nodes up to `depth` steps from the selection in `direction`, in place of the
+    /// cone previewed before, without recording it so that the depth can be adjusted live.
+    pub(crate) fn preview_cone(&mut self, direction: Direction, depth: usize) {
+        each!(graph_ui in self => {
+            let seed = graph_ui.cone.take().unwrap_or_else(|| graph_ui.graph.clone());
+            graph_ui.graph = seed.clone();
+            graph_ui.graph.extend_selection(Some((direction, depth)));
+            graph_ui.cone = Some(seed);
+        });
+    }
+
+    /// Goes back to the graph from before the cone being previewed, if any.
+    pub(crate) fn end_cone(&mut self) {
+        each!(graph_ui in self => {
+            if let Some(seed) = graph_ui.cone.take() {
+                graph_ui.graph = seed;
+            }
+        });
+    }
+
+    pub(crate) fn set_expanded_all(&mut self, expanded: bool) {
+        interact!(self, |graph| graph.set_expanded_all(expanded));
+    }
+
+    /// Collapses thunks so that about `budget` nodes and wires are drawn, returning how many
+    /// thunks were left collapsed.
+    pub(crate) fn collapse_to_budget(&mut self, budget: usize) -> usize {
+        interact!(self, |graph| graph.collapse_to_budget(budget))
+    }
+
+    pub(crate) fn set_constants_folded(&mut self, folded: bool) {
+        interact!(self, |graph| graph.set_constants_folded(folded));
+    }
+
+    pub(crate) fn set_chains_folded(&mut self, folded: bool) {
+        interact!(self, |graph| graph.set_chains_folded(folded));
+    }
+
+    /// The nodes toggled by the last click on the graph, if it has not already been asked for.
+    pub(crate) fn take_toggles(&mut self) -> Vec<Toggle> {
+        each!(graph_ui in self => graph_ui
+            .take_click()
+            .map(|before| graph_ui.graph.toggles_since(&before))
+            .unwrap_or_default())
+    }
+
+    /// The clicks which recreate the expanded thunks and selection of the graph on the program
+    /// compiled afresh.
+    pub(crate) fn toggles(&self) -> Vec<Toggle> {
+        each!(graph_ui in self => graph_ui.graph.toggles())
+    }
+
+    /// The addresses of the selected nodes.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn selected(&self) -> Vec<NodeAddress> {
+        self.toggles()
+            .into_iter()
+            .filter_map(|toggle| match toggle {
+                Toggle::Selected(address) => Some(address),
+                Toggle::Expanded(_) => None,
+            })
+            .collect()
+    }
+
+    /// Repeats a click recorded by [`Self::take_toggles`], returning whether there was a node to
+    /// click on.
+    pub(crate) fn toggle(&mut self, toggle: &Toggle) -> bool {
+        interact!(self, |graph| graph.toggle(toggle))
+    }
+
+    /// The number of nodes and edges in the program, including those inside thunks.
+    pub(crate) fn size(&self) -> usize {
+        each!(graph_ui in self => size(graph_ui.graph.inner()))
+    }
+
+    /// The addresses of the thunks of the program, which can be exported on their own.
+    pub(crate) fn thunks(&self) -> Vec<NodeAddress> {
+        each!(graph_ui in self => addresses(graph_ui.graph.inner())
+            .into_iter()
+            .filter_map(|(address, node)| matches!(node, Node::Thunk(_)).then_some(address))
+            .collect())
+    }
+
+    /// A Markdown outline of the structure of the part of the program covered by `scope`.
+    pub(crate) fn outline(&self, title: &str, scope: &ExportScope) -> Option<String> {
+        each!(graph_ui in self => match scope {
+            ExportScope::Full => Some(to_markdown(graph_ui.graph.inner(), title)),
+            ExportScope::Visible => Some(to_markdown(&graph_ui.graph, title)),
+            ExportScope::Thunk(address) => match node_at(graph_ui.graph.inner(), address)? {
+                Node::Thunk(thunk) => Some(to_markdown(&thunk, title)),
+                Node::Operation(_) => None,
+            },
+        })
+    }
+
+    /// The part of the program covered by `scope` as a dot digraph, with thunks as clusters.
+    pub(crate) fn dot(&self, scope: &ExportScope) -> Option<String> {
+        each!(graph_ui in self => match scope {
+            ExportScope::Full => Some(graph_to_dot(graph_ui.graph.inner())),
+            ExportScope::Visible => Some(graph_to_dot(&graph_ui.graph)),
+            ExportScope::Thunk(address) => match node_at(graph_ui.graph.inner(), address)? {
+                Node::Thunk(thunk) => Some(graph_to_dot(&thunk)),
+                Node::Operation(_) => None,
+            },
+        })
+    }
+
+    /// Streams the part of the diagram covered by `scope` to a file at `path`, drawn as `drawing`,
+    /// on another thread so that the window stays responsive while it is laid out.
+    pub(crate) fn export_drawing(
+        &self,
+        path: PathBuf,
+        scope: &ExportScope,
+        drawing: Drawing,
+    ) -> Promise<io::Result<()>> {
+        each!(graph_ui in self => match scope {
+            ExportScope::Full => {
+                let mut graph = graph_ui.graph.clone();
+                graph.set_expanded_all(true);
+                graph.set_focused_thunk(None);
+                spawn_drawing(&graph, graph_ui.solver, None, path, drawing)
+            }
+            ExportScope::Visible => graph_ui.export_drawing(path, drawing),
+            ExportScope::Thunk(address) => match node_at(graph_ui.graph.inner(), address) {
+                Some(Node::Thunk(thunk)) => {
+                    spawn_drawing(&thunk, graph_ui.solver, None, path, drawing)
+                }
+                _ => Promise::from_ready(Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no thunk at {address}"),
+                ))),
+            },
+        })
+    }
+
+    /// The diagram as it is shown as an svg document, unless it has not been laid out yet.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn svg(&self) -> Option<String> {
+        each!(graph_ui in self => graph_ui.svg())
+    }
+
+    /// Expands the thunks hiding matches for `query`, returning whether any were expanded.
+    pub(crate) fn expand_matches(&mut self, query: &Query) -> bool {
+        interact!(self, |graph| graph.expand_matches(query))
+    }
+}
+
+fn size<G: Graph>(graph: &G) -> usize {
+    graph.graph_inputs().count()
+        + graph
+            .nodes()
+            .map(|node| {
+                1 + node.number_of_outputs()
+                    + match &node {
+                        Node::Operation(_) => 0,
+                        Node::Thunk(thunk) => size(thunk),
+                    }
+            })
+            .sum::<usize>()
+}
+
+/// A copy of `graph` with every thunk collapsed, which is much quicker to lay out.
+fn collapsed<G: Graph>(graph: &InteractiveGraph<G>) -> InteractiveGraph<G> {
+    let mut graph = graph.clone();
+    graph.set_expanded_all(false);
+    graph
+}
+
+/// The heat of the nodes of the graph from a profile, kept until either of them changes.
+struct HeatCache<G: Graph> {
+    key: Key<G>,
+    profile: Arc<Profile>,
+    nodes: HashMap<Node<G::Ctx>, f32>,
+}
+
+/// The nodes on the paths between two selected nodes, kept until the graph or selection changes.
+struct PathCache<G: Graph> {
+    key: Key<G>,
+    ends: [Node<G::Ctx>; 2],
+    flat: bool,
+    nodes: IndexSet<Node<G::Ctx>>,
 }
 
 pub struct GraphUiInternal<G: Graph> {
@@ -87,7 +615,36 @@ pub struct GraphUiInternal<G: Graph> {
     panzoom: Panzoom,
     ready: bool,
     reset_requested: bool,
+    fit_selection_requested: bool,
     solver: Solver,
+    /// On-disk cache entry for the layout of the graph in its initial state.
+    cache_key: Option<(Key<G>, CacheKey)>,
+    /// A graph whose shapes are shown while those of the current graph are laid out.
+    preview: Option<Key<G>>,
+    history: History<G>,
+    /// A search to pan to once the layout is ready.
+    pending_find: Option<(Query, usize)>,
+    /// The graph before the last click on it, until the click has been recorded.
+    last_click: Option<G>,
+    /// The graph before the cone being previewed was selected, from whose selection the cone is
+    /// grown, until the cone is kept or cancelled.
+    cone: Option<G>,
+    /// The node focused from the keyboard, and where it was last drawn, so that the focus can move
+    /// to whatever is drawn there once the node is gone.
+    focus: Option<(Node<G::Ctx>, Pos2)>,
+    /// The thunk under the pointer when focusing on a thunk was asked for, until it is focused.
+    focus_request: Option<Thunk<G::Ctx>>,
+    /// The views of the whole program and of each thunk focused on while another is shown, so
+    /// that each keeps its own zoom and scroll.
+    focus_views: HashMap<Option<Thunk<G::Ctx>>, Panzoom>,
+    /// The wire last double-clicked, until the variable it carries is asked for to rename it.
+    rename_request: Option<Edge<G::Ctx>>,
+    /// The clusters of the operations of a Chil program by the definitions they were written in.
+    clusters: Arc<Vec<Cluster>>,
+    /// The paths between the two selected nodes when last drawn.
+    path: Option<PathCache<G>>,
+    /// The heat of the nodes from the profile when last drawn.
+    heat: Option<HeatCache<G>>,
 }
 
 impl<G> GraphUiInternal<G>
@@ -100,41 +657,180 @@ where
             panzoom: Panzoom::default(),
             ready: false,
             reset_requested: true,
+            fit_selection_requested: false,
             solver,
+            cache_key: None,
+            preview: None,
+            history: History::default(),
+            pending_find: None,
+            last_click: None,
+            cone: None,
+            focus: None,
+            focus_request: None,
+            focus_views: HashMap::new(),
+            rename_request: None,
+            clusters: Arc::default(),
+            path: None,
+            heat: None,
         }
     }
 
-    pub(crate) fn ui(&mut self, ui: &mut egui::Ui, search: Option<&str>)
+    /// Use the on-disk cache entry `cache_key` for the layout of the graph in its current state.
+    pub(crate) fn with_cache_key(mut self, cache_key: Option<CacheKey>) -> Self {
+        self.cache_key = cache_key.map(|cache_key| (self.graph.key(), cache_key));
+        self
+    }
+
+    /// Keep the clusters of the operations of a Chil program, to colour the operations by them.
+    pub(crate) fn with_clusters(mut self, clusters: Vec<Cluster>) -> Self {
+        self.clusters = Arc::new(clusters);
+        self
+    }
+
+    /// Start laying out `preview`, showing it until the layout of the graph is ready.
+    pub(crate) fn with_preview(mut self, preview: &G) -> Self
     where
+        Edge<G::Ctx>: ExtensibleEdge,
+        Operation<G::Ctx>: Shapeable,
+        Weight<Operation<G::Ctx>>: Display,
+        Weight<Edge<G::Ctx>>: Display,
+    {
+        let key = preview.key();
+        if key != self.graph.key() {
+            generate_shapes(preview, self.solver, None);
+            self.preview = Some(key);
+        }
+        self
+    }
+
+    /// The on-disk cache entry for the layout, unless the graph has since been changed.
+    fn cache_key(&self) -> Option<CacheKey> {
+        self.cache_key
+            .as_ref()
+            .filter(|(key, _)| *key == self.graph.key())
+            .map(|(_, cache_key)| *cache_key)
+    }
+
+    pub(crate) fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        search: Option<&Query>,
+        labels: LabelVisibility,
+        badges: &[Badge],
+        profile: Option<&Arc<Profile>>,
+        overlays: Overlays,
+        operation_colors: Option<&dyn Fn(&Operation<G::Ctx>) -> Option<Color32>>,
+    ) where
         // Needed for render
         G: RenderableGraph,
         Edge<G::Ctx>: Codeable,
         Operation<G::Ctx>: Codeable + Matchable,
         Thunk<G::Ctx>: Codeable + Matchable,
+        Weight<Thunk<G::Ctx>>: Display,
         // Needed for generate_shapes
         Edge<G::Ctx>: ExtensibleEdge,
         Operation<G::Ctx>: Shapeable,
         Weight<Operation<G::Ctx>>: Display,
-        Weight<Edge<G::Ctx>>: WithType,
+        Weight<Edge<G::Ctx>>: Display + WithType,
+        // Needed for history
+        G: SameState,
     {
-        let shapes = generate_shapes(&self.graph, self.solver);
-        let guard = shapes.lock().unwrap();
-        if let Some(shapes) = guard.ready() {
+        if let Some((query, offset)) = self.pending_find.take() {
+            self.find(&query, offset);
+        }
+
+        let key = self.graph.key();
+        let shapes = generate_shapes(&self.graph, self.solver, self.cache_key());
+        let mut guard = shapes.lock().unwrap();
+        let current = guard.ready().is_some();
+
+        // Keep showing the last layout until the refined one is ready
+        let preview = if current {
+            self.preview = Some(key);
+            None
+        } else {
+            self.preview
+                .as_ref()
+                .filter(|preview| **preview != key)
+                .and_then(cached_shapes::<G>)
+        };
+        let mut preview_guard = preview.as_ref().map(|shapes| shapes.lock().unwrap());
+        // Failing that, show a draft of the current graph
+        let draft = (!current).then(|| generate_draft_shapes(&self.graph, self.solver));
+        let mut draft_guard = draft.as_ref().map(|shapes| shapes.lock().unwrap());
+        let mut rerun_exact = false;
+
+        if let Some(shapes) = guard
+            .ready_mut()
+            .or_else(|| preview_guard.as_mut().and_then(|guard| guard.ready_mut()))
+            .or_else(|| draft_guard.as_mut().and_then(|guard| guard.ready_mut()))
+        {
             let (response, painter) =
                 ui.allocate_painter(ui.available_size_before_wrap(), egui::Sense::drag());
 
             let to_screen = self.panzoom.transform(response.rect);
 
+            // With a node focused, the arrow keys move the focus rather than panning
+            let mut bounds = None;
+            let mut activate = None;
+            if response.contains_pointer()
+                && (self.focus.is_some() || ui.input(|i| i.key_pressed(egui::Key::Tab)))
+            {
+                let bounds = bounds.insert(shapes.node_bounds());
+                ui.input_mut(|i| {
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::Tab) {
+                        self.focus = navigation::nearest(bounds, self.panzoom.translation())
+                            .map(|node| (node, self.panzoom.translation()));
+                    }
+                    let Some((focus, _)) = self.focus.clone() else {
+                        return;
+                    };
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::Escape) {
+                        self.focus = None;
+                        return;
+                    }
+                    for (key, step) in [
+                        (egui::Key::ArrowUp, Step::Up),
+                        (egui::Key::ArrowDown, Step::Down),
+                        (egui::Key::ArrowLeft, Step::Left),
+                        (egui::Key::ArrowRight, Step::Right),
+                    ] {
+                        if i.consume_key(egui::Modifiers::NONE, key) {
+                            if let Some(node) = navigation::step(bounds, &focus, step) {
+                                let rect = bounds[&node];
+                                if !to_screen.from().contains_rect(rect) {
+                                    self.panzoom.set_pan(rect.center());
+                                }
+                                self.focus = Some((node, rect.center()));
+                            }
+                        }
+                    }
+                    // As for clicks, the primary action expands or collapses thunks
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::Enter) {
+                        activate = Some(true);
+                    } else if i.consume_key(egui::Modifiers::NONE, egui::Key::Space) {
+                        activate = Some(false);
+                    }
+                });
+            }
+
             if response.contains_pointer() {
                 ui.input(|i| {
+                    // Also covers pinching on touch screens
                     if let Some(hover_pos) = i.pointer.hover_pos() {
                         let anchor = to_screen.inverse().transform_pos(hover_pos);
                         self.panzoom.zoom(i.zoom_delta(), anchor);
                     }
 
                     self.panzoom.pan(i.smooth_scroll_delta);
+
+                    // The first finger of a gesture also moves the pointer, so it must not pan
+                    // the view a second time as a drag
+                    match i.multi_touch() {
+                        Some(touch) => self.panzoom.pan(touch.translation_delta),
+                        None => self.panzoom.pan(response.drag_delta()),
+                    }
                 });
-                self.panzoom.pan(response.drag_delta());
 
                 ui.input_mut(|i| {
                     let mut pan_by_key = |key, pan: fn(&mut Panzoom) -> ()| {
@@ -161,6 +857,59 @@ where
                     .reset(shapes.size, response.rect.max - response.rect.min);
                 self.reset_requested = false;
             }
+            if std::mem::take(&mut self.fit_selection_requested) {
+                let bounds = bounds.get_or_insert_with(|| shapes.node_bounds());
+                let selection = bounds
+                    .iter()
+                    .filter(|(node, _)| self.graph.selected((*node).clone()))
+                    .map(|(_, rect)| *rect)
+                    .reduce(egui::Rect::union);
+                if let Some(selection) = selection {
+                    self.panzoom
+                        .fit(selection, response.rect.size(), DEFAULT_ZOOM);
+                }
+            }
+            let pointer = response
+                .hover_pos()
+                .map(|pos| to_screen.inverse().transform_pos(pos));
+            if let Some(pointer) =
+                pointer.filter(|_| ui.input_mut(|i| i.consume_shortcut(&help::ZOOM_TO_NODE)))
+            {
+                let bounds = bounds.get_or_insert_with(|| shapes.node_bounds());
+                // Thunks contain the nodes inside them, so the smallest is the innermost
+                let node = bounds
+                    .values()
+                    .filter(|rect| rect.contains(pointer))
+                    .min_by(|x, y| x.area().total_cmp(&y.area()));
+                if let Some(node) = node {
+                    self.panzoom
+                        .fit(*node, response.rect.size(), DEFAULT_ZOOM * NODE_ZOOM);
+                }
+            }
+            // Preview and draft shapes are of another graph, whose thunks cannot be focused on
+            if let Some(pointer) = pointer
+                .filter(|_| current && ui.input_mut(|i| i.consume_shortcut(&help::FOCUS_THUNK)))
+            {
+                let bounds = bounds.get_or_insert_with(|| shapes.node_bounds());
+                self.focus_request = bounds
+                    .iter()
+                    .filter_map(|(node, rect)| match node {
+                        Node::Thunk(thunk) if rect.contains(pointer) => Some((thunk, rect)),
+                        _ => None,
+                    })
+                    .min_by(|(_, x), (_, y)| x.area().total_cmp(&y.area()))
+                    .map(|(thunk, _)| thunk.clone());
+            }
+            // Likewise, only the wires of the current graph carry its variables
+            if let Some(pointer) = pointer.filter(|_| {
+                current
+                    && ui.input(|i| {
+                        i.pointer
+                            .button_double_clicked(egui::PointerButton::Primary)
+                    })
+            }) {
+                self.rename_request = shapes.edge_at(pointer);
+            }
             // Background
             painter.add(Shape::rect_filled(
                 response.rect,
@@ -168,38 +917,339 @@ where
                 ui.visuals().faint_bg_color,
             ));
 
+            let before =
+                (response.clicked() || response.secondary_clicked()).then(|| self.graph.clone());
             painter.extend(sd_graphics::render::render(
                 &mut self.graph,
                 ui,
-                &shapes.shapes,
+                shapes,
                 &response,
                 to_screen,
                 search,
+                labels,
+                !overlays.hide_ordering,
+                overlays.type_colors,
+                overlays.bundle_copies,
             ));
-            self.ready = true;
+            if let Some(before) = before {
+                self.last_click = Some(before.clone());
+                self.history.record(before, &self.graph);
+            }
+            if let Some((focus, pos)) = self.focus.clone() {
+                let bounds = bounds.get_or_insert_with(|| shapes.node_bounds());
+                match bounds.get(&focus) {
+                    Some(rect) => {
+                        painter.rect_stroke(
+                            to_screen.transform_rect(*rect).expand(4.0),
+                            Rounding::same(4.0),
+                            ui.visuals().selection.stroke,
+                        );
+                        // Preview and draft shapes are of another graph, which cannot be clicked
+                        if let Some(primary) = activate.filter(|_| current) {
+                            let before = self.graph.clone();
+                            match focus {
+                                Node::Operation(op) => self.graph.clicked_operation(op, primary),
+                                Node::Thunk(thunk) => self.graph.clicked_thunk(thunk, primary),
+                            }
+                            self.last_click = Some(before.clone());
+                            self.history.record(before, &self.graph);
+                        }
+                    }
+                    // The node was collapsed, expanded or laid out afresh
+                    None => {
+                        self.focus = navigation::nearest(bounds, pos).map(|node| (node, pos));
+                    }
+                }
+            }
+            if let Some(profile) = profile {
+                let key = self.graph.key();
+                let heat = match self.heat.take() {
+                    Some(heat) if heat.key == key && Arc::ptr_eq(&heat.profile, profile) => heat,
+                    _ => HeatCache {
+                        key,
+                        profile: profile.clone(),
+                        nodes: profile.heat(&self.graph),
+                    },
+                };
+                painter.extend(sd_graphics::render::render_heat(shapes, &heat.nodes));
+                if let Some((min, max)) = profile.range() {
+                    painter.extend(sd_graphics::render::render_heat_legend(
+                        ui,
+                        response.rect,
+                        min,
+                        max,
+                    ));
+                }
+                self.heat = Some(heat);
+            }
+            if let Some(color) = operation_colors {
+                painter.extend(sd_graphics::render::render_operation_colors(shapes, color));
+            }
+            // Preview and draft shapes are of another graph, whose nodes cannot be selected
+            if overlays.paths && current {
+                let bounds = bounds.get_or_insert_with(|| shapes.node_bounds());
+                let selected = bounds
+                    .keys()
+                    .filter(|node| self.graph.selected((*node).clone()))
+                    .take(3)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if let [a, b] = selected.as_slice() {
+                    let key = self.graph.key();
+                    let flat = overlays.flat_paths;
+                    // The selected nodes come in no particular order
+                    let path = match self.path.take() {
+                        Some(path)
+                            if path.key == key
+                                && path.flat == flat
+                                && (path.ends == [a.clone(), b.clone()]
+                                    || path.ends == [b.clone(), a.clone()]) =>
+                        {
+                            path.nodes
+                        }
+                        _ if flat => NReachable::between_flat(a, b),
+                        _ => NReachable::between(a, b),
+                    };
+                    if path.is_empty() {
+                        painter.text(
+                            response.rect.center_top() + egui::vec2(0.0, 8.0),
+                            egui::Align2::CENTER_TOP,
+                            "No path between the selected nodes",
+                            egui::FontId::default(),
+                            ui.visuals().weak_text_color(),
+                        );
+                    } else {
+                        painter.extend(sd_graphics::render::render_path(ui, shapes, &path));
+                    }
+                    self.path = Some(PathCache {
+                        key,
+                        ends: [a.clone(), b.clone()],
+                        flat,
+                        nodes: path,
+                    });
+                }
+            }
+            painter.extend(sd_graphics::render::render_badges(ui, shapes, badges));
+            if overlays.type_colors {
+                painter.extend(sd_graphics::render::render_type_legend(
+                    ui,
+                    shapes,
+                    response.rect,
+                ));
+            }
+            if overlays.control_flow {
+                painter.extend(sd_graphics::render::render_control_flow(
+                    ui, shapes, to_screen,
+                ));
+            }
+            // Where text in the bottom left corner goes, above the minimap if it is shown
+            let mut bottom_left = response.rect.left_bottom() + egui::vec2(8.0, -8.0);
+            if overlays.minimap {
+                let (minimap, to_minimap) = sd_graphics::render::render_minimap(
+                    ui,
+                    shapes,
+                    response.rect,
+                    *to_screen.from(),
+                );
+                painter.extend(minimap);
+                let minimap_response = ui.interact(
+                    *to_minimap.to(),
+                    response.id.with("minimap"),
+                    egui::Sense::click_and_drag(),
+                );
+                if let Some(pos) = minimap_response.interact_pointer_pos() {
+                    self.panzoom
+                        .set_pan(to_minimap.inverse().transform_pos(pos));
+                }
+                bottom_left = to_minimap.to().left_top() + egui::vec2(0.0, -8.0);
+            }
+            if overlays.crossings {
+                painter.extend(sd_graphics::render::render_hotspots(
+                    ui, shapes, to_screen, HOTSPOTS,
+                ));
+                let total = shapes
+                    .hotspots
+                    .iter()
+                    .map(|hotspot| hotspot.crossings)
+                    .sum::<usize>();
+                painter.text(
+                    bottom_left,
+                    egui::Align2::LEFT_BOTTOM,
+                    format!("{total} wire crossings in {} slices", shapes.hotspots.len()),
+                    egui::FontId::default(),
+                    ui.visuals().text_color(),
+                );
+            }
+            if !current {
+                ui.put(
+                    egui::Rect::from_min_size(
+                        response.rect.right_top() + egui::vec2(-24.0, 8.0),
+                        egui::vec2(16.0, 16.0),
+                    ),
+                    egui::Spinner::new(),
+                );
+            }
+            if let Some(error) = &shapes.error {
+                painter.text(
+                    response.rect.left_top() + egui::vec2(8.0, 8.0),
+                    egui::Align2::LEFT_TOP,
+                    format!("Layout failed: {error}"),
+                    egui::FontId::default(),
+                    ui.visuals().error_fg_color,
+                );
+                rerun_exact = ui
+                    .put(
+                        egui::Rect::from_min_size(
+                            response.rect.left_top() + egui::vec2(8.0, 28.0),
+                            egui::vec2(100.0, 20.0),
+                        ),
+                        egui::Button::new("Retry layout"),
+                    )
+                    .clicked();
+            } else if shapes.approximate {
+                painter.text(
+                    response.rect.left_top() + egui::vec2(8.0, 8.0),
+                    egui::Align2::LEFT_TOP,
+                    "Layout approximated: the solver ran out of time",
+                    egui::FontId::default(),
+                    ui.visuals().warn_fg_color,
+                );
+            } else if shapes.draft {
+                painter.text(
+                    response.rect.left_top() + egui::vec2(8.0, 8.0),
+                    egui::Align2::LEFT_TOP,
+                    "Draft layout: the solver is still running",
+                    egui::FontId::default(),
+                    ui.visuals().weak_text_color(),
+                );
+            } else if shapes.heuristic {
+                painter.text(
+                    response.rect.left_top() + egui::vec2(8.0, 8.0),
+                    egui::Align2::LEFT_TOP,
+                    "Fast layout: the graph is too big to solve quickly",
+                    egui::FontId::default(),
+                    ui.visuals().warn_fg_color,
+                );
+                rerun_exact = ui
+                    .put(
+                        egui::Rect::from_min_size(
+                            response.rect.left_top() + egui::vec2(8.0, 28.0),
+                            egui::vec2(100.0, 20.0),
+                        ),
+                        egui::Button::new("Re-run exact"),
+                    )
+                    .on_hover_text("Solve the layout, which may take minutes")
+                    .clicked();
+            }
+            self.ready = current;
         } else {
-            ui.centered_and_justified(eframe::egui::Ui::spinner);
+            progress::ui(ui);
             self.ready = false;
         }
+
+        if rerun_exact {
+            drop((guard, preview_guard, draft_guard));
+            generate_exact_shapes(&self.graph, self.solver, self.cache_key());
+        }
     }
 
     pub(crate) const fn ready(&self) -> bool {
         self.ready
     }
 
+    /// How long the layout of the graph took, once it is ready.
+    pub(crate) fn layout_time(&self) -> Option<Duration> {
+        let shapes = cached_shapes::<G>(&self.graph.key())?;
+        let guard = shapes.lock().unwrap();
+        guard.ready().map(|shapes| shapes.layout_time)
+    }
+
     pub(crate) fn reset(&mut self) {
         self.reset_requested = true;
     }
 
-    /// Searches through the shapes and pans to the one which matches the query
-    pub(crate) fn find(&mut self, query: &str, offset: usize)
+    /// The graph as it was before the last click on it, if it has not already been asked for.
+    pub(crate) fn take_click(&mut self) -> Option<G> {
+        self.last_click.take()
+    }
+
+    /// Fits the selected nodes in the window once the layout is drawn.
+    pub(crate) fn fit_selection(&mut self) {
+        self.fit_selection_requested = true;
+    }
+
+    /// The current viewport.
+    pub(crate) const fn panzoom(&self) -> Panzoom {
+        self.panzoom
+    }
+
+    /// Jump to a previously stored viewport.
+    pub(crate) fn set_panzoom(&mut self, panzoom: Panzoom) {
+        self.panzoom = panzoom;
+        self.reset_requested = false;
+    }
+
+    /// The current viewport, unless it is still to be fitted to the graph once it is drawn.
+    pub(crate) const fn settled_panzoom(&self) -> Option<Panzoom> {
+        if self.reset_requested {
+            None
+        } else {
+            Some(self.panzoom)
+        }
+    }
+
+    /// Applies `f` to the graph, recording the previous state so that it can be undone.
+    pub(crate) fn interact<R>(&mut self, f: impl FnOnce(&mut G) -> R) -> R
+    where
+        G: SameState,
+    {
+        let before = self.graph.clone();
+        let result = f(&mut self.graph);
+        self.history.record(before, &self.graph);
+        result
+    }
+
+    /// Reverts the last interaction with the graph.
+    pub(crate) fn undo(&mut self)
+    where
+        G: SameState,
+    {
+        self.history.undo(&mut self.graph);
+    }
+
+    /// Reapplies the last interaction that was undone.
+    pub(crate) fn redo(&mut self)
+    where
+        G: SameState,
+    {
+        self.history.redo(&mut self.graph);
+    }
+
+    pub(crate) fn can_undo(&self) -> bool
+    where
+        G: SameState,
+    {
+        self.history.can_undo()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool
+    where
+        G: SameState,
+    {
+        self.history.can_redo()
+    }
+
+    /// Searches through the shapes and pans to the one which matches the query, waiting for the
+    /// layout if it is not ready yet.
+    pub(crate) fn find(&mut self, query: &Query, offset: usize)
     where
         Edge<G::Ctx>: ExtensibleEdge,
         Operation<G::Ctx>: Matchable + Shapeable,
         Thunk<G::Ctx>: Matchable,
         Weight<Operation<G::Ctx>>: Display,
+        Weight<Edge<G::Ctx>>: Display,
     {
-        let shapes = generate_shapes(&self.graph, self.solver);
+        let shapes = generate_shapes(&self.graph, self.solver, self.cache_key());
         let guard = shapes.lock().unwrap();
 
         if let Some(shapes) = guard.ready() {
@@ -212,6 +1262,8 @@ where
                 let shape = matches[offset % matches.len()];
                 self.panzoom.set_pan(shape.center());
             }
+        } else {
+            self.pending_find = Some((query.clone(), offset));
         }
     }
 
@@ -222,15 +1274,79 @@ where
         }
     }
 
-    pub(crate) fn export_svg(&self) -> String
+    /// Streams the diagram to a file at `path`, drawn as `drawing`, on another thread.
+    pub(crate) fn export_drawing(&self, path: PathBuf, drawing: Drawing) -> Promise<io::Result<()>>
     where
         Edge<G::Ctx>: ExtensibleEdge,
         Operation<G::Ctx>: Shapeable,
         Weight<Operation<G::Ctx>>: Display,
+        Weight<Edge<G::Ctx>>: Display,
     {
-        let shapes = generate_shapes(&self.graph, self.solver);
-        let guard = shapes.lock().unwrap(); // this would lock the UI, but by the time we get here
-                                            // the shapes have already been computed
-        guard.block_until_ready().to_svg().to_string()
+        spawn_drawing(&self.graph, self.solver, self.cache_key(), path, drawing)
+    }
+
+    /// The diagram as an svg document, unless it has not been laid out yet.
+    ///
+    /// Unlike [`Self::export_drawing`], the document is built in memory on this thread, so that it can
+    /// be built on the web.
+    #[cfg(target_arch = "wasm32")]
+    fn svg(&self) -> Option<String>
+    where
+        Edge<G::Ctx>: ExtensibleEdge,
+        Operation<G::Ctx>: Shapeable,
+        Weight<Operation<G::Ctx>>: Display,
+        Weight<Edge<G::Ctx>>: Display,
+    {
+        let shapes = cached_shapes::<G>(&self.graph.key())?;
+        let guard = shapes.lock().unwrap();
+        Some(guard.ready()?.to_svg())
+    }
+}
+
+impl<G> GraphUiInternal<InteractiveGraph<G>>
+where
+    G: Graph + 'static,
+{
+    /// Focuses on `thunk`, or on the whole program if it is `None`, returning to the view it was
+    /// last shown in, or fitting it to the window if it has not been shown before.
+    fn focus_on(&mut self, thunk: Option<&Thunk<<InteractiveGraph<G> as Graph>::Ctx>>) {
+        if let Some(panzoom) = self.settled_panzoom() {
+            self.focus_views.insert(self.graph.focused_thunk(), panzoom);
+        }
+        self.interact(|graph| graph.set_focused_thunk(thunk));
+        match self.focus_views.get(&thunk.cloned()) {
+            Some(panzoom) => self.set_panzoom(*panzoom),
+            None => self.reset(),
+        }
     }
 }
+
+/// Lays out `graph` and streams it to a file at `path`, drawn as `drawing`, on another thread
+/// which waits for the layout to be done.
+fn spawn_drawing<G>(
+    graph: &G,
+    solver: Solver,
+    cache_key: Option<CacheKey>,
+    path: PathBuf,
+    drawing: Drawing,
+) -> Promise<io::Result<()>>
+where
+    G: Graph + 'static,
+    Edge<G::Ctx>: ExtensibleEdge,
+    Operation<G::Ctx>: Shapeable,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
+{
+    let shapes = generate_shapes(graph, solver, cache_key);
+    crate::spawn!("export", {
+        // The lock is only held while looking, as the same shapes may be drawn in the window
+        loop {
+            let guard = shapes.lock().unwrap();
+            if let Some(shapes) = guard.ready() {
+                break drawing.write(shapes, &path);
+            }
+            drop(guard);
+            std::thread::sleep(EXPORT_POLL_INTERVAL);
+        }
+    })
+}
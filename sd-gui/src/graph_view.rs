@@ -0,0 +1,180 @@
+//! A diagram widget which other egui applications can embed.
+//!
+//! A [`GraphView`] draws any hypergraph and lets the user pan, zoom, expand and collapse thunks
+//! and select nodes, as in the visualiser itself. The application is told about clicks on nodes
+//! and changes to the selection through callbacks, with the nodes of the graph it passed in:
+//!
+//! ```ignore
+//! let mut view = GraphView::new(graph, Solver::default())
+//!     .on_node_click(|node| println!("clicked {node:?}"))
+//!     .on_selection_change(|nodes| println!("{} selected", nodes.len()));
+//! // Each frame
+//! view.ui(ui);
+//! ```
+
+#![allow(clippy::inline_always)]
+
+use std::fmt::Display;
+
+use delegate::delegate;
+use eframe::egui;
+use sd_core::{
+    codeable::Codeable,
+    common::{Matchable, Query},
+    hypergraph::{
+        address::node_at,
+        generic::{Edge, Node, Operation, Thunk, Weight},
+        subgraph::ExtensibleEdge,
+        traits::{Graph, WithType, WithWeight},
+    },
+    interactive::{InteractiveGraph, Toggle},
+    lp::Solver,
+};
+use sd_graphics::{
+    common::{LabelVisibility, Shapeable},
+    renderable::RenderableGraph,
+};
+
+use crate::graph_ui::{GraphUiInternal, Overlays};
+
+type InteractiveCtx<G> = <InteractiveGraph<G> as Graph>::Ctx;
+type NodeCallback<G> = Box<dyn FnMut(&Node<<G as Graph>::Ctx>)>;
+type SelectionCallback<G> = Box<dyn FnMut(&[Node<<G as Graph>::Ctx>])>;
+
+/// An interactive diagram of a graph, which can be drawn in any [`egui::Ui`].
+pub struct GraphView<G: Graph> {
+    graph_ui: GraphUiInternal<InteractiveGraph<G>>,
+    labels: LabelVisibility,
+    /// Called with each node clicked on, or activated from the keyboard.
+    node_click: Option<NodeCallback<G>>,
+    /// Called with the selected nodes whenever the user changes the selection.
+    selection_change: Option<SelectionCallback<G>>,
+}
+
+impl<G> GraphView<G>
+where
+    G: Graph + 'static,
+{
+    /// A view of `graph`, with every thunk expanded, laid out by `solver`.
+    pub fn new(graph: G, solver: Solver) -> Self {
+        Self {
+            graph_ui: GraphUiInternal::new(InteractiveGraph::new(graph), solver),
+            labels: LabelVisibility::default(),
+            node_click: None,
+            selection_change: None,
+        }
+    }
+
+    /// Reveal the labels of operations at the text sizes given by `labels`.
+    #[must_use]
+    pub const fn with_labels(mut self, labels: LabelVisibility) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Call `f` with each node the user clicks on, or activates from the keyboard.
+    #[must_use]
+    pub fn on_node_click(mut self, f: impl FnMut(&Node<G::Ctx>) + 'static) -> Self {
+        self.node_click = Some(Box::new(f));
+        self
+    }
+
+    /// Call `f` with the selected nodes whenever the user changes the selection.
+    #[must_use]
+    pub fn on_selection_change(mut self, f: impl FnMut(&[Node<G::Ctx>]) + 'static) -> Self {
+        self.selection_change = Some(Box::new(f));
+        self
+    }
+
+    /// The graph being shown, including which thunks are expanded and which nodes are selected.
+    pub const fn graph(&self) -> &InteractiveGraph<G> {
+        &self.graph_ui.graph
+    }
+
+    /// The nodes of the graph which are selected.
+    pub fn selection(&self) -> Vec<Node<G::Ctx>> {
+        self.graph_ui
+            .graph
+            .0
+            .inner()
+            .inner()
+            .inner()
+            .inner()
+            .selection()
+            .iter()
+            .collect()
+    }
+
+    /// Whether the layout of the graph has been drawn.
+    pub const fn ready(&self) -> bool {
+        self.graph_ui.ready()
+    }
+
+    /// Fits the whole diagram in the view once it is next drawn.
+    pub fn reset(&mut self) {
+        self.graph_ui.reset();
+    }
+
+    delegate! {
+        to self.graph_ui {
+            pub fn zoom_in(&mut self);
+            pub fn zoom_out(&mut self);
+        }
+    }
+
+    /// Draws the diagram in the space available, reporting what the user did to it through the
+    /// callbacks.
+    pub fn ui(&mut self, ui: &mut egui::Ui)
+    where
+        // Needed for GraphUiInternal::ui
+        G: RenderableGraph,
+        Edge<InteractiveCtx<G>>: Codeable + ExtensibleEdge,
+        Operation<InteractiveCtx<G>>: Codeable + Matchable + Shapeable,
+        Thunk<InteractiveCtx<G>>: Codeable + Matchable,
+        Weight<Operation<InteractiveCtx<G>>>: Display,
+        Weight<Thunk<InteractiveCtx<G>>>: Display,
+        Weight<Edge<InteractiveCtx<G>>>: Display + WithType,
+        // Needed for toggles_since
+        Operation<G::Ctx>: WithWeight,
+        Thunk<G::Ctx>: WithWeight,
+        Weight<Operation<G::Ctx>>: Display,
+        Weight<Thunk<G::Ctx>>: Display,
+    {
+        self.graph_ui
+            .ui(ui, None, self.labels, &[], None, Overlays::default(), None);
+
+        let Some(before) = self.graph_ui.take_click() else {
+            return;
+        };
+        let toggles = self.graph_ui.graph.toggles_since(&before);
+        if let Some(f) = &mut self.node_click {
+            for toggle in &toggles {
+                let (Toggle::Expanded(address) | Toggle::Selected(address)) = toggle;
+                if let Some(node) = node_at(self.graph_ui.graph.inner(), address) {
+                    f(&node);
+                }
+            }
+        }
+        if toggles
+            .iter()
+            .any(|toggle| matches!(toggle, Toggle::Selected(_)))
+        {
+            let selection = self.selection();
+            if let Some(f) = &mut self.selection_change {
+                f(&selection);
+            }
+        }
+    }
+
+    /// Pans to the next node matching `query`, counting from the first for `offset`.
+    pub fn find(&mut self, query: &Query, offset: usize)
+    where
+        Edge<InteractiveCtx<G>>: ExtensibleEdge,
+        Operation<InteractiveCtx<G>>: Matchable + Shapeable,
+        Thunk<InteractiveCtx<G>>: Matchable,
+        Weight<Operation<InteractiveCtx<G>>>: Display,
+        Weight<Edge<InteractiveCtx<G>>>: Display,
+    {
+        self.graph_ui.find(query, offset);
+    }
+}
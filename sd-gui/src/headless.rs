@@ -0,0 +1,79 @@
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::bail;
+use sd_core::{dot::DotSettings, lp::Solver};
+
+use crate::{
+    graph_ui::{Drawing, ExportScope, GraphUi},
+    parser::{parse, UiLanguage},
+    settings,
+    shape_generator::{set_simplification, set_unbounded},
+    view_script::ViewScript,
+};
+
+/// Parses, lays out, and exports a diagram to `output` without opening a window, styled by the CSS
+/// in `stylesheet`, with the thunks collapsed and nodes selected as in `view`.
+///
+/// The format is chosen by the extension of `output`: an svg drawing, a png image, a TikZ picture
+/// (`.tex` or `.tikz`), or the graph as a dot digraph (`.dot` or `.gv`), which is written without
+/// laying it out.
+///
+/// Nobody is waiting on a window, so the layout is solved in full however big the graph is and
+/// however long the solver takes.
+pub fn render(
+    code: &str,
+    language: UiLanguage,
+    solver: Solver,
+    output: &Path,
+    stylesheet: Option<&str>,
+    view: Option<&ViewScript>,
+) -> anyhow::Result<()> {
+    let drawing = match output.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => Some(Drawing::Svg {
+            stylesheet: stylesheet.map(str::to_owned),
+        }),
+        Some("png") => Some(Drawing::Png),
+        Some("tex" | "tikz") => Some(Drawing::Tikz),
+        Some("dot" | "gv") => None,
+        _ => bail!(
+            "cannot render to {}: only svg, png, TikZ and dot output are supported",
+            output.display()
+        ),
+    };
+
+    if let Some(level) = view.and_then(|view| view.simplification) {
+        set_simplification(level);
+    }
+    set_unbounded(true);
+
+    let parse_output = parse(code, language)?;
+    let mut graph_ui = GraphUi::from_parse(
+        &parse_output,
+        DotSettings::default(),
+        &settings::load_mlir(),
+        false,
+        &BTreeSet::new(),
+        solver,
+        None,
+    )?;
+
+    for toggle in view.iter().flat_map(|view| &view.toggles) {
+        if !graph_ui.toggle(toggle) {
+            tracing::warn!("Nothing to toggle at {toggle:?} in the view script");
+        }
+    }
+
+    tracing::info!("Rendering to {}", output.display());
+    match drawing {
+        Some(drawing) => graph_ui
+            .export_drawing(output.to_owned(), &ExportScope::Visible, drawing)
+            .block_and_take()?,
+        None => {
+            let dot = graph_ui
+                .dot(&ExportScope::Visible)
+                .expect("the current view always exists");
+            std::fs::write(output, dot)?;
+        }
+    }
+    Ok(())
+}
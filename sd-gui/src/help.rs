@@ -0,0 +1,453 @@
+//! Searchable documentation of the features of the visualiser, kept as a table so that the
+//! shortcuts of the menus and the help describing them cannot drift apart.
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+pub(crate) const IMPORT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::O);
+pub(crate) const COMPILE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F5);
+pub(crate) const RESET: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Num0);
+pub(crate) const FIT_SELECTION: KeyboardShortcut = KeyboardShortcut::new(
+    Modifiers {
+        shift: true,
+        ..Modifiers::COMMAND
+    },
+    Key::Num0,
+);
+pub(crate) const ZOOM_TO_NODE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::Z);
+pub(crate) const FOCUS_THUNK: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F);
+pub(crate) const ZOOM_IN: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::Plus);
+pub(crate) const ZOOM_OUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::Minus);
+pub(crate) const FIND: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::F);
+pub(crate) const BOOKMARK: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::B);
+pub(crate) const SAVE_SELECTION: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+pub(crate) const UNDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+pub(crate) const REDO: KeyboardShortcut = KeyboardShortcut::new(
+    Modifiers {
+        shift: true,
+        ..Modifiers::COMMAND
+    },
+    Key::Z,
+);
+pub(crate) const HELP: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F1);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Section {
+    Files,
+    Graph,
+    Search,
+    Tools,
+}
+
+impl Section {
+    const ALL: [Self; 4] = [Self::Files, Self::Graph, Self::Search, Self::Tools];
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Files => "Programs and files",
+            Self::Graph => "Exploring the graph",
+            Self::Search => "Search queries",
+            Self::Tools => "Tools",
+        }
+    }
+}
+
+/// How a feature is reached from the keyboard.
+#[derive(Clone, Copy, Debug)]
+enum Keys {
+    None,
+    Shortcut(KeyboardShortcut),
+    /// Keys which are not a single shortcut, such as those for panning.
+    Described(&'static str),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    section: Section,
+    title: &'static str,
+    keys: Keys,
+    text: &'static str,
+}
+
+impl Entry {
+    fn is_match(&self, query: &str, ctx: &egui::Context) -> bool {
+        let query = query.to_lowercase();
+        let keys = match self.keys {
+            Keys::None => String::new(),
+            Keys::Shortcut(shortcut) => ctx.format_shortcut(&shortcut),
+            Keys::Described(keys) => keys.to_owned(),
+        };
+        [self.title, self.text, &keys, self.section.title()]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+const ENTRIES: &[Entry] = &[
+    Entry {
+        section: Section::Files,
+        title: "Import file",
+        keys: Keys::Shortcut(IMPORT),
+        text: "Open a program, guessing its language from its extension, the extensions added \
+               in the settings file, or its contents.",
+    },
+    Entry {
+        section: Section::Files,
+        title: "Compile",
+        keys: Keys::Shortcut(COMPILE),
+        text: "Draw the program in the editor. With Live on, programs are also compiled shortly \
+               after each edit which parses.",
+    },
+    Entry {
+        section: Section::Files,
+        title: "Files",
+        keys: Keys::None,
+        text: "Switch between the open files, search all of them at once, or link the open Chil \
+               files of a program split across several into one.",
+    },
+    Entry {
+        section: Section::Files,
+        title: "Sessions",
+        keys: Keys::None,
+        text: "The program, collapsed thunks, selection, saved selections, pan and zoom are \
+               restored on the next launch. Clear session in the Files menu starts afresh.",
+    },
+    Entry {
+        section: Section::Files,
+        title: "Export",
+        keys: Keys::None,
+        text: "Save the diagram as SVG or as TikZ for LaTeX, the graph as dot for Graphviz, an \
+               outline of the program, the source as HTML, or a view script which recreates the collapsed thunks, selection, pan and zoom with --view.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Pan",
+        keys: Keys::Described("Arrows or H J K L"),
+        text: "Move around the diagram. Dragging and scrolling also pan. While a node is \
+               focused the arrows move the focus instead.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Keyboard navigation",
+        keys: Keys::Described("Tab, arrows, Enter, Space, Escape"),
+        text: "Tab focuses the node nearest the middle of the view. Up and Down move the focus \
+               along the wires to the nodes it depends on and those depending on it, Left and \
+               Right across its slice. Enter expands or collapses a focused thunk, Space \
+               selects the focused node, and Escape gives the arrows back to panning.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Minimap",
+        keys: Keys::None,
+        text: "Show the whole diagram in a corner from the Overlays menu, with the part on screen \
+               outlined. Click or drag on it to pan there.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Zoom in",
+        keys: Keys::Shortcut(ZOOM_IN),
+        text: "Zoom in on the diagram. Pinching and zoom gestures also zoom.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Zoom out",
+        keys: Keys::Shortcut(ZOOM_OUT),
+        text: "Zoom out of the diagram.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Fit diagram",
+        keys: Keys::Shortcut(RESET),
+        text: "Fit the whole diagram in the window.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Fit selection",
+        keys: Keys::Shortcut(FIT_SELECTION),
+        text: "Fit the selected nodes in the window.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Zoom to node",
+        keys: Keys::Shortcut(ZOOM_TO_NODE),
+        text: "Zoom in on the node under the pointer, fitting it in the window. Over an \
+               expanded thunk but none of its nodes, the whole thunk is fitted.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Focus on a thunk",
+        keys: Keys::Shortcut(FOCUS_THUNK),
+        text: "Draw only the body of the expanded thunk under the pointer, as if it were the whole \
+               program. The trail above the diagram leads back out through the thunks around it. \
+               Collapsing the thunk shows the whole program until it is expanded again.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Rename a variable",
+        keys: Keys::Described("Double-click a wire"),
+        text: "Rename the variable a wire carries everywhere it is bound and used, in Spartan and \
+               Chil programs. The code is printed again from the renamed program and recompiled, \
+               and the rename is undone like a rewrite.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Expand and collapse thunks",
+        keys: Keys::Described("Click a thunk"),
+        text: "Show or hide the body of a thunk. Expand all and Collapse all act on every thunk.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Fold chains",
+        keys: Keys::Described("Alt+click an operation in a chain"),
+        text: "Draw a straight line of operations, each using only the result of the one before, \
+               as a single node labelled by all of them. Click the node to unfold it again. Fold \
+               chains and Unfold chains act on every chain.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Select",
+        keys: Keys::Described("Right click a node"),
+        text: "Select nodes to save them as a selection, which shows the code and diagram of \
+               just those nodes, or to extend the selection along the wires.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Select a cone",
+        keys: Keys::Described("Extend selection, then Forward cone or Backward cone"),
+        text: "Select the nodes reachable from the selection along the wires, up to a depth set \
+               with a slider which updates the selection as it moves. Select keeps the cone, and \
+               Cancel goes back to the selection it started from.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Save selection",
+        keys: Keys::Shortcut(SAVE_SELECTION),
+        text: "Open the selected nodes in a window of their own. Open in that window decompiles \
+               them into a file of their own, to edit apart from the rest of the program.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Manage selections",
+        keys: Keys::None,
+        text: "Rename, duplicate, reorder or delete saved selections with the buttons beside \
+               them in the selection panel.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Undo",
+        keys: Keys::Shortcut(UNDO),
+        text: "Undo the last expansion, collapse or selection, or the last edit of the code: a \
+               rewrite applied, a variable renamed, or the edits typed before the last compile.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Redo",
+        keys: Keys::Shortcut(REDO),
+        text: "Redo what was last undone.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Bookmark",
+        keys: Keys::Shortcut(BOOKMARK),
+        text: "Remember the current view, to jump back to it from the Bookmarks menu.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Wire types",
+        keys: Keys::None,
+        text: "Colour the wires of Chil programs by the types of their values, with a legend of \
+               the types on screen. Hovering over a wire shows its type.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Ports",
+        keys: Keys::Described("Hover over an operation"),
+        text: "Number the inputs and outputs of the operation under the pointer, and list them \
+               with the variables on their wires in the tooltip. The port nearest the pointer \
+               is circled and picked out in the list.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Bundle copies",
+        keys: Keys::None,
+        text: "Draw a value copied to many consumers as one wire, thicker the more consumers it \
+               reaches, which splits near them. Turn it on from the Overlays menu.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Clusters",
+        keys: Keys::None,
+        text: "Outline the operations of Chil programs in a colour for the top-level definition \
+               they were written in, such as a function and its body, or collapse definitions \
+               into single operations, from the Clusters menu.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Paths",
+        keys: Keys::None,
+        text: "With Paths turned on in the Overlays menu, selecting two nodes highlights every \
+               path between them and dims the rest of the diagram. Paths at selection depth \
+               highlights the thunks the paths go through instead of the nodes inside them.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Profile",
+        keys: Keys::None,
+        text: "Load profile… in the File menu reads a JSON or CSV file giving a number for each \
+               node address, such as an execution count, and colours the operations from blue \
+               for the smallest value to red for the largest.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Diff",
+        keys: Keys::None,
+        text: "Diff against file… and Diff against this version in the File menu draw an earlier \
+               version of the program beside the code being edited, updating as it is edited. \
+               Operations added are green, removed red and rewired amber. With Mirror expansion \
+               ticked, expanding or collapsing a thunk in one does the same in the other.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Simplify",
+        keys: Keys::None,
+        text: "Choose how far diagrams are simplified before they are laid out: none, hiding \
+               identities, folding copies, or full normalisation.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Collapse large programs",
+        keys: Keys::None,
+        text: "Large programs open with their thunks expanded breadth first only while the \
+               diagram stays small enough to lay out quickly. The limit is set in the Simplify \
+               menu.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Solver",
+        keys: Keys::None,
+        text: "Choose the solver which lays diagrams out from the Solver menu. When it fails or \
+               runs out of time, layouts fall back to another solver and say so.",
+    },
+    Entry {
+        section: Section::Graph,
+        title: "Fast layouts",
+        keys: Keys::None,
+        text: "Graphs too big to solve quickly are laid out heuristically first. Re-run exact \
+               solves the layout, which may take minutes.",
+    },
+    Entry {
+        section: Section::Search,
+        title: "Find",
+        keys: Keys::Shortcut(FIND),
+        text: "Find the nodes and wires matching a query, expanding thunks to reveal them. \
+               Enter moves to the next match.",
+    },
+    Entry {
+        section: Section::Search,
+        title: "Substrings",
+        keys: Keys::None,
+        text: "A query matches the operations and thunks whose name contains it, ignoring case, \
+               such as `add` for `arith.addi` in MLIR or `plus` in Spartan.",
+    },
+    Entry {
+        section: Section::Search,
+        title: "Regular expressions",
+        keys: Keys::None,
+        text: "A query between slashes is a regular expression, ignoring case, such as \
+               `/^arith\\.(add|sub)i$/`.",
+    },
+    Entry {
+        section: Section::Search,
+        title: "Variables",
+        keys: Keys::None,
+        text: "Operations also match by the variables they define. Chil variables such as \
+               `foo(id: %0)` are matched by the whole variable, by `foo`, or by `%0`.",
+    },
+    Entry {
+        section: Section::Search,
+        title: "Thunks",
+        keys: Keys::None,
+        text: "A query matches thunks by their address, such as `@3` in Chil.",
+    },
+    Entry {
+        section: Section::Tools,
+        title: "Macros",
+        keys: Keys::None,
+        text: "Record expansions, collapses and selections, and replay them on another program.",
+    },
+    Entry {
+        section: Section::Tools,
+        title: "Rewrites",
+        keys: Keys::None,
+        text: "List the places in a Spartan program where rewrite rules apply, preview them, and \
+               apply them one at a time. Rules can be loaded from theory files of lines like \
+               `rule plus-zero: plus(x, 0) = x`.",
+    },
+    Entry {
+        section: Section::Tools,
+        title: "Annotations",
+        keys: Keys::None,
+        text: "Load change tags from an external differ to draw them on the nodes they refer to.",
+    },
+    Entry {
+        section: Section::Tools,
+        title: "Help",
+        keys: Keys::Shortcut(HELP),
+        text: "Show this window.",
+    },
+];
+
+#[derive(Default)]
+pub(crate) struct Help {
+    pub(crate) open: bool,
+    query: String,
+}
+
+impl Help {
+    pub(crate) fn ui(&mut self, ctx: &egui::Context) {
+        if ctx.input_mut(|i| i.consume_shortcut(&HELP)) {
+            self.open = !self.open;
+        }
+        egui::Window::new("Help")
+            .open(&mut self.open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.query).hint_text("Search help"));
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let mut found = false;
+                    for section in Section::ALL {
+                        let mut entries = ENTRIES
+                            .iter()
+                            .filter(|entry| entry.section == section)
+                            .filter(|entry| entry.is_match(&self.query, ctx))
+                            .peekable();
+                        if entries.peek().is_none() {
+                            continue;
+                        }
+                        found = true;
+                        ui.heading(section.title());
+                        for entry in entries {
+                            ui.horizontal(|ui| {
+                                ui.strong(entry.title);
+                                match entry.keys {
+                                    Keys::None => {}
+                                    Keys::Shortcut(shortcut) => {
+                                        ui.weak(ctx.format_shortcut(&shortcut));
+                                    }
+                                    Keys::Described(keys) => {
+                                        ui.weak(keys);
+                                    }
+                                }
+                            });
+                            ui.label(entry.text);
+                            ui.add_space(4.0);
+                        }
+                    }
+                    if !found {
+                        ui.weak("Nothing matches the search");
+                    }
+                });
+            });
+    }
+}
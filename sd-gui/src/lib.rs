@@ -1,17 +1,69 @@
 #![warn(clippy::all, rust_2018_idioms)]
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod annotations;
 mod app;
+pub(crate) mod autosave;
+pub(crate) mod bookmarks;
+#[cfg(not(target_arch = "wasm32"))]
+mod check;
 pub(crate) mod code_generator;
 pub(crate) mod code_ui;
+#[cfg(not(target_arch = "wasm32"))]
+mod conformance;
+pub(crate) mod crash;
+#[cfg(not(target_arch = "wasm32"))]
+mod diagnostics;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod diff;
+pub(crate) mod disk_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod export;
+pub(crate) mod files;
 pub(crate) mod graph_ui;
+mod graph_view;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+pub(crate) mod help;
 pub(crate) mod highlighter;
+pub(crate) mod navigation;
 pub(crate) mod panzoom;
 pub(crate) mod parser;
+#[cfg(target_arch = "wasm32")]
+mod pen;
+pub(crate) mod progress;
+pub(crate) mod recorder;
+pub(crate) mod rename;
+pub(crate) mod rewrites;
 pub(crate) mod selection;
+pub(crate) mod session;
+pub(crate) mod settings;
 pub(crate) mod shape_generator;
 pub(crate) mod squiggly_line;
+pub(crate) mod theme;
+pub(crate) mod tutorial;
+pub(crate) mod view_script;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod watch;
+#[cfg(target_arch = "wasm32")]
+mod web_api;
 
 pub use app::App;
+#[cfg(not(target_arch = "wasm32"))]
+pub use check::check_examples;
+#[cfg(not(target_arch = "wasm32"))]
+pub use conformance::check_conformance;
+#[cfg(not(target_arch = "wasm32"))]
+pub use diagnostics::LogWriter;
+pub use graph_view::GraphView;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::render;
 pub use parser::UiLanguage;
+pub use shape_generator::set_simplification;
+pub use view_script::ViewScript;
+#[cfg(not(target_arch = "wasm32"))]
+pub use watch::read_program;
+#[cfg(target_arch = "wasm32")]
+pub use web_api::{get_svg, set_code};
 
 #[cfg(not(target_arch = "wasm32"))]
 macro_rules! spawn {
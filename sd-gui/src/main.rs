@@ -3,8 +3,8 @@
 
 use std::path::PathBuf;
 
-use clap::Parser;
-use sd_core::lp::Solver;
+use clap::{Parser, Subcommand};
+use sd_core::{lp::Solver, monoidal::Simplification};
 
 #[derive(Parser)]
 #[command(
@@ -22,13 +22,16 @@ use sd_core::lp::Solver;
 )]
 /// String diagram visualiser
 ///
+/// Programs read in with --chil, --spartan, --mlir or --dot are reloaded whenever they change on
+/// disk.
+///
 /// Homepage: <https://sd-visualiser.github.io/sd-visualiser>
 ///
 /// Please report bugs at <https://github.com/sd-visualiser/sd-visualiser/issues>.
 struct Args {
-    /// Read in a chil file
-    #[arg(long, value_name = "FILE")]
-    chil: Option<PathBuf>,
+    /// Read in a chil file, or the files of a chil program split across several, linked together
+    #[arg(long, value_name = "FILE", num_args = 1..)]
+    chil: Vec<PathBuf>,
 
     /// Read in a spartan file
     #[arg(long, value_name = "FILE")]
@@ -42,9 +45,60 @@ struct Args {
     #[arg(long, value_name = "FILE")]
     dot: Option<PathBuf>,
 
+    /// Render FILE without opening a window, guessing its language from its extension or contents
+    #[arg(long, value_name = "FILE", requires = "output")]
+    render: Option<PathBuf>,
+
+    /// Where to write the diagram when rendering, as an svg drawing, a png image, a TikZ picture or
+    /// a dot graph by its extension
+    #[arg(
+        long,
+        value_name = "FILE.svg|FILE.png|FILE.tex|FILE.dot",
+        requires = "render"
+    )]
+    output: Option<PathBuf>,
+
+    /// CSS to style the rendered diagram with, using the classes operation, thunk, wire and dot
+    #[arg(long, value_name = "FILE.css", requires = "render")]
+    stylesheet: Option<PathBuf>,
+
+    /// Collapse and select the nodes, and pan and zoom to the view, recorded in a view script;
+    /// the program is read from the file named in the script unless one is given
+    #[arg(long, value_name = "FILE.sdview")]
+    view: Option<PathBuf>,
+
+    /// How far to simplify graphs before laying them out, overriding any level in the view script
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    simplify: Option<Simplification>,
+
     /// Choose LP solver
     #[arg(long, value_enum, default_value_t)]
     solver: Solver,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check each program in a conformance suite against its NAME.EXT.expected file
+    ///
+    /// Expectation files contain `nodes: N`, `edges: N`, `free: x, y` and `decompiled:`
+    /// lines, with the expected decompilation on the lines after `decompiled:`.
+    Conformance {
+        /// Directory containing the suite
+        #[arg(value_name = "DIR")]
+        suite: PathBuf,
+    },
+    /// Parse, build and lay out every program under a directory, timing each stage
+    ///
+    /// Prints a table of how long each program took to parse, build into a hypergraph and lay
+    /// out, and of the errors met, failing if any program could not be checked.
+    Check {
+        /// Directory containing the programs, such as .sd and .chil files
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
 }
 
 // When compiling natively:
@@ -52,17 +106,74 @@ struct Args {
 fn main() -> anyhow::Result<()> {
     // Log to stdout (if you run with `RUST_LOG=debug`).
 
-    use anyhow::anyhow;
+    use anyhow::{anyhow, Context};
     use egui::ViewportBuilder;
-    tracing_subscriber::fmt::fmt()
-        .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
-        .with_thread_names(true)
+    use tracing_subscriber::{
+        filter::{EnvFilter, LevelFilter},
+        fmt,
+        prelude::*,
+    };
+    tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_thread_names(true)
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        // Kept for diagnostic bundles, whatever the level of the console log
+        .with(
+            fmt::layer()
+                .with_thread_names(true)
+                .with_ansi(false)
+                .with_writer(|| sd_gui::LogWriter)
+                .with_filter(LevelFilter::INFO),
+        )
         .init();
 
     let args = Args::parse();
 
     tracing::info!("lp solver: {:?}", args.solver);
 
+    match &args.command {
+        Some(Command::Conformance { suite }) => return sd_gui::check_conformance(suite),
+        Some(Command::Check { dir }) => return sd_gui::check_examples(dir, args.solver),
+        None => {}
+    }
+
+    let mut view = args
+        .view
+        .as_ref()
+        .map(|path| {
+            std::fs::read_to_string(path)?
+                .parse::<sd_gui::ViewScript>()
+                .with_context(|| format!("failed to read view script {}", path.display()))
+        })
+        .transpose()?;
+    if let Some(level) = args.simplify {
+        if let Some(view) = &mut view {
+            view.simplification = Some(level);
+        }
+        sd_gui::set_simplification(level);
+    }
+
+    if let (Some(input), Some(output)) = (&args.render, &args.output) {
+        let code = std::fs::read_to_string(input)?;
+        let language = sd_gui::UiLanguage::guess(&input.to_string_lossy(), &code)
+            .ok_or_else(|| anyhow!("cannot guess the language of {}", input.display()))?;
+        let stylesheet = args
+            .stylesheet
+            .as_ref()
+            .map(std::fs::read_to_string)
+            .transpose()?;
+        return sd_gui::render(
+            &code,
+            language,
+            args.solver,
+            output,
+            stylesheet.as_deref(),
+            view.as_ref(),
+        );
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: ViewportBuilder {
             maximized: Some(true),
@@ -71,18 +182,36 @@ fn main() -> anyhow::Result<()> {
         ..Default::default()
     };
 
-    let file = if let Some(path) = args.chil {
-        let code = std::fs::read_to_string(path)?;
-        Some((code, sd_gui::UiLanguage::Chil))
-    } else if let Some(path) = args.spartan {
-        let code = std::fs::read_to_string(path)?;
-        Some((code, sd_gui::UiLanguage::Spartan))
-    } else if let Some(path) = args.mlir {
-        let code = std::fs::read_to_string(path)?;
-        Some((code, sd_gui::UiLanguage::Mlir))
-    } else if let Some(path) = args.dot {
-        let code = std::fs::read_to_string(path)?;
-        Some((code, sd_gui::UiLanguage::Dot))
+    // Programs named on the command line are reloaded when they change on disk
+    let watched = if args.chil.is_empty() {
+        [
+            (&args.spartan, sd_gui::UiLanguage::Spartan),
+            (&args.mlir, sd_gui::UiLanguage::Mlir),
+            (&args.dot, sd_gui::UiLanguage::Dot),
+        ]
+        .into_iter()
+        .find_map(|(path, language)| Some((vec![path.clone()?], language)))
+    } else {
+        Some((args.chil.clone(), sd_gui::UiLanguage::Chil))
+    };
+
+    let file = if let Some((paths, language)) = &watched {
+        Some((sd_gui::read_program(paths, *language)?, *language))
+    } else if let Some(file) = view.as_ref().and_then(|view| view.file.as_ref()) {
+        // Named relative to the script
+        let path = args
+            .view
+            .as_ref()
+            .and_then(|path| path.parent())
+            .unwrap_or(std::path::Path::new("."))
+            .join(file);
+        let code = std::fs::read_to_string(&path)?;
+        let language = view
+            .as_ref()
+            .and_then(|view| view.language)
+            .or_else(|| sd_gui::UiLanguage::guess(&path.to_string_lossy(), &code))
+            .ok_or_else(|| anyhow!("cannot guess the language of {}", path.display()))?;
+        Some((code, language))
     } else {
         None
     };
@@ -92,8 +221,13 @@ fn main() -> anyhow::Result<()> {
         Box::new(move |cc| {
             let mut app = sd_gui::App::new(cc, args.solver);
 
-            if let Some((code, language)) = file {
-                app.set_file(&code, Some(language));
+            match (file, view) {
+                (Some((code, language)), Some(view)) => app.set_view(&code, language, view),
+                (Some((code, language)), None) => app.set_file(&code, Some(language)),
+                (None, _) => {}
+            }
+            if let Some((paths, language)) = watched {
+                app.watch(&cc.egui_ctx, paths, language);
             }
 
             Box::new(app)
@@ -110,7 +244,7 @@ fn main() {
     // Redirect `log` message to `console.log` and friends:
     eframe::WebLogger::init(log::LevelFilter::Debug).ok();
     tracing_wasm::set_as_global_default();
-    tracing::info!("lp solver: Clarabel");
+    tracing::info!("lp solver: Microlp");
 
     let web_options = eframe::WebOptions::default();
 
@@ -0,0 +1,78 @@
+//! Moving a focus between the nodes of a diagram from the keyboard, following the wires up to
+//! the nodes a node depends on and down to those depending on it, and across its slice.
+
+use std::collections::HashMap;
+
+use eframe::epaint::{Pos2, Rect};
+use sd_core::hypergraph::{
+    generic::{Ctx, Endpoint, Node},
+    traits::{EdgeLike, NodeLike},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Step {
+    /// To a node whose outputs feed the focused node.
+    Up,
+    /// To a node fed by the outputs of the focused node.
+    Down,
+    Left,
+    Right,
+}
+
+/// The node drawn nearest to `pos`.
+pub(crate) fn nearest<T: Ctx>(bounds: &HashMap<Node<T>, Rect>, pos: Pos2) -> Option<Node<T>> {
+    bounds
+        .iter()
+        .min_by(|(_, x), (_, y)| {
+            x.distance_sq_to_pos(pos)
+                .total_cmp(&y.distance_sq_to_pos(pos))
+        })
+        .map(|(node, _)| node.clone())
+}
+
+/// The node to focus after taking `step` from `focus`, if there is one in that direction.
+pub(crate) fn step<T: Ctx>(
+    bounds: &HashMap<Node<T>, Rect>,
+    focus: &Node<T>,
+    step: Step,
+) -> Option<Node<T>> {
+    let rect = bounds.get(focus)?;
+    let center = rect.center();
+    let candidates: Vec<Node<T>> = match step {
+        Step::Up => focus
+            .inputs()
+            .filter_map(|edge| match edge.source() {
+                Endpoint::Node(node) => Some(node),
+                Endpoint::Boundary(_) => None,
+            })
+            .collect(),
+        Step::Down => focus
+            .outputs()
+            .flat_map(|edge| edge.targets().collect::<Vec<_>>())
+            .filter_map(|target| match target {
+                Endpoint::Node(node) => Some(node),
+                Endpoint::Boundary(_) => None,
+            })
+            .collect(),
+        // The other nodes in the same thunk whose slice the focused node is in
+        Step::Left | Step::Right => bounds
+            .iter()
+            .filter(|(node, other)| {
+                *node != focus
+                    && node.backlink() == focus.backlink()
+                    && other.y_range().contains(center.y)
+                    && if step == Step::Left {
+                        other.center().x < center.x
+                    } else {
+                        other.center().x > center.x
+                    }
+            })
+            .map(|(node, _)| node.clone())
+            .collect(),
+    };
+    candidates
+        .into_iter()
+        .filter_map(|node| Some((bounds.get(&node)?.center().distance_sq(center), node)))
+        .min_by(|(x, _), (y, _)| x.total_cmp(y))
+        .map(|(_, node)| node)
+}
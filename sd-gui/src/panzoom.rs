@@ -5,6 +5,8 @@ use eframe::{
 
 const PAN_FACTOR: f32 = 10.0;
 const ZOOM_FACTOR: f32 = 1.25;
+/// The zoom of a new view, and the furthest fitting the diagram zooms in.
+pub const DEFAULT_ZOOM: f32 = 50.0;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Panzoom {
@@ -16,12 +18,27 @@ impl Default for Panzoom {
     fn default() -> Self {
         Self {
             translation: Pos2::default(),
-            zoom: 50.0,
+            zoom: DEFAULT_ZOOM,
         }
     }
 }
 
 impl Panzoom {
+    /// A view centred on `translation`, scaled by `zoom`.
+    pub const fn new(translation: Pos2, zoom: f32) -> Self {
+        Self { translation, zoom }
+    }
+
+    /// The point in the middle of the view.
+    pub const fn translation(self) -> Pos2 {
+        self.translation
+    }
+
+    /// How many pixels a unit of the graph takes up.
+    pub const fn zoom_factor(self) -> f32 {
+        self.zoom
+    }
+
     /// Construct a `RectTransform` that applies panzoom.
     pub fn transform(self, screen: Rect) -> RectTransform {
         let from = Rect::from_center_size(self.translation, screen.size() / self.zoom);
@@ -30,11 +47,21 @@ impl Panzoom {
 
     /// Pan to the center and reset the zoom.
     pub fn reset(&mut self, size: Vec2, screen_size: Vec2) {
-        self.translation = (size / 2.0).to_pos2();
+        self.fit(
+            Rect::from_min_size(Pos2::ZERO, size),
+            screen_size,
+            DEFAULT_ZOOM,
+        );
+    }
+
+    /// Pan to the center of `rect` and zoom so that it fits on a screen of `screen_size` with a
+    /// margin of a unit all round, zooming in no further than `max_zoom`.
+    pub fn fit(&mut self, rect: Rect, screen_size: Vec2, max_zoom: f32) {
+        self.translation = rect.center();
         self.zoom = [
-            50.0,
-            screen_size.x / (size.x + 2.0),
-            screen_size.y / (size.y + 2.0),
+            max_zoom,
+            screen_size.x / (rect.width() + 2.0),
+            screen_size.y / (rect.height() + 2.0),
         ]
         .into_iter()
         .min_by(|x, y| x.partial_cmp(y).unwrap())
@@ -1,15 +1,20 @@
+use std::path::Path;
+
 use from_pest::{ConversionError, FromPest, Void};
 use pest::{error, Parser as _};
 use sd_core::language::{
-    chil::{self, ChilParser},
+    chil,
     mlir::{
         self,
         internal::{MlirParser, TopLevelItem},
     },
     spartan::{self, SpartanParser},
+    Expr, Language,
 };
 use thiserror::Error;
 
+use crate::settings;
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub enum UiLanguage {
     Chil,
@@ -28,6 +33,77 @@ impl UiLanguage {
             Self::Dot => "dot",
         }
     }
+
+    /// The usual extension of files in the language.
+    #[must_use]
+    pub const fn extension(&self) -> &str {
+        match self {
+            Self::Chil => "chil",
+            Self::Spartan => "sd",
+            Self::Mlir => "mlir",
+            Self::Dot => "dot",
+        }
+    }
+
+    /// Guesses the language of a file from its extension.
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "chil" => Some(Self::Chil),
+            "sd" => Some(Self::Spartan),
+            "mlir" => Some(Self::Mlir),
+            "dot" | "gv" => Some(Self::Dot),
+            _ => None,
+        }
+    }
+
+    /// Guesses the language of the file `name` from its extension, including those added in the
+    /// settings file, or failing that from its contents.
+    #[must_use]
+    pub fn guess(name: &str, source: &str) -> Option<Self> {
+        Path::new(name)
+            .extension()
+            .map(|extension| extension.to_string_lossy())
+            .and_then(|extension| {
+                settings::load()
+                    .extensions
+                    .get(extension.as_ref())
+                    .copied()
+                    .or_else(|| Self::from_extension(&extension))
+            })
+            .or_else(|| Self::sniff(source))
+    }
+
+    /// Guesses the language of `source` from its first token, checking the guess by parsing it,
+    /// and trying the other languages in turn if it does not parse.
+    #[must_use]
+    pub fn sniff(source: &str) -> Option<Self> {
+        let first = source
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("//"))?;
+        let token = first
+            .split(|c: char| c.is_whitespace() || c == '(' || c == '{')
+            .next()
+            .unwrap_or_default();
+        let hint = match token {
+            "def" | "output" => Some(Self::Chil),
+            "bind" => Some(Self::Spartan),
+            "digraph" | "graph" | "strict" => Some(Self::Dot),
+            _ if token == "module" || token.starts_with(['%', '"']) || token.contains('.') => {
+                Some(Self::Mlir)
+            }
+            _ => None,
+        };
+        hint.into_iter()
+            .chain(
+                [Self::Chil, Self::Spartan, Self::Mlir, Self::Dot]
+                    .into_iter()
+                    .filter(|language| Some(*language) != hint),
+            )
+            .find(|language| parse_with_warnings(source, *language).is_ok())
+            .or(hint)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -41,7 +117,7 @@ pub enum ParseOutput {
 #[derive(Clone, Debug, Error)]
 pub enum ParseError {
     #[error("Chil parsing error:\n{0}")]
-    Chil(#[from] Box<error::Error<chil::Rule>>),
+    Chil(#[from] chil::parser::ParseError),
 
     #[error("Spartan parsing error:\n{0}")]
     Spartan(#[from] Box<error::Error<spartan::Rule>>),
@@ -57,17 +133,32 @@ pub enum ParseError {
 }
 
 pub fn parse(source: &str, language: UiLanguage) -> Result<ParseOutput, ParseError> {
-    match language {
+    let (output, warnings) = parse_with_warnings(source, language)?;
+    for warning in warnings {
+        tracing::warn!("{warning}");
+    }
+    Ok(output)
+}
+
+/// Parses `source`, also returning any problems which did not stop it from being parsed.
+pub fn parse_with_warnings(
+    source: &str,
+    language: UiLanguage,
+) -> Result<(ParseOutput, Vec<String>), ParseError> {
+    let mut warnings = Vec::new();
+    let output = match language {
         UiLanguage::Chil => {
-            let mut pairs = ChilParser::parse(chil::Rule::program, source).map_err(Box::new)?;
-            let expr = chil::Expr::from_pest(&mut pairs)?;
-            Ok(ParseOutput::Chil(expr))
+            let (expr, chil_warnings) = chil::parser::parse_with_warnings(source)?;
+            warnings.extend(chil_warnings.iter().map(ToString::to_string));
+            check_arities(&expr, &mut warnings);
+            ParseOutput::Chil(expr)
         }
         UiLanguage::Spartan => {
             let mut pairs =
                 SpartanParser::parse(spartan::Rule::program, source).map_err(Box::new)?;
             let expr = spartan::Expr::from_pest(&mut pairs)?;
-            Ok(ParseOutput::Spartan(expr))
+            check_arities(&expr, &mut warnings);
+            ParseOutput::Spartan(expr)
         }
         UiLanguage::Mlir => {
             let mut pairs =
@@ -82,11 +173,23 @@ pub fn parse(source: &str, language: UiLanguage) -> Result<ParseOutput, ParseErr
                 })
                 .collect();
             let expr = mlir::Expr::from(ops);
-            Ok(ParseOutput::Mlir(expr))
+            check_arities(&expr, &mut warnings);
+            ParseOutput::Mlir(expr)
         }
         UiLanguage::Dot => {
             let graph = graphviz_rust::parse(source).map_err(ParseError::Dot)?;
-            Ok(ParseOutput::Dot(graph))
+            ParseOutput::Dot(graph)
         }
-    }
+    };
+    Ok((output, warnings))
+}
+
+/// Warns about operations with a number of inputs or outputs their language does not expect,
+/// which are likely to come from a malformed compiler dump.
+fn check_arities<T: Language>(expr: &Expr<T>, warnings: &mut Vec<String>) {
+    warnings.extend(
+        expr.arity_violations()
+            .iter()
+            .map(|violation| format!("Unexpected arity: {violation}")),
+    );
 }
@@ -0,0 +1,67 @@
+//! Hovering over the diagram with a pen on the web, so that tablets highlight what is under the
+//! pen as a mouse would. Browsers report a pen hovering over the page as pointer events, but not
+//! always as the mouse events the web runner listens for, and the touch events it listens for
+//! only begin once the pen touches the screen.
+
+use std::cell::RefCell;
+
+use eframe::egui;
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::{HtmlCanvasElement, PointerEvent};
+
+thread_local! {
+    static EVENTS: RefCell<Vec<egui::Event>> = RefCell::default();
+}
+
+/// Starts listening for a pen hovering over the canvas, drawing a frame with `ctx` for each
+/// movement.
+pub(crate) fn install(ctx: &egui::Context) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let ctx = ctx.clone();
+    let listener = Closure::<dyn FnMut(PointerEvent)>::new(move |event: PointerEvent| {
+        if let Some(event) = hover(&event, &ctx) {
+            EVENTS.with_borrow_mut(|events| events.push(event));
+            ctx.request_repaint();
+        }
+    });
+    // Leaving the canvas is only seen as `pointerout`, as `pointerleave` does not bubble
+    for kind in ["pointermove", "pointerout"] {
+        if let Err(err) =
+            window.add_event_listener_with_callback(kind, listener.as_ref().unchecked_ref())
+        {
+            tracing::warn!("Failed to listen for {kind} events: {err:?}");
+        }
+    }
+    // The listener lives as long as the page
+    listener.forget();
+}
+
+/// The pen movements since this was last called, as egui input.
+pub(crate) fn take_events() -> Vec<egui::Event> {
+    EVENTS.with_borrow_mut(std::mem::take)
+}
+
+/// The pointer movement `event` stands for, if it is a pen hovering over or leaving the canvas.
+/// Once the pen touches the screen, it is followed by touch events instead.
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn hover(event: &PointerEvent, ctx: &egui::Context) -> Option<egui::Event> {
+    if event.pointer_type() != "pen" {
+        return None;
+    }
+    let canvas = event.target()?.dyn_into::<HtmlCanvasElement>().ok()?;
+    match event.type_().as_str() {
+        "pointermove" if event.buttons() == 0 => {
+            // As the web runner places mouse events
+            let rect = canvas.get_bounding_client_rect();
+            let zoom_factor = ctx.zoom_factor();
+            Some(egui::Event::PointerMoved(egui::pos2(
+                (event.client_x() as f32 - rect.left() as f32) / zoom_factor,
+                (event.client_y() as f32 - rect.top() as f32) / zoom_factor,
+            )))
+        }
+        "pointerout" => Some(egui::Event::PointerGone),
+        _ => None,
+    }
+}
@@ -0,0 +1,50 @@
+//! The progress of the latest conversion running in the background, shown in place of a spinner
+//! and logged for headless rendering.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use eframe::egui;
+use sd_core::progress::Callback;
+
+#[derive(Clone, Copy, Debug)]
+struct Progress {
+    stage: &'static str,
+    done: usize,
+    total: usize,
+}
+
+/// The progress of the unfinished conversion which reported most recently, if any.
+static PROGRESS: Mutex<Option<Progress>> = Mutex::new(None);
+
+/// A callback recording progress through `stage`.
+pub(crate) fn reporter(stage: &'static str) -> Callback {
+    Arc::new(move |done, total| {
+        if total > 0 && (done * 100 / total) % 10 == 0 {
+            tracing::info!("{stage}: {done}/{total}");
+        }
+        *PROGRESS.lock().unwrap_or_else(PoisonError::into_inner) =
+            (done < total).then_some(Progress { stage, done, total });
+    })
+}
+
+/// Shows a progress bar for the latest conversion, or a spinner if there is none.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn ui(ui: &mut egui::Ui) {
+    let progress = *PROGRESS.lock().unwrap_or_else(PoisonError::into_inner);
+    match progress {
+        Some(Progress { stage, done, total }) => {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 2.0);
+                ui.add(
+                    egui::ProgressBar::new(done as f32 / total as f32)
+                        .desired_width(300.0)
+                        .text(format!("{stage} ({done}/{total})"))
+                        .animate(true),
+                );
+            });
+        }
+        None => {
+            ui.centered_and_justified(egui::Ui::spinner);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use eframe::egui;
+use sd_core::{common::Direction, interactive::Toggle};
+
+/// A step of a recorded macro, which can be replayed on whichever program is shown.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Action {
+    /// Clicking on a node, to expand or select it.
+    Toggle(Toggle),
+    /// Expanding or collapsing every thunk.
+    SetExpandedAll(bool),
+    ExtendSelection(Option<(Direction, usize)>),
+    ClearSelection,
+    SaveSelection,
+}
+
+/// Keys which a macro can be bound to, chosen to stay clear of the other shortcuts.
+const KEYS: [egui::Key; 6] = [
+    egui::Key::F7,
+    egui::Key::F8,
+    egui::Key::F9,
+    egui::Key::F10,
+    egui::Key::F11,
+    egui::Key::F12,
+];
+
+/// Records the actions taken on the graph, to replay them on other programs.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    /// The actions recorded so far, while recording.
+    recording: Option<Vec<Action>>,
+    /// The last macro recorded.
+    actions: Vec<Action>,
+    /// A key which replays the macro.
+    key: Option<egui::Key>,
+}
+
+impl Recorder {
+    pub(crate) const fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Records an action, if recording.
+    pub(crate) fn record(&mut self, action: Action) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(action);
+        }
+    }
+
+    /// Whether the key bound to the macro was pressed.
+    pub(crate) fn shortcut_pressed(&self, ctx: &egui::Context) -> bool {
+        !self.is_recording()
+            && !self.actions.is_empty()
+            && self.key.is_some_and(|key| {
+                ctx.input_mut(|i| {
+                    i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::NONE, key))
+                })
+            })
+    }
+
+    pub(crate) fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// Show the macro menu.
+    /// Returns whether the user asked to replay the macro.
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui, ready: bool) -> bool {
+        match self.recording.take() {
+            Some(recording) => {
+                if ui
+                    .button(format!("Stop recording ({} actions)", recording.len()))
+                    .clicked()
+                {
+                    self.actions = recording;
+                    ui.close_menu();
+                } else {
+                    self.recording = Some(recording);
+                }
+            }
+            None => {
+                if ui.button("Start recording").clicked() {
+                    self.recording = Some(Vec::new());
+                    ui.close_menu();
+                }
+            }
+        }
+
+        let shortcut = self.key.map_or_else(String::new, |key| {
+            ui.ctx()
+                .format_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::NONE, key))
+        });
+        let replay = ui
+            .add_enabled(
+                ready && !self.is_recording() && !self.actions.is_empty(),
+                egui::Button::new(format!("Replay ({} actions)", self.actions.len()))
+                    .shortcut_text(shortcut),
+            )
+            .clicked();
+
+        egui::ComboBox::from_label("Replay key")
+            .selected_text(self.key.map_or("None", egui::Key::name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.key, None, "None");
+                for key in KEYS {
+                    ui.selectable_value(&mut self.key, Some(key), key.name());
+                }
+            });
+
+        if replay {
+            ui.close_menu();
+        }
+        replay
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Context;
+use eframe::egui;
+use sd_core::{
+    language::{chil, spartan, Expr},
+    prettyprinter::PrettyPrint,
+    rename::Rename,
+};
+
+use crate::parser::{parse, ParseOutput, UiLanguage};
+
+/// The variable carried by a wire which was double-clicked, in the languages whose programs can
+/// be printed back as code.
+#[derive(Clone, Debug)]
+pub(crate) enum Variable {
+    Chil(chil::Variable),
+    Spartan(spartan::Variable),
+}
+
+impl Variable {
+    fn name(&self) -> String {
+        match self {
+            Self::Chil(var) => var
+                .name
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            Self::Spartan(var) => var.to_string(),
+        }
+    }
+}
+
+/// A window renaming a variable everywhere in the code of the program.
+pub(crate) struct RenameVariable {
+    variable: Variable,
+    /// The new name typed so far.
+    name: String,
+    /// Why the last name tried could not be used.
+    error: Option<String>,
+    request_focus: bool,
+}
+
+impl RenameVariable {
+    pub(crate) fn new(variable: Variable) -> Self {
+        let name = variable.name();
+        Self {
+            variable,
+            name,
+            error: None,
+            request_focus: true,
+        }
+    }
+
+    /// Draws the window for the program `parse`, if it has been parsed.
+    /// Returns whether to keep the window open, and the code of the program with the variable
+    /// renamed once the rename has been confirmed.
+    pub(crate) fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        parse: Option<&ParseOutput>,
+    ) -> (bool, Option<String>) {
+        let mut open = true;
+        let mut rename = false;
+        let mut cancel = false;
+        egui::Window::new("Rename variable")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let binder = match (&self.variable, parse) {
+                    (Variable::Chil(var), Some(ParseOutput::Chil(expr))) => {
+                        expr.binder(var).map(|binder| (var.to_string(), binder))
+                    }
+                    (Variable::Spartan(var), Some(ParseOutput::Spartan(expr))) => {
+                        expr.binder(var).map(|binder| (var.to_string(), binder))
+                    }
+                    _ => None,
+                };
+                if let Some((var, binder)) = binder {
+                    ui.label(format!("`{var}` is {binder}."));
+                }
+                let response = ui.text_edit_singleline(&mut self.name);
+                if std::mem::take(&mut self.request_focus) {
+                    response.request_focus();
+                }
+                if response.changed() {
+                    self.error = None;
+                }
+                rename = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.horizontal(|ui| {
+                    rename |= ui
+                        .add_enabled(parse.is_some(), egui::Button::new("Rename"))
+                        .clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if cancel || !open {
+            return (false, None);
+        }
+        let Some(parse) = parse.filter(|_| rename) else {
+            return (true, None);
+        };
+        match self.rename(parse) {
+            Ok(code) => (false, Some(code)),
+            Err(err) => {
+                self.error = Some(format!("{err:#}"));
+                self.request_focus = true;
+                (true, None)
+            }
+        }
+    }
+
+    fn rename(&self, parse: &ParseOutput) -> anyhow::Result<String> {
+        match (&self.variable, parse) {
+            (Variable::Chil(var), ParseOutput::Chil(expr)) => {
+                renamed_code(expr, var, &self.name, UiLanguage::Chil)
+            }
+            (Variable::Spartan(var), ParseOutput::Spartan(expr)) => {
+                renamed_code(expr, var, &self.name, UiLanguage::Spartan)
+            }
+            _ => anyhow::bail!("The program is no longer in the same language"),
+        }
+    }
+}
+
+/// The code of `expr`, in `language`, with `var` called `name`.
+fn renamed_code<T: Rename>(
+    expr: &Expr<T>,
+    var: &T::Var,
+    name: &str,
+    language: UiLanguage,
+) -> anyhow::Result<String>
+where
+    Expr<T>: PrettyPrint,
+{
+    let code = expr.rename_var(var, name)?.to_pretty();
+    // Checked before the code is replaced, so that a rename never leaves a program which the
+    // graph cannot be rebuilt from
+    parse(&code, language).context("The renamed program does not parse")?;
+    Ok(code)
+}
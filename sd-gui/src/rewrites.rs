@@ -0,0 +1,334 @@
+use delegate::delegate;
+use eframe::egui;
+use sd_core::{
+    graph::SyntaxHypergraph,
+    interactive::InteractiveGraph,
+    language::spartan::{Expr, Spartan, Value},
+    lp::Solver,
+    prettyprinter::PrettyPrint,
+    rewrite::{Match, RewriteRule},
+};
+use sd_graphics::common::LabelVisibility;
+
+use crate::graph_ui::{GraphUiInternal, Overlays};
+
+type PreviewGraph = GraphUiInternal<InteractiveGraph<SyntaxHypergraph<Spartan>>>;
+
+/// The size of each of the diagrams previewing a rewrite.
+const PREVIEW_SIZE: egui::Vec2 = egui::vec2(220.0, 160.0);
+
+/// The name of the rules shipped with the visualiser.
+const BUILT_IN: &str = "built-in laws";
+
+/// Diagrams of a match before and after it is rewritten.
+struct Preview {
+    /// The index of the match in the list.
+    index: usize,
+    before: PreviewGraph,
+    after: PreviewGraph,
+}
+
+impl Preview {
+    fn new(index: usize, rewrite: &Match<Spartan>, solver: Solver) -> Option<Self> {
+        let diagram = |value: &Value| {
+            let expr = Expr {
+                binds: Vec::new(),
+                values: vec![value.clone()],
+            };
+            let graph = expr.to_graph(false).ok()?;
+            Some(GraphUiInternal::new(InteractiveGraph::new(graph), solver))
+        };
+        Some(Self {
+            index,
+            before: diagram(&rewrite.before)?,
+            after: diagram(&rewrite.after)?,
+        })
+    }
+}
+
+/// Edits made to the code, whether rewrites, edits made from the graph or edits typed and then
+/// compiled, by the code before and after each of them, to undo them after the interactions with
+/// the recompiled graph have been.
+#[derive(Clone, Debug, Default)]
+struct CodeHistory {
+    undo: Vec<(String, String)>,
+    redo: Vec<(String, String)>,
+}
+
+impl CodeHistory {
+    fn record(&mut self, before: String, after: String) {
+        self.undo.push((before, after));
+        self.redo.clear();
+    }
+
+    /// Records the edits which turned `before` into `after` as one step, unless the code was
+    /// replaced by a step already recorded or by undoing or redoing one.
+    fn record_edit(&mut self, before: String, after: String) {
+        if before != after && !self.can_undo(&after) && !self.can_redo(&after) {
+            self.record(before, after);
+        }
+    }
+
+    /// Whether the last rewrite can be undone, which it cannot once the code has been edited.
+    fn can_undo(&self, code: &str) -> bool {
+        self.undo.last().is_some_and(|(_, after)| after == code)
+    }
+
+    fn can_redo(&self, code: &str) -> bool {
+        self.redo.last().is_some_and(|(before, _)| before == code)
+    }
+
+    /// The code before the last rewrite, if it can be undone.
+    fn undo(&mut self, code: &str) -> Option<String> {
+        if !self.can_undo(code) {
+            return None;
+        }
+        let (before, after) = self.undo.pop()?;
+        self.redo.push((before.clone(), after));
+        Some(before)
+    }
+
+    /// The code after the last rewrite undone, if it can be redone.
+    fn redo(&mut self, code: &str) -> Option<String> {
+        if !self.can_redo(code) {
+            return None;
+        }
+        let (before, after) = self.redo.pop()?;
+        self.undo.push((before, after.clone()));
+        Some(after)
+    }
+}
+
+/// A panel listing the places in a Spartan program where rewrite rules apply, to apply them one
+/// at a time.
+pub struct Rewrites {
+    pub(crate) open: bool,
+    /// Where the rules came from.
+    theory: String,
+    rules: Vec<RewriteRule<Spartan>>,
+    load_error: Option<String>,
+    /// The program last searched for matches, and the matches found in it.
+    matches: Option<(Expr, Vec<Match<Spartan>>)>,
+    /// The match being hovered over.
+    preview: Option<Preview>,
+    history: CodeHistory,
+    solver: Solver,
+}
+
+impl Rewrites {
+    pub(crate) fn new(solver: Solver) -> Self {
+        Self {
+            open: false,
+            theory: BUILT_IN.to_owned(),
+            rules: RewriteRule::spartan_laws(),
+            load_error: None,
+            matches: None,
+            preview: None,
+            history: CodeHistory::default(),
+            solver,
+        }
+    }
+
+    /// Lays previews out with `solver` from now on.
+    pub(crate) fn set_solver(&mut self, solver: Solver) {
+        self.solver = solver;
+        self.preview = None;
+    }
+
+    fn set_rules(&mut self, theory: String, rules: Vec<RewriteRule<Spartan>>) {
+        self.theory = theory;
+        self.rules = rules;
+        self.load_error = None;
+        self.matches = None;
+        self.preview = None;
+    }
+
+    /// Replaces the rules with those of a theory file picked by the user.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_theory(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Theory", &["theory"])
+            .pick_file()
+        else {
+            return;
+        };
+        match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|source| Ok(RewriteRule::parse_theory(&source)?))
+        {
+            Ok(rules) => {
+                let name = path
+                    .file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+                    .into_owned();
+                self.set_rules(name, rules);
+            }
+            Err(err) => self.load_error = Some(format!("Failed to load theory: {err:#}")),
+        }
+    }
+
+    delegate! {
+        to self.history {
+            /// Records an edit of the code made other than by a rewrite, such as renaming a
+            /// variable, to undo it along with the rewrites.
+            pub(crate) fn record(&mut self, before: String, after: String);
+            /// Records the edits typed between compiles of the code, to undo them along with the
+            /// rewrites.
+            pub(crate) fn record_edit(&mut self, before: String, after: String);
+            pub(crate) fn can_undo(&self, code: &str) -> bool;
+            pub(crate) fn can_redo(&self, code: &str) -> bool;
+            pub(crate) fn undo(&mut self, code: &str) -> Option<String>;
+            pub(crate) fn redo(&mut self, code: &str) -> Option<String>;
+        }
+    }
+
+    /// Draws the panel for the program `expr` parsed from `code`.
+    /// Returns the code to replace it with if a rewrite was applied or undone.
+    pub(crate) fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        code: &str,
+        expr: Option<&Expr>,
+        labels: LabelVisibility,
+    ) -> Option<String> {
+        if let Some(expr) = expr {
+            if self
+                .matches
+                .as_ref()
+                .map_or(true, |(searched, _)| searched != expr)
+            {
+                self.matches = Some((expr.clone(), expr.matches(&self.rules)));
+                self.preview = None;
+            }
+        }
+
+        let mut edit = None;
+        egui::SidePanel::left("rewrite_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Rewrites");
+                if ui
+                    .add_enabled(self.history.can_undo(code), egui::Button::new("Undo"))
+                    .on_hover_text("Revert the last rewrite applied")
+                    .clicked()
+                {
+                    edit = self.history.undo(code);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.weak(format!("Rules from {}", self.theory));
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui
+                    .button("Load…")
+                    .on_hover_text("Use the rules of a theory file instead")
+                    .clicked()
+                {
+                    self.load_theory();
+                }
+                if self.theory != BUILT_IN && ui.button("Reset").clicked() {
+                    self.set_rules(BUILT_IN.to_owned(), RewriteRule::spartan_laws());
+                }
+            });
+            if let Some(error) = &self.load_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ui.separator();
+
+            let Some((expr, matches)) = &self.matches else {
+                ui.weak("Waiting for the program to parse");
+                return;
+            };
+            if matches.is_empty() {
+                ui.weak("No rules apply");
+            }
+            egui::ScrollArea::vertical()
+                .id_source("rewrites")
+                .show(ui, |ui| {
+                    for (index, rewrite) in matches.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.button("Apply").clicked() {
+                                if let Some(rewritten) = expr.rewrite(rewrite) {
+                                    let rewritten = rewritten.to_pretty();
+                                    self.history.record(code.to_owned(), rewritten.clone());
+                                    edit = Some(rewritten);
+                                }
+                            }
+                            ui.label(format!(
+                                "{}: {}",
+                                self.rules[rewrite.rule].name,
+                                rewrite.before.to_pretty()
+                            ))
+                            .on_hover_ui(|ui| {
+                                preview_ui(
+                                    ui,
+                                    &mut self.preview,
+                                    index,
+                                    rewrite,
+                                    self.solver,
+                                    labels,
+                                );
+                            });
+                        });
+                    }
+                });
+        });
+        edit
+    }
+}
+
+/// Shows the diagrams of a match before and after rewriting it, laying them out on first hover.
+fn preview_ui(
+    ui: &mut egui::Ui,
+    preview: &mut Option<Preview>,
+    index: usize,
+    rewrite: &Match<Spartan>,
+    solver: Solver,
+    labels: LabelVisibility,
+) {
+    if preview
+        .as_ref()
+        .map_or(true, |preview| preview.index != index)
+    {
+        *preview = Preview::new(index, rewrite, solver);
+    }
+    ui.label(format!(
+        "{} ⟶ {}",
+        rewrite.before.to_pretty(),
+        rewrite.after.to_pretty()
+    ));
+    let Some(preview) = preview else {
+        ui.weak("Cannot draw this rewrite");
+        return;
+    };
+    ui.horizontal(|ui| {
+        for graph_ui in [&mut preview.before, &mut preview.after] {
+            ui.allocate_ui(PREVIEW_SIZE, |ui| {
+                graph_ui.ui(ui, None, labels, &[], None, Overlays::default(), None);
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CodeHistory;
+
+    #[test]
+    fn typed_edits_undo_with_rewrites() {
+        let mut history = CodeHistory::default();
+        history.record("a".to_owned(), "b".to_owned());
+        // Compiling after the rewrite finds it already recorded
+        history.record_edit("a".to_owned(), "b".to_owned());
+        history.record_edit("b".to_owned(), "c".to_owned());
+
+        assert_eq!(history.undo("c").as_deref(), Some("b"));
+        // Compiling the code undone to records nothing, so it can still be redone
+        history.record_edit("c".to_owned(), "b".to_owned());
+        assert_eq!(history.undo("b").as_deref(), Some("a"));
+        assert_eq!(history.redo("a").as_deref(), Some("b"));
+        assert_eq!(history.redo("b").as_deref(), Some("c"));
+
+        // Nothing can be undone once the code has been edited again
+        assert_eq!(history.undo("d"), None);
+    }
+}
@@ -1,21 +1,88 @@
 #![allow(clippy::inline_always)]
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::anyhow;
 use delegate::delegate;
 use eframe::egui;
+#[cfg(not(target_arch = "wasm32"))]
+use eframe::egui::{Color32, Stroke};
+use indexmap::IndexSet;
+use poll_promise::Promise;
 use sd_core::{
     graph::SyntaxHypergraph,
-    interactive::InteractiveSubgraph,
+    hypergraph::{address::NodeAddress, generic::Node},
+    interactive::{InteractiveGraph, InteractiveSubgraph, Toggle},
     language::{chil::Chil, mlir::Mlir, spartan::Spartan, Expr, Language, Thunk},
     lp::Solver,
     prettyprinter::PrettyPrint,
 };
+use sd_graphics::common::LabelVisibility;
+#[cfg(not(target_arch = "wasm32"))]
+use sd_graphics::shape::{Shape, Shapes};
 
 use crate::{
     code_generator::generate_code,
     code_ui::code_ui,
-    graph_ui::{GraphUi, GraphUiInternal},
-    parser::UiLanguage,
+    files::OpenFile,
+    graph_ui::{GraphUi, GraphUiInternal, Overlays},
+    panzoom::Panzoom,
+    parser::{parse, ParseOutput, UiLanguage},
 };
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{graph_ui::Drawing, shape_generator::generate_shapes};
+
+/// A request to splice the edited code of a selection back into the main buffer.
+#[derive(Clone, Debug)]
+pub struct Promotion {
+    /// Code originally generated for the selection.
+    pub original: String,
+    /// Code the user has edited it to.
+    pub replacement: String,
+}
+
+/// What a selection window asks of the main window.
+pub(crate) enum SelectionRequest {
+    Promote(Promotion),
+    /// Open the code of the selection as a file of its own.
+    Open(OpenFile),
+}
+
+/// Languages whose selections can be recompiled from their (edited) code.
+pub trait SelectionLanguage: Language + 'static {
+    const UI_LANGUAGE: UiLanguage;
+
+    fn compile(code: &str) -> anyhow::Result<SyntaxHypergraph<Self>>;
+}
+
+macro_rules! selection_language {
+    ($language:ident, $variant:ident) => {
+        impl SelectionLanguage for $language {
+            const UI_LANGUAGE: UiLanguage = UiLanguage::$variant;
+
+            fn compile(code: &str) -> anyhow::Result<SyntaxHypergraph<Self>> {
+                match parse(code, Self::UI_LANGUAGE)? {
+                    ParseOutput::$variant(expr) => Ok(expr.to_graph(false)?),
+                    _ => Err(anyhow!("unexpected parse output")),
+                }
+            }
+        }
+    };
+}
+
+selection_language!(Chil, Chil);
+selection_language!(Mlir, Mlir);
+selection_language!(Spartan, Spartan);
 
 pub enum Selection {
     Chil(SelectionInternal<Chil>),
@@ -30,41 +97,128 @@ impl Selection {
             Self::Mlir(selection) => selection,
             Self::Spartan(selection) => selection,
         } {
-            pub(crate) fn ui(&mut self, ctx: &egui::Context);
+            pub(crate) fn ui(&mut self, ctx: &egui::Context, labels: LabelVisibility, source: &str) -> Option<SelectionRequest>;
             pub(crate) fn name(&self) -> &str;
+            pub(crate) fn rename(&mut self, name: String);
             pub(crate) fn displayed(&mut self) -> &mut bool;
+            pub(crate) fn addresses(&self) -> &[NodeAddress];
+            pub(crate) const fn view(&self) -> Option<Panzoom>;
+            pub(crate) fn set_view(&mut self, view: Panzoom);
         }
     }
 
     pub fn from_graph(graph_ui: &GraphUi, name: String, solver: Solver) -> Option<Self> {
+        let addresses = graph_ui
+            .toggles()
+            .into_iter()
+            .filter_map(|toggle| match toggle {
+                Toggle::Selected(address) => Some(address),
+                Toggle::Expanded(_) => None,
+            })
+            .collect();
         match graph_ui {
-            GraphUi::Chil(graph_ui) => Some(Self::Chil(SelectionInternal::new(
-                graph_ui.graph.to_subgraph(),
+            GraphUi::Chil(graph_ui) => Some(Self::Chil(SelectionInternal::from_graph(
+                &graph_ui.graph,
                 name,
+                addresses,
                 solver,
             ))),
-            GraphUi::Mlir(graph_ui) => Some(Self::Mlir(SelectionInternal::new(
-                graph_ui.graph.to_subgraph(),
+            GraphUi::Mlir(graph_ui) => Some(Self::Mlir(SelectionInternal::from_graph(
+                &graph_ui.graph,
                 name,
+                addresses,
                 solver,
             ))),
-            GraphUi::Spartan(graph_ui) => Some(Self::Spartan(SelectionInternal::new(
-                graph_ui.graph.to_subgraph(),
+            GraphUi::Spartan(graph_ui) => Some(Self::Spartan(SelectionInternal::from_graph(
+                &graph_ui.graph,
                 name,
+                addresses,
                 solver,
             ))),
             GraphUi::Dot(_) => None,
         }
     }
+
+    /// Selects the nodes at `addresses` in the graph of `graph_ui`, without touching the nodes
+    /// selected there, skipping addresses with no node.
+    pub(crate) fn from_addresses(
+        graph_ui: &GraphUi,
+        name: String,
+        addresses: &[NodeAddress],
+        solver: Solver,
+    ) -> Option<Self> {
+        macro_rules! select {
+            ($variant:ident, $graph_ui:expr) => {{
+                let mut graph = $graph_ui.graph.clone();
+                graph.clear_selection();
+                let addresses = addresses
+                    .iter()
+                    .filter(|address| graph.toggle(&Toggle::Selected((*address).clone())))
+                    .cloned()
+                    .collect();
+                Some(Self::$variant(SelectionInternal::from_graph(
+                    &graph, name, addresses, solver,
+                )))
+            }};
+        }
+
+        match graph_ui {
+            GraphUi::Chil(graph_ui) => select!(Chil, graph_ui),
+            GraphUi::Mlir(graph_ui) => select!(Mlir, graph_ui),
+            GraphUi::Spartan(graph_ui) => select!(Spartan, graph_ui),
+            GraphUi::Dot(_) => None,
+        }
+    }
+
+    /// A copy of the selection under `name`, drawn in `graph_ui`.
+    pub(crate) fn duplicate(
+        &self,
+        graph_ui: &GraphUi,
+        name: String,
+        solver: Solver,
+    ) -> Option<Self> {
+        Self::from_addresses(graph_ui, name, self.addresses(), solver)
+    }
 }
 
+/// Gives each selection window an id of its own, so that it keeps its place when renamed.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+type SubgraphPromise<T> = Promise<anyhow::Result<InteractiveSubgraph<SyntaxHypergraph<T>>>>;
+
+/// A selection together with the operations around it, and the set of those operations.
+type Context<T> = (
+    InteractiveSubgraph<SyntaxHypergraph<T>>,
+    IndexSet<Node<SyntaxHypergraph<T>>>,
+);
+
 pub struct SelectionInternal<T: Language> {
+    id: egui::Id,
     name: String,
+    /// The addresses of the selected nodes in the program, which outlive the program being
+    /// recompiled.
+    addresses: Vec<NodeAddress>,
     displayed: bool,
     graph_ui: GraphUiInternal<InteractiveSubgraph<SyntaxHypergraph<T>>>,
+    /// The code generated for the selection, once available.
+    generated: Option<String>,
+    /// The (possibly edited) code shown in the code pane.
+    code: String,
+    /// Pending recompilation of the edited code.
+    compile: Option<SubgraphPromise<T>>,
+    compile_error: Option<String>,
+    /// Whether the generated code is in the main buffer exactly once, so that it can be promoted,
+    /// together with a hash of the buffer this was worked out for.
+    promotable: Option<(u64, bool)>,
+    /// The selection with one ring of surrounding operations, while it matches the selection.
+    context: Option<Context<T>>,
+    /// Whether exports include the surrounding operations as stubs.
+    export_context: bool,
+    export_error: Option<String>,
+    solver: Solver,
 }
 
-impl<T: 'static + Language> SelectionInternal<T> {
+impl<T: SelectionLanguage> SelectionInternal<T> {
     pub(crate) fn new(
         subgraph: InteractiveSubgraph<SyntaxHypergraph<T>>,
         name: String,
@@ -73,36 +227,387 @@ impl<T: 'static + Language> SelectionInternal<T> {
         let graph_ui = GraphUiInternal::new(subgraph, solver);
 
         Self {
+            id: egui::Id::new(("selection", NEXT_ID.fetch_add(1, Ordering::Relaxed))),
             name,
+            addresses: Vec::new(),
             displayed: true,
             graph_ui,
+            generated: None,
+            code: String::new(),
+            compile: None,
+            compile_error: None,
+            promotable: None,
+            context: None,
+            export_context: false,
+            export_error: None,
+            solver,
         }
     }
 
+    /// The selection of `graph`, whose selected nodes are at `addresses`.
+    fn from_graph(
+        graph: &InteractiveGraph<SyntaxHypergraph<T>>,
+        name: String,
+        addresses: Vec<NodeAddress>,
+        solver: Solver,
+    ) -> Self {
+        let mut selection = Self::new(graph.to_subgraph(), name, solver)
+            .with_context(graph.to_subgraph_with_context());
+        selection.addresses = addresses;
+        selection
+    }
+
+    /// Use `context` when exporting the selection with its surroundings.
+    pub(crate) fn with_context(mut self, context: Context<T>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
     pub(crate) fn name(&self) -> &str {
         &self.name
     }
 
+    pub(crate) fn rename(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub(crate) fn displayed(&mut self) -> &mut bool {
         &mut self.displayed
     }
 
-    pub(crate) fn ui(&mut self, ctx: &egui::Context)
+    pub(crate) fn addresses(&self) -> &[NodeAddress] {
+        &self.addresses
+    }
+
+    /// The zoom and scroll of the window, to save with the selection, once it has been drawn.
+    pub(crate) const fn view(&self) -> Option<Panzoom> {
+        self.graph_ui.settled_panzoom()
+    }
+
+    /// Shows the selection at the zoom and scroll it was saved with, rather than fitting it to the
+    /// window.
+    pub(crate) fn set_view(&mut self, view: Panzoom) {
+        self.graph_ui.set_panzoom(view);
+    }
+
+    /// Whether the generated code is in `source` exactly once, so that there is no doubt which
+    /// code a promotion replaces.
+    fn promotable(&mut self, source: &str) -> bool {
+        let Some(generated) = &self.generated else {
+            return false;
+        };
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        let hash = hasher.finish();
+        match self.promotable {
+            Some((seen, promotable)) if seen == hash => promotable,
+            _ => {
+                let promotable = locate(source, generated).is_some();
+                self.promotable = Some((hash, promotable));
+                promotable
+            }
+        }
+    }
+
+    /// Draws the selection window, for a program whose code in the main buffer is `source`.
+    /// Returns a promotion if the user asked for the edited code to be spliced into the main
+    /// buffer, or a file if they asked for the selection to be opened in an editor of its own.
+    pub(crate) fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        labels: LabelVisibility,
+        source: &str,
+    ) -> Option<SelectionRequest>
     where
         Expr<T>: PrettyPrint,
         Thunk<T>: PrettyPrint,
     {
+        let mut request = None;
+
+        // Swap in the recompiled subgraph as soon as it is ready
+        if let Some(result) = self.compile.take_if(|promise| promise.ready().is_some()) {
+            match result.block_and_take() {
+                Ok(subgraph) => {
+                    // The window keeps its own viewport across recompilations
+                    let panzoom = self.graph_ui.panzoom();
+                    self.graph_ui = GraphUiInternal::new(subgraph, self.solver);
+                    self.graph_ui.set_panzoom(panzoom);
+                    self.compile_error = None;
+                    self.context = None;
+                }
+                Err(err) => self.compile_error = Some(err.to_string()),
+            }
+        }
+
+        let edited = self
+            .generated
+            .as_ref()
+            .is_some_and(|generated| *generated != self.code);
+        let promotable = edited && self.promotable(source);
+
         egui::Window::new(self.name.clone())
+            .id(self.id)
             .open(&mut self.displayed)
             .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let promote = ui
+                        .add_enabled(promotable, egui::Button::new("Promote"))
+                        .on_hover_text("Replace the selection in the main buffer with this code");
+                    let promote = if edited {
+                        promote.on_disabled_hover_text(
+                            "The code of the selection is not in the main buffer exactly once, so \
+                             there is no telling what it would replace",
+                        )
+                    } else {
+                        promote
+                    };
+                    if promote.clicked() {
+                        request = self.generated.clone().map(|original| {
+                            SelectionRequest::Promote(Promotion {
+                                original,
+                                replacement: self.code.clone(),
+                            })
+                        });
+                    }
+                    if ui
+                        .add_enabled(edited, egui::Button::new("Revert"))
+                        .clicked()
+                    {
+                        if let Some(generated) = &self.generated {
+                            self.code.clone_from(generated);
+                            self.compile = Some(spawn_compile::<T>(self.code.clone(), ctx));
+                        }
+                    }
+                    if ui
+                        .button("Open")
+                        .on_hover_text("Edit the selection as a program of its own")
+                        .clicked()
+                    {
+                        match self.graph_ui.graph.decompile() {
+                            Ok(expr) => {
+                                request = Some(SelectionRequest::Open(OpenFile {
+                                    name: format!("{}.{}", self.name, T::UI_LANGUAGE.extension()),
+                                    code: expr.to_pretty(),
+                                    language: T::UI_LANGUAGE,
+                                }));
+                            }
+                            Err(err) => {
+                                self.export_error = Some(format!("Failed to decompile: {err}"));
+                            }
+                        }
+                    }
+                    ui.separator();
+                    if ui.button("+").on_hover_text("Zoom in").clicked() {
+                        self.graph_ui.zoom_in();
+                    }
+                    if ui.button("−").on_hover_text("Zoom out").clicked() {
+                        self.graph_ui.zoom_out();
+                    }
+                    if ui.button("Fit").on_hover_text("Reset the view").clicked() {
+                        self.graph_ui.reset();
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        ui.separator();
+                        if ui.button("Export SVG…").clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("SVG", &["svg"])
+                                .set_file_name(format!("{}.svg", self.name))
+                                .save_file()
+                            {
+                                let context = self.context.as_ref().filter(|_| self.export_context);
+                                self.export_error =
+                                    export_svg(&self.graph_ui, context, self.solver, &path)
+                                        .err()
+                                        .map(|err| format!("Failed to export svg: {err}"));
+                            }
+                        }
+                        if let Some((graph, _)) = &self.context {
+                            if ui
+                                .checkbox(&mut self.export_context, "With context")
+                                .on_hover_text(
+                                    "Include the operations around the selection as greyed stubs",
+                                )
+                                .changed()
+                                && self.export_context
+                            {
+                                // Start laying out the context so it is ready by the time it is exported
+                                generate_shapes(graph, self.solver, None);
+                            }
+                        }
+                    }
+                    if let Some(error) = self.compile_error.as_ref().or(self.export_error.as_ref())
+                    {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                });
+
                 ui.columns(2, |columns| {
-                    let code = generate_code(&self.graph_ui.graph);
-                    let guard = code.lock().unwrap();
-                    if let Some(code) = guard.ready() {
-                        code_ui(&mut columns[0], &mut code.as_str(), UiLanguage::Spartan);
+                    if self.generated.is_none() {
+                        let code = generate_code(&self.graph_ui.graph);
+                        let guard = code.lock().unwrap();
+                        if let Some(code) = guard.ready() {
+                            self.code.clone_from(code);
+                            self.generated = Some(code.clone());
+                        }
                     }
-                    self.graph_ui.ui(&mut columns[1], None);
+                    if self.generated.is_some() {
+                        let output = code_ui(&mut columns[0], &mut self.code, T::UI_LANGUAGE);
+                        if output.response.changed() {
+                            self.compile = Some(spawn_compile::<T>(self.code.clone(), ctx));
+                        }
+                    }
+                    self.graph_ui.ui(
+                        &mut columns[1],
+                        None,
+                        labels,
+                        &[],
+                        None,
+                        Overlays::default(),
+                        None,
+                    );
                 });
             });
+
+        request
+    }
+}
+
+/// Streams a selection to an svg file at `path`, surrounded by `context` drawn as greyed stubs.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_svg<T: SelectionLanguage>(
+    graph_ui: &GraphUiInternal<InteractiveSubgraph<SyntaxHypergraph<T>>>,
+    context: Option<&Context<T>>,
+    solver: Solver,
+    path: &Path,
+) -> io::Result<()> {
+    let Some((graph, stubs)) = context else {
+        return graph_ui
+            .export_drawing(path.to_owned(), Drawing::Svg { stylesheet: None })
+            .block_and_take();
+    };
+
+    let shapes = generate_shapes(graph, solver, None);
+    let guard = shapes.lock().unwrap();
+    let shapes = guard.block_until_ready();
+    let greyed = shapes
+        .shapes
+        .iter()
+        .cloned()
+        .map(|mut shape| {
+            if let Shape::Operation {
+                addr, fill, stroke, ..
+            } = &mut shape
+            {
+                if stubs.contains(&addr.inner().clone().into_inner()) {
+                    *fill = Some(Color32::LIGHT_GRAY);
+                    *stroke = Some(Stroke::new(1.0, Color32::GRAY));
+                }
+            }
+            shape
+        })
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    Shapes::new(greyed, shapes.size).write_svg(&mut writer, None)?;
+    writer.flush()
+}
+
+/// Recompiles edited selection code into a subgraph view containing the whole of the result.
+fn spawn_compile<T: SelectionLanguage>(code: String, ctx: &egui::Context) -> SubgraphPromise<T> {
+    let ctx = ctx.clone();
+    crate::spawn!("selection_compile", {
+        let mut graph = InteractiveGraph::new(T::compile(&code)?);
+        graph.select_all();
+        ctx.request_repaint();
+        Ok(graph.to_subgraph())
+    })
+}
+
+/// Finds the byte range of `fragment` in `source`, ignoring differences in whitespace, unless it
+/// is not there or is there more than once.
+pub(crate) fn locate(source: &str, fragment: &str) -> Option<Range<usize>> {
+    let needle: Vec<char> = fragment.chars().filter(|c| !c.is_whitespace()).collect();
+    if needle.is_empty() {
+        return None;
+    }
+
+    let haystack: Vec<(usize, char)> = source
+        .char_indices()
+        .filter(|(_, c)| !c.is_whitespace())
+        .collect();
+
+    let mut matches = haystack
+        .windows(needle.len())
+        .filter(|window| window.iter().map(|(_, c)| *c).eq(needle.iter().copied()))
+        .map(|window| {
+            let (start, _) = window[0];
+            let (end, c) = window[window.len() - 1];
+            start..end + c.len_utf8()
+        });
+    let span = matches.next()?;
+    matches.next().is_none().then_some(span)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::fs;
+
+    use sd_core::{
+        hypergraph::address::NodeAddress,
+        interactive::{InteractiveGraph, Toggle},
+        language::spartan::Spartan,
+        lp::Solver,
+    };
+
+    use super::{export_svg, locate, SelectionInternal, SelectionLanguage};
+
+    #[test]
+    fn exports_partial_selections() -> anyhow::Result<()> {
+        let mut graph =
+            InteractiveGraph::new(Spartan::compile("bind x = plus(a, b) in times(x, x)")?);
+        let address: NodeAddress = "plus".parse()?;
+        assert!(graph.toggle(&Toggle::Selected(address.clone())));
+        // The wire from `plus` to `times` leaves the selection, so is drawn with an arrow
+        let selection = SelectionInternal::from_graph(
+            &graph,
+            "plus".to_owned(),
+            vec![address],
+            Solver::default(),
+        );
+
+        let path = std::env::temp_dir().join(format!("sd-selection-{}.svg", std::process::id()));
+        let without_context = export_svg(&selection.graph_ui, None, selection.solver, &path)
+            .and_then(|()| fs::read_to_string(&path));
+        let with_context = export_svg(
+            &selection.graph_ui,
+            selection.context.as_ref(),
+            selection.solver,
+            &path,
+        )
+        .and_then(|()| fs::read_to_string(&path));
+        let _ = fs::remove_file(&path);
+
+        let (without_context, with_context) = (without_context?, with_context?);
+        assert!(without_context.contains("op-plus"));
+        assert!(!without_context.contains("op-times"));
+        assert!(with_context.contains("op-plus"));
+        assert!(with_context.contains("op-times"));
+        Ok(())
+    }
+
+    #[test]
+    fn locates_unique_fragments() {
+        let source = "bind x = plus(a, b) in\nbind y = times(x,  x) in\ntimes(y, y)";
+        assert_eq!(
+            locate(source, "times(x, x)").map(|span| &source[span]),
+            Some("times(x,  x)")
+        );
+        // Not there, such as the outputs added to the code of a partial selection
+        assert_eq!(locate(source, "bind x = plus(a, b) in x"), None);
+        assert_eq!(locate(source, "times(y, y)"), Some(48..59));
+        // There twice, so either could be meant
+        assert_eq!(locate(source, "times("), None);
+        assert_eq!(locate(source, ""), None);
     }
 }
@@ -0,0 +1,168 @@
+//! The program, the view of it, the saved selections, the theme and whether the tour has been seen,
+//! stored by eframe when the application closes (and every so often while it runs) so that the
+//! next launch picks up where this one left off. Natively eframe keeps them in a file in the data directory, while on the web they go in
+//! local storage.
+
+use eframe::egui::Pos2;
+use sd_core::hypergraph::address::NodeAddress;
+use sd_graphics::theme::Theme;
+
+use crate::{panzoom::Panzoom, theme, view_script::ViewScript};
+
+const CODE: &str = "session-code";
+const VIEW: &str = "session-view";
+const SELECTIONS: &str = "session-selections";
+const THEME: &str = "session-theme";
+const TOUR_SEEN: &str = "session-tour-seen";
+
+/// A saved selection: its name, the addresses of its nodes, and the zoom and scroll of its window
+/// once it has been drawn.
+pub(crate) type SavedSelection = (String, Vec<NodeAddress>, Option<Panzoom>);
+
+pub(crate) fn save(
+    storage: &mut dyn eframe::Storage,
+    code: &str,
+    view: &ViewScript,
+    selections: &[SavedSelection],
+    theme: &Theme,
+    tour_seen: bool,
+) {
+    storage.set_string(CODE, code.to_owned());
+    storage.set_string(VIEW, view.to_string());
+    storage.set_string(SELECTIONS, write_selections(selections));
+    storage.set_string(THEME, theme::write(theme));
+    storage.set_string(TOUR_SEEN, tour_seen.to_string());
+}
+
+/// The program, view and selections saved by the last session, unless there was no program in
+/// the editor.
+pub(crate) fn load(
+    storage: &dyn eframe::Storage,
+) -> Option<(String, ViewScript, Vec<SavedSelection>)> {
+    let code = storage
+        .get_string(CODE)
+        .filter(|code| !code.trim().is_empty())?;
+    let view = storage
+        .get_string(VIEW)
+        .and_then(|view| {
+            view.parse::<ViewScript>()
+                .map_err(|err| tracing::warn!("Failed to restore the view of the session: {err}"))
+                .ok()
+        })
+        .unwrap_or_default();
+    let selections = storage
+        .get_string(SELECTIONS)
+        .map(|selections| read_selections(&selections))
+        .unwrap_or_default();
+    Some((code, view, selections))
+}
+
+/// The theme of the last session, which is kept even when there was no program in the editor.
+pub(crate) fn load_theme(storage: &dyn eframe::Storage) -> Theme {
+    storage
+        .get_string(THEME)
+        .and_then(|source| {
+            theme::parse(&source)
+                .map_err(|err| {
+                    tracing::warn!("Failed to restore the theme of the session: {err:#}")
+                })
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether the tour was finished or skipped in an earlier session.
+pub(crate) fn load_tour_seen(storage: &dyn eframe::Storage) -> bool {
+    storage
+        .get_string(TOUR_SEEN)
+        .is_some_and(|seen| seen == "true")
+}
+
+/// Writes each selection as a line `selection NAME`, then `view X Y ZOOM` if it has a view,
+/// followed by the addresses of its nodes, one to a line and indented.
+fn write_selections(selections: &[SavedSelection]) -> String {
+    let mut out = String::new();
+    for (name, addresses, view) in selections {
+        out.push_str(&format!("selection {}\n", name.replace('\n', " ")));
+        if let Some(view) = view {
+            let translation = view.translation();
+            out.push_str(&format!(
+                "view {} {} {}\n",
+                translation.x,
+                translation.y,
+                view.zoom_factor()
+            ));
+        }
+        for address in addresses {
+            out.push_str(&format!("  {address}\n"));
+        }
+    }
+    out
+}
+
+/// Reads the selections written by [`write_selections`], skipping addresses and views which do not
+/// parse.
+fn read_selections(s: &str) -> Vec<SavedSelection> {
+    let mut selections: Vec<SavedSelection> = Vec::new();
+    for line in s.lines().filter(|line| !line.trim().is_empty()) {
+        if let Some(name) = line.strip_prefix("selection ") {
+            selections.push((name.to_owned(), Vec::new(), None));
+        } else if let Some(view) = line.strip_prefix("view ") {
+            if let Some((_, _, saved)) = selections.last_mut() {
+                *saved = read_view(view);
+            }
+        } else if let Some((_, addresses, _)) = selections.last_mut() {
+            match line.trim().parse() {
+                Ok(address) => addresses.push(address),
+                Err(err) => tracing::warn!("Failed to restore a selection of the session: {err}"),
+            }
+        }
+    }
+    selections
+}
+
+/// Reads a view written as `X Y ZOOM`.
+fn read_view(s: &str) -> Option<Panzoom> {
+    let mut numbers = s.split_whitespace().map(str::parse::<f32>);
+    let (Some(Ok(x)), Some(Ok(y)), Some(Ok(zoom)), None) = (
+        numbers.next(),
+        numbers.next(),
+        numbers.next(),
+        numbers.next(),
+    ) else {
+        tracing::warn!("Failed to restore the view of a selection of the session: `{s}`");
+        return None;
+    };
+    Some(Panzoom::new(Pos2::new(x, y), zoom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_selections, write_selections};
+    use crate::panzoom::Panzoom;
+
+    #[test]
+    fn selections_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let selections = vec![
+            (
+                "zoomed".to_owned(),
+                vec!["%3/add#1".parse()?],
+                Some(Panzoom::new(eframe::egui::pos2(1.5, -2.0), 120.0)),
+            ),
+            ("unseen".to_owned(), vec!["mul".parse()?], None),
+        ];
+        let read = read_selections(&write_selections(&selections));
+        assert_eq!(read.len(), 2);
+        for ((name, addresses, view), (expected_name, expected_addresses, expected_view)) in
+            read.iter().zip(&selections)
+        {
+            assert_eq!(name, expected_name);
+            assert_eq!(addresses, expected_addresses);
+            assert_eq!(
+                view.map(|view| (view.translation(), view.zoom_factor())),
+                expected_view.map(|view| (view.translation(), view.zoom_factor()))
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Settings read from a file in the platform configuration directory, for options too fiddly to
+//! set from the menus, such as which MLIR dialects to filter out of imported graphs.
+//!
+//! The file has one `key = value` setting per line, with `#` starting a comment:
+//!
+//! ```text
+//! mlir.link_symbols = true
+//! mlir.drop = llvm.intr.dbg, llvm.metadata
+//! mlir.collapse = tosa
+//! extensions.chil = ir, dump
+//! ```
+//!
+//! where `extensions.LANGUAGE` lists extensions to open as files in the language with the usual
+//! extension `LANGUAGE`.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use sd_core::language::mlir::MlirSettings;
+
+use crate::parser::UiLanguage;
+
+/// The platform configuration directory for the application.
+fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?
+    };
+    Some(base.join("sd-visualiser"))
+}
+
+/// The settings in the settings file, with the defaults for any it does not set.
+#[derive(Default)]
+pub(crate) struct Settings {
+    pub(crate) mlir: MlirSettings,
+    /// Extensions of files in each language, besides the usual ones.
+    pub(crate) extensions: HashMap<String, UiLanguage>,
+}
+
+/// Loads the settings file.
+pub(crate) fn load() -> Settings {
+    let mut settings = Settings::default();
+    let Some(path) = config_dir().map(|dir| dir.join("settings")) else {
+        return settings;
+    };
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return settings;
+    };
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            tracing::warn!("{}:{}: expected `key = value`", path.display(), number + 1);
+            continue;
+        };
+        let value = value.trim();
+        let list = || {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|prefix| !prefix.is_empty())
+                .map(ToOwned::to_owned)
+        };
+        match key.trim() {
+            "mlir.link_symbols" => match value.parse() {
+                Ok(link) => settings.mlir.sym_name_linking = link,
+                Err(err) => tracing::warn!("{}:{}: {err}", path.display(), number + 1),
+            },
+            "mlir.drop" => settings.mlir.filter.drop.extend(list()),
+            "mlir.collapse" => settings.mlir.filter.collapse.extend(list()),
+            key => match key
+                .strip_prefix("extensions.")
+                .and_then(UiLanguage::from_extension)
+            {
+                Some(language) => settings.extensions.extend(
+                    list()
+                        .map(|extension| (extension.trim_start_matches('.').to_owned(), language)),
+                ),
+                None => {
+                    tracing::warn!("{}:{}: unknown setting `{key}`", path.display(), number + 1);
+                }
+            },
+        }
+    }
+    settings
+}
+
+/// Loads the MLIR settings from the settings file, using the defaults for any it does not set.
+pub(crate) fn load_mlir() -> MlirSettings {
+    load().mlir
+}
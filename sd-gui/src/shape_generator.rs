@@ -1,35 +1,80 @@
 use std::{
     fmt::Display,
-    sync::{Arc, Mutex, OnceLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock, Weak,
+    },
+    time::Duration,
 };
 
-use eframe::egui::{util::IdTypeMap, Id};
+use eframe::egui::{util::IdTypeMap, Id, Vec2};
 use lru::LruCache;
 use poll_promise::Promise;
 use sd_core::{
     hypergraph::{
-        generic::{Edge, Key, Operation, Weight},
+        generic::{Edge, Key, Node, Operation, Weight},
         subgraph::ExtensibleEdge,
-        traits::Graph,
+        traits::{Graph, NodeLike},
+    },
+    lp::{Budget, CancellationToken, LpError, SolutionLog, Solver},
+    monoidal::{
+        graph::MonoidalGraph,
+        wired_graph::{from_graph, from_graph_with_progress},
+        Simplification,
     },
-    lp::Solver,
-    monoidal::{graph::MonoidalGraph, wired_graph::from_graph},
 };
-use sd_graphics::{common::Shapeable, layout::layout, render, shape::Shapes};
+use sd_graphics::{
+    common::Shapeable,
+    crossings::hotspots,
+    layout::{draft_layout, layout, LayoutError},
+    render,
+    shape::Shapes,
+};
+use web_time::Instant;
+
+use crate::{
+    disk_cache::{self, CacheKey},
+    progress,
+};
 
 static CACHE: OnceLock<Mutex<IdTypeMap>> = OnceLock::new();
 
+/// Cancels the solves of the shapes in the cache when it is cleared.
+static CANCEL: OnceLock<Mutex<CancellationToken>> = OnceLock::new();
+
+/// How far graphs are simplified when they are laid out, for the rest of the session.
+static SIMPLIFICATION: Mutex<Simplification> = Mutex::new(Simplification::HideIdentities);
+
+/// Failures of the solver in layouts made since they were last taken, to show to the user.
+static SOLVER_FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Whether layouts are solved in full however long they take, rather than within
+/// [`SOLVER_BUDGET`] and heuristically past [`EXACT_LIMIT`].
+static UNBOUNDED: AtomicBool = AtomicBool::new(false);
+
+/// How long the problems of a layout may be solved for altogether before falling back to a
+/// cheaper layout.
+const SOLVER_BUDGET: Duration = Duration::from_secs(5);
+
+/// The most variables, as counted by [`estimated_variables`], for which layouts are solved
+/// straight away. Solving bigger graphs tends to run past [`SOLVER_BUDGET`], after which the
+/// fallback solver can take minutes, so they are laid out heuristically until the user asks for
+/// the layout to be solved.
+const EXACT_LIMIT: usize = 5000;
+
 type Cache<G> = LruCache<Key<G>, Arc<Mutex<Promise<Shapes<<G as Graph>::Ctx>>>>>;
 
-fn shape_cache<G>() -> Arc<Mutex<Cache<G>>>
+/// The cache of solved layouts, or of drafts if `draft` is set.
+fn shape_cache<G>(draft: bool) -> Arc<Mutex<Cache<G>>>
 where
     G: Graph + 'static,
 {
+    let id = if draft { Id::new("draft") } else { Id::NULL };
     CACHE
         .get_or_init(Mutex::default)
         .lock()
         .unwrap()
-        .get_temp_mut_or_insert_with::<Arc<Mutex<Cache<G>>>>(Id::NULL, || {
+        .get_temp_mut_or_insert_with::<Arc<Mutex<Cache<G>>>>(id, || {
             tracing::trace!("initialise shape cache");
             Arc::new(Mutex::new(LruCache::unbounded()))
         })
@@ -40,39 +85,266 @@ pub fn clear_shape_cache() {
     if let Some(cache) = CACHE.get() {
         cache.lock().unwrap().clear();
     }
+    if let Some(cancel) = CANCEL.get() {
+        std::mem::take(&mut *cancel.lock().unwrap()).cancel();
+    }
+}
+
+/// The level graphs are simplified to when they are laid out.
+pub fn simplification() -> Simplification {
+    *SIMPLIFICATION.lock().unwrap()
+}
+
+/// Lays graphs out again, simplified to `simplification`.
+pub fn set_simplification(simplification: Simplification) {
+    let previous = std::mem::replace(&mut *SIMPLIFICATION.lock().unwrap(), simplification);
+    if previous != simplification {
+        clear_shape_cache();
+    }
+}
+
+/// The failures of the solver since this was last called. Layouts recover from them by falling
+/// back to another solver, or to a cheaper layout.
+pub fn take_solver_failures() -> Vec<String> {
+    std::mem::take(&mut *SOLVER_FAILURES.lock().unwrap())
+}
+
+/// Solves layouts in full however big the graph is and however long the solver takes, for when
+/// they are made without a window to keep responsive.
+pub fn set_unbounded(unbounded: bool) {
+    UNBOUNDED.store(unbounded, Ordering::Relaxed);
 }
 
-pub fn generate_shapes<G>(graph: &G, solver: Solver) -> Arc<Mutex<Promise<Shapes<G::Ctx>>>>
+fn cancellation_token() -> CancellationToken {
+    CANCEL.get_or_init(Mutex::default).lock().unwrap().clone()
+}
+
+/// The shapes of the graph with `key`, if they have already been requested.
+pub fn cached_shapes<G>(key: &Key<G>) -> Option<Arc<Mutex<Promise<Shapes<G::Ctx>>>>>
+where
+    G: Graph + 'static,
+{
+    shape_cache::<G>(false).lock().unwrap().peek(key).cloned()
+}
+
+pub fn generate_shapes<G>(
+    graph: &G,
+    solver: Solver,
+    cache_key: Option<CacheKey>,
+) -> Arc<Mutex<Promise<Shapes<G::Ctx>>>>
 where
     G: Graph + 'static,
     Edge<G::Ctx>: ExtensibleEdge,
     Operation<G::Ctx>: Shapeable,
     Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
 {
-    let cache = shape_cache::<G>();
+    let cache = shape_cache::<G>(false);
     let mut guard = cache.lock().unwrap();
     guard
         .get_or_insert(graph.key(), || {
-            let graph = graph.clone();
-            Arc::new(Mutex::new(crate::spawn!("shape", {
-                tracing::info!("Converting to monoidal term");
-                let monoidal_term = from_graph(&graph, solver);
-                tracing::debug!("Got term {:#?}", monoidal_term);
-
-                tracing::info!("Inserting swaps and copies");
-                let monoidal_graph = Arc::new(MonoidalGraph::from(&monoidal_term));
-                tracing::debug!("Got graph {:#?}", monoidal_graph);
-
-                tracing::info!("Calculating layout...");
-                let layout = layout(&monoidal_graph, solver).unwrap();
-                tracing::info!("Calculating shapes...");
-                let mut shapes = Vec::new();
-                render::generate_shapes(&mut shapes, &layout, true);
-                tracing::debug!("Generated {} shapes...", shapes.len());
-                Shapes {
-                    shapes,
-                    size: layout.size(),
+            spawn_shapes(graph, solver, cache_key, false)
+        })
+        .clone()
+}
+
+/// Lays `graph` out again by solving its layout, replacing a heuristic layout made because it
+/// looked too big to solve quickly.
+pub fn generate_exact_shapes<G>(
+    graph: &G,
+    solver: Solver,
+    cache_key: Option<CacheKey>,
+) -> Arc<Mutex<Promise<Shapes<G::Ctx>>>>
+where
+    G: Graph + 'static,
+    Edge<G::Ctx>: ExtensibleEdge,
+    Operation<G::Ctx>: Shapeable,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
+{
+    let shapes = spawn_shapes(graph, solver, cache_key, true);
+    shape_cache::<G>(false)
+        .lock()
+        .unwrap()
+        .put(graph.key(), shapes.clone());
+    shapes
+}
+
+/// A rough count of the variables of the layout problems of `graph`: one for each node and one
+/// for each end of each wire, including those inside thunks.
+fn estimated_variables<G: Graph>(graph: &G) -> usize {
+    graph
+        .nodes()
+        .map(|node| {
+            let inner = match &node {
+                Node::Operation(_) => 0,
+                Node::Thunk(thunk) => estimated_variables(thunk),
+            };
+            1 + node.inputs().count() + node.outputs().count() + inner
+        })
+        .sum()
+}
+
+/// Starts laying out `graph`, solving the layout if `exact` is set or it looks small enough to
+/// solve quickly, and otherwise laying it out heuristically.
+fn spawn_shapes<G>(
+    graph: &G,
+    solver: Solver,
+    cache_key: Option<CacheKey>,
+    exact: bool,
+) -> Arc<Mutex<Promise<Shapes<G::Ctx>>>>
+where
+    G: Graph + 'static,
+    Edge<G::Ctx>: ExtensibleEdge,
+    Operation<G::Ctx>: Shapeable,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
+{
+    let graph = graph.clone();
+    let unbounded = UNBOUNDED.load(Ordering::Relaxed);
+    let exact = exact || unbounded;
+    let budget = if unbounded {
+        Budget::unlimited(cancellation_token())
+    } else {
+        Budget::new(SOLVER_BUDGET, cancellation_token())
+    };
+    let simplification = simplification();
+    // Stored solutions are only for the problems of the default level
+    let cache_key = cache_key.filter(|_| simplification == Simplification::default());
+    Arc::new_cyclic(|this: &Weak<Mutex<Promise<Shapes<G::Ctx>>>>| {
+        let this = this.clone();
+        Mutex::new(crate::spawn!("shape", {
+            let start = Instant::now();
+            let solutions = cache_key.and_then(disk_cache::load).unwrap_or_default();
+            // Stored solutions are replayed quickly however big the graph is
+            if !exact && solutions.is_empty() && estimated_variables(&graph) > EXACT_LIMIT {
+                tracing::info!(
+                    "Laying out heuristically, as the graph is too big to solve quickly"
+                );
+                let mut shapes = heuristic_shapes(&graph, solver);
+                shapes.heuristic = true;
+                shapes.layout_time = start.elapsed();
+                shapes.simplification = simplification;
+                return shapes;
+            }
+            let ((layout, budget), log) = solutions.record(|| {
+                budget.run(|| {
+                    tracing::info!("Converting to monoidal term");
+                    let monoidal_term = from_graph_with_progress(
+                        &graph,
+                        solver,
+                        simplification,
+                        progress::reporter("Converting to monoidal term"),
+                    );
+                    tracing::debug!("Got term {:#?}", monoidal_term);
+
+                    tracing::info!("Inserting swaps and copies");
+                    let monoidal_graph =
+                        Arc::new(MonoidalGraph::from_wired(&monoidal_term, simplification));
+                    tracing::debug!("Got graph {:#?}", monoidal_graph);
+
+                    tracing::info!("Calculating layout...");
+                    layout(&monoidal_graph, solver)
+                })
+            });
+            SOLVER_FAILURES
+                .lock()
+                .unwrap()
+                .extend(budget.failures().iter().cloned());
+            let layout = match layout {
+                Ok(layout) => layout,
+                Err(LayoutError::LpError(LpError::Cancelled)) => {
+                    // Laid out afresh if the graph is asked for again
+                    tracing::info!("Abandoned layout");
+                    evict::<G>(&graph.key(), &this);
+                    return Shapes::new(Vec::new(), Vec2::ZERO);
+                }
+                Err(err) => {
+                    SOLVER_FAILURES
+                        .lock()
+                        .unwrap()
+                        .push(format!("Failed to lay out the graph: {err}"));
+                    let mut shapes = Shapes::new(Vec::new(), Vec2::ZERO);
+                    shapes.error = Some(err.to_string());
+                    return shapes;
                 }
+            };
+            // Approximate layouts are worth solving properly next time
+            if let Some(cache_key) = cache_key.filter(|_| log.is_dirty() && !budget.is_exceeded()) {
+                disk_cache::store(cache_key, &log);
+            }
+
+            tracing::info!("Calculating shapes...");
+            let mut shapes = Vec::new();
+            render::generate_shapes(&mut shapes, &layout, true);
+            tracing::debug!("Generated {} shapes...", shapes.len());
+            let mut shapes = Shapes::new(shapes, layout.size());
+            shapes.approximate = budget.is_exceeded();
+            shapes.layout_time = start.elapsed();
+            shapes.hotspots = hotspots(&layout);
+            shapes.simplification = simplification;
+            shapes
+        }))
+    })
+}
+
+/// Removes the shapes of the graph with `key` from the cache, if they are still `shapes`.
+fn evict<G>(key: &Key<G>, shapes: &Weak<Mutex<Promise<Shapes<G::Ctx>>>>)
+where
+    G: Graph + 'static,
+{
+    let cache = shape_cache::<G>(false);
+    let mut cache = cache.lock().unwrap();
+    if cache
+        .peek(key)
+        .is_some_and(|cached| std::ptr::eq(Arc::as_ptr(cached), shapes.as_ptr()))
+    {
+        cache.pop(key);
+    }
+}
+
+/// Lays `graph` out without solving anything, layering it by rank and placing the nodes of each
+/// layer side by side.
+fn heuristic_shapes<G>(graph: &G, solver: Solver) -> Shapes<G::Ctx>
+where
+    G: Graph + 'static,
+    Edge<G::Ctx>: ExtensibleEdge,
+    Operation<G::Ctx>: Shapeable,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
+{
+    // With no time to solve in, the monoidal term falls back to a layering by rank
+    let budget = Budget::new(Duration::ZERO, cancellation_token());
+    let (layout, _) = budget.run(|| {
+        let monoidal_term = from_graph(graph, solver);
+        draft_layout(&MonoidalGraph::from(&monoidal_term))
+    });
+    let mut shapes = Vec::new();
+    render::generate_shapes(&mut shapes, &layout, true);
+    let mut shapes = Shapes::new(shapes, layout.size());
+    shapes.hotspots = hotspots(&layout);
+    shapes
+}
+
+/// The shapes of a draft layout of `graph`, which is quick to make as nothing is solved, to show
+/// while the shapes from [`generate_shapes`] are being laid out.
+pub fn generate_draft_shapes<G>(graph: &G, solver: Solver) -> Arc<Mutex<Promise<Shapes<G::Ctx>>>>
+where
+    G: Graph + 'static,
+    Edge<G::Ctx>: ExtensibleEdge,
+    Operation<G::Ctx>: Shapeable,
+    Weight<Operation<G::Ctx>>: Display,
+    Weight<Edge<G::Ctx>>: Display,
+{
+    let cache = shape_cache::<G>(true);
+    let mut guard = cache.lock().unwrap();
+    guard
+        .get_or_insert(graph.key(), || {
+            let graph = graph.clone();
+            Arc::new(Mutex::new(crate::spawn!("draft_shape", {
+                let mut shapes = heuristic_shapes(&graph, solver);
+                shapes.draft = true;
+                shapes
             })))
         })
         .clone()
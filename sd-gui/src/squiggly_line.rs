@@ -6,6 +6,15 @@ use pest::{
     error::{Error, LineColLocation},
     RuleType,
 };
+use sd_core::{
+    common::Span,
+    graph::ConvertError,
+    language::{
+        chil::{self, Chil},
+        mlir::Mlir,
+        Language,
+    },
+};
 
 fn is_in_line(cursor: usize, line_col: &LineColLocation) -> bool {
     // Pest lines are 1 indexed, egui are 0 ☹
@@ -23,8 +32,61 @@ fn lines_contained(line_col: &LineColLocation) -> Vec<usize> {
 }
 
 pub fn show_parse_error(ui: &egui::Ui, err: &Error<impl RuleType>, text_edit_out: &TextEditOutput) {
+    show_error_at(ui, &err.line_col, &err.to_string(), text_edit_out);
+}
+
+/// Squiggles the line of an error from the hand-written Chil parser, which only knows the line
+/// and column it stopped at.
+pub fn show_chil_parse_error(
+    ui: &egui::Ui,
+    err: &chil::parser::ParseError,
+    text_edit_out: &TextEditOutput,
+) {
+    // Lines and columns count from 1, as in pest
+    let line_col = LineColLocation::Pos((err.line, err.column));
+    show_error_at(ui, &line_col, &err.to_string(), text_edit_out);
+}
+
+/// Squiggles the operations of the cyclic definitions which stopped the program from compiling,
+/// for the languages which keep track of where operations were written.
+pub fn show_compile_error(ui: &egui::Ui, err: &anyhow::Error, text_edit_out: &TextEditOutput) {
+    fn cycle<T: Language + 'static>(err: &anyhow::Error) -> Option<&[Span]> {
+        match err.downcast_ref::<ConvertError<T>>()? {
+            ConvertError::Cycle(_, spans) => Some(spans),
+            _ => None,
+        }
+    }
+
+    let Some(spans) = cycle::<Chil>(err).or_else(|| cycle::<Mlir>(err)) else {
+        return;
+    };
+    let text = text_edit_out.galley.text();
+    let message = err.to_string();
+    for span in spans {
+        let Some(before) = text.get(..span.start) else {
+            continue;
+        };
+        // Lines and columns count from 1, as in pest
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        show_error_at(
+            ui,
+            &LineColLocation::Pos((line, column)),
+            &message,
+            text_edit_out,
+        );
+    }
+}
+
+/// Squiggles the lines at `line_col`, showing `message` when they are hovered over.
+fn show_error_at(
+    ui: &egui::Ui,
+    line_col: &LineColLocation,
+    message: &str,
+    text_edit_out: &TextEditOutput,
+) {
     let painter = ui.painter();
-    for l in lines_contained(&err.line_col) {
+    for l in lines_contained(line_col) {
         if let Some(row) = text_edit_out.galley.rows.get(l) {
             // Draw squiggly line under error line
             const SQUIGGLE_HEIGHT: f32 = 5.0;
@@ -63,9 +125,9 @@ pub fn show_parse_error(ui: &egui::Ui, err: &Error<impl RuleType>, text_edit_out
 
         if text_edit_out.galley.rect.contains((pos.x, pos.y).into()) {
             let cursor = text_edit_out.galley.cursor_from_pos(pos);
-            if is_in_line(cursor.rcursor.row, &err.line_col) {
+            if is_in_line(cursor.rcursor.row, line_col) {
                 show_tooltip_at_pointer(ui.ctx(), Id::new("hover_tooltip"), |ui| {
-                    ui.label(RichText::new(err.to_string()).font(FontId::monospace(13.5)))
+                    ui.label(RichText::new(message).font(FontId::monospace(13.5)))
                 });
             }
         }
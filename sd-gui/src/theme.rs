@@ -0,0 +1,164 @@
+//! Themes, which colour the elements of diagrams beyond what the light and dark modes choose, and
+//! the window which edits them.
+//!
+//! A theme can be saved to and loaded from a TOML file giving any of the colours as hex strings,
+//! for example
+//!
+//! ```toml
+//! node_fill = "#2e3440"
+//! edge = "#88c0d0"
+//! copy = "#bf616a"
+//! ```
+//!
+//! with the keys listed by [`Theme::colors`]. The colours left out follow the light or dark mode.
+
+use anyhow::{anyhow, bail, Context};
+use eframe::egui::{self, Color32};
+use egui_notify::Toasts;
+use sd_graphics::theme::Theme;
+
+/// Reads a theme from a TOML file.
+pub(crate) fn parse(source: &str) -> anyhow::Result<Theme> {
+    let mut table = source.parse::<toml::Table>()?;
+    let mut theme = Theme::default();
+    for (key, _, color) in theme.colors_mut() {
+        let Some(value) = table.remove(key) else {
+            continue;
+        };
+        let hex = value
+            .as_str()
+            .with_context(|| format!("`{key}` should be a colour such as \"#88c0d0\""))?;
+        *color = Some(parse_color(hex).ok_or_else(|| anyhow!("`{key}` is not a colour: {hex}"))?);
+    }
+    if let Some(key) = table.keys().next() {
+        bail!("unknown theme colour `{key}`");
+    }
+    Ok(theme)
+}
+
+/// Writes `theme` as a TOML file, leaving out the colours it does not set.
+pub(crate) fn write(theme: &Theme) -> String {
+    theme
+        .colors()
+        .into_iter()
+        .filter_map(|(key, _, color)| {
+            Some((key.to_owned(), toml::Value::String(write_color(color?))))
+        })
+        .collect::<toml::Table>()
+        .to_string()
+}
+
+/// Parses a colour written `#rrggbb`, or `#rrggbbaa` with an alpha channel.
+fn parse_color(hex: &str) -> Option<Color32> {
+    let hex = hex.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channels = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    match channels[..] {
+        [r, g, b] => Some(Color32::from_rgb(r, g, b)),
+        [r, g, b, a] => Some(Color32::from_rgba_unmultiplied(r, g, b, a)),
+        _ => None,
+    }
+}
+
+fn write_color(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == u8::MAX {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
+
+/// The window editing the theme.
+#[derive(Default)]
+pub(crate) struct ThemeEditor {
+    pub(crate) open: bool,
+}
+
+impl ThemeEditor {
+    /// Draws the window editing `theme`, if it is open.
+    pub(crate) fn ui(&mut self, ctx: &egui::Context, theme: &mut Theme, toasts: &mut Toasts) {
+        // Themes are only loaded and saved natively
+        #[cfg(target_arch = "wasm32")]
+        let _ = toasts;
+        egui::Window::new("Theme")
+            .open(&mut self.open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                // The colour to start from when one is first customised
+                let fallback = ui.visuals().text_color();
+                egui::Grid::new("theme_colors")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (_, name, color) in theme.colors_mut() {
+                            let mut custom = color.is_some();
+                            ui.checkbox(&mut custom, name)
+                                .on_hover_text("Unless ticked, follows the light or dark mode");
+                            if custom {
+                                ui.color_edit_button_srgba(color.get_or_insert(fallback));
+                            } else {
+                                *color = None;
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        if ui.button("Load…").clicked() {
+                            load(theme, toasts);
+                        }
+                        if ui.button("Save…").clicked() {
+                            save(theme, toasts);
+                        }
+                    }
+                    if ui
+                        .add_enabled(*theme != Theme::default(), egui::Button::new("Reset"))
+                        .clicked()
+                    {
+                        *theme = Theme::default();
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load(theme: &mut Theme, toasts: &mut Toasts) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Theme", &["toml"])
+        .pick_file()
+    else {
+        return;
+    };
+    match std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|source| parse(&source))
+    {
+        Ok(loaded) => *theme = loaded,
+        Err(err) => {
+            toasts.error(format!("Failed to load theme: {err:#}"));
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save(theme: &Theme, toasts: &mut Toasts) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Theme", &["toml"])
+        .set_file_name("theme.toml")
+        .save_file()
+    else {
+        return;
+    };
+    if let Err(err) = std::fs::write(&path, write(theme)) {
+        toasts.error(format!("Failed to save theme: {err:#}"));
+    }
+}
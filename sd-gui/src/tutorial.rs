@@ -0,0 +1,141 @@
+//! A guided tour of the interface, shown on first launch.
+
+use eframe::{
+    egui::{self, Align2, Color32, Id, LayerId, Order, Rect, Rounding, Stroke, Vec2},
+    emath::vec2,
+};
+
+/// A stop on the tour, each pointing out one part of the interface.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Step {
+    Editor,
+    Compile,
+    Expand,
+    Selections,
+}
+
+impl Step {
+    const ALL: [Self; 4] = [Self::Editor, Self::Compile, Self::Expand, Self::Selections];
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Editor => "Editor",
+            Self::Compile => "Compile",
+            Self::Expand => "Thunks",
+            Self::Selections => "Selections",
+        }
+    }
+
+    const fn text(self) -> &'static str {
+        match self {
+            Self::Editor => {
+                "Write or paste a program into the code editor, which this button shows and \
+                 hides. Import file loads a program from disk instead."
+            }
+            Self::Compile => "Compile (F5) draws the program as a string diagram.",
+            Self::Expand => {
+                "Click a thunk to collapse it into a single node, and click it again to expand \
+                 it. Scroll to zoom and drag to pan."
+            }
+            Self::Selections => {
+                "Right-click operations and thunks to select them, then Save selection to open \
+                 them in a window of their own. Saved selections are listed here."
+            }
+        }
+    }
+}
+
+/// The state of the tour: which step is shown, and where each step's part of the interface was
+/// last drawn.
+#[derive(Debug, Default)]
+pub(crate) struct Tutorial {
+    step: Option<Step>,
+    targets: [Option<Rect>; Step::ALL.len()],
+    /// Whether the tour has been finished or skipped, in this session or an earlier one.
+    seen: bool,
+}
+
+impl Tutorial {
+    /// A tour which starts straight away unless it has been `seen` before.
+    pub(crate) fn new(seen: bool) -> Self {
+        let mut tutorial = Self {
+            seen,
+            ..Self::default()
+        };
+        if !seen {
+            tutorial.start();
+        }
+        tutorial
+    }
+
+    /// Whether the tour has been finished or skipped, so that it is not started on the next launch.
+    pub(crate) const fn seen(&self) -> bool {
+        self.seen
+    }
+
+    pub(crate) fn start(&mut self) {
+        self.step = Some(Step::Editor);
+    }
+
+    /// Records where the part of the interface for `step` was drawn this frame.
+    pub(crate) fn target(&mut self, step: Step, rect: Rect) {
+        self.targets[step as usize] = Some(rect);
+    }
+
+    fn finish(&mut self) {
+        self.step = None;
+        self.seen = true;
+    }
+
+    /// Outlines the part of the interface for the current step and explains it.
+    pub(crate) fn ui(&mut self, ctx: &egui::Context) {
+        let Some(step) = self.step else {
+            return;
+        };
+        let index = step as usize;
+
+        if let Some(rect) = self.targets[index] {
+            ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("tutorial")))
+                .rect_stroke(
+                    rect.expand(4.0),
+                    Rounding::same(4.0),
+                    Stroke::new(3.0, Color32::from_rgb(0xff, 0xa0, 0x00)),
+                );
+        }
+
+        egui::Window::new(step.title())
+            .id(Id::new("tutorial_window"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -32.0))
+            .fixed_size(Vec2::new(320.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(step.text());
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    ui.weak(format!("{} of {}", index + 1, Step::ALL.len()));
+                    if ui.button("Skip tour").clicked() {
+                        self.finish();
+                    }
+                    if ui
+                        .add_enabled(index > 0, egui::Button::new("Back"))
+                        .clicked()
+                    {
+                        self.step = Some(Step::ALL[index - 1]);
+                    }
+                    match Step::ALL.get(index + 1) {
+                        Some(next) => {
+                            if ui.button("Next").clicked() {
+                                self.step = Some(*next);
+                            }
+                        }
+                        None => {
+                            if ui.button("Finish").clicked() {
+                                self.finish();
+                            }
+                        }
+                    }
+                });
+            });
+    }
+}
@@ -0,0 +1,117 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use eframe::epaint::Pos2;
+use sd_core::{interactive::Toggle, monoidal::Simplification};
+
+use crate::{panzoom::Panzoom, parser::UiLanguage};
+
+/// The state of the view of a program, written as a script which recreates it on the program
+/// compiled afresh, for example to redraw a figure after its source has changed.
+///
+/// Each line is one of `file NAME`, `language EXTENSION`, `simplify LEVEL`, `collapse ADDRESS`,
+/// `select ADDRESS`, `pan X Y` and `zoom FACTOR`, where addresses are those of [`sd_core::hypergraph::address`].
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct ViewScript {
+    /// The name of the file the program was opened from, relative to the script.
+    pub file: Option<String>,
+    pub language: Option<UiLanguage>,
+    /// How far the graph was simplified when it was laid out.
+    pub simplification: Option<Simplification>,
+    /// The clicks which turn the freshly compiled program, with every thunk expanded and nothing
+    /// selected, into the view.
+    pub(crate) toggles: Vec<Toggle>,
+    pub(crate) panzoom: Option<Panzoom>,
+}
+
+impl Display for ViewScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# Recreate this view with `sd-gui --view SCRIPT`")?;
+        if let Some(file) = &self.file {
+            writeln!(f, "file {file}")?;
+        }
+        if let Some(language) = self.language {
+            writeln!(f, "language {}", language.extension())?;
+        }
+        if let Some(simplification) = self.simplification {
+            writeln!(f, "simplify {simplification}")?;
+        }
+        for toggle in &self.toggles {
+            match toggle {
+                Toggle::Expanded(address) => writeln!(f, "collapse {address}")?,
+                Toggle::Selected(address) => writeln!(f, "select {address}")?,
+            }
+        }
+        if let Some(panzoom) = self.panzoom {
+            let translation = panzoom.translation();
+            writeln!(f, "pan {} {}", translation.x, translation.y)?;
+            writeln!(f, "zoom {}", panzoom.zoom_factor())?;
+        }
+        Ok(())
+    }
+}
+
+/// A line of a [`ViewScript`].
+enum Command {
+    File(String),
+    Language(UiLanguage),
+    Simplify(Simplification),
+    Toggle(Toggle),
+    Pan(Pos2),
+    Zoom(f32),
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (command, argument) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let argument = argument.trim();
+        Ok(match command {
+            "file" => Self::File(argument.to_owned()),
+            "language" => Self::Language(
+                UiLanguage::from_extension(argument)
+                    .ok_or_else(|| anyhow!("unknown language {argument:?}"))?,
+            ),
+            "simplify" => Self::Simplify(argument.parse().map_err(|err: String| anyhow!(err))?),
+            "collapse" => Self::Toggle(Toggle::Expanded(argument.parse()?)),
+            "select" => Self::Toggle(Toggle::Selected(argument.parse()?)),
+            "pan" => {
+                let (x, y) = argument
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| anyhow!("expected `pan X Y`"))?;
+                Self::Pan(Pos2::new(x.trim().parse()?, y.trim().parse()?))
+            }
+            "zoom" => Self::Zoom(argument.parse()?),
+            _ => bail!("unknown command {command:?}"),
+        })
+    }
+}
+
+impl FromStr for ViewScript {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut script = Self::default();
+        let (mut translation, mut zoom) = (None, None);
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.parse().with_context(|| format!("line {}", i + 1))? {
+                Command::File(file) => script.file = Some(file),
+                Command::Language(language) => script.language = Some(language),
+                Command::Simplify(level) => script.simplification = Some(level),
+                Command::Toggle(toggle) => script.toggles.push(toggle),
+                Command::Pan(pan) => translation = Some(pan),
+                Command::Zoom(factor) => zoom = Some(factor),
+            }
+        }
+        script.panzoom = translation
+            .zip(zoom)
+            .map(|(translation, zoom)| Panzoom::new(translation, zoom));
+        Ok(script)
+    }
+}
@@ -0,0 +1,152 @@
+//! Reloading a program opened on the command line whenever it changes on disk, so that the
+//! visualiser can be left open next to a compiler which regenerates the file.
+//!
+//! The directories containing the files are watched rather than the files themselves, as many
+//! tools replace a file by writing a new one and renaming it over the old, which would end a watch
+//! on the old file.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    time::Duration,
+};
+
+use eframe::egui;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sd_core::{language::chil::module::parse_module, prettyprinter::PrettyPrint};
+
+use crate::parser::UiLanguage;
+
+/// How long to wait after a change before reloading, as a file is often written in several goes.
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+/// Reads a program in `language` from `paths`, linking the files of a Chil program split across
+/// several together.
+pub fn read_program(paths: &[PathBuf], language: UiLanguage) -> anyhow::Result<String> {
+    match paths {
+        [path] => Ok(std::fs::read_to_string(path)?),
+        _ if language == UiLanguage::Chil => {
+            let sources = paths
+                .iter()
+                .map(|path| Ok((path.display().to_string(), std::fs::read_to_string(path)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let expr = parse_module(
+                sources
+                    .iter()
+                    .map(|(name, source)| (name.as_str(), source.as_str())),
+            )?;
+            Ok(expr.to_pretty())
+        }
+        _ => anyhow::bail!("only chil programs can be read from several files"),
+    }
+}
+
+pub(crate) struct FileWatcher {
+    paths: Vec<PathBuf>,
+    language: UiLanguage,
+    /// Watches for as long as it is kept.
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<Event>>,
+    /// When one of the files last changed, if it has not been reloaded since.
+    changed: Option<f64>,
+}
+
+impl FileWatcher {
+    /// Starts watching the files at `paths`, drawing a frame with `ctx` when any of them changes.
+    pub(crate) fn new(
+        paths: Vec<PathBuf>,
+        language: UiLanguage,
+        ctx: &egui::Context,
+    ) -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let ctx = ctx.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            // The receiver is only gone once the application is closing
+            if tx.send(event).is_ok() {
+                ctx.request_repaint();
+            }
+        })?;
+        let directories = paths
+            .iter()
+            .map(|path| {
+                path.parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."))
+            })
+            .collect::<BTreeSet<_>>();
+        for directory in directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+        Ok(Self {
+            paths,
+            language,
+            _watcher: watcher,
+            rx,
+            changed: None,
+        })
+    }
+
+    pub(crate) const fn language(&self) -> UiLanguage {
+        self.language
+    }
+
+    /// The name of the program, to tell the user what was reloaded.
+    pub(crate) fn name(&self) -> String {
+        self.paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether the files have changed and since settled, so that the program should be reloaded.
+    pub(crate) fn poll(&mut self, ctx: &egui::Context) -> bool {
+        let now = ctx.input(|i| i.time);
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                Ok(event) if self.concerns(&event) => self.changed = Some(now),
+                Ok(_) => {}
+                Err(err) => tracing::warn!("Failed to watch files: {err}"),
+            }
+        }
+        let Some(changed) = self.changed else {
+            return false;
+        };
+        let wait = SETTLE_DELAY.as_secs_f64() - (now - changed);
+        if wait > 0.0 {
+            // Make sure a frame comes along to reload once nothing else changes
+            ctx.request_repaint_after(Duration::from_secs_f64(wait));
+            return false;
+        }
+        self.changed = None;
+        true
+    }
+
+    /// Reads the program afresh.
+    pub(crate) fn read(&self) -> anyhow::Result<String> {
+        read_program(&self.paths, self.language)
+    }
+
+    /// Whether `event` changed one of the watched files, rather than something else in the same
+    /// directory.
+    fn concerns(&self, event: &Event) -> bool {
+        matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+        ) && event
+            .paths
+            .iter()
+            .any(|changed| self.paths.iter().any(|path| same_file(path, changed)))
+    }
+}
+
+/// Whether `path`, as given on the command line, names the same file as `changed`, which notify
+/// reports with an absolute path.
+fn same_file(path: &Path, changed: &Path) -> bool {
+    match (path.canonicalize(), changed.canonicalize()) {
+        (Ok(path), Ok(changed)) => path == changed,
+        // The file was removed, and may yet be replaced
+        _ => changed.file_name() == path.file_name(),
+    }
+}
@@ -0,0 +1,163 @@
+//! The interface through which a web page embedding the visualiser drives it.
+//!
+//! A page which loads the visualiser itself can call [`set_code`] and [`get_svg`] on the module.
+//! A page which embeds it in an iframe can post the same requests to the frame as messages:
+//!
+//! - `{type: "setCode", code, language}` shows `code`, which is in `language` (`"spartan"`,
+//!   `"chil"`, `"mlir"` or `"dot"`, or their file extensions).
+//! - `{type: "getSvg"}` asks for the diagram, which is posted back as `{type: "svg", svg}`, with
+//!   `svg` null if the diagram has not been laid out yet.
+//!
+//! Whenever the user clicks to change the selection, `{type: "selectionChanged", addresses}` is
+//! posted to the embedding page, or to the window itself when the visualiser is not embedded, with
+//! the addresses of the selected nodes.
+
+use std::cell::RefCell;
+
+use eframe::egui;
+use js_sys::{Array, Function, Object, Promise, Reflect};
+use sd_core::hypergraph::address::NodeAddress;
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::MessageEvent;
+
+use crate::parser::UiLanguage;
+
+/// A request from the embedding page, handled when the next frame is drawn.
+pub(crate) enum Request {
+    SetCode(String, UiLanguage),
+    GetSvg(Reply),
+}
+
+/// Where to send the diagram asked for: to the promise returned by [`get_svg`], or back to the
+/// embedding page as a message.
+pub(crate) struct Reply(Option<Function>);
+
+impl Reply {
+    pub(crate) fn send(self, svg: Option<&str>) {
+        let svg = svg.map_or(JsValue::NULL, JsValue::from_str);
+        match self.0 {
+            Some(resolve) => {
+                if let Err(err) = resolve.call1(&JsValue::NULL, &svg) {
+                    tracing::warn!("Failed to resolve getSvg: {err:?}");
+                }
+            }
+            None => post("svg", &[("svg", svg)]),
+        }
+    }
+}
+
+thread_local! {
+    static REQUESTS: RefCell<Vec<Request>> = RefCell::default();
+    static CONTEXT: RefCell<Option<egui::Context>> = RefCell::default();
+}
+
+/// Starts listening for requests from the embedding page, drawing a frame with `ctx` to handle
+/// each of them.
+pub(crate) fn install(ctx: &egui::Context) {
+    CONTEXT.set(Some(ctx.clone()));
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let listener = Closure::<dyn FnMut(MessageEvent)>::new(|event: MessageEvent| {
+        receive(&event.data());
+    });
+    if let Err(err) =
+        window.add_event_listener_with_callback("message", listener.as_ref().unchecked_ref())
+    {
+        tracing::warn!("Failed to listen for messages: {err:?}");
+    }
+    // The listener lives as long as the page
+    listener.forget();
+}
+
+/// The requests made since this was last called.
+pub(crate) fn take_requests() -> Vec<Request> {
+    REQUESTS.with_borrow_mut(std::mem::take)
+}
+
+/// Tells the embedding page that the nodes at `addresses` are now the ones selected.
+pub(crate) fn selection_changed(addresses: &[NodeAddress]) {
+    let addresses = addresses
+        .iter()
+        .map(|address| JsValue::from_str(&address.to_string()))
+        .collect::<Array>();
+    post("selectionChanged", &[("addresses", addresses.into())]);
+}
+
+fn request(request: Request) {
+    REQUESTS.with_borrow_mut(|requests| requests.push(request));
+    CONTEXT.with_borrow(|ctx| {
+        if let Some(ctx) = ctx {
+            ctx.request_repaint();
+        }
+    });
+}
+
+fn parse_language(name: &str) -> Option<UiLanguage> {
+    [
+        UiLanguage::Chil,
+        UiLanguage::Spartan,
+        UiLanguage::Mlir,
+        UiLanguage::Dot,
+    ]
+    .into_iter()
+    .find(|language| language.name() == name)
+    .or_else(|| UiLanguage::from_extension(name))
+}
+
+/// Handles a message posted to the window. Messages which are not requests, including those this
+/// module posts to the window itself, are ignored.
+fn receive(data: &JsValue) {
+    let field = |name: &str| {
+        Reflect::get(data, &JsValue::from_str(name))
+            .ok()
+            .and_then(|value| value.as_string())
+    };
+    match field("type").as_deref() {
+        Some("setCode") => {
+            let (Some(code), Some(name)) = (field("code"), field("language")) else {
+                tracing::warn!("Ignoring setCode message without code and language");
+                return;
+            };
+            match parse_language(&name) {
+                Some(language) => request(Request::SetCode(code, language)),
+                None => tracing::warn!("Ignoring setCode message in unknown language `{name}`"),
+            }
+        }
+        Some("getSvg") => request(Request::GetSvg(Reply(None))),
+        _ => {}
+    }
+}
+
+/// Posts a message of the type `kind` with `fields` to the embedding page, or to the window itself
+/// when the visualiser is not embedded.
+fn post(kind: &str, fields: &[(&str, JsValue)]) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let message = Object::new();
+    for (key, value) in [("type", JsValue::from_str(kind))].iter().chain(fields) {
+        // Setting a property of a plain object cannot fail
+        let _ = Reflect::set(&message, &JsValue::from_str(key), value);
+    }
+    let target = window.parent().ok().flatten().unwrap_or(window);
+    if let Err(err) = target.post_message(&message, "*") {
+        tracing::warn!("Failed to post {kind} message: {err:?}");
+    }
+}
+
+/// Shows `code`, which is in `language` (`"spartan"`, `"chil"`, `"mlir"` or `"dot"`, or their file
+/// extensions), replacing the program shown.
+#[wasm_bindgen]
+pub fn set_code(code: String, language: &str) -> Result<(), JsError> {
+    let language = parse_language(language)
+        .ok_or_else(|| JsError::new(&format!("unknown language `{language}`")))?;
+    request(Request::SetCode(code, language));
+    Ok(())
+}
+
+/// Resolves to the diagram as an svg document, or to null if it has not been laid out yet.
+#[wasm_bindgen]
+pub fn get_svg() -> Promise {
+    Promise::new(&mut |resolve, _reject| request(Request::GetSvg(Reply(Some(resolve)))))
+}